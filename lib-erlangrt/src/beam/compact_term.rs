@@ -139,9 +139,7 @@ fn parse_ext_tag(b: u8, r: &mut BinaryReader) -> RtResult<fterm::FTerm> {
     x if x == CTEExtTag::List as u8 => parse_ext_list(r),
     x if x == CTEExtTag::FloatReg as u8 => parse_ext_fpreg(r),
     x if x == CTEExtTag::Literal as u8 => parse_ext_literal(r),
-    x if x == CTEExtTag::AllocList as u8 => {
-      panic!("Don't know how to decode an alloclist")
-    }
+    x if x == CTEExtTag::AllocList as u8 => parse_ext_alloclist(r),
     other => {
       let msg = format!("Ext tag {} unknown", other);
       make_err(CTError::BadExtendedTag(msg))
@@ -153,10 +151,7 @@ fn parse_ext_tag(b: u8, r: &mut BinaryReader) -> RtResult<fterm::FTerm> {
 fn parse_ext_tag(b: u8, r: &mut BinaryReader) -> RtResult<fterm::FTerm> {
   match b {
     x if x == CTEExtTag::List as u8 => parse_ext_list(r),
-    x if x == CTEExtTag::AllocList as u8 => {
-      panic!("Don't know how to decode an alloclist");
-      // Ok(fterm::FTerm::AllocList_)
-    }
+    x if x == CTEExtTag::AllocList as u8 => parse_ext_alloclist(r),
     x if x == CTEExtTag::FloatReg as u8 => parse_ext_fpreg(r),
     x if x == CTEExtTag::Literal as u8 => parse_ext_literal(r),
     other => {
@@ -206,6 +201,27 @@ fn parse_ext_list(r: &mut BinaryReader) -> RtResult<fterm::FTerm> {
   Ok(fterm::FTerm::LoadTimeExtlist(el))
 }
 
+/// The alloc list is a count of pairs, each pair is a type (0 for words, 1 for
+/// floats) and an amount.
+fn parse_ext_alloclist(r: &mut BinaryReader) -> RtResult<fterm::FTerm> {
+  let n_pairs = read_int(r);
+  let mut words = 0;
+  let mut floats = 0;
+  for _i in 0..n_pairs {
+    let alloc_type = read_int(r);
+    let amount = read_int(r) as Word;
+    match alloc_type {
+      0 => words += amount,
+      1 => floats += amount,
+      other => {
+        let msg = format!("Alloc list type {} unknown", other);
+        return make_err(CTError::BadExtendedTag(msg));
+      }
+    }
+  }
+  Ok(fterm::FTerm::LoadTimeAlloclist { words, floats })
+}
+
 /// Assume that the stream contains a tagged small integer (check the tag!)
 /// read it and return the unwrapped value as word.
 fn read_int(r: &mut BinaryReader) -> SWord {
@@ -302,6 +318,19 @@ mod tests {
     );
   }
 
+  #[test]
+  #[cfg(feature = "r20")]
+  fn test_alloclist() {
+    // {alloc, [{words, 5}, {floats, 2}]}
+    try_parse(
+      vec![0b0011_0111u8, 0x20, 0x00, 0x50, 0x10, 0x20],
+      fterm::FTerm::LoadTimeAlloclist {
+        words: 5,
+        floats: 2,
+      },
+    );
+  }
+
   // TODO: test extended
 
   /// Given the vec<u8> input, we read the word encoded in it and compare with
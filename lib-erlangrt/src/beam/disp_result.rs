@@ -7,6 +7,11 @@ pub enum DispatchResult {
   Normal,
   // Process falls asleep (loses its running status and waits for its turn)
   Yield,
+  // Process waits for a message or the receive timer
+  Wait,
   // Process is done
   Finished,
+  // Process has received an exit signal and must terminate, the reason is
+  // stored in the process error field
+  Exited,
 }
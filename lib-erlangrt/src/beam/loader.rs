@@ -624,13 +624,16 @@ impl Loader {
 
           // Each value convert to LTerm and also push forming a tuple
           for (index, t) in jtab.iter().enumerate() {
-            let new_t = if let FTerm::LoadTimeLabel(f) = *t {
-              // Try to resolve labels and convert now, or postpone
-              let ploc =
-                PatchLocation::PatchJtabElement(LTerm::make_boxed(heap_jtab), index);
-              self.maybe_convert_label(LabelId(f), ploc)
-            } else {
-              t.to_lterm(&mut self.lit_heap).raw()
+            let new_t = match *t {
+              FTerm::LoadTimeLabel(f) => {
+                // Try to resolve labels and convert now, or postpone
+                let ploc =
+                  PatchLocation::PatchJtabElement(LTerm::make_boxed(heap_jtab), index);
+                self.maybe_convert_label(LabelId(f), ploc)
+              }
+              // Map keys in ext lists can be literals
+              FTerm::LoadTimeLit(lit_index) => self.lit_tab[lit_index].raw(),
+              _ => t.to_lterm(&mut self.lit_heap).raw(),
             };
 
             unsafe { boxed::Tuple::set_raw_word_base0(heap_jtab, index, new_t) }
@@ -648,6 +651,17 @@ impl Loader {
         // Load-time literals are already loaded on `self.lit_heap`
         FTerm::LoadTimeLit(lit_index) => self.code.push(self.lit_tab[lit_index].raw()),
 
+        // Alloc list becomes a tuple `{Words, Floats}`, the opcode converts
+        // floats to words
+        FTerm::LoadTimeAlloclist { words, floats } => {
+          let alloc = tuple::make_tuple2(
+            LTerm::make_small_unsigned(words),
+            LTerm::make_small_unsigned(floats),
+            &mut self.lit_heap,
+          )?;
+          self.code.push(alloc.raw())
+        }
+
         // Otherwise convert via a simple method
         _ => self.code.push(a.to_lterm(&mut self.lit_heap).raw()),
       }
@@ -2,8 +2,10 @@
 pub mod op_bif;
 pub mod op_data;
 pub mod op_execution;
+pub mod op_float;
 pub mod op_fun;
 pub mod op_list;
+pub mod op_map;
 pub mod op_memory;
 pub mod op_message;
 pub mod op_predicates;
@@ -12,8 +14,9 @@ pub mod op_tuple;
 pub mod op_type_checks;
 
 pub use crate::beam::opcodes::{
-  op_bif::*, op_data::*, op_execution::*, op_fun::*, op_list::*, op_memory::*,
-  op_message::*, op_predicates::*, op_try_catch::*, op_tuple::*, op_type_checks::*,
+  op_bif::*, op_data::*, op_execution::*, op_float::*, op_fun::*, op_list::*,
+  op_map::*, op_memory::*, op_message::*, op_predicates::*, op_try_catch::*,
+  op_tuple::*, op_type_checks::*,
 };
 use crate::{
   beam::gen_op,
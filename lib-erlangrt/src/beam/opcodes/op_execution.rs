@@ -201,6 +201,7 @@ fn shared_call_ext(
 
   match unsafe { boxed::Import::const_from_term(dst_import) } {
     Ok(import_ptr) => unsafe {
+      curr_p.save_call((*import_ptr).mfarity);
       if (*import_ptr).is_bif {
         // Perform a BIF application
         let cb_target = call_bif::CallBifTarget::ImportPointer(import_ptr);
@@ -220,8 +221,7 @@ fn shared_call_ext(
         if save_cp {
           ctx.cp = ctx.ip; // Points at the next opcode after this
         }
-        let cs = vm.get_code_server_p();
-        ctx.ip = (*import_ptr).resolve(&mut (*cs))?;
+        ctx.ip = (*import_ptr).resolve(&mut vm.code_server())?;
         Ok(DispatchResult::Normal)
       }
     },
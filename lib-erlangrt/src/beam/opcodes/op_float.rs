@@ -0,0 +1,219 @@
+//! Module implements opcodes which work with float registers. The float
+//! registers hold raw `f64` values, they are boxed only when moved out to an X
+//! or Y register. The result of every operation is checked immediately, so
+//! `fclearerror` and `fcheckerror` have nothing left to do.
+use crate::{
+  beam::disp_result::DispatchResult,
+  emulator::{arith::float, heap, process::Process, runtime_ctx::Context, vm::VM},
+  fail::{self, RtResult},
+  term::lterm::LTerm,
+};
+
+/// Store a float operation result into the `dst` float register. If the
+/// result is not finite, jump to the fail label or raise `badarith`.
+#[inline]
+fn store_result(
+  ctx: &mut Context,
+  fail_label: LTerm,
+  dst: LTerm,
+  val: f64,
+) -> RtResult<DispatchResult> {
+  if !val.is_finite() {
+    if fail_label.is_cp() {
+      ctx.jump(fail_label);
+      return Ok(DispatchResult::Normal);
+    }
+    return fail::create::badarith();
+  }
+  ctx.set_fp(dst, val);
+  Ok(DispatchResult::Normal)
+}
+
+/// Shared part of the binary float operations.
+/// Structure: fadd|fsub|fmul|fdiv(fail:label, a:fpreg, b:fpreg, dst:fpreg)
+#[inline]
+fn float_binop(ctx: &mut Context, op: fn(f64, f64) -> f64) -> RtResult<DispatchResult> {
+  let fail_label = ctx.fetch_term();
+  let a = ctx.fetch_term();
+  let b = ctx.fetch_term();
+  let dst = ctx.fetch_term();
+  let result = op(ctx.get_fp(a), ctx.get_fp(b));
+  store_result(ctx, fail_label, dst, result)
+}
+
+/// Move a float between float registers, from a term into a float register,
+/// or from a float register to an X or Y register (boxing it on the heap).
+/// Structure: fmove(src, dst)
+pub struct OpcodeFmove {}
+
+impl OpcodeFmove {
+  pub const ARITY: usize = 2;
+
+  #[inline]
+  pub fn run(
+    _vm: &mut VM,
+    ctx: &mut Context,
+    curr_p: &mut Process,
+  ) -> RtResult<DispatchResult> {
+    let src = ctx.fetch_term();
+    let dst = ctx.fetch_term();
+
+    if src.is_regfp() {
+      let val = ctx.get_fp(src);
+      if dst.is_regfp() {
+        ctx.set_fp(dst, val);
+      } else {
+        let hp = &mut curr_p.heap;
+        let boxed_val = heap::build_on_heap(hp, |hp| float::make_float(hp, val))?;
+        ctx.store_value(boxed_val, dst, hp)?;
+      }
+    } else {
+      // The compiler only moves known floats into float registers
+      let src_val = ctx.load(src, &curr_p.heap);
+      match float::to_f64(src_val) {
+        Some(val) => ctx.set_fp(dst, val),
+        None => return fail::create::badarith(),
+      }
+    }
+    Ok(DispatchResult::Normal)
+  }
+}
+
+/// Convert a number to float and store it into a float register.
+/// Structure: fconv(src, dst:fpreg)
+pub struct OpcodeFconv {}
+
+impl OpcodeFconv {
+  pub const ARITY: usize = 2;
+
+  #[inline]
+  pub fn run(
+    _vm: &mut VM,
+    ctx: &mut Context,
+    curr_p: &mut Process,
+  ) -> RtResult<DispatchResult> {
+    let src = ctx.fetch_and_load(&curr_p.heap);
+    let dst = ctx.fetch_term();
+    match float::to_f64(src) {
+      // A bignum too big for a float fails too
+      Some(val) => store_result(ctx, LTerm::nil(), dst, val),
+      None => fail::create::badarith(),
+    }
+  }
+}
+
+pub struct OpcodeFadd {}
+
+impl OpcodeFadd {
+  pub const ARITY: usize = 4;
+
+  #[inline]
+  pub fn run(
+    _vm: &mut VM,
+    ctx: &mut Context,
+    _curr_p: &mut Process,
+  ) -> RtResult<DispatchResult> {
+    float_binop(ctx, |a, b| a + b)
+  }
+}
+
+pub struct OpcodeFsub {}
+
+impl OpcodeFsub {
+  pub const ARITY: usize = 4;
+
+  #[inline]
+  pub fn run(
+    _vm: &mut VM,
+    ctx: &mut Context,
+    _curr_p: &mut Process,
+  ) -> RtResult<DispatchResult> {
+    float_binop(ctx, |a, b| a - b)
+  }
+}
+
+pub struct OpcodeFmul {}
+
+impl OpcodeFmul {
+  pub const ARITY: usize = 4;
+
+  #[inline]
+  pub fn run(
+    _vm: &mut VM,
+    ctx: &mut Context,
+    _curr_p: &mut Process,
+  ) -> RtResult<DispatchResult> {
+    float_binop(ctx, |a, b| a * b)
+  }
+}
+
+/// Division by zero gives an infinity or NaN which fails with `badarith`.
+pub struct OpcodeFdiv {}
+
+impl OpcodeFdiv {
+  pub const ARITY: usize = 4;
+
+  #[inline]
+  pub fn run(
+    _vm: &mut VM,
+    ctx: &mut Context,
+    _curr_p: &mut Process,
+  ) -> RtResult<DispatchResult> {
+    float_binop(ctx, |a, b| a / b)
+  }
+}
+
+/// Structure: fnegate(fail:label, src:fpreg, dst:fpreg)
+pub struct OpcodeFnegate {}
+
+impl OpcodeFnegate {
+  pub const ARITY: usize = 3;
+
+  #[inline]
+  pub fn run(
+    _vm: &mut VM,
+    ctx: &mut Context,
+    _curr_p: &mut Process,
+  ) -> RtResult<DispatchResult> {
+    let fail_label = ctx.fetch_term();
+    let src = ctx.fetch_term();
+    let dst = ctx.fetch_term();
+    let result = -ctx.get_fp(src);
+    store_result(ctx, fail_label, dst, result)
+  }
+}
+
+/// Float errors are checked after every operation, nothing to clear.
+/// Structure: fclearerror()
+pub struct OpcodeFclearerror {}
+
+impl OpcodeFclearerror {
+  pub const ARITY: usize = 0;
+
+  #[inline]
+  pub fn run(
+    _vm: &mut VM,
+    _ctx: &mut Context,
+    _curr_p: &mut Process,
+  ) -> RtResult<DispatchResult> {
+    Ok(DispatchResult::Normal)
+  }
+}
+
+/// Float errors are checked after every operation, nothing to check here.
+/// Structure: fcheckerror(fail:label)
+pub struct OpcodeFcheckerror {}
+
+impl OpcodeFcheckerror {
+  pub const ARITY: usize = 1;
+
+  #[inline]
+  pub fn run(
+    _vm: &mut VM,
+    ctx: &mut Context,
+    _curr_p: &mut Process,
+  ) -> RtResult<DispatchResult> {
+    let _fail_label = ctx.fetch_term();
+    Ok(DispatchResult::Normal)
+  }
+}
@@ -153,7 +153,7 @@ fn fixed_apply(
   }
 
   println!("call_mfa {}", mfa);
-  let l_result = vm.code_server.lookup_mfa(mfa, true);
+  let l_result = vm.code_server().lookup_mfa(mfa, true);
   if l_result.is_err() {
     return fail::create::undef();
   }
@@ -0,0 +1,213 @@
+//! Module implements opcodes related to map creation and matching.
+use crate::{
+  beam::disp_result::DispatchResult,
+  emulator::{heap, process::Process, runtime_ctx::Context, vm::VM},
+  fail::{self, RtResult},
+  term::{boxed, lterm::LTerm, map},
+};
+
+/// Read the ext list operand (a literal tuple) and load its elements, which
+/// can be registers.
+#[inline]
+fn load_ext_list(ctx: &Context, list: LTerm, curr_p: &Process) -> Vec<LTerm> {
+  let tuple_p = list.get_tuple_ptr();
+  let arity = unsafe { (*tuple_p).get_arity() };
+  (0..arity)
+    .map(|i| {
+      let elem = unsafe { boxed::Tuple::get_element_base0(tuple_p, i) };
+      ctx.load(elem, &curr_p.heap)
+    })
+    .collect()
+}
+
+/// Split a loaded ext list of `[Key1, Value1, ...]` into pairs.
+#[inline]
+fn to_pairs(list: &[LTerm]) -> Vec<(LTerm, LTerm)> {
+  list.chunks(2).map(|kv| (kv[0], kv[1])).collect()
+}
+
+/// Shared part of `put_map_assoc` and `put_map_exact`.
+/// Structure: put_map_*(on_fail:label, src:map, dst, live:smallint,
+///   pairs:extlist)
+struct PutMapArgs {
+  fail_label: LTerm,
+  src: LTerm,
+  dst: LTerm,
+  pairs: Vec<(LTerm, LTerm)>,
+}
+
+impl PutMapArgs {
+  #[inline]
+  fn fetch(ctx: &mut Context, curr_p: &mut Process) -> Self {
+    let fail_label = ctx.fetch_term();
+    let src = ctx.fetch_and_load(&curr_p.heap);
+    let dst = ctx.fetch_term();
+    let _live = ctx.fetch_term();
+    let list = ctx.fetch_term();
+    let pairs = to_pairs(&load_ext_list(ctx, list, curr_p));
+    Self {
+      fail_label,
+      src,
+      dst,
+      pairs,
+    }
+  }
+
+  /// Create the updated map and store it in `dst`. The result is built in a
+  /// heap fragment if the heap is full.
+  fn put_and_store(self, ctx: &mut Context, curr_p: &mut Process) -> RtResult<()> {
+    let hp = &mut curr_p.heap;
+    let result =
+      heap::build_on_heap(hp, |hp| map::put_pairs(self.src, &self.pairs, hp))?;
+    ctx.store_value(result, self.dst, hp)
+  }
+}
+
+/// Create a new map from `src` with the keys added or replaced.
+/// Structure: put_map_assoc(on_fail:label, src:map, dst, live:smallint,
+///   pairs:extlist)
+pub struct OpcodePutMapAssoc {}
+
+impl OpcodePutMapAssoc {
+  pub const ARITY: usize = 5;
+
+  #[inline]
+  pub fn run(
+    _vm: &mut VM,
+    ctx: &mut Context,
+    curr_p: &mut Process,
+  ) -> RtResult<DispatchResult> {
+    let args = PutMapArgs::fetch(ctx, curr_p);
+    if !args.src.is_map() {
+      if args.fail_label.is_cp() {
+        ctx.jump(args.fail_label);
+        return Ok(DispatchResult::Normal);
+      }
+      return fail::create::badmap_val(args.src, &mut curr_p.heap);
+    }
+    args.put_and_store(ctx, curr_p)?;
+    Ok(DispatchResult::Normal)
+  }
+}
+
+/// Create a new map from `src` with the values replaced, all keys must
+/// already exist in `src`.
+/// Structure: put_map_exact(on_fail:label, src:map, dst, live:smallint,
+///   pairs:extlist)
+pub struct OpcodePutMapExact {}
+
+impl OpcodePutMapExact {
+  pub const ARITY: usize = 5;
+
+  #[inline]
+  pub fn run(
+    _vm: &mut VM,
+    ctx: &mut Context,
+    curr_p: &mut Process,
+  ) -> RtResult<DispatchResult> {
+    let args = PutMapArgs::fetch(ctx, curr_p);
+    let missing_key = if args.src.is_map() {
+      let mut missing = None;
+      for (key, _) in &args.pairs {
+        if map::get(args.src, *key)?.is_none() {
+          missing = Some(*key);
+          break;
+        }
+      }
+      missing
+    } else {
+      None
+    };
+
+    if !args.src.is_map() || missing_key.is_some() {
+      if args.fail_label.is_cp() {
+        ctx.jump(args.fail_label);
+        return Ok(DispatchResult::Normal);
+      }
+      let hp = &mut curr_p.heap;
+      return match missing_key {
+        Some(key) => fail::create::badkey_val(key, hp),
+        None => fail::create::badmap_val(args.src, hp),
+      };
+    }
+    args.put_and_store(ctx, curr_p)?;
+    Ok(DispatchResult::Normal)
+  }
+}
+
+/// Check that all keys exist in the map, otherwise jump to the fail label.
+/// Structure: has_map_fields(on_fail:label, src:map, keys:extlist)
+pub struct OpcodeHasMapFields {}
+
+impl OpcodeHasMapFields {
+  pub const ARITY: usize = 3;
+
+  #[inline]
+  pub fn run(
+    _vm: &mut VM,
+    ctx: &mut Context,
+    curr_p: &mut Process,
+  ) -> RtResult<DispatchResult> {
+    let fail_label = ctx.fetch_term();
+    let src = ctx.fetch_and_load(&curr_p.heap);
+    let list = ctx.fetch_term();
+    if !src.is_map() {
+      ctx.jump(fail_label);
+      return Ok(DispatchResult::Normal);
+    }
+
+    for key in load_ext_list(ctx, list, curr_p) {
+      if map::get(src, key)?.is_none() {
+        ctx.jump(fail_label);
+        break;
+      }
+    }
+    Ok(DispatchResult::Normal)
+  }
+}
+
+/// Read values for the keys from the map and store them, if a key does not
+/// exist, jump to the fail label.
+/// Structure: get_map_elements(on_fail:label, src:map, pairs:extlist) where
+///   the pairs are `[Key1, Dst1, ...]`
+pub struct OpcodeGetMapElements {}
+
+impl OpcodeGetMapElements {
+  pub const ARITY: usize = 3;
+
+  #[inline]
+  pub fn run(
+    _vm: &mut VM,
+    ctx: &mut Context,
+    curr_p: &mut Process,
+  ) -> RtResult<DispatchResult> {
+    let fail_label = ctx.fetch_term();
+    let src = ctx.fetch_and_load(&curr_p.heap);
+    let list = ctx.fetch_term();
+    if !src.is_map() {
+      ctx.jump(fail_label);
+      return Ok(DispatchResult::Normal);
+    }
+
+    // Destinations are registers, so only the keys are loaded
+    let tuple_p = list.get_tuple_ptr();
+    let arity = unsafe { (*tuple_p).get_arity() };
+    for i in (0..arity).step_by(2) {
+      let (key, dst) = unsafe {
+        (
+          boxed::Tuple::get_element_base0(tuple_p, i),
+          boxed::Tuple::get_element_base0(tuple_p, i + 1),
+        )
+      };
+      let key = ctx.load(key, &curr_p.heap);
+      match map::get(src, key)? {
+        Some(value) => ctx.store_value(value, dst, &mut curr_p.heap)?,
+        None => {
+          ctx.jump(fail_label);
+          break;
+        }
+      }
+    }
+    Ok(DispatchResult::Normal)
+  }
+}
@@ -2,9 +2,27 @@ use crate::{
   beam::disp_result::DispatchResult,
   emulator::{process::Process, runtime_ctx::Context, vm::VM},
   fail::RtResult,
-  term::lterm::LTerm,
+  term::{boxed, lterm::LTerm},
 };
 
+/// Read the heap need of an opcode. It is a small integer, or an alloc list
+/// `{Words, Floats}` when floats will be boxed from the float registers.
+#[inline]
+fn fetch_heap_need(ctx: &mut Context) -> usize {
+  let need = ctx.fetch_term();
+  if need.is_small() {
+    return need.get_small_unsigned();
+  }
+  let (words, floats) = unsafe {
+    let p = need.get_tuple_ptr();
+    (
+      boxed::Tuple::get_element_base0(p, 0).get_small_unsigned(),
+      boxed::Tuple::get_element_base0(p, 1).get_small_unsigned(),
+    )
+  };
+  words + floats * boxed::Float::storage_size().words()
+}
+
 /// Shared code for stack checks and allocations with an optional heap check.
 #[inline]
 fn shared_allocate(
@@ -14,25 +32,21 @@ fn shared_allocate(
   heap_need: usize,
   live: usize,
   zero: bool,
-) {
+) -> RtResult<()> {
   ctx.live = live;
 
-  let hp = &mut curr_p.heap;
-
-  if !hp.heap_has_available(heap_need) {
-    panic!("Heap doesn't have {} words", heap_need);
+  if !curr_p.heap.heap_has_available(heap_need) {
+    // Heap has not enough, invoke GC and possibly fail
+    curr_p.garbage_collect(ctx, heap_need, live)?;
   }
 
-  if hp.stack_have(stack_need + 1) {
-    // Stack has enough words, we can allocate unchecked
-    if stack_need > 0 {
-      hp.stack_alloc_unchecked(stack_need, zero);
-    }
-    hp.stack_push_lterm_unchecked(ctx.cp.to_cp_term());
-  } else {
-    // Stack has not enough, invoke GC and possibly fail
-    panic!("TODO GC here or fail");
+  let hp = &mut curr_p.heap;
+  // Stack lives in a separate memory segment and grows when needed
+  if stack_need > 0 {
+    hp.stack_alloc(stack_need, zero);
   }
+  hp.stack_push_lterm(ctx.cp.to_cp_term());
+  Ok(())
 }
 
 /// Allocate `need` words on stack, in case of GC use `live` amount of registers.
@@ -56,7 +70,7 @@ impl OpcodeAllocateZero {
     curr_p: &mut Process,
   ) -> RtResult<DispatchResult> {
     let (stack_need, live) = Self::fetch_args(ctx);
-    shared_allocate(ctx, curr_p, stack_need, 0, live, true);
+    shared_allocate(ctx, curr_p, stack_need, 0, live, true)?;
     Ok(DispatchResult::Normal)
   }
 }
@@ -81,7 +95,7 @@ impl OpcodeAllocate {
     curr_p: &mut Process,
   ) -> RtResult<DispatchResult> {
     let (stack_need, live) = Self::fetch_args(ctx);
-    shared_allocate(ctx, curr_p, stack_need, 0, live, false);
+    shared_allocate(ctx, curr_p, stack_need, 0, live, false)?;
     Ok(DispatchResult::Normal)
   }
 }
@@ -98,7 +112,7 @@ impl OpcodeAllocateHeapZero {
   #[inline]
   fn fetch_args(ctx: &mut Context) -> (usize, usize, usize) {
     let stack_need = ctx.fetch_term().get_small_unsigned();
-    let heap_need = ctx.fetch_term().get_small_unsigned();
+    let heap_need = fetch_heap_need(ctx);
     let live = ctx.fetch_term().get_small_unsigned();
     (stack_need, heap_need, live)
   }
@@ -110,7 +124,7 @@ impl OpcodeAllocateHeapZero {
     curr_p: &mut Process,
   ) -> RtResult<DispatchResult> {
     let (stack_need, heap_need, live) = Self::fetch_args(ctx);
-    shared_allocate(ctx, curr_p, stack_need, heap_need, live, true);
+    shared_allocate(ctx, curr_p, stack_need, heap_need, live, true)?;
     Ok(DispatchResult::Normal)
   }
 }
@@ -124,7 +138,7 @@ impl OpcodeAllocateHeap {
   #[inline]
   fn fetch_args(ctx: &mut Context) -> (usize, usize, usize) {
     let stack_need = ctx.fetch_term().get_small_unsigned();
-    let heap_need = ctx.fetch_term().get_small_unsigned();
+    let heap_need = fetch_heap_need(ctx);
     let live = ctx.fetch_term().get_small_unsigned();
     (stack_need, heap_need, live)
   }
@@ -136,7 +150,7 @@ impl OpcodeAllocateHeap {
     curr_p: &mut Process,
   ) -> RtResult<DispatchResult> {
     let (stack_need, heap_need, live) = Self::fetch_args(ctx);
-    shared_allocate(ctx, curr_p, stack_need, heap_need, live, false);
+    shared_allocate(ctx, curr_p, stack_need, heap_need, live, false)?;
     Ok(DispatchResult::Normal)
   }
 }
@@ -164,7 +178,7 @@ impl OpcodeDeallocate {
 
 /// Check that there are `heap_need` words available on heap, otherwise run the
 /// GC using `live` amount of registers as a part of root set.
-/// Structure: test_heap(heap_need:int, live:int)
+/// Structure: test_heap(heap_need:int|alloc_list, live:int)
 pub struct OpcodeTestHeap {}
 
 impl OpcodeTestHeap {
@@ -175,12 +189,12 @@ impl OpcodeTestHeap {
     ctx: &mut Context,
     curr_p: &mut Process,
   ) -> RtResult<DispatchResult> {
-    let heap_need = ctx.fetch_term().get_small_unsigned();
-    let _live = ctx.fetch_term().get_small_unsigned();
+    let heap_need = fetch_heap_need(ctx);
+    let live = ctx.fetch_term().get_small_unsigned();
 
     if !curr_p.heap.heap_has_available(heap_need) {
       // Heap has not enough, invoke GC and possibly fail
-      panic!("TODO GC here or fail");
+      curr_p.garbage_collect(ctx, heap_need, live)?;
     }
 
     Ok(DispatchResult::Normal)
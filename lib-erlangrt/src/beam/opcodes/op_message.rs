@@ -1,12 +1,17 @@
 use crate::{
   beam::disp_result::DispatchResult,
-  emulator::{process::Process, runtime_ctx::Context, vm::VM},
-  fail::{self, RtResult},
+  defs::{exc_type::ExceptionType, Reductions},
+  emulator::{
+    gen_atoms, mailbox::Message, process::Process, runtime_ctx::Context,
+    signal::Signal, vm::VM,
+  },
+  fail::{self, Error, RtResult},
   term::lterm::*,
 };
 
 /// Sends to x0 value x1, x1 is moved to x0 as result of the operation.
-/// If process with pid x0 does not exist, no error is raised.
+/// x0 is a pid or a registered name. If process with pid x0 does not exist,
+/// no error is raised, but an unregistered name is a `badarg`.
 /// Structure: send()
 pub struct OpcodeSend {}
 
@@ -17,18 +22,22 @@ impl OpcodeSend {
   pub fn run(
     vm: &mut VM,
     ctx: &mut Context,
-    _curr_p: &mut Process,
+    curr_p: &mut Process,
   ) -> RtResult<DispatchResult> {
-    let sched = vm.get_scheduler_p();
     let x1 = ctx.get_x(1);
-    if let Some(p) = unsafe {
-      let x0 = ctx.get_x(0);
-      if !x0.is_pid() {
-        return fail::create::badarg();
-      }
-      (*sched).lookup_pid_mut(x0)
-    } {
-      p.deliver_message(x1)?;
+    let x0 = match ctx.get_x(0) {
+      pid if pid.is_pid() => pid,
+      name if name.is_atom() => match vm.shared.whereis(name) {
+        Some(pid) => pid,
+        None => return fail::create::badarg(),
+      },
+      _ => return fail::create::badarg(),
+    };
+    ctx.consume_reductions(Reductions::SEND_COST);
+    if x0 == curr_p.pid {
+      curr_p.mailbox.put(send_copy(ctx, x1)?);
+    } else if vm.shared.is_process_alive(x0) {
+      vm.shared.send_signal(x0, Signal::Message(send_copy(ctx, x1)?));
     }
 
     ctx.set_x(0, x1);
@@ -36,6 +45,14 @@ impl OpcodeSend {
   }
 }
 
+/// Copy a message for sending, bigger messages cost more reductions.
+fn send_copy(ctx: &mut Context, term: LTerm) -> RtResult<Message> {
+  let message = Message::new(term)?;
+  let cost = Reductions::for_units(message.size(), Reductions::COPY_WORDS_PER_REDUCTION);
+  ctx.consume_reductions(cost);
+  Ok(message)
+}
+
 /// Picks up next message in the message queue and places it into `x0`.
 /// If there is no next message, jumps to `fail` label which points to a `wait`
 /// or `wait_timeout` instruction.
@@ -59,7 +76,15 @@ impl OpcodeLoopRec {
     curr_p: &mut Process,
   ) -> RtResult<DispatchResult> {
     let (fail, _source) = Self::fetch_args(ctx);
-    if let Some(msg) = curr_p.mailbox.get_current() {
+    let mut current = curr_p.mailbox.get_current();
+    if current.is_none() && curr_p.handle.has_incoming_signals() {
+      if let Some(reason) = curr_p.handle_signals()? {
+        curr_p.set_exception(ExceptionType::Exit, reason);
+        return Ok(DispatchResult::Exited);
+      }
+      current = curr_p.mailbox.get_current();
+    }
+    if let Some(msg) = current {
       ctx.set_x(0, msg);
     } else {
       ctx.jump(fail);
@@ -89,7 +114,9 @@ impl OpcodeLoopRecEnd {
   }
 }
 
-/// Removes the current message in the process message list and moves it to `x0`
+/// Removes the current message in the process message list and moves it to
+/// `x0`. The save pointer is reset to the beginning of the message queue and
+/// the receive timer is cancelled.
 /// Structure: remove_message()
 pub struct OpcodeRemoveMessage {}
 
@@ -98,18 +125,22 @@ impl OpcodeRemoveMessage {
 
   #[inline]
   pub fn run(
-    _vm: &mut VM,
+    vm: &mut VM,
     ctx: &mut Context,
     curr_p: &mut Process,
   ) -> RtResult<DispatchResult> {
-    let message = curr_p.mailbox.remove_current();
+    let sched = vm.get_scheduler_p();
+    unsafe { (*sched).cancel_receive_timer(curr_p) };
+    curr_p.timed_out = false;
+    let message = curr_p.mailbox.remove_current(&mut curr_p.heap);
     ctx.set_x(0, message);
     Ok(DispatchResult::Normal)
   }
 }
 
 /// Suspends the current process and sets the ip to the label (beginning of the
-/// receive loop).
+/// receive loop). The save pointer stays at the end of the message queue, so
+/// when the process wakes up only the new messages are examined.
 /// Structure: wait(label:cp)
 pub struct OpcodeWait {}
 
@@ -124,6 +155,119 @@ impl OpcodeWait {
   ) -> RtResult<DispatchResult> {
     let label = ctx.fetch_term();
     ctx.jump(label);
-    Ok(DispatchResult::Yield)
+    Ok(DispatchResult::Wait)
+  }
+}
+
+/// Suspends the current process like `wait` but also starts the receive timer,
+/// unless it is already running (a message has arrived and did not match).
+/// When the timer has fired, execution continues to the next instruction which
+/// is `timeout`. Timeout value is milliseconds or `infinity`.
+/// Structure: wait_timeout(label:cp, timeout:src)
+pub struct OpcodeWaitTimeout {}
+
+impl OpcodeWaitTimeout {
+  pub const ARITY: usize = 2;
+
+  #[inline]
+  fn fetch_args(ctx: &mut Context, curr_p: &mut Process) -> (LTerm, LTerm) {
+    let label = ctx.fetch_term();
+    let timeout = ctx.fetch_and_load(&curr_p.heap);
+    (label, timeout)
+  }
+
+  #[inline]
+  pub fn run(
+    vm: &mut VM,
+    ctx: &mut Context,
+    curr_p: &mut Process,
+  ) -> RtResult<DispatchResult> {
+    let (label, timeout) = Self::fetch_args(ctx, curr_p);
+    if curr_p.timed_out {
+      return Ok(DispatchResult::Normal);
+    }
+    if timeout == gen_atoms::INFINITY {
+      ctx.jump(label);
+      return Ok(DispatchResult::Wait);
+    }
+    if !timeout.is_small() || timeout.get_small_signed() < 0 {
+      return Err(Error::Exception(
+        ExceptionType::Error,
+        gen_atoms::TIMEOUT_VALUE,
+      ));
+    }
+    let timeout_ms = timeout.get_small_unsigned() as u64;
+    if timeout_ms == 0 {
+      return Ok(DispatchResult::Normal);
+    }
+    if curr_p.receive_timer.is_none() {
+      let sched = vm.get_scheduler_p();
+      unsafe { (*sched).start_receive_timer(curr_p, timeout_ms) };
+    }
+    ctx.jump(label);
+    Ok(DispatchResult::Wait)
+  }
+}
+
+/// Executed when the receive timer has fired (or the timeout was 0). Resets
+/// the save pointer to the beginning of the message queue and continues with
+/// the `after` clause of the `receive`.
+/// Structure: timeout()
+pub struct OpcodeTimeout {}
+
+impl OpcodeTimeout {
+  pub const ARITY: usize = 0;
+
+  #[inline]
+  pub fn run(
+    _vm: &mut VM,
+    _ctx: &mut Context,
+    curr_p: &mut Process,
+  ) -> RtResult<DispatchResult> {
+    curr_p.timed_out = false;
+    curr_p.mailbox.reset_save();
+    Ok(DispatchResult::Normal)
+  }
+}
+
+/// Saves the end of the message queue and the label, so that a `recv_set`
+/// with the same label can skip the messages which arrived before this point.
+/// Used by the compiler before creating a reference which a following
+/// `receive` will match on.
+/// Structure: recv_mark(label:cp)
+pub struct OpcodeRecvMark {}
+
+impl OpcodeRecvMark {
+  pub const ARITY: usize = 1;
+
+  #[inline]
+  pub fn run(
+    _vm: &mut VM,
+    ctx: &mut Context,
+    curr_p: &mut Process,
+  ) -> RtResult<DispatchResult> {
+    let label = ctx.fetch_term();
+    curr_p.mailbox.set_mark(label);
+    Ok(DispatchResult::Normal)
+  }
+}
+
+/// Moves the save pointer to the position saved by `recv_mark` with the same
+/// label, if there was one.
+/// Structure: recv_set(label:cp)
+pub struct OpcodeRecvSet {}
+
+impl OpcodeRecvSet {
+  pub const ARITY: usize = 1;
+
+  #[inline]
+  pub fn run(
+    _vm: &mut VM,
+    ctx: &mut Context,
+    curr_p: &mut Process,
+  ) -> RtResult<DispatchResult> {
+    let label = ctx.fetch_term();
+    curr_p.mailbox.set_save_to_mark(label);
+    Ok(DispatchResult::Normal)
   }
 }
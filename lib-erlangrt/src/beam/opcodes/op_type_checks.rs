@@ -4,7 +4,6 @@ use crate::{
   fail::{self, RtResult},
   term::{boxed, lterm::LTerm},
 };
-use num::Signed;
 
 /// Same function for all type check opcodes
 #[inline]
@@ -79,7 +78,7 @@ impl OpcodeIsFunction2 {
       unsafe {
         let big_p = boxed::Bignum::const_from_term(arity_t)?;
         // negative arity bignum is not ok
-        if (*big_p).value.is_negative() {
+        if boxed::Bignum::is_negative(big_p) {
           return fail::create::badarg();
         }
         // positive arity bignum is ok but can't possibly match
@@ -151,13 +150,39 @@ impl OpcodeIsTuple {
 }
 
 
-/// Checks that argument is a boxed binary or an empty binary.
+/// Checks that argument is a boxed binary or an empty binary. Bitstrings
+/// which have bit length not divisible by 8 are not binaries.
 /// Structure: is_binary(on_false:label, val:src)
 pub struct OpcodeIsBinary {}
 
 impl OpcodeIsBinary {
   pub const ARITY: usize = 2;
 
+  #[inline]
+  pub fn run(
+    _vm: &mut VM,
+    ctx: &mut Context,
+    curr_p: &mut Process,
+  ) -> RtResult<DispatchResult> {
+    let (fail_label, val) = fetch_args(ctx, curr_p);
+    if val != LTerm::empty_binary()
+      && !(val.is_binary()
+        && unsafe { boxed::Binary::is_byte_sized(val.get_box_ptr()) })
+    {
+      ctx.jump(fail_label)
+    }
+    Ok(DispatchResult::Normal)
+  }
+}
+
+
+/// Checks that argument is a boxed binary, a bitstring or an empty binary.
+/// Structure: is_bitstr(on_false:label, val:src)
+pub struct OpcodeIsBitstr {}
+
+impl OpcodeIsBitstr {
+  pub const ARITY: usize = 2;
+
   #[inline]
   pub fn run(
     _vm: &mut VM,
@@ -302,3 +327,24 @@ impl OpcodeIsList {
     Ok(DispatchResult::Normal)
   }
 }
+
+/// Checks that argument is a map, otherwise jumps to label.
+/// Structure: is_map(on_false:label, val:src)
+pub struct OpcodeIsMap {}
+
+impl OpcodeIsMap {
+  pub const ARITY: usize = 2;
+
+  #[inline]
+  pub fn run(
+    _vm: &mut VM,
+    ctx: &mut Context,
+    curr_p: &mut Process,
+  ) -> RtResult<DispatchResult> {
+    let (fail_label, val) = fetch_args(ctx, curr_p);
+    if !val.is_map() {
+      ctx.jump(fail_label)
+    }
+    Ok(DispatchResult::Normal)
+  }
+}
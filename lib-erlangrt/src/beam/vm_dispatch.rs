@@ -107,6 +107,11 @@ pub fn dispatch_op_inline(vm: &mut VM, op: RawOpcode, ctx: &mut Context, curr_p:
       return OpcodeRemoveMessage::run(vm, ctx, curr_p);
     },
 
+    OPCODE_TIMEOUT => {
+      assert_arity(OPCODE_TIMEOUT, OpcodeTimeout::ARITY);
+      return OpcodeTimeout::run(vm, ctx, curr_p);
+    },
+
     OPCODE_LOOP_REC => {
       assert_arity(OPCODE_LOOP_REC, OpcodeLoopRec::ARITY);
       return OpcodeLoopRec::run(vm, ctx, curr_p);
@@ -122,6 +127,11 @@ pub fn dispatch_op_inline(vm: &mut VM, op: RawOpcode, ctx: &mut Context, curr_p:
       return OpcodeWait::run(vm, ctx, curr_p);
     },
 
+    OPCODE_WAIT_TIMEOUT => {
+      assert_arity(OPCODE_WAIT_TIMEOUT, OpcodeWaitTimeout::ARITY);
+      return OpcodeWaitTimeout::run(vm, ctx, curr_p);
+    },
+
     OPCODE_IS_LT => {
       assert_arity(OPCODE_IS_LT, OpcodeIsLt::ARITY);
       return OpcodeIsLt::run(vm, ctx, curr_p);
@@ -267,6 +277,51 @@ pub fn dispatch_op_inline(vm: &mut VM, op: RawOpcode, ctx: &mut Context, curr_p:
       return OpcodeCallExtOnly::run(vm, ctx, curr_p);
     },
 
+    OPCODE_FCLEARERROR => {
+      assert_arity(OPCODE_FCLEARERROR, OpcodeFclearerror::ARITY);
+      return OpcodeFclearerror::run(vm, ctx, curr_p);
+    },
+
+    OPCODE_FCHECKERROR => {
+      assert_arity(OPCODE_FCHECKERROR, OpcodeFcheckerror::ARITY);
+      return OpcodeFcheckerror::run(vm, ctx, curr_p);
+    },
+
+    OPCODE_FMOVE => {
+      assert_arity(OPCODE_FMOVE, OpcodeFmove::ARITY);
+      return OpcodeFmove::run(vm, ctx, curr_p);
+    },
+
+    OPCODE_FCONV => {
+      assert_arity(OPCODE_FCONV, OpcodeFconv::ARITY);
+      return OpcodeFconv::run(vm, ctx, curr_p);
+    },
+
+    OPCODE_FADD => {
+      assert_arity(OPCODE_FADD, OpcodeFadd::ARITY);
+      return OpcodeFadd::run(vm, ctx, curr_p);
+    },
+
+    OPCODE_FSUB => {
+      assert_arity(OPCODE_FSUB, OpcodeFsub::ARITY);
+      return OpcodeFsub::run(vm, ctx, curr_p);
+    },
+
+    OPCODE_FMUL => {
+      assert_arity(OPCODE_FMUL, OpcodeFmul::ARITY);
+      return OpcodeFmul::run(vm, ctx, curr_p);
+    },
+
+    OPCODE_FDIV => {
+      assert_arity(OPCODE_FDIV, OpcodeFdiv::ARITY);
+      return OpcodeFdiv::run(vm, ctx, curr_p);
+    },
+
+    OPCODE_FNEGATE => {
+      assert_arity(OPCODE_FNEGATE, OpcodeFnegate::ARITY);
+      return OpcodeFnegate::run(vm, ctx, curr_p);
+    },
+
     OPCODE_MAKE_FUN2 => {
       assert_arity(OPCODE_MAKE_FUN2, OpcodeMakeFun2::ARITY);
       return OpcodeMakeFun2::run(vm, ctx, curr_p);
@@ -317,16 +372,56 @@ pub fn dispatch_op_inline(vm: &mut VM, op: RawOpcode, ctx: &mut Context, curr_p:
       return OpcodeGcBif2::run(vm, ctx, curr_p);
     },
 
+    OPCODE_IS_BITSTR => {
+      assert_arity(OPCODE_IS_BITSTR, OpcodeIsBitstr::ARITY);
+      return OpcodeIsBitstr::run(vm, ctx, curr_p);
+    },
+
     OPCODE_TRIM => {
       assert_arity(OPCODE_TRIM, OpcodeTrim::ARITY);
       return OpcodeTrim::run(vm, ctx, curr_p);
     },
 
+    OPCODE_RECV_MARK => {
+      assert_arity(OPCODE_RECV_MARK, OpcodeRecvMark::ARITY);
+      return OpcodeRecvMark::run(vm, ctx, curr_p);
+    },
+
+    OPCODE_RECV_SET => {
+      assert_arity(OPCODE_RECV_SET, OpcodeRecvSet::ARITY);
+      return OpcodeRecvSet::run(vm, ctx, curr_p);
+    },
+
     OPCODE_GC_BIF3 => {
       assert_arity(OPCODE_GC_BIF3, OpcodeGcBif3::ARITY);
       return OpcodeGcBif3::run(vm, ctx, curr_p);
     },
 
+    OPCODE_PUT_MAP_ASSOC => {
+      assert_arity(OPCODE_PUT_MAP_ASSOC, OpcodePutMapAssoc::ARITY);
+      return OpcodePutMapAssoc::run(vm, ctx, curr_p);
+    },
+
+    OPCODE_PUT_MAP_EXACT => {
+      assert_arity(OPCODE_PUT_MAP_EXACT, OpcodePutMapExact::ARITY);
+      return OpcodePutMapExact::run(vm, ctx, curr_p);
+    },
+
+    OPCODE_IS_MAP => {
+      assert_arity(OPCODE_IS_MAP, OpcodeIsMap::ARITY);
+      return OpcodeIsMap::run(vm, ctx, curr_p);
+    },
+
+    OPCODE_HAS_MAP_FIELDS => {
+      assert_arity(OPCODE_HAS_MAP_FIELDS, OpcodeHasMapFields::ARITY);
+      return OpcodeHasMapFields::run(vm, ctx, curr_p);
+    },
+
+    OPCODE_GET_MAP_ELEMENTS => {
+      assert_arity(OPCODE_GET_MAP_ELEMENTS, OpcodeGetMapElements::ARITY);
+      return OpcodeGetMapElements::run(vm, ctx, curr_p);
+    },
+
     OPCODE_IS_TAGGED_TUPLE => {
       assert_arity(OPCODE_IS_TAGGED_TUPLE, OpcodeIsTaggedTuple::ARITY);
       return OpcodeIsTaggedTuple::run(vm, ctx, curr_p);
@@ -1,7 +1,8 @@
 use crate::{
   beam::{disp_result::DispatchResult, gen_op, vm_dispatch::dispatch_op_inline},
-  emulator::{disasm, scheduler::SliceResult, vm::VM},
-  fail::{Error, RtResult},
+  defs::exc_type::ExceptionType,
+  emulator::{disasm, runtime_ctx::call_bif, scheduler::SliceResult, vm::VM},
+  fail::{self, Error, RtResult},
 };
 
 // fn module() -> &'static str { "vm_loop: " }
@@ -18,7 +19,7 @@ impl VM {
     let scheduler = self.get_scheduler_p();
     let curr_p = match unsafe { (*scheduler).next_process() } {
       None => return Ok(false),
-      Some(p) => unsafe { (*scheduler).lookup_pid_mut(p).unwrap() },
+      Some(p) => unsafe { &mut (*p) },
     };
 
     // Ugly borrowing the context from the process, but we guarantee that the
@@ -29,35 +30,59 @@ impl VM {
     ctx.swap_in(); // tell the context, that it is active now
                    // curr_p.heap.print_stack();
 
-    let cs = self.get_code_server_p();
-
     // Fetch some opcodes, Execute some opcodes
     //
     loop {
-      if cfg!(feature = "trace_opcode_execution") {
-        print!("   ↳ ");
-        unsafe {
-          disasm::disasm_op(ctx.ip.get_pointer(), &(*cs));
+      let disp_result = if let Some(trap) = curr_p.bif_trap.take() {
+        // A long BIF has used up the previous time slice, let it continue
+        call_bif::resume_trapped_bif(self, ctx, curr_p, trap)
+      } else {
+        if cfg!(feature = "trace_opcode_execution") {
+          print!("   ↳ ");
+          unsafe {
+            disasm::disasm_op(ctx.ip.get_pointer(), &self.code_server());
+          }
         }
-      }
 
-      // Take next opcode
-      let op = ctx.fetch_opcode();
-      debug_assert!(
-        op <= gen_op::OPCODE_MAX,
-        "Opcode too big (wrong memory address?) got 0x{:x}",
-        op.get()
-      );
+        // Take next opcode
+        let op = ctx.fetch_opcode();
+        debug_assert!(
+          op <= gen_op::OPCODE_MAX,
+          "Opcode too big (wrong memory address?) got 0x{:x}",
+          op.get()
+        );
 
-      // Handle next opcode
-      let disp_result = match dispatch_op_inline(self, op, &mut ctx, curr_p) {
+        // Handle next opcode
+        dispatch_op_inline(self, op, &mut ctx, curr_p)
+      };
+
+      let disp_result = match disp_result {
         Err(Error::Exception(exc_type, exc_reason)) => {
           println!("vm: Exception type={} reason={}", exc_type, exc_reason);
           curr_p.set_exception(exc_type, exc_reason);
           curr_p.timeslice_result = SliceResult::Exception;
           return Ok(true);
         }
-        other => other?,
+        Err(Error::BifTrap) => {
+          // The BIF state is saved in the process, run something else
+          curr_p.timeslice_result = SliceResult::Yield;
+          return Ok(true);
+        }
+        Err(Error::Hibernate) => {
+          // Any message wakes the process up, see `Scheduler::enqueue_wait`
+          curr_p.timeslice_result = SliceResult::Wait;
+          return Ok(true);
+        }
+        Err(e) => {
+          // Not an Erlang exception but a failure inside the emulator, it
+          // kills the process instead of the scheduler
+          println!("vm: Process {} failed with {:?}", curr_p.pid, e);
+          let reason = fail::create::emulator_error_reason(&e, &mut curr_p.heap);
+          curr_p.set_exception(ExceptionType::Exit, reason);
+          curr_p.timeslice_result = SliceResult::Exited;
+          return Ok(true);
+        }
+        Ok(r) => r,
       };
 
       match disp_result {
@@ -66,6 +91,10 @@ impl VM {
           curr_p.timeslice_result = SliceResult::Yield;
           return Ok(true);
         }
+        DispatchResult::Wait => {
+          curr_p.timeslice_result = SliceResult::Wait;
+          return Ok(true);
+        }
         DispatchResult::Normal => {
           // curr_p.timeslice_result = SliceResult::None;
         } // keep looping
@@ -74,12 +103,10 @@ impl VM {
           curr_p.timeslice_result = SliceResult::Finished;
           return Ok(true);
         }
-      }
-
-      // TODO: this seems to not work?
-      if self.scheduler.get_process_count() == 0 {
-        println!("All processes finished, this is the end.");
-        return Ok(false);
+        DispatchResult::Exited => {
+          curr_p.timeslice_result = SliceResult::Exited;
+          return Ok(true);
+        }
       }
 
       if ctx.reductions <= 0 {
@@ -1,12 +1,23 @@
+//! Arithmetic BIFs. Integer operations are implemented in `emulator::arith`
+//! for any combination of small integers and bignums, results which do not
+//! fit a small integer are promoted to bignums and bignum results which fit
+//! are demoted back. If any argument is a float, the float arithmetic is used.
+//! When the process heap is full the result is built in a heap fragment.
 use crate::{
-  emulator::{arith::multiplication, process::Process, vm::VM},
-  fail::RtResult,
-  term::{boxed, lterm::*},
+  bif::assert_arity,
+  emulator::{
+    arith::{big::BigInteger, float, integer, multiplication},
+    heap,
+    process::Process,
+    vm::VM,
+  },
+  fail::{self, RtResult},
+  term::lterm::*,
 };
-use num;
 
-fn module() -> &'static str {
-  "bif_arith: "
+#[inline]
+fn any_float(a: LTerm, b: LTerm) -> bool {
+  a.is_float() || b.is_float()
 }
 
 /// Subtraction for 2 mixed terms. Algorithm comes from Erlang/OTP file
@@ -16,19 +27,32 @@ pub fn ubif_erlang_minus_2(
   cur_proc: &mut Process,
   args: &[LTerm],
 ) -> RtResult<LTerm> {
-  assert_eq!(args.len(), 2, "{}ubif_sminus_2_2 takes 2 args", module());
-  let a: LTerm = args[0];
-  let b: LTerm = args[1];
-  if a.is_small() {
-    if b.is_small() {
-      subtract_two_small(cur_proc, a, b)
+  assert_arity("erlang:-", 2, args);
+  let (a, b) = (args[0], args[1]);
+  heap::build_on_heap(&mut cur_proc.heap, |hp| {
+    if any_float(a, b) {
+      float::subtract(hp, a, b)
     } else {
-      // TODO: See Erlang OTP erl_arith.c function erts_mixed_minus
-      panic!("{}subtract: b={} other than small notimpl", module(), b)
+      integer::subtract(hp, a, b)
     }
-  } else {
-    panic!("{}subtract: a={} other than small notimpl", module(), a)
-  }
+  })
+}
+
+/// Unary minus.
+pub fn ubif_erlang_minus_1(
+  _vm: &mut VM,
+  cur_proc: &mut Process,
+  args: &[LTerm],
+) -> RtResult<LTerm> {
+  assert_arity("erlang:-", 1, args);
+  let a = args[0];
+  heap::build_on_heap(&mut cur_proc.heap, |hp| {
+    if a.is_float() {
+      float::negate(hp, a)
+    } else {
+      integer::negate(hp, a)
+    }
+  })
 }
 
 /// Addition for 2 mixed terms.
@@ -37,68 +61,188 @@ pub fn ubif_erlang_plus_2(
   cur_proc: &mut Process,
   args: &[LTerm],
 ) -> RtResult<LTerm> {
-  assert_eq!(args.len(), 2, "{}ubif_sminus_2_2 takes 2 args", module());
-  let a: LTerm = args[0];
-  let b: LTerm = args[1];
-  if a.is_small() {
-    if b.is_small() {
-      add_two_small(cur_proc, a, b)
+  assert_arity("erlang:+", 2, args);
+  let (a, b) = (args[0], args[1]);
+  heap::build_on_heap(&mut cur_proc.heap, |hp| {
+    if any_float(a, b) {
+      float::add(hp, a, b)
     } else {
-      // TODO: See Erlang OTP erl_arith.c function erts_mixed_plus
-      panic!("{}subtract: b={} other than small notimpl", module(), b)
+      integer::add(hp, a, b)
     }
-  } else {
-    panic!("{}subtract: a={} other than small notimpl", module(), a)
-  }
+  })
 }
 
-/// So the check above has concluded that `a` and `b` are both small integers.
-/// Implement subtraction, possibly creating a big integer.
-fn subtract_two_small(cur_proc: &mut Process, a: LTerm, b: LTerm) -> RtResult<LTerm> {
-  // Both a and b are small, we've got an easy time
-  let iresult = a.get_small_signed() - b.get_small_signed();
-  // Even better: the result is also a small
-  if LTerm::small_fits(iresult) {
-    return Ok(LTerm::make_small_signed(iresult));
-  }
+/// Multiplication for 2 mixed terms.
+pub fn ubif_erlang_multiply_2(
+  _vm: &mut VM,
+  cur_proc: &mut Process,
+  args: &[LTerm],
+) -> RtResult<LTerm> {
+  assert_arity("erlang:*", 2, args);
+  let (a, b) = (args[0], args[1]);
+  heap::build_on_heap(&mut cur_proc.heap, |hp| {
+    if any_float(a, b) {
+      float::multiply(hp, a, b)
+    } else {
+      multiplication::multiply(hp, a, b)
+    }
+  })
+}
 
-  create_bigint(cur_proc, iresult)
+/// Division, the result is always a float.
+pub fn ubif_erlang_divide_2(
+  _vm: &mut VM,
+  cur_proc: &mut Process,
+  args: &[LTerm],
+) -> RtResult<LTerm> {
+  assert_arity("erlang:/", 2, args);
+  heap::build_on_heap(&mut cur_proc.heap, |hp| float::divide(hp, args[0], args[1]))
 }
 
-/// So the check above has concluded that `a` and `b` are both small integers.
-/// Implement addition, possibly creating a big integer.
-fn add_two_small(cur_proc: &mut Process, a: LTerm, b: LTerm) -> RtResult<LTerm> {
-  // Both a and b are small, we've got an easy time.
-  // The overflow in addition of two smalls will always fit Rust integer because
-  // small use less bits than a Rust integer.
-  let iresult = a.get_small_signed() + b.get_small_signed();
-  // Even better: the result is also a small
-  if LTerm::small_fits(iresult) {
-    return Ok(LTerm::make_small_signed(iresult));
+/// Integer division, truncated towards zero.
+pub fn ubif_erlang_int_div_2(
+  _vm: &mut VM,
+  cur_proc: &mut Process,
+  args: &[LTerm],
+) -> RtResult<LTerm> {
+  assert_arity("erlang:div", 2, args);
+  heap::build_on_heap(&mut cur_proc.heap, |hp| integer::int_div(hp, args[0], args[1]))
+}
+
+/// Remainder of the integer division, has the sign of the dividend.
+pub fn ubif_erlang_rem_2(
+  _vm: &mut VM,
+  cur_proc: &mut Process,
+  args: &[LTerm],
+) -> RtResult<LTerm> {
+  assert_arity("erlang:rem", 2, args);
+  heap::build_on_heap(&mut cur_proc.heap, |hp| integer::rem(hp, args[0], args[1]))
+}
+
+pub fn ubif_erlang_abs_1(
+  _vm: &mut VM,
+  cur_proc: &mut Process,
+  args: &[LTerm],
+) -> RtResult<LTerm> {
+  assert_arity("erlang:abs", 1, args);
+  let a = args[0];
+  heap::build_on_heap(&mut cur_proc.heap, |hp| {
+    if a.is_float() {
+      float::abs(hp, a)
+    } else {
+      integer::abs(hp, a)
+    }
+  })
+}
+
+pub fn ubif_erlang_band_2(
+  _vm: &mut VM,
+  cur_proc: &mut Process,
+  args: &[LTerm],
+) -> RtResult<LTerm> {
+  assert_arity("erlang:band", 2, args);
+  heap::build_on_heap(&mut cur_proc.heap, |hp| integer::band(hp, args[0], args[1]))
+}
+
+pub fn ubif_erlang_bor_2(
+  _vm: &mut VM,
+  cur_proc: &mut Process,
+  args: &[LTerm],
+) -> RtResult<LTerm> {
+  assert_arity("erlang:bor", 2, args);
+  heap::build_on_heap(&mut cur_proc.heap, |hp| integer::bor(hp, args[0], args[1]))
+}
+
+pub fn ubif_erlang_bxor_2(
+  _vm: &mut VM,
+  cur_proc: &mut Process,
+  args: &[LTerm],
+) -> RtResult<LTerm> {
+  assert_arity("erlang:bxor", 2, args);
+  heap::build_on_heap(&mut cur_proc.heap, |hp| integer::bxor(hp, args[0], args[1]))
+}
+
+pub fn ubif_erlang_bnot_1(
+  _vm: &mut VM,
+  cur_proc: &mut Process,
+  args: &[LTerm],
+) -> RtResult<LTerm> {
+  assert_arity("erlang:bnot", 1, args);
+  heap::build_on_heap(&mut cur_proc.heap, |hp| integer::bnot(hp, args[0]))
+}
+
+pub fn ubif_erlang_bsl_2(
+  _vm: &mut VM,
+  cur_proc: &mut Process,
+  args: &[LTerm],
+) -> RtResult<LTerm> {
+  assert_arity("erlang:bsl", 2, args);
+  heap::build_on_heap(&mut cur_proc.heap, |hp| integer::bsl(hp, args[0], args[1]))
+}
+
+pub fn ubif_erlang_bsr_2(
+  _vm: &mut VM,
+  cur_proc: &mut Process,
+  args: &[LTerm],
+) -> RtResult<LTerm> {
+  assert_arity("erlang:bsr", 2, args);
+  heap::build_on_heap(&mut cur_proc.heap, |hp| integer::bsr(hp, args[0], args[1]))
+}
+
+/// Convert a number to float.
+pub fn ubif_erlang_float_1(
+  _vm: &mut VM,
+  cur_proc: &mut Process,
+  args: &[LTerm],
+) -> RtResult<LTerm> {
+  assert_arity("erlang:float", 1, args);
+  let a = args[0];
+  if a.is_float() {
+    return Ok(a);
+  }
+  match float::to_f64(a) {
+    Some(val) if val.is_finite() => {
+      heap::build_on_heap(&mut cur_proc.heap, |hp| float::make_float(hp, val))
+    }
+    _ => fail::create::badarg(),
   }
-  create_bigint(cur_proc, iresult)
 }
 
-/// Multiplication for 2 mixed terms.
-pub fn ubif_erlang_multiply_2(
+/// Drop the fractional part of a number.
+pub fn ubif_erlang_trunc_1(
   _vm: &mut VM,
   cur_proc: &mut Process,
   args: &[LTerm],
 ) -> RtResult<LTerm> {
-  assert_eq!(args.len(), 2, "{}ubif_stimes_2_2 takes 2 args", module());
-  let a: LTerm = args[0];
-  let b: LTerm = args[1];
-  return multiplication::multiply(&mut cur_proc.heap, a, b);
+  assert_arity("erlang:trunc", 1, args);
+  float_to_integer(cur_proc, args[0], f64::trunc)
 }
 
-// TODO: shorten, use only heap of the process, inline, move to a lib module in arith
-fn create_bigint(cur_proc: &mut Process, iresult: isize) -> RtResult<LTerm> {
-  // We're out of luck - the result is not a small, but we have BigInt!
-  let big = num::BigInt::from(iresult);
-  // Place a new BigInt on heap
-  // TODO: Make a tool function for this, also ext_term_format:decode_big
-  let heap = &mut cur_proc.heap;
-  let rbig_result = unsafe { boxed::Bignum::create_into(heap, big)? };
+/// Round a number to the nearest integer, halfway cases away from zero.
+pub fn ubif_erlang_round_1(
+  _vm: &mut VM,
+  cur_proc: &mut Process,
+  args: &[LTerm],
+) -> RtResult<LTerm> {
+  assert_arity("erlang:round", 1, args);
+  float_to_integer(cur_proc, args[0], f64::round)
+}
 
-  Ok(LTerm::make_boxed(rbig_result))
+/// Apply `rounding` to a float and convert it to an integer, integers are
+/// returned unchanged.
+fn float_to_integer(
+  cur_proc: &mut Process,
+  a: LTerm,
+  rounding: fn(f64) -> f64,
+) -> RtResult<LTerm> {
+  if a.is_integer() {
+    return Ok(a);
+  }
+  if !a.is_float() {
+    return fail::create::badarg();
+  }
+  let val = rounding(unsafe { a.get_f64_unsafe() });
+  heap::build_on_heap(&mut cur_proc.heap, |hp| {
+    BigInteger::from_f64_trunc(val).to_term(hp)
+  })
 }
@@ -0,0 +1,85 @@
+use crate::{
+  bif::assert_arity,
+  emulator::{process::Process, vm::VM},
+  fail::{self, RtResult},
+  term::{boxed, lterm::*},
+};
+
+/// For a binary term return its size in bytes. Bitstrings and other terms
+/// are not accepted.
+fn get_byte_size(bin: LTerm) -> Option<usize> {
+  if bin == LTerm::empty_binary() {
+    return Some(0);
+  }
+  if !bin.is_binary() {
+    return None;
+  }
+  let bin_p = bin.get_box_ptr::<boxed::Binary>();
+  unsafe {
+    if !boxed::Binary::is_byte_sized(bin_p) {
+      return None;
+    }
+    Some(boxed::Binary::get_size(bin_p).bytes())
+  }
+}
+
+/// Split a binary at the given byte position into two sub-binaries without
+/// copying the data, returns a pair `{Bin1, Bin2}`.
+pub fn bif_erlang_split_binary_2(
+  _vm: &mut VM,
+  curr_p: &mut Process,
+  args: &[LTerm],
+) -> RtResult<LTerm> {
+  assert_arity("erlang:split_binary", 2, args);
+  let bin = args[0];
+  let size = match get_byte_size(bin) {
+    Some(s) => s,
+    None => return fail::create::badarg(),
+  };
+  if !args[1].is_small() || args[1].get_small_signed() < 0 {
+    return fail::create::badarg();
+  }
+  let pos = args[1].get_small_unsigned();
+  if pos > size {
+    return fail::create::badarg();
+  }
+
+  let hp = &mut curr_p.heap;
+  let (part1, part2) = unsafe {
+    (
+      boxed::Binary::create_sub(hp, bin, 0, pos * 8)?,
+      boxed::Binary::create_sub(hp, bin, pos * 8, (size - pos) * 8)?,
+    )
+  };
+  tuple::make_tuple2(part1, part2, hp)
+}
+
+/// Extract a part of a binary as a sub-binary. `Len` can be negative, then
+/// the part is taken backwards from `Pos`.
+pub fn bif_binary_part_3(
+  _vm: &mut VM,
+  curr_p: &mut Process,
+  args: &[LTerm],
+) -> RtResult<LTerm> {
+  assert_arity("binary:part", 3, args);
+  let bin = args[0];
+  let size = match get_byte_size(bin) {
+    Some(s) => s as isize,
+    None => return fail::create::badarg(),
+  };
+  if !args[1].is_small() || !args[2].is_small() {
+    return fail::create::badarg();
+  }
+  let mut pos = args[1].get_small_signed();
+  let mut len = args[2].get_small_signed();
+  if len < 0 {
+    pos += len;
+    len = -len;
+  }
+  if pos < 0 || pos + len > size {
+    return fail::create::badarg();
+  }
+
+  let hp = &mut curr_p.heap;
+  unsafe { boxed::Binary::create_sub(hp, bin, pos as usize * 8, len as usize * 8) }
+}
@@ -1,6 +1,11 @@
 use crate::{
   bif::assert_arity,
-  emulator::{process::Process, vm::VM},
+  defs::Reductions,
+  emulator::{
+    heap::{self, allocate_cons, Heap},
+    process::Process,
+    vm::VM,
+  },
   fail::{self, RtResult},
   term::{compare, lterm::*},
 };
@@ -10,20 +15,87 @@ use core::cmp::Ordering;
 //  "bif_compare: "
 //}
 
+/// How a walk over a list (`walk_list`) has ended.
+enum ListWalk {
+  /// The function has returned true for an element
+  Found,
+  /// The end of the list was reached, this is the tail (NIL for proper lists)
+  End(LTerm),
+  /// The reductions have run out, this is the rest of the list to continue
+  Rest(LTerm),
+}
+
+/// Run `func` for the elements of a list, until it returns true or the
+/// reductions left in the time slice are used up. At least one reduction worth
+/// of elements is always processed, so that the BIF makes progress. The
+/// process is charged for the walked elements.
+/// Returns how the walk has ended and how many elements were walked.
+fn walk_list<F>(
+  cur_proc: &mut Process,
+  list: LTerm,
+  mut func: F,
+) -> RtResult<(ListWalk, usize)>
+where
+  F: FnMut(&mut Heap, LTerm) -> RtResult<bool>,
+{
+  let reductions = cur_proc.context.reductions.max(1) as usize;
+  let budget = reductions * Reductions::LIST_ELEMENTS_PER_REDUCTION;
+  let mut walked = 0;
+  let mut rest = list;
+  let result = loop {
+    if !rest.is_cons() {
+      break ListWalk::End(rest);
+    }
+    if walked == budget {
+      break ListWalk::Rest(rest);
+    }
+    let p = rest.get_cons_ptr();
+    walked += 1;
+    if func(&mut cur_proc.heap, unsafe { (*p).hd() })? {
+      break ListWalk::Found;
+    }
+    rest = unsafe { (*p).tl() };
+  };
+  let cost = Reductions::for_units(walked, Reductions::LIST_ELEMENTS_PER_REDUCTION);
+  cur_proc.context.consume_reductions(cost);
+  Ok((result, walked))
+}
+
 /// Calculate length of a list by traversing it.
 pub fn gcbif_erlang_length_1(
   _vm: &mut VM,
-  _cur_proc: &mut Process,
+  cur_proc: &mut Process,
   args: &[LTerm],
 ) -> RtResult<LTerm> {
   assert_arity("erlang:length", 1, args);
+  if !args[0].is_list() {
+    return fail::create::badarg();
+  }
+  length(cur_proc, args[0], 0)
+}
 
-  let result = cons::list_length(args[0])?;
-  Ok(LTerm::make_small_unsigned(result))
+/// Continue `length/1` with the rest of the list and the count so far.
+fn length_trap(_vm: &mut VM, cur_proc: &mut Process, args: &[LTerm]) -> RtResult<LTerm> {
+  length(cur_proc, args[0], args[1].get_small_unsigned())
+}
+
+fn length(cur_proc: &mut Process, list: LTerm, count: usize) -> RtResult<LTerm> {
+  let (walk, walked) = walk_list(cur_proc, list, |_, _| Ok(false))?;
+  let count = count + walked;
+  match walk {
+    ListWalk::End(tail) if tail == LTerm::nil() => Ok(LTerm::make_small_unsigned(count)),
+    ListWalk::Rest(rest) => {
+      cur_proc.trap(length_trap, &[rest, LTerm::make_small_unsigned(count)])
+    }
+    _ => fail::create::badarg(),
+  }
 }
 
 /// Calculate a new list made of two lists joined together.
 /// Arg1 must be list or NIL.
+/// Like in ERTS the first list is copied in reverse order and then the copy
+/// is reversed again onto the second list, so both steps can trap without
+/// modifying any cells which were already built.
 pub fn bif_erlang_plusplus_2(
   _vm: &mut VM,
   curr_p: &mut Process,
@@ -40,17 +112,88 @@ pub fn bif_erlang_plusplus_2(
     return Ok(args[1]);
   }
 
-  // Copy the list args[0] without setting its tail, ...
-  let hp = &mut curr_p.heap;
-  let (l1, tail) = unsafe { cons::copy_list_leave_tail(args[0], hp) }?;
+  plusplus_reverse(curr_p, args[0], LTerm::nil(), args[1])
+}
 
-  // then append the tail
-  unsafe {
-    (*tail).set_tl(args[1]);
+/// Continue reversing the first list of `++`.
+fn plusplus_reverse_trap(
+  _vm: &mut VM,
+  curr_p: &mut Process,
+  args: &[LTerm],
+) -> RtResult<LTerm> {
+  plusplus_reverse(curr_p, args[0], args[1], args[2])
+}
+
+/// Prepend the elements of `list` in reverse order to `reversed`, then
+/// continue with `plusplus_build`.
+fn plusplus_reverse(
+  curr_p: &mut Process,
+  list: LTerm,
+  reversed: LTerm,
+  tail: LTerm,
+) -> RtResult<LTerm> {
+  let mut elems = Vec::new();
+  let (walk, _) = walk_list(curr_p, list, |_, elem| {
+    elems.push(elem);
+    Ok(false)
+  })?;
+  let reversed = prepend_slice(&mut curr_p.heap, &elems, reversed)?;
+  match walk {
+    ListWalk::End(end) if end == LTerm::nil() => plusplus_build(curr_p, reversed, tail),
+    ListWalk::Rest(rest) => curr_p.trap(plusplus_reverse_trap, &[rest, reversed, tail]),
+    _ => fail::create::badarg(),
   }
+}
 
-  // Return what we got joined together
-  Ok(l1)
+/// Continue building the result of `++`.
+fn plusplus_build_trap(
+  _vm: &mut VM,
+  curr_p: &mut Process,
+  args: &[LTerm],
+) -> RtResult<LTerm> {
+  plusplus_build(curr_p, args[0], args[1])
+}
+
+/// Prepend the elements of the reversed first list to the result.
+fn plusplus_build(
+  curr_p: &mut Process,
+  reversed: LTerm,
+  result: LTerm,
+) -> RtResult<LTerm> {
+  let mut elems = Vec::new();
+  let (walk, _) = walk_list(curr_p, reversed, |_, elem| {
+    elems.push(elem);
+    Ok(false)
+  })?;
+  let result = prepend_slice(&mut curr_p.heap, &elems, result)?;
+  match walk {
+    ListWalk::Rest(rest) => curr_p.trap(plusplus_build_trap, &[rest, result]),
+    _ => Ok(result),
+  }
+}
+
+/// Prepend `elems` one by one to `tail`, so that they appear in reverse order
+/// in the result. The cells of one time slice are built with
+/// `heap::build_on_heap`, because no garbage collection can run between the
+/// slices of a trapping BIF.
+fn prepend_slice(hp: &mut Heap, elems: &[LTerm], tail: LTerm) -> RtResult<LTerm> {
+  heap::build_on_heap(hp, |hp| {
+    let mut result = tail;
+    for elem in elems {
+      result = prepend(*elem, result, hp)?;
+    }
+    Ok(result)
+  })
+}
+
+/// Create a new cell `[elem | tail]`.
+fn prepend(elem: LTerm, tail: LTerm, hp: &mut Heap) -> RtResult<LTerm> {
+  let p = allocate_cons(hp)?;
+  unsafe {
+    (*p).set_hd(elem);
+    (*p).set_tl(tail);
+  }
+  Ok(LTerm::make_cons(p))
 }
 
 /// Takes head of a cons value, otherwise returns badarg.
@@ -81,26 +224,70 @@ pub fn ubif_erlang_tl_1(
   unsafe { Ok((*p).tl()) }
 }
 
+/// Check whether the element is in the list, walking it until found.
 pub fn bif_lists_member_2(
   _vm: &mut VM,
-  _curr_p: &mut Process,
+  curr_p: &mut Process,
   args: &[LTerm],
 ) -> RtResult<LTerm> {
   assert_arity("lists:member", 2, args);
-  let list = args[1];
-  if list == LTerm::nil() {
-    return Ok(LTerm::make_bool(false));
-  }
-  if !list.is_cons() {
+  if !args[1].is_list() {
     return fail::create::badarg();
   }
-  let sample = args[0];
-  let result = cons::any(list, |elem| {
+  member(curr_p, args[0], args[1])
+}
+
+/// Continue `lists:member/2` with the rest of the list.
+fn member_trap(_vm: &mut VM, curr_p: &mut Process, args: &[LTerm]) -> RtResult<LTerm> {
+  member(curr_p, args[0], args[1])
+}
+
+fn member(curr_p: &mut Process, sample: LTerm, list: LTerm) -> RtResult<LTerm> {
+  let (walk, _) = walk_list(curr_p, list, |_, elem| {
     let cmp_result = compare::cmp_terms(sample, elem, true);
-    if cmp_result.is_err() {
-      return false;
+    Ok(cmp_result.map(|o| o == Ordering::Equal).unwrap_or(false))
+  })?;
+  match walk {
+    ListWalk::Found => Ok(LTerm::make_bool(true)),
+    ListWalk::End(tail) if tail == LTerm::nil() => Ok(LTerm::make_bool(false)),
+    ListWalk::Rest(rest) => curr_p.trap(member_trap, &[sample, rest]),
+    _ => fail::create::badarg(),
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::bif::bif_process::make_list;
+
+  #[test]
+  fn test_prepend_slice_longer_than_heap() {
+    const COUNT: usize = 10_000;
+    const SLICE: usize =
+      Reductions::DEFAULT as usize * Reductions::LIST_ELEMENTS_PER_REDUCTION;
+    let mut src_hp = Heap::new(COUNT * 2 + 64);
+    let elems: Vec<LTerm> = (0..COUNT).map(LTerm::make_small_unsigned).collect();
+    let expected = make_list(&elems, &mut src_hp).unwrap();
+
+    // Like `++`: reverse in slices, then reverse again onto the tail
+    let mut hp = Heap::new(1024);
+    let mut reversed = LTerm::nil();
+    for chunk in elems.chunks(SLICE) {
+      reversed = prepend_slice(&mut hp, chunk, reversed).unwrap();
     }
-    cmp_result.unwrap() == Ordering::Equal
-  });
-  return Ok(LTerm::make_bool(result));
+    let mut reversed_elems = Vec::new();
+    while reversed.is_cons() {
+      let p = reversed.get_cons_ptr();
+      unsafe {
+        reversed_elems.push((*p).hd());
+        reversed = (*p).tl();
+      }
+    }
+    let mut result = LTerm::nil();
+    for chunk in reversed_elems.chunks(SLICE) {
+      result = prepend_slice(&mut hp, chunk, result).unwrap();
+    }
+    let order = compare::cmp_terms(result, expected, true).unwrap();
+    assert_eq!(order, Ordering::Equal);
+  }
 }
@@ -0,0 +1,161 @@
+//! BIFs in the `maps` module. Results are built with `heap::build_on_heap`,
+//! so they go to a heap fragment when the process heap is full.
+use crate::{
+  bif::{
+    assert_arity,
+    bif_process::{make_list, make_pairs_list},
+  },
+  defs::Reductions,
+  emulator::{gen_atoms, heap, process::Process, vm::VM},
+  fail::{self, RtResult},
+  term::{
+    boxed,
+    lterm::{cons, tuple::make_tuple2, *},
+    map,
+  },
+};
+
+/// Fail with `{badmap, Map}` if the argument is not a map.
+#[inline]
+fn check_map(cur_proc: &mut Process, m: LTerm) -> RtResult<()> {
+  if !m.is_map() {
+    return fail::create::badmap_val(m, &mut cur_proc.heap);
+  }
+  Ok(())
+}
+
+/// Charge the process for visiting all pairs of a map.
+#[inline]
+fn charge_map_walk(cur_proc: &mut Process, m: LTerm) {
+  let cost = Reductions::for_units(m.map_size(), Reductions::LIST_ELEMENTS_PER_REDUCTION);
+  cur_proc.context.consume_reductions(cost);
+}
+
+/// Return the value for the key or fail with `{badkey, Key}`.
+pub fn bif_maps_get_2(
+  _vm: &mut VM,
+  cur_proc: &mut Process,
+  args: &[LTerm],
+) -> RtResult<LTerm> {
+  assert_arity("maps:get", 2, args);
+  check_map(cur_proc, args[1])?;
+  match map::get(args[1], args[0])? {
+    Some(value) => Ok(value),
+    None => fail::create::badkey_val(args[0], &mut cur_proc.heap),
+  }
+}
+
+/// Return `{ok, Value}` for the key or `error`.
+pub fn bif_maps_find_2(
+  _vm: &mut VM,
+  cur_proc: &mut Process,
+  args: &[LTerm],
+) -> RtResult<LTerm> {
+  assert_arity("maps:find", 2, args);
+  check_map(cur_proc, args[1])?;
+  match map::get(args[1], args[0])? {
+    Some(value) => heap::build_on_heap(&mut cur_proc.heap, |hp| {
+      make_tuple2(gen_atoms::OK, value, hp)
+    }),
+    None => Ok(gen_atoms::ERROR),
+  }
+}
+
+/// Return a new map with the key associated with the value.
+pub fn bif_maps_put_3(
+  _vm: &mut VM,
+  cur_proc: &mut Process,
+  args: &[LTerm],
+) -> RtResult<LTerm> {
+  assert_arity("maps:put", 3, args);
+  check_map(cur_proc, args[2])?;
+  heap::build_on_heap(&mut cur_proc.heap, |hp| {
+    map::put(args[2], args[0], args[1], hp)
+  })
+}
+
+/// Return a new map without the key.
+pub fn bif_maps_remove_2(
+  _vm: &mut VM,
+  cur_proc: &mut Process,
+  args: &[LTerm],
+) -> RtResult<LTerm> {
+  assert_arity("maps:remove", 2, args);
+  check_map(cur_proc, args[1])?;
+  heap::build_on_heap(&mut cur_proc.heap, |hp| map::remove(args[1], args[0], hp))
+}
+
+/// Return the list of keys of a map.
+pub fn bif_maps_keys_1(
+  _vm: &mut VM,
+  cur_proc: &mut Process,
+  args: &[LTerm],
+) -> RtResult<LTerm> {
+  assert_arity("maps:keys", 1, args);
+  check_map(cur_proc, args[0])?;
+  charge_map_walk(cur_proc, args[0]);
+  let mut keys = Vec::with_capacity(args[0].map_size());
+  map::for_each_pair(args[0], |k, _| keys.push(k));
+  heap::build_on_heap(&mut cur_proc.heap, |hp| make_list(&keys, hp))
+}
+
+/// Return the list of values of a map, in the same order as `maps:keys/1`.
+pub fn bif_maps_values_1(
+  _vm: &mut VM,
+  cur_proc: &mut Process,
+  args: &[LTerm],
+) -> RtResult<LTerm> {
+  assert_arity("maps:values", 1, args);
+  check_map(cur_proc, args[0])?;
+  charge_map_walk(cur_proc, args[0]);
+  let mut values = Vec::with_capacity(args[0].map_size());
+  map::for_each_pair(args[0], |_, v| values.push(v));
+  heap::build_on_heap(&mut cur_proc.heap, |hp| make_list(&values, hp))
+}
+
+/// Return the list of `{Key, Value}` pairs of a map.
+pub fn bif_maps_to_list_1(
+  _vm: &mut VM,
+  cur_proc: &mut Process,
+  args: &[LTerm],
+) -> RtResult<LTerm> {
+  assert_arity("maps:to_list", 1, args);
+  check_map(cur_proc, args[0])?;
+  charge_map_walk(cur_proc, args[0]);
+  let mut pairs = Vec::with_capacity(args[0].map_size());
+  map::for_each_pair(args[0], |k, v| pairs.push((k, v)));
+  heap::build_on_heap(&mut cur_proc.heap, |hp| make_pairs_list(&pairs, hp))
+}
+
+/// Build a map from a list of `{Key, Value}` tuples, if a key repeats, the
+/// last value is used.
+pub fn bif_maps_from_list_1(
+  _vm: &mut VM,
+  cur_proc: &mut Process,
+  args: &[LTerm],
+) -> RtResult<LTerm> {
+  assert_arity("maps:from_list", 1, args);
+  if !args[0].is_list() {
+    return fail::create::badarg();
+  }
+  let mut pairs = Vec::new();
+  let tail = cons::for_each(args[0], |elem| {
+    if !elem.is_tuple() || unsafe { (*elem.get_tuple_ptr()).get_arity() } != 2 {
+      return fail::create::badarg();
+    }
+    let p = elem.get_tuple_ptr();
+    unsafe {
+      pairs.push((
+        boxed::Tuple::get_element_base0(p, 0),
+        boxed::Tuple::get_element_base0(p, 1),
+      ))
+    }
+    Ok(())
+  })?;
+  if tail.is_some() && tail != Some(LTerm::nil()) {
+    return fail::create::badarg();
+  }
+  let cost = Reductions::for_units(pairs.len(), Reductions::LIST_ELEMENTS_PER_REDUCTION);
+  cur_proc.context.consume_reductions(cost);
+  heap::build_on_heap(&mut cur_proc.heap, |hp| map::from_pairs(&pairs, hp))
+}
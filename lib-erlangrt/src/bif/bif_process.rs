@@ -1,15 +1,22 @@
 use crate::{
-  bif::assert_arity,
-  defs::exc_type::ExceptionType,
+  bif::{assert_arity, BifFn},
+  defs::{exc_type::ExceptionType, Reductions, MAX_XREGS},
   emulator::{
     gen_atoms,
-    mfa::{Args, MFASomething, MFArity},
+    heap::{self, Heap},
+    mailbox::MessageQueueData,
+    mfa::MFArity,
     process::Process,
     scheduler::Prio,
+    signal::{is_down_message, make_down_message, make_exit_message, Signal},
     vm::VM,
   },
-  fail::{Error, RtResult},
-  term::{boxed, lterm::*},
+  fail::{self, Error, RtResult},
+  term::{
+    boxed,
+    lterm::{tuple::make_tuple2, *},
+    term_builder::ListBuilder,
+  },
 };
 
 pub fn ubif_erlang_self_0(
@@ -40,19 +47,352 @@ pub fn bif_erlang_make_fun_3(
   Ok(expt)
 }
 
-/// Creates a new process specified by `module:function/arity` with `args`
-/// (args are passed as list), `arity` is length of args list.
-/// Spec: erlang:spawn(mod, fun, args:list)
-pub fn bif_erlang_spawn_3(
+/// Link the calling process with another. If the other process does not
+/// exist, raises `noproc` or, if exits are trapped, receives the message
+/// `{'EXIT', Pid, noproc}`.
+pub fn bif_erlang_link_1(
+  vm: &mut VM,
+  cur_proc: &mut Process,
+  args: &[LTerm],
+) -> RtResult<LTerm> {
+  assert_arity("erlang:link", 1, args);
+  let pid = args[0];
+  if !pid.is_pid() {
+    return fail::create::badarg();
+  }
+  if pid == cur_proc.pid || cur_proc.links.contains(&pid) {
+    return Ok(LTerm::make_bool(true));
+  }
+  if vm.shared.send_signal(pid, Signal::Link(cur_proc.pid)) {
+    cur_proc.links.insert(pid);
+  } else if cur_proc.trap_exit {
+    let msg = make_exit_message(pid, gen_atoms::NOPROC)?;
+    cur_proc.mailbox.put(msg);
+  } else {
+    return Err(Error::Exception(ExceptionType::Error, gen_atoms::NOPROC));
+  }
+  Ok(LTerm::make_bool(true))
+}
+
+/// Remove the link between the calling process and another, if it existed.
+/// An exit signal which was sent through the link and did not arrive yet
+/// will be ignored.
+pub fn bif_erlang_unlink_1(
+  vm: &mut VM,
+  cur_proc: &mut Process,
+  args: &[LTerm],
+) -> RtResult<LTerm> {
+  assert_arity("erlang:unlink", 1, args);
+  let pid = args[0];
+  if !pid.is_pid() {
+    return fail::create::badarg();
+  }
+  if cur_proc.links.remove(&pid) {
+    vm.shared.send_signal(pid, Signal::Unlink(cur_proc.pid));
+  }
+  Ok(LTerm::make_bool(true))
+}
+
+/// Send an exit signal to a process. Reason `kill` terminates the process
+/// even if it traps exits, reason `normal` is ignored by other processes.
+/// Spec: erlang:exit(pid, reason)
+pub fn bif_erlang_exit_2(
+  vm: &mut VM,
+  cur_proc: &mut Process,
+  args: &[LTerm],
+) -> RtResult<LTerm> {
+  assert_arity("erlang:exit/2", 2, args);
+  let pid = args[0];
+  if !pid.is_pid() {
+    return fail::create::badarg();
+  }
+  // A signal to self is handled when the process looks at its signal queue
+  // next time, the same way as a signal from another process
+  let signal = Signal::exit(cur_proc.pid, args[1], false)?;
+  vm.shared.send_signal(pid, signal);
+  Ok(LTerm::make_bool(true))
+}
+
+/// Largest value for the `save_calls` process flag.
+const MAX_SAVE_CALLS: usize = 10000;
+
+/// Set a process flag and return its old value. Supported flags:
+/// `trap_exit`, `priority`, `save_calls`, `message_queue_data` and
+/// `max_heap_size` (the size in words, 0 for no limit).
+pub fn bif_erlang_process_flag_2(
+  _vm: &mut VM,
+  cur_proc: &mut Process,
+  args: &[LTerm],
+) -> RtResult<LTerm> {
+  assert_arity("erlang:process_flag", 2, args);
+  let (flag, value) = (args[0], args[1]);
+  match flag {
+    gen_atoms::TRAP_EXIT => {
+      if !value.is_bool() {
+        return fail::create::badarg();
+      }
+      let old = cur_proc.trap_exit;
+      cur_proc.trap_exit = value == gen_atoms::TRUE;
+      Ok(LTerm::make_bool(old))
+    }
+    gen_atoms::PRIORITY => {
+      // The running process is in no run queue, the new priority selects the
+      // queue when it is scheduled next time
+      let prio = match Prio::from_atom(value) {
+        Some(p) => p,
+        None => return fail::create::badarg(),
+      };
+      let old = cur_proc.prio;
+      cur_proc.prio = prio;
+      Ok(old.to_atom())
+    }
+    gen_atoms::SAVE_CALLS => set_save_calls(cur_proc, value),
+    gen_atoms::MESSAGE_QUEUE_DATA => {
+      let mqd = match value {
+        gen_atoms::ON_HEAP => MessageQueueData::OnHeap,
+        gen_atoms::OFF_HEAP => MessageQueueData::OffHeap,
+        _ => return fail::create::badarg(),
+      };
+      let old = cur_proc.message_queue_data;
+      cur_proc.message_queue_data = mqd;
+      Ok(match old {
+        MessageQueueData::OnHeap => gen_atoms::ON_HEAP,
+        MessageQueueData::OffHeap => gen_atoms::OFF_HEAP,
+      })
+    }
+    gen_atoms::MAX_HEAP_SIZE => {
+      if !value.is_small() || value.get_small_signed() < 0 {
+        return fail::create::badarg();
+      }
+      let old = cur_proc.max_heap_size;
+      cur_proc.max_heap_size = value.get_small_unsigned();
+      Ok(LTerm::make_small_unsigned(old))
+    }
+    _ => fail::create::badarg(),
+  }
+}
+
+/// Set a flag of another process, only `save_calls` is supported.
+/// Spec: erlang:process_flag(pid, save_calls, n)
+pub fn bif_erlang_process_flag_3(
+  vm: &mut VM,
+  cur_proc: &mut Process,
+  args: &[LTerm],
+) -> RtResult<LTerm> {
+  assert_arity("erlang:process_flag", 3, args);
+  let (pid, flag, value) = (args[0], args[1], args[2]);
+  if !pid.is_local_pid() || flag != gen_atoms::SAVE_CALLS {
+    return fail::create::badarg();
+  }
+  if pid == cur_proc.pid {
+    return set_save_calls(cur_proc, value);
+  }
+  let retry = (bif_erlang_process_flag_3 as BifFn, args);
+  match vm.with_other_process(cur_proc, pid, retry, |p, _| set_save_calls(p, value))? {
+    Some(result) => result,
+    None => fail::create::badarg(),
+  }
+}
+
+/// Set `save_calls` flag and return its old value.
+fn set_save_calls(p: &mut Process, value: LTerm) -> RtResult<LTerm> {
+  if !value.is_small()
+    || value.get_small_signed() < 0
+    || value.get_small_unsigned() > MAX_SAVE_CALLS
+  {
+    return fail::create::badarg();
+  }
+  let old = p.save_calls;
+  p.set_save_calls(value.get_small_unsigned());
+  Ok(LTerm::make_small_unsigned(old))
+}
+
+/// Put the process to sleep until it receives a message, then continue with
+/// `apply(M, F, Args)`. The stack is discarded, so this call never returns,
+/// and the heap is shrunk to the live data.
+/// Spec: erlang:hibernate(module, function, args)
+pub fn bif_erlang_hibernate_3(
+  vm: &mut VM,
+  cur_proc: &mut Process,
+  args: &[LTerm],
+) -> RtResult<LTerm> {
+  assert_arity("erlang:hibernate", 3, args);
+  if !args[0].is_atom() || !args[1].is_atom() || !args[2].is_list() {
+    return fail::create::badarg();
+  }
+  let mut call_args = Vec::new();
+  let tail = cons::for_each(args[2], |arg| {
+    call_args.push(arg);
+    Ok(())
+  })?;
+  if (tail.is_some() && tail != Some(LTerm::nil())) || call_args.len() > MAX_XREGS {
+    return fail::create::badarg();
+  }
+  let mfa = MFArity::new(args[0], args[1], call_args.len());
+  let code = match vm.code_server().lookup_beam_code_and_load(&mfa) {
+    Ok(code) => code,
+    Err(_) => return fail::create::undef(),
+  };
+  cur_proc.hibernate(code, &call_args);
+  Err(Error::Hibernate)
+}
+
+/// Start monitoring a process, returns the monitor reference. When the
+/// process terminates, the message `{'DOWN', Ref, process, Pid, Reason}` is
+/// received. If the process does not exist, the reason is `noproc`.
+/// Spec: erlang:monitor(process, pid)
+pub fn bif_erlang_monitor_2(
+  vm: &mut VM,
+  cur_proc: &mut Process,
+  args: &[LTerm],
+) -> RtResult<LTerm> {
+  assert_arity("erlang:monitor", 2, args);
+  let pid = args[1];
+  if args[0] != gen_atoms::PROCESS || !pid.is_pid() {
+    return fail::create::badarg();
+  }
+  let ref_id = vm.shared.next_ref_id();
+  let monitor_ref = heap::build_on_heap(&mut cur_proc.heap, |hp| {
+    boxed::Reference::create_into(hp, ref_id)
+  })?;
+  // Monitoring self does nothing, the monitor can never fire
+  if pid == cur_proc.pid {
+    return Ok(monitor_ref);
+  }
+  let signal = Signal::Monitor {
+    from: cur_proc.pid,
+    ref_id,
+  };
+  if vm.shared.send_signal(pid, signal) {
+    cur_proc.monitors.insert(ref_id, pid);
+  } else {
+    let msg = make_down_message(pid, ref_id, gen_atoms::NOPROC)?;
+    cur_proc.mailbox.put(msg);
+  }
+  Ok(monitor_ref)
+}
+
+/// Remove a monitor. A `'DOWN'` message which has already arrived stays in
+/// the message queue, but one which did not arrive yet will be ignored.
+pub fn bif_erlang_demonitor_1(
+  vm: &mut VM,
+  cur_proc: &mut Process,
+  args: &[LTerm],
+) -> RtResult<LTerm> {
+  assert_arity("erlang:demonitor", 1, args);
+  demonitor(vm, cur_proc, args[0])?;
+  Ok(LTerm::make_bool(true))
+}
+
+/// Remove a monitor, with options list:
+/// `flush` also removes the `'DOWN'` message for this monitor from the message
+/// queue, `info` makes the result `false` if the monitor was not found (it has
+/// fired already or was removed).
+pub fn bif_erlang_demonitor_2(
+  vm: &mut VM,
+  cur_proc: &mut Process,
+  args: &[LTerm],
+) -> RtResult<LTerm> {
+  assert_arity("erlang:demonitor", 2, args);
+  let (mut flush, mut info) = (false, false);
+  if !args[1].is_list() {
+    return fail::create::badarg();
+  }
+  let tail = cons::for_each(args[1], |opt| {
+    match opt {
+      gen_atoms::FLUSH => flush = true,
+      gen_atoms::INFO => info = true,
+      _ => return fail::create::badarg(),
+    }
+    Ok(())
+  })?;
+  if tail.is_some() && tail != Some(LTerm::nil()) {
+    return fail::create::badarg();
+  }
+
+  let removed = demonitor(vm, cur_proc, args[0])?;
+  if flush {
+    // A down signal which has not been handled yet is ignored because the
+    // monitor is gone, only the mailbox needs to be cleaned
+    let ref_id = boxed::Reference::get_id(args[0]);
+    cur_proc.mailbox.remove_matching(|msg| is_down_message(msg, ref_id));
+  }
+  Ok(LTerm::make_bool(!info || removed))
+}
+
+/// Remove the monitor identified by `monitor_ref` and notify the monitored
+/// process. Returns whether the monitor existed.
+fn demonitor(vm: &mut VM, cur_proc: &mut Process, monitor_ref: LTerm) -> RtResult<bool> {
+  if !monitor_ref.is_local_ref() {
+    return fail::create::badarg();
+  }
+  let ref_id = boxed::Reference::get_id(monitor_ref);
+  match cur_proc.monitors.remove(&ref_id) {
+    Some(pid) => {
+      let signal = Signal::Demonitor {
+        from: cur_proc.pid,
+        ref_id,
+      };
+      vm.shared.send_signal(pid, signal);
+      Ok(true)
+    }
+    None => Ok(false),
+  }
+}
+
+/// Register a name for a process. Fails with `badarg` if the name is taken,
+/// the process does not exist or already has a name.
+pub fn bif_erlang_register_2(
   vm: &mut VM,
   _cur_proc: &mut Process,
   args: &[LTerm],
 ) -> RtResult<LTerm> {
-  assert_arity("erlang:spawn", 3, args);
-  let mfargs = MFASomething::new(args[0], args[1], Args::AsList(args[2]));
-  let pid = vm.create_process(LTerm::nil(), &mfargs, Prio::Normal)?;
+  assert_arity("erlang:register", 2, args);
+  let (name, pid) = (args[0], args[1]);
+  if !name.is_atom() || name == gen_atoms::UNDEFINED || !pid.is_pid() {
+    return fail::create::badarg();
+  }
+  if !vm.shared.register_name(name, pid) {
+    return fail::create::badarg();
+  }
+  Ok(LTerm::make_bool(true))
+}
 
-  Ok(pid)
+pub fn bif_erlang_unregister_1(
+  vm: &mut VM,
+  _cur_proc: &mut Process,
+  args: &[LTerm],
+) -> RtResult<LTerm> {
+  assert_arity("erlang:unregister", 1, args);
+  if !args[0].is_atom() || !vm.shared.registry.write().unwrap().unregister(args[0]) {
+    return fail::create::badarg();
+  }
+  Ok(LTerm::make_bool(true))
+}
+
+/// Find a process by its registered name, returns `undefined` if there is
+/// no such name.
+pub fn bif_erlang_whereis_1(
+  vm: &mut VM,
+  _cur_proc: &mut Process,
+  args: &[LTerm],
+) -> RtResult<LTerm> {
+  assert_arity("erlang:whereis", 1, args);
+  if !args[0].is_atom() {
+    return fail::create::badarg();
+  }
+  Ok(vm.shared.whereis(args[0]).unwrap_or(gen_atoms::UNDEFINED))
+}
+
+/// Return the list of all registered names.
+pub fn bif_erlang_registered_0(
+  vm: &mut VM,
+  cur_proc: &mut Process,
+  args: &[LTerm],
+) -> RtResult<LTerm> {
+  assert_arity("erlang:registered", 0, args);
+  let names = vm.shared.registry.read().unwrap().get_names();
+  heap::build_on_heap(&mut cur_proc.heap, |hp| make_list(&names, hp))
 }
 
 pub fn bif_erlang_is_process_alive_1(
@@ -61,6 +401,222 @@ pub fn bif_erlang_is_process_alive_1(
   args: &[LTerm],
 ) -> RtResult<LTerm> {
   assert_arity("erlang:is_process_alive", 1, args);
-  let result = vm.scheduler.lookup_pid(args[0]).is_some();
+  let result = vm.shared.is_process_alive(args[0]);
   Ok(LTerm::make_bool(result))
 }
+
+/// Build a list of terms on the process heap.
+pub(crate) fn make_list(terms: &[LTerm], hp: &mut Heap) -> RtResult<LTerm> {
+  if terms.is_empty() {
+    return Ok(LTerm::nil());
+  }
+  unsafe {
+    let mut lb = ListBuilder::new(hp)?;
+    for t in terms {
+      lb.append(*t)?;
+    }
+    Ok(lb.make_term())
+  }
+}
+
+/// Build a list of `{Key, Value}` tuples for the process dictionary contents.
+pub(crate) fn make_pairs_list(
+  pairs: &[(LTerm, LTerm)],
+  hp: &mut Heap,
+) -> RtResult<LTerm> {
+  let mut tuples = Vec::with_capacity(pairs.len());
+  for (k, v) in pairs {
+    tuples.push(make_tuple2(*k, *v, hp)?);
+  }
+  make_list(&tuples, hp)
+}
+
+/// Charge the process for building a list of `n` elements from the process
+/// dictionary.
+#[inline]
+fn charge_list_build(cur_proc: &mut Process, n: usize) {
+  let cost = Reductions::for_units(n, Reductions::LIST_ELEMENTS_PER_REDUCTION);
+  cur_proc.context.consume_reductions(cost);
+}
+
+/// Store a value in the process dictionary, return the previous value or
+/// `undefined`.
+pub fn bif_erlang_put_2(
+  _vm: &mut VM,
+  cur_proc: &mut Process,
+  args: &[LTerm],
+) -> RtResult<LTerm> {
+  assert_arity("erlang:put", 2, args);
+  let old = cur_proc.dictionary.put(args[0], args[1])?;
+  Ok(old.unwrap_or(gen_atoms::UNDEFINED))
+}
+
+/// Return the process dictionary as a list of `{Key, Value}`.
+pub fn bif_erlang_get_0(
+  _vm: &mut VM,
+  cur_proc: &mut Process,
+  args: &[LTerm],
+) -> RtResult<LTerm> {
+  assert_arity("erlang:get", 0, args);
+  let pairs = cur_proc.dictionary.get_pairs();
+  charge_list_build(cur_proc, pairs.len());
+  heap::build_on_heap(&mut cur_proc.heap, |hp| make_pairs_list(&pairs, hp))
+}
+
+/// Return a value from the process dictionary or `undefined`.
+pub fn bif_erlang_get_1(
+  _vm: &mut VM,
+  cur_proc: &mut Process,
+  args: &[LTerm],
+) -> RtResult<LTerm> {
+  assert_arity("erlang:get", 1, args);
+  let value = cur_proc.dictionary.get(args[0])?;
+  Ok(value.unwrap_or(gen_atoms::UNDEFINED))
+}
+
+/// Return all keys in the process dictionary.
+pub fn bif_erlang_get_keys_0(
+  _vm: &mut VM,
+  cur_proc: &mut Process,
+  args: &[LTerm],
+) -> RtResult<LTerm> {
+  assert_arity("erlang:get_keys", 0, args);
+  let keys = cur_proc.dictionary.get_keys();
+  charge_list_build(cur_proc, keys.len());
+  heap::build_on_heap(&mut cur_proc.heap, |hp| make_list(&keys, hp))
+}
+
+/// Return the keys in the process dictionary which have the given value.
+pub fn bif_erlang_get_keys_1(
+  _vm: &mut VM,
+  cur_proc: &mut Process,
+  args: &[LTerm],
+) -> RtResult<LTerm> {
+  assert_arity("erlang:get_keys", 1, args);
+  let keys = cur_proc.dictionary.get_keys_with_value(args[0])?;
+  // All values were visited to find the keys
+  charge_list_build(cur_proc, cur_proc.dictionary.size());
+  heap::build_on_heap(&mut cur_proc.heap, |hp| make_list(&keys, hp))
+}
+
+/// Clear the process dictionary, return its old contents as a list of
+/// `{Key, Value}`.
+pub fn bif_erlang_erase_0(
+  _vm: &mut VM,
+  cur_proc: &mut Process,
+  args: &[LTerm],
+) -> RtResult<LTerm> {
+  assert_arity("erlang:erase", 0, args);
+  let pairs = cur_proc.dictionary.erase_all();
+  charge_list_build(cur_proc, pairs.len());
+  heap::build_on_heap(&mut cur_proc.heap, |hp| make_pairs_list(&pairs, hp))
+}
+
+/// Remove a key from the process dictionary, return its value or
+/// `undefined`.
+pub fn bif_erlang_erase_1(
+  _vm: &mut VM,
+  cur_proc: &mut Process,
+  args: &[LTerm],
+) -> RtResult<LTerm> {
+  assert_arity("erlang:erase", 1, args);
+  let old = cur_proc.dictionary.erase(args[0])?;
+  Ok(old.unwrap_or(gen_atoms::UNDEFINED))
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::{
+    command_line_args::ErlStartArgs,
+    defs::Reductions,
+    emulator::{code::CodePtr, process::SpawnOptions, vm::VMShared},
+  };
+
+  fn make_vm_and_process() -> (VM, Process) {
+    let shared = VMShared::new(&mut ErlStartArgs::new());
+    let vm = VM::new(&shared, 0);
+    let pid = LTerm::make_local_pid(1);
+    let opts = SpawnOptions::default();
+    let p = Process::with_code(pid, LTerm::nil(), CodePtr::null(), &opts);
+    (vm, p)
+  }
+
+  fn flag(vm: &mut VM, p: &mut Process, f: LTerm, value: LTerm) -> RtResult<LTerm> {
+    bif_erlang_process_flag_2(vm, p, &[f, value])
+  }
+
+  #[test]
+  fn test_process_flag_returns_old_value() {
+    let (mut vm, mut p) = make_vm_and_process();
+    let small = LTerm::make_small_unsigned;
+    let t = gen_atoms::TRUE;
+    assert_eq!(flag(&mut vm, &mut p, gen_atoms::TRAP_EXIT, t).unwrap(), gen_atoms::FALSE);
+    assert_eq!(flag(&mut vm, &mut p, gen_atoms::TRAP_EXIT, gen_atoms::FALSE).unwrap(), t);
+
+    let (prio, high) = (gen_atoms::PRIORITY, gen_atoms::HIGH);
+    assert_eq!(flag(&mut vm, &mut p, prio, high).unwrap(), gen_atoms::NORMAL);
+    assert_eq!(flag(&mut vm, &mut p, prio, gen_atoms::LOW).unwrap(), high);
+
+    assert_eq!(flag(&mut vm, &mut p, gen_atoms::SAVE_CALLS, small(5)).unwrap(), small(0));
+    assert_eq!(flag(&mut vm, &mut p, gen_atoms::SAVE_CALLS, small(0)).unwrap(), small(5));
+    // The same flag through process_flag/3 on self
+    let args = [p.pid, gen_atoms::SAVE_CALLS, small(7)];
+    assert_eq!(bif_erlang_process_flag_3(&mut vm, &mut p, &args).unwrap(), small(0));
+    assert_eq!(p.save_calls, 7);
+
+    let (mqd, off) = (gen_atoms::MESSAGE_QUEUE_DATA, gen_atoms::OFF_HEAP);
+    assert_eq!(flag(&mut vm, &mut p, mqd, off).unwrap(), gen_atoms::ON_HEAP);
+    assert_eq!(flag(&mut vm, &mut p, mqd, gen_atoms::ON_HEAP).unwrap(), off);
+
+    let max = gen_atoms::MAX_HEAP_SIZE;
+    assert_eq!(flag(&mut vm, &mut p, max, small(100_000)).unwrap(), small(0));
+    assert_eq!(flag(&mut vm, &mut p, max, small(0)).unwrap(), small(100_000));
+  }
+
+  #[test]
+  fn test_process_flag_badarg() {
+    let (mut vm, mut p) = make_vm_and_process();
+    let negative = LTerm::make_small_signed(-1);
+    let too_many = LTerm::make_small_unsigned(MAX_SAVE_CALLS + 1);
+    assert!(flag(&mut vm, &mut p, gen_atoms::MAX_HEAP_SIZE, negative).is_err());
+    assert!(flag(&mut vm, &mut p, gen_atoms::SAVE_CALLS, too_many).is_err());
+    assert!(flag(&mut vm, &mut p, gen_atoms::SAVE_CALLS, negative).is_err());
+    assert!(flag(&mut vm, &mut p, gen_atoms::PRIORITY, gen_atoms::OK).is_err());
+    assert!(flag(&mut vm, &mut p, gen_atoms::TRAP_EXIT, gen_atoms::OK).is_err());
+    assert!(flag(&mut vm, &mut p, gen_atoms::MESSAGE_QUEUE_DATA, gen_atoms::OK).is_err());
+    assert!(flag(&mut vm, &mut p, gen_atoms::OK, gen_atoms::TRUE).is_err());
+    // Failed calls do not change the flags
+    assert_eq!(p.max_heap_size, 0);
+    assert_eq!(p.save_calls, 0);
+    assert_eq!(p.prio, Prio::Normal);
+  }
+
+  #[test]
+  fn test_max_heap_size_kills() {
+    let (mut vm, mut p) = make_vm_and_process();
+    let ctx_p = p.get_context_p();
+    let ctx = unsafe { &mut *ctx_p };
+    // The first collection creates the old generation
+    p.garbage_collect(ctx, 0, 0).unwrap();
+    let limit = p.heap.get_total_size();
+    let limit_term = LTerm::make_small_unsigned(limit);
+    flag(&mut vm, &mut p, gen_atoms::MAX_HEAP_SIZE, limit_term).unwrap();
+
+    // Within the limit nothing happens
+    ctx.reductions = Reductions::DEFAULT;
+    p.garbage_collect(ctx, 0, 0).unwrap();
+    assert!(!p.handle.has_incoming_signals());
+    assert_eq!(ctx.reductions, Reductions::DEFAULT);
+
+    // Grow the heap past the limit
+    let need = p.heap.get_heap_max_capacity() * 2;
+    p.garbage_collect(ctx, need, 0).unwrap();
+    assert!(p.heap.get_total_size() > limit);
+    assert!(p.handle.has_incoming_signals());
+    assert_eq!(ctx.reductions, 0);
+    // The queued signal is an untrappable kill
+    p.trap_exit = true;
+    assert_eq!(p.handle_signals().unwrap(), Some(gen_atoms::KILLED));
+  }
+}
@@ -0,0 +1,367 @@
+//! Process introspection: `processes/0` and `process_info/1,2`.
+//! Another process can be examined only while it is not running, so the
+//! result is built in a heap fragment while the other process is locked, and
+//! attached to the caller heap afterwards.
+use crate::{
+  bif::{assert_arity, bif_process::make_list, bif_process::make_pairs_list, BifFn},
+  defs::Reductions,
+  emulator::{
+    gen_atoms,
+    heap::{self, build_in_fragment, copy_term, Heap},
+    process::Process,
+    scheduler::Queue,
+    vm::VM,
+  },
+  fail::{self, RtResult},
+  term::lterm::{
+    cons,
+    tuple::{make_tuple2, make_tuple3},
+    *,
+  },
+};
+
+/// Items returned by `process_info/1`, `registered_name` goes first if the
+/// process has a name.
+const DEFAULT_ITEMS: &[LTerm] = &[
+  gen_atoms::CURRENT_FUNCTION,
+  gen_atoms::STATUS,
+  gen_atoms::MESSAGE_QUEUE_LEN,
+  gen_atoms::LINKS,
+  gen_atoms::DICTIONARY,
+  gen_atoms::TRAP_EXIT,
+  gen_atoms::PRIORITY,
+  gen_atoms::HEAP_SIZE,
+  gen_atoms::STACK_SIZE,
+  gen_atoms::REDUCTIONS,
+];
+
+fn is_info_item(item: LTerm) -> bool {
+  item == gen_atoms::REGISTERED_NAME
+    || item == gen_atoms::MESSAGES
+    || item == gen_atoms::MONITORS
+    || item == gen_atoms::PARENT
+    || item == gen_atoms::LAST_CALLS
+    || DEFAULT_ITEMS.contains(&item)
+}
+
+/// What was asked from `process_info`.
+enum InfoRequest {
+  /// `process_info/1`, a list of the default items
+  Default,
+  /// `process_info(Pid, Item)`, a single `{Item, Value}`
+  Item(LTerm),
+  /// `process_info(Pid, [Item])`, a list of `{Item, Value}`
+  List(Vec<LTerm>),
+}
+
+/// Return pids of all processes.
+pub fn bif_erlang_processes_0(
+  vm: &mut VM,
+  cur_proc: &mut Process,
+  args: &[LTerm],
+) -> RtResult<LTerm> {
+  assert_arity("erlang:processes", 0, args);
+  let pids = vm.shared.get_pids();
+  heap::build_on_heap(&mut cur_proc.heap, |hp| make_list(&pids, hp))
+}
+
+/// Return a list of `{Item, Value}` with the default information about a
+/// process, or `undefined` if it does not exist.
+pub fn bif_erlang_process_info_1(
+  vm: &mut VM,
+  cur_proc: &mut Process,
+  args: &[LTerm],
+) -> RtResult<LTerm> {
+  assert_arity("erlang:process_info", 1, args);
+  let retry = (bif_erlang_process_info_1 as BifFn, args);
+  process_info(vm, cur_proc, args[0], InfoRequest::Default, retry)
+}
+
+/// Return `{Item, Value}` for an item, or a list of them for a list of items.
+/// Returns `undefined` if the process does not exist.
+pub fn bif_erlang_process_info_2(
+  vm: &mut VM,
+  cur_proc: &mut Process,
+  args: &[LTerm],
+) -> RtResult<LTerm> {
+  assert_arity("erlang:process_info", 2, args);
+  let request = if args[1].is_atom() {
+    if !is_info_item(args[1]) {
+      return fail::create::badarg();
+    }
+    InfoRequest::Item(args[1])
+  } else if args[1].is_list() {
+    let mut items = Vec::new();
+    let tail = cons::for_each(args[1], |item| {
+      if !is_info_item(item) {
+        return fail::create::badarg();
+      }
+      items.push(item);
+      Ok(())
+    })?;
+    if tail.is_some() && tail != Some(LTerm::nil()) {
+      return fail::create::badarg();
+    }
+    InfoRequest::List(items)
+  } else {
+    return fail::create::badarg();
+  };
+  let retry = (bif_erlang_process_info_2 as BifFn, args);
+  process_info(vm, cur_proc, args[0], request, retry)
+}
+
+fn process_info(
+  vm: &VM,
+  cur_proc: &mut Process,
+  pid: LTerm,
+  request: InfoRequest,
+  retry: (BifFn, &[LTerm]),
+) -> RtResult<LTerm> {
+  if !pid.is_local_pid() {
+    return fail::create::badarg();
+  }
+  let result = if pid == cur_proc.pid {
+    build_in_fragment(|hp| build_info(vm, cur_proc, true, true, &request, hp))
+  } else {
+    let built = vm.with_other_process(cur_proc, pid, retry, |p, running| {
+      build_in_fragment(|hp| build_info(vm, p, false, running, &request, hp))
+    })?;
+    match built {
+      Some(r) => r,
+      None => return Ok(gen_atoms::UNDEFINED),
+    }
+  };
+  let (term, fragment) = result?;
+  let copied = fragment.get_heap_used_words();
+  let cost = Reductions::for_units(copied, Reductions::COPY_WORDS_PER_REDUCTION);
+  cur_proc.context.consume_reductions(cost);
+  cur_proc.heap.attach_fragment(fragment);
+  Ok(term)
+}
+
+fn build_info(
+  vm: &VM,
+  p: &Process,
+  is_self: bool,
+  running: bool,
+  request: &InfoRequest,
+  hp: &mut Heap,
+) -> RtResult<LTerm> {
+  let item_tuple = |item, hp: &mut Heap| -> RtResult<LTerm> {
+    let value = get_item(vm, p, item, is_self, running, hp)?;
+    make_tuple2(item, value, hp)
+  };
+  match request {
+    InfoRequest::Item(gen_atoms::REGISTERED_NAME) => {
+      // Unlike in the lists, a process without a name gives `[]`
+      match vm.shared.get_registered_name(p.pid) {
+        Some(name) => make_tuple2(gen_atoms::REGISTERED_NAME, name, hp),
+        None => Ok(LTerm::nil()),
+      }
+    }
+    InfoRequest::Item(item) => item_tuple(*item, hp),
+    InfoRequest::List(items) => {
+      let mut result = Vec::with_capacity(items.len());
+      for item in items {
+        result.push(item_tuple(*item, hp)?);
+      }
+      make_list(&result, hp)
+    }
+    InfoRequest::Default => {
+      let mut result = Vec::with_capacity(DEFAULT_ITEMS.len() + 1);
+      if vm.shared.get_registered_name(p.pid).is_some() {
+        result.push(item_tuple(gen_atoms::REGISTERED_NAME, hp)?);
+      }
+      for item in DEFAULT_ITEMS {
+        result.push(item_tuple(*item, hp)?);
+      }
+      make_list(&result, hp)
+    }
+  }
+}
+
+/// Build the value of one item. Terms from the process are copied into `hp`.
+fn get_item(
+  vm: &VM,
+  p: &Process,
+  item: LTerm,
+  is_self: bool,
+  running: bool,
+  hp: &mut Heap,
+) -> RtResult<LTerm> {
+  let value = match item {
+    gen_atoms::STATUS => {
+      if is_self || running {
+        gen_atoms::RUNNING
+      } else {
+        match p.current_queue {
+          Queue::InfiniteWait | Queue::TimedWait => gen_atoms::WAITING,
+          _ => gen_atoms::RUNNABLE,
+        }
+      }
+    }
+    gen_atoms::MESSAGE_QUEUE_LEN => {
+      let incoming = p.handle.get_incoming_messages().len();
+      LTerm::make_small_unsigned(p.mailbox.message_count() + incoming)
+    }
+    gen_atoms::MESSAGES => {
+      let mut messages = p.mailbox.get_messages();
+      messages.extend(p.handle.get_incoming_messages());
+      for m in messages.iter_mut() {
+        *m = copy_term::copy_to(*m, hp)?;
+      }
+      make_list(&messages, hp)?
+    }
+    gen_atoms::LINKS => {
+      let mut links: Vec<LTerm> = p.links.iter().cloned().collect();
+      links.sort_by_key(|pid| pid.raw());
+      make_list(&links, hp)?
+    }
+    gen_atoms::MONITORS => {
+      let mut monitors = Vec::with_capacity(p.monitors.len());
+      for target in p.monitors.values() {
+        monitors.push(make_tuple2(gen_atoms::PROCESS, *target, hp)?);
+      }
+      make_list(&monitors, hp)?
+    }
+    gen_atoms::DICTIONARY => {
+      let mut pairs = p.dictionary.get_pairs();
+      for (k, v) in pairs.iter_mut() {
+        *k = copy_term::copy_to(*k, hp)?;
+        *v = copy_term::copy_to(*v, hp)?;
+      }
+      make_pairs_list(&pairs, hp)?
+    }
+    gen_atoms::HEAP_SIZE => LTerm::make_small_unsigned(p.heap.get_heap_size()),
+    gen_atoms::STACK_SIZE => LTerm::make_small_unsigned(p.heap.stack_depth()),
+    gen_atoms::REDUCTIONS => {
+      let mut reductions = p.reductions;
+      if is_self {
+        // Reductions of the current time slice are not counted yet
+        reductions += (Reductions::DEFAULT - p.context.reductions).max(0) as usize;
+      }
+      LTerm::make_small_unsigned(reductions)
+    }
+    gen_atoms::CURRENT_FUNCTION => {
+      match vm.code_server().code_reverse_lookup(p.context.ip) {
+        Some(mfa) => {
+          let arity = LTerm::make_small_unsigned(mfa.arity);
+          make_tuple3(mfa.m, mfa.f, arity, hp)?
+        }
+        None => gen_atoms::UNDEFINED,
+      }
+    }
+    gen_atoms::REGISTERED_NAME => {
+      vm.shared.get_registered_name(p.pid).unwrap_or_else(LTerm::nil)
+    }
+    gen_atoms::LAST_CALLS => {
+      if p.save_calls == 0 {
+        gen_atoms::FALSE
+      } else {
+        let mut calls = Vec::with_capacity(p.last_calls.len());
+        for mfa in p.last_calls.iter() {
+          let arity = LTerm::make_small_unsigned(mfa.arity);
+          calls.push(make_tuple3(mfa.m, mfa.f, arity, hp)?);
+        }
+        make_list(&calls, hp)?
+      }
+    }
+    gen_atoms::PARENT => {
+      if p.parent.is_pid() {
+        p.parent
+      } else {
+        gen_atoms::UNDEFINED
+      }
+    }
+    gen_atoms::PRIORITY => p.prio.to_atom(),
+    gen_atoms::TRAP_EXIT => LTerm::make_bool(p.trap_exit),
+    _ => return fail::create::badarg(),
+  };
+  Ok(value)
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::{
+    command_line_args::ErlStartArgs,
+    emulator::{
+      code::CodePtr,
+      mailbox::Message,
+      process::SpawnOptions,
+      signal::Signal,
+      vm::VMShared,
+    },
+    term::compare::cmp_terms,
+  };
+  use core::cmp::Ordering;
+
+  fn make_vm_and_process() -> (VM, Process) {
+    let shared = VMShared::new(&mut ErlStartArgs::new());
+    let vm = VM::new(&shared, 0);
+    let pid = LTerm::make_local_pid(1);
+    let opts = SpawnOptions::default();
+    let p = Process::with_code(pid, LTerm::nil(), CodePtr::null(), &opts);
+    (vm, p)
+  }
+
+  fn info(vm: &mut VM, p: &mut Process, request: LTerm) -> RtResult<LTerm> {
+    let args = [p.pid, request];
+    bif_erlang_process_info_2(vm, p, &args)
+  }
+
+  fn assert_same(a: LTerm, b: LTerm) {
+    assert_eq!(cmp_terms(a, b, true).unwrap(), Ordering::Equal, "{} /= {}", a, b);
+  }
+
+  #[test]
+  fn test_registered_name_without_name() {
+    let (mut vm, mut p) = make_vm_and_process();
+    let result = info(&mut vm, &mut p, gen_atoms::REGISTERED_NAME).unwrap();
+    assert_eq!(result, LTerm::nil());
+
+    // In a list the item is still present
+    let mut hp = Heap::new(64);
+    let request = make_list(&[gen_atoms::REGISTERED_NAME], &mut hp).unwrap();
+    let result = info(&mut vm, &mut p, request).unwrap();
+    let item = make_tuple2(gen_atoms::REGISTERED_NAME, LTerm::nil(), &mut hp).unwrap();
+    assert_same(result, make_list(&[item], &mut hp).unwrap());
+  }
+
+  #[test]
+  fn test_bad_items() {
+    let (mut vm, mut p) = make_vm_and_process();
+    let mut hp = Heap::new(64);
+    assert!(info(&mut vm, &mut p, gen_atoms::OK).is_err());
+
+    let bad_in_list = make_list(&[gen_atoms::STATUS, gen_atoms::OK], &mut hp).unwrap();
+    assert!(info(&mut vm, &mut p, bad_in_list).is_err());
+
+    // [status | heap_size] is not a proper list
+    let cell = heap::allocate_cons(&mut hp).unwrap();
+    unsafe {
+      (*cell).set_hd(gen_atoms::STATUS);
+      (*cell).set_tl(gen_atoms::HEAP_SIZE);
+    }
+    assert!(info(&mut vm, &mut p, LTerm::make_cons(cell)).is_err());
+
+    assert!(info(&mut vm, &mut p, LTerm::small_1()).is_err());
+  }
+
+  #[test]
+  fn test_messages_include_incoming() {
+    let (mut vm, mut p) = make_vm_and_process();
+    let (one, two) = (LTerm::small_1(), LTerm::make_small_signed(2));
+    p.mailbox.put(Message::new(one).unwrap());
+    assert!(p.handle.push_signal(Signal::Message(Message::new(two).unwrap())));
+
+    let result = info(&mut vm, &mut p, gen_atoms::MESSAGES).unwrap();
+    let mut hp = Heap::new(64);
+    let messages = make_list(&[one, two], &mut hp).unwrap();
+    assert_same(result, make_tuple2(gen_atoms::MESSAGES, messages, &mut hp).unwrap());
+
+    let result = info(&mut vm, &mut p, gen_atoms::MESSAGE_QUEUE_LEN).unwrap();
+    let len = make_tuple2(gen_atoms::MESSAGE_QUEUE_LEN, two, &mut hp).unwrap();
+    assert_same(result, len);
+  }
+}
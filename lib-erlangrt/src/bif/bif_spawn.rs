@@ -0,0 +1,348 @@
+//! Spawn BIFs: `spawn`, `spawn_link`, `spawn_monitor` and `spawn_opt`.
+//! The new process can start from `M:F(Args)` or from a fun without
+//! arguments. Functions taking a node only accept the local node.
+use crate::{
+  bif::assert_arity,
+  emulator::{
+    gen_atoms,
+    heap,
+    mfa::{Args, MFASomething},
+    process::{Process, SpawnOptions},
+    scheduler::Prio,
+    vm::VM,
+  },
+  fail::{self, RtResult},
+  term::{
+    boxed,
+    lterm::{cons, tuple::make_tuple2, *},
+  },
+};
+
+/// Start a process running `fun()`, the fun must take no arguments. A closure
+/// starts with its frozen values as arguments.
+fn mfargs_from_fun(fun: LTerm) -> RtResult<MFASomething> {
+  if !fun.is_fun_of_arity(0) {
+    return fail::create::badarg();
+  }
+  unsafe {
+    if fun.is_export() {
+      let expt = boxed::Export::const_from_term(fun)?;
+      let mfa = &(*expt).exp.mfa;
+      return Ok(MFASomething::new(mfa.m, mfa.f, Args::AsList(LTerm::nil())));
+    }
+    let closure = boxed::Closure::const_from_term(fun)?;
+    let frozen = boxed::Closure::get_frozen(closure);
+    let mfa = &(*closure).mfa;
+    let args = Args::AsSlice(frozen.as_ptr(), frozen.len());
+    Ok(MFASomething::new(mfa.m, mfa.f, args))
+  }
+}
+
+/// Start a process running `m:f(args...)`.
+fn mfargs_from_mfa(m: LTerm, f: LTerm, args: LTerm) -> RtResult<MFASomething> {
+  if !m.is_atom() || !f.is_atom() || !args.is_list() {
+    return fail::create::badarg();
+  }
+  if cons::list_length(args).is_err() {
+    return fail::create::badarg();
+  }
+  Ok(MFASomething::new(m, f, Args::AsList(args)))
+}
+
+/// Spawning on other nodes is not supported.
+fn check_node(node: LTerm) -> RtResult<()> {
+  if node != gen_atoms::NONODE_AT_NOHOST {
+    return fail::create::badarg();
+  }
+  Ok(())
+}
+
+fn parse_prio(p: LTerm) -> RtResult<Prio> {
+  match Prio::from_atom(p) {
+    Some(prio) => Ok(prio),
+    None => fail::create::badarg(),
+  }
+}
+
+fn parse_non_neg(n: LTerm) -> RtResult<usize> {
+  if !n.is_small() || n.get_small_signed() < 0 {
+    return fail::create::badarg();
+  }
+  Ok(n.get_small_unsigned())
+}
+
+/// Parse the option list of `spawn_opt`: `link`, `monitor`,
+/// `{priority, P}`, `{min_heap_size, N}` and `{fullsweep_after, N}`.
+/// The monitor reference is created later, when the process is spawned.
+fn parse_spawn_options(opts: LTerm) -> RtResult<(SpawnOptions, bool)> {
+  if !opts.is_list() {
+    return fail::create::badarg();
+  }
+  let mut result = SpawnOptions::default();
+  let mut monitor = false;
+  let tail = cons::for_each(opts, |opt| {
+    match opt {
+      gen_atoms::LINK => result.link = true,
+      gen_atoms::MONITOR => monitor = true,
+      _ if opt.is_tuple() => {
+        let t = opt.get_tuple_ptr();
+        let (key, value) = unsafe {
+          if (*t).get_arity() != 2 {
+            return fail::create::badarg();
+          }
+          (
+            boxed::Tuple::get_element_base0(t, 0),
+            boxed::Tuple::get_element_base0(t, 1),
+          )
+        };
+        match key {
+          gen_atoms::PRIORITY => result.prio = parse_prio(value)?,
+          gen_atoms::MIN_HEAP_SIZE => result.min_heap_size = parse_non_neg(value)?,
+          gen_atoms::FULLSWEEP_AFTER => {
+            result.fullsweep_after = Some(parse_non_neg(value)?)
+          }
+          _ => return fail::create::badarg(),
+        }
+      }
+      _ => return fail::create::badarg(),
+    }
+    Ok(())
+  })?;
+  if tail.is_some() && tail != Some(LTerm::nil()) {
+    return fail::create::badarg();
+  }
+  Ok((result, monitor))
+}
+
+/// Create the process, link and monitor it if asked. Returns the pid, or
+/// `{Pid, MonitorRef}` if the process is monitored.
+fn spawn(
+  vm: &mut VM,
+  cur_proc: &mut Process,
+  mfargs: &MFASomething,
+  mut opts: SpawnOptions,
+  monitor: bool,
+) -> RtResult<LTerm> {
+  if monitor {
+    opts.monitor = Some(vm.shared.next_ref_id());
+  }
+  let pid = vm.create_process(cur_proc.pid, mfargs, &opts)?;
+  if opts.link {
+    cur_proc.links.insert(pid);
+  }
+  match opts.monitor {
+    Some(ref_id) => {
+      cur_proc.monitors.insert(ref_id, pid);
+      heap::build_on_heap(&mut cur_proc.heap, |hp| {
+        let monitor_ref = boxed::Reference::create_into(hp, ref_id)?;
+        make_tuple2(pid, monitor_ref, hp)
+      })
+    }
+    None => Ok(pid),
+  }
+}
+
+/// Spec: erlang:spawn(fun)
+pub fn bif_erlang_spawn_1(
+  vm: &mut VM,
+  cur_proc: &mut Process,
+  args: &[LTerm],
+) -> RtResult<LTerm> {
+  assert_arity("erlang:spawn", 1, args);
+  let mfargs = mfargs_from_fun(args[0])?;
+  spawn(vm, cur_proc, &mfargs, SpawnOptions::default(), false)
+}
+
+/// Spec: erlang:spawn(node, fun)
+pub fn bif_erlang_spawn_2(
+  vm: &mut VM,
+  cur_proc: &mut Process,
+  args: &[LTerm],
+) -> RtResult<LTerm> {
+  assert_arity("erlang:spawn", 2, args);
+  check_node(args[0])?;
+  let mfargs = mfargs_from_fun(args[1])?;
+  spawn(vm, cur_proc, &mfargs, SpawnOptions::default(), false)
+}
+
+/// Creates a new process specified by `module:function/arity` with `args`
+/// (args are passed as list), `arity` is length of args list.
+/// Spec: erlang:spawn(mod, fun, args:list)
+pub fn bif_erlang_spawn_3(
+  vm: &mut VM,
+  cur_proc: &mut Process,
+  args: &[LTerm],
+) -> RtResult<LTerm> {
+  assert_arity("erlang:spawn", 3, args);
+  let mfargs = mfargs_from_mfa(args[0], args[1], args[2])?;
+  spawn(vm, cur_proc, &mfargs, SpawnOptions::default(), false)
+}
+
+/// Spec: erlang:spawn(node, mod, fun, args:list)
+pub fn bif_erlang_spawn_4(
+  vm: &mut VM,
+  cur_proc: &mut Process,
+  args: &[LTerm],
+) -> RtResult<LTerm> {
+  assert_arity("erlang:spawn", 4, args);
+  check_node(args[0])?;
+  let mfargs = mfargs_from_mfa(args[1], args[2], args[3])?;
+  spawn(vm, cur_proc, &mfargs, SpawnOptions::default(), false)
+}
+
+/// Options for the `spawn_link` variants.
+fn link_options() -> SpawnOptions {
+  SpawnOptions {
+    link: true,
+    ..SpawnOptions::default()
+  }
+}
+
+/// Same as `spawn/1` but also links the calling process with the new one.
+/// Spec: erlang:spawn_link(fun)
+pub fn bif_erlang_spawn_link_1(
+  vm: &mut VM,
+  cur_proc: &mut Process,
+  args: &[LTerm],
+) -> RtResult<LTerm> {
+  assert_arity("erlang:spawn_link", 1, args);
+  let mfargs = mfargs_from_fun(args[0])?;
+  spawn(vm, cur_proc, &mfargs, link_options(), false)
+}
+
+/// Spec: erlang:spawn_link(node, fun)
+pub fn bif_erlang_spawn_link_2(
+  vm: &mut VM,
+  cur_proc: &mut Process,
+  args: &[LTerm],
+) -> RtResult<LTerm> {
+  assert_arity("erlang:spawn_link", 2, args);
+  check_node(args[0])?;
+  let mfargs = mfargs_from_fun(args[1])?;
+  spawn(vm, cur_proc, &mfargs, link_options(), false)
+}
+
+/// Same as `spawn/3` but also links the calling process with the new one. The
+/// link exists before the new process runs.
+/// Spec: erlang:spawn_link(mod, fun, args:list)
+pub fn bif_erlang_spawn_link_3(
+  vm: &mut VM,
+  cur_proc: &mut Process,
+  args: &[LTerm],
+) -> RtResult<LTerm> {
+  assert_arity("erlang:spawn_link", 3, args);
+  let mfargs = mfargs_from_mfa(args[0], args[1], args[2])?;
+  spawn(vm, cur_proc, &mfargs, link_options(), false)
+}
+
+/// Spec: erlang:spawn_link(node, mod, fun, args:list)
+pub fn bif_erlang_spawn_link_4(
+  vm: &mut VM,
+  cur_proc: &mut Process,
+  args: &[LTerm],
+) -> RtResult<LTerm> {
+  assert_arity("erlang:spawn_link", 4, args);
+  check_node(args[0])?;
+  let mfargs = mfargs_from_mfa(args[1], args[2], args[3])?;
+  spawn(vm, cur_proc, &mfargs, link_options(), false)
+}
+
+/// Spawn and monitor the new process, returns `{Pid, MonitorRef}`.
+/// Spec: erlang:spawn_monitor(fun)
+pub fn bif_erlang_spawn_monitor_1(
+  vm: &mut VM,
+  cur_proc: &mut Process,
+  args: &[LTerm],
+) -> RtResult<LTerm> {
+  assert_arity("erlang:spawn_monitor", 1, args);
+  let mfargs = mfargs_from_fun(args[0])?;
+  spawn(vm, cur_proc, &mfargs, SpawnOptions::default(), true)
+}
+
+/// Spec: erlang:spawn_monitor(mod, fun, args:list)
+pub fn bif_erlang_spawn_monitor_3(
+  vm: &mut VM,
+  cur_proc: &mut Process,
+  args: &[LTerm],
+) -> RtResult<LTerm> {
+  assert_arity("erlang:spawn_monitor", 3, args);
+  let mfargs = mfargs_from_mfa(args[0], args[1], args[2])?;
+  spawn(vm, cur_proc, &mfargs, SpawnOptions::default(), true)
+}
+
+/// Spec: erlang:spawn_opt(fun, options:list)
+pub fn bif_erlang_spawn_opt_2(
+  vm: &mut VM,
+  cur_proc: &mut Process,
+  args: &[LTerm],
+) -> RtResult<LTerm> {
+  assert_arity("erlang:spawn_opt", 2, args);
+  let mfargs = mfargs_from_fun(args[0])?;
+  let (opts, monitor) = parse_spawn_options(args[1])?;
+  spawn(vm, cur_proc, &mfargs, opts, monitor)
+}
+
+/// Spec: erlang:spawn_opt(node, fun, options:list)
+pub fn bif_erlang_spawn_opt_3(
+  vm: &mut VM,
+  cur_proc: &mut Process,
+  args: &[LTerm],
+) -> RtResult<LTerm> {
+  assert_arity("erlang:spawn_opt", 3, args);
+  check_node(args[0])?;
+  let mfargs = mfargs_from_fun(args[1])?;
+  let (opts, monitor) = parse_spawn_options(args[2])?;
+  spawn(vm, cur_proc, &mfargs, opts, monitor)
+}
+
+/// Spec: erlang:spawn_opt(mod, fun, args:list, options:list)
+pub fn bif_erlang_spawn_opt_4(
+  vm: &mut VM,
+  cur_proc: &mut Process,
+  args: &[LTerm],
+) -> RtResult<LTerm> {
+  assert_arity("erlang:spawn_opt", 4, args);
+  let mfargs = mfargs_from_mfa(args[0], args[1], args[2])?;
+  let (opts, monitor) = parse_spawn_options(args[3])?;
+  spawn(vm, cur_proc, &mfargs, opts, monitor)
+}
+
+/// Spec: erlang:spawn_opt(node, mod, fun, args:list, options:list)
+pub fn bif_erlang_spawn_opt_5(
+  vm: &mut VM,
+  cur_proc: &mut Process,
+  args: &[LTerm],
+) -> RtResult<LTerm> {
+  assert_arity("erlang:spawn_opt", 5, args);
+  check_node(args[0])?;
+  let mfargs = mfargs_from_mfa(args[1], args[2], args[3])?;
+  let (opts, monitor) = parse_spawn_options(args[4])?;
+  spawn(vm, cur_proc, &mfargs, opts, monitor)
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::{bif::bif_process::make_list, emulator::heap::Heap};
+
+  #[test]
+  fn test_parse_spawn_options() {
+    let mut hp = Heap::new(32);
+    let prio = make_tuple2(gen_atoms::PRIORITY, gen_atoms::HIGH, &mut hp).unwrap();
+    let heap_size =
+      make_tuple2(gen_atoms::MIN_HEAP_SIZE, LTerm::make_small_unsigned(100), &mut hp)
+        .unwrap();
+    let opts = [gen_atoms::LINK, prio, heap_size, gen_atoms::MONITOR];
+    let list = make_list(&opts, &mut hp).unwrap();
+    let (parsed, monitor) = parse_spawn_options(list).unwrap();
+    assert!(parsed.link && monitor);
+    assert_eq!(parsed.prio, Prio::High);
+    assert_eq!(parsed.min_heap_size, 100);
+    assert_eq!(parsed.fullsweep_after, None);
+
+    let bad = make_tuple2(gen_atoms::PRIORITY, gen_atoms::OK, &mut hp).unwrap();
+    let list = make_list(&[bad], &mut hp).unwrap();
+    assert!(parse_spawn_options(list).is_err());
+    assert!(parse_spawn_options(gen_atoms::LINK).is_err());
+  }
+}
@@ -1,9 +1,9 @@
 use crate::{
   bif::assert_arity,
   defs::exc_type::ExceptionType,
-  emulator::{process::Process, vm::VM},
+  emulator::{gen_atoms, heap, process::Process, vm::VM},
   fail::{Error, RtResult},
-  term::{builders::make_badfun_n, lterm::LTerm, term_builder::TupleBuilder},
+  term::{boxed, builders::make_badfun_n, lterm::LTerm, term_builder::TupleBuilder},
 };
 
 #[allow(dead_code)]
@@ -11,6 +11,30 @@ fn module() -> &'static str {
   "bif_sys: "
 }
 
+/// Name of the local node. Distribution is not supported, so the node is
+/// always `nonode@nohost`.
+pub fn bif_erlang_node_0(
+  _vm: &mut VM,
+  _cur_proc: &mut Process,
+  args: &[LTerm],
+) -> RtResult<LTerm> {
+  assert_arity("erlang:node", 0, args);
+  Ok(gen_atoms::NONODE_AT_NOHOST)
+}
+
+/// Create a new reference, unique within this VM.
+pub fn bif_erlang_make_ref_0(
+  vm: &mut VM,
+  cur_proc: &mut Process,
+  args: &[LTerm],
+) -> RtResult<LTerm> {
+  assert_arity("erlang:make_ref", 0, args);
+  let ref_id = vm.shared.next_ref_id();
+  heap::build_on_heap(&mut cur_proc.heap, |hp| {
+    boxed::Reference::create_into(hp, ref_id)
+  })
+}
+
 /// Create an error for a NIF not loaded/not implemented.
 pub fn bif_erlang_nif_error_1(
   _vm: &mut VM,
@@ -62,6 +86,17 @@ pub fn bif_erlang_error_1(
   Err(Error::Exception(ExceptionType::Error, args[0]))
 }
 
+/// Create an exception of type `exit`. If not caught, the process
+/// terminates and its linked processes receive exit signals with the reason.
+pub fn bif_erlang_exit_1(
+  _vm: &mut VM,
+  _curr_p: &mut Process,
+  args: &[LTerm],
+) -> RtResult<LTerm> {
+  assert_arity("erlang:exit/1", 1, args);
+  Err(Error::Exception(ExceptionType::Exit, args[0]))
+}
+
 /// Return `true` if the value is a boolean (atom `true` or atom `false`)
 pub fn ubif_erlang_is_boolean_1(
   _vm: &mut VM,
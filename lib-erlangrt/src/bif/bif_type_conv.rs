@@ -1,8 +1,13 @@
 use crate::{
   bif::assert_arity,
-  emulator::{atom, process::Process, vm::VM},
+  emulator::{atom, gen_atoms, heap, process::Process, vm::VM},
   fail::{self, RtResult},
-  term::lterm::{cons, LTerm},
+  rt_util::ext_term_format,
+  term::{
+    boxed,
+    lterm::{cons, LTerm},
+    term_builder::TermBuilder,
+  },
 };
 
 pub fn bif_erlang_atom_to_list_1(
@@ -37,3 +42,159 @@ pub fn bif_erlang_integer_to_list_1(
   }
   unsafe { cons::integer_to_list(val, &mut curr_p.heap) }
 }
+
+/// Converts a reference to Erlang string, same as it is printed.
+pub fn bif_erlang_ref_to_list_1(
+  _vm: &mut VM,
+  curr_p: &mut Process,
+  args: &[LTerm],
+) -> RtResult<LTerm> {
+  assert_arity("erlang:ref_to_list", 1, args);
+  if !args[0].is_ref() {
+    return fail::create::badarg();
+  }
+  let s = format!("{}", args[0]);
+  heap::build_on_heap(&mut curr_p.heap, |hp| unsafe {
+    cons::rust_str_to_list(&s, hp)
+  })
+}
+
+/// Encodes a term into a binary in the external term format.
+pub fn bif_erlang_term_to_binary_1(
+  _vm: &mut VM,
+  curr_p: &mut Process,
+  args: &[LTerm],
+) -> RtResult<LTerm> {
+  assert_arity("erlang:term_to_binary", 1, args);
+  let data = ext_term_format::encode(args[0])?;
+  heap::build_on_heap(&mut curr_p.heap, |hp| unsafe {
+    TermBuilder::new(hp).create_binary(&data)
+  })
+}
+
+/// How `float_to_list` formats a value.
+enum FloatFormat {
+  /// Digits after the point in scientific notation
+  Scientific(usize),
+  /// Digits after the point, and whether trailing zeros are removed
+  Decimals(usize, bool),
+}
+
+/// Converts a float to Erlang string, same as `float_to_list(F, [{scientific,
+/// 20}])`.
+pub fn bif_erlang_float_to_list_1(
+  _vm: &mut VM,
+  curr_p: &mut Process,
+  args: &[LTerm],
+) -> RtResult<LTerm> {
+  assert_arity("erlang:float_to_list", 1, args);
+  float_to_list(curr_p, args[0], &FloatFormat::Scientific(20))
+}
+
+/// Converts a float to Erlang string, supported options are
+/// `{decimals, 0..253}`, `{scientific, 0..249}` and `compact`.
+pub fn bif_erlang_float_to_list_2(
+  _vm: &mut VM,
+  curr_p: &mut Process,
+  args: &[LTerm],
+) -> RtResult<LTerm> {
+  assert_arity("erlang:float_to_list", 2, args);
+  let format = parse_float_format(args[1])?;
+  float_to_list(curr_p, args[0], &format)
+}
+
+fn float_to_list(
+  curr_p: &mut Process,
+  val: LTerm,
+  format: &FloatFormat,
+) -> RtResult<LTerm> {
+  if !val.is_float() {
+    return fail::create::badarg();
+  }
+  let text = format_float(unsafe { val.get_f64_unsafe() }, format);
+  heap::build_on_heap(&mut curr_p.heap, |hp| unsafe {
+    cons::rust_str_to_list(&text, hp)
+  })
+}
+
+/// Read the options list of `float_to_list/2`, later options override the
+/// earlier ones.
+fn parse_float_format(opts: LTerm) -> RtResult<FloatFormat> {
+  if !opts.is_list() {
+    return fail::create::badarg();
+  }
+  let mut format = FloatFormat::Scientific(20);
+  let mut compact = false;
+  let tail = cons::for_each(opts, |opt| {
+    if opt == gen_atoms::COMPACT {
+      compact = true;
+      return Ok(());
+    }
+    if !opt.is_tuple() || unsafe { (*opt.get_tuple_ptr()).get_arity() } != 2 {
+      return fail::create::badarg();
+    }
+    let p = opt.get_tuple_ptr();
+    let (key, n) = unsafe {
+      (
+        boxed::Tuple::get_element_base0(p, 0),
+        boxed::Tuple::get_element_base0(p, 1),
+      )
+    };
+    if !n.is_small() || n.get_small_signed() < 0 {
+      return fail::create::badarg();
+    }
+    let n = n.get_small_unsigned();
+    format = match key {
+      k if k == gen_atoms::DECIMALS && n <= 253 => FloatFormat::Decimals(n, false),
+      k if k == gen_atoms::SCIENTIFIC && n <= 249 => FloatFormat::Scientific(n),
+      _ => return fail::create::badarg(),
+    };
+    Ok(())
+  })?;
+  if tail.is_some() && tail != Some(LTerm::nil()) {
+    return fail::create::badarg();
+  }
+  if let FloatFormat::Decimals(n, _) = format {
+    format = FloatFormat::Decimals(n, compact);
+  }
+  Ok(format)
+}
+
+/// Format like C `printf` does with `%.*e` or `%.*f`.
+fn format_float(val: f64, format: &FloatFormat) -> String {
+  match *format {
+    FloatFormat::Scientific(digits) => {
+      // Rust prints the exponent as `e5`, C has a sign and 2 digits `e+05`
+      let text = format!("{:.*e}", digits, val);
+      let (mantissa, exp) = text.split_at(text.find('e').unwrap());
+      let exp: i32 = exp[1..].parse().unwrap();
+      let sign = if exp < 0 { '-' } else { '+' };
+      format!("{}e{}{:02}", mantissa, sign, exp.abs())
+    }
+    FloatFormat::Decimals(digits, compact) => {
+      let mut text = format!("{:.*}", digits, val);
+      if compact && text.contains('.') {
+        // Keep at least one digit after the point
+        while text.ends_with('0') && !text.ends_with(".0") {
+          text.pop();
+        }
+      }
+      text
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn format_float_like_c() {
+    let sci = FloatFormat::Scientific(20);
+    assert_eq!(format_float(1.5, &sci), "1.50000000000000000000e+00");
+    assert_eq!(format_float(-0.00123, &FloatFormat::Scientific(2)), "-1.23e-03");
+    assert_eq!(format_float(2.5, &FloatFormat::Decimals(4, false)), "2.5000");
+    assert_eq!(format_float(2.5, &FloatFormat::Decimals(4, true)), "2.5");
+    assert_eq!(format_float(2.0, &FloatFormat::Decimals(4, true)), "2.0");
+  }
+}
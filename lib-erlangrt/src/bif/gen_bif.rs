@@ -25,8 +25,12 @@ pub static BIF_TABLE: &'static [BifTabItem] = &[
         func: bif::ubif_erlang_plus_2 },
     BifTabItem { m: gen_atoms::ERLANG, f: gen_atoms::PLUSPLUS, arity: 2,
         func: bif::bif_erlang_plusplus_2 },
+    BifTabItem { m: gen_atoms::ERLANG, f: gen_atoms::SYM_MINUS, arity: 1,
+        func: bif::ubif_erlang_minus_1 },
     BifTabItem { m: gen_atoms::ERLANG, f: gen_atoms::SYM_MINUS, arity: 2,
         func: bif::ubif_erlang_minus_2 },
+    BifTabItem { m: gen_atoms::ERLANG, f: gen_atoms::DIVIDE, arity: 2,
+        func: bif::ubif_erlang_divide_2 },
     BifTabItem { m: gen_atoms::ERLANG, f: gen_atoms::NOTEQUAL, arity: 2,
         func: bif::ubif_erlang_notequal_2 },
     BifTabItem { m: gen_atoms::ERLANG, f: gen_atoms::LESSTHAN, arity: 2,
@@ -43,35 +47,165 @@ pub static BIF_TABLE: &'static [BifTabItem] = &[
         func: bif::ubif_erlang_greaterthan_2 },
     BifTabItem { m: gen_atoms::ERLANG, f: gen_atoms::GREATEREQUAL, arity: 2,
         func: bif::ubif_erlang_greaterequal_2 },
+    BifTabItem { m: gen_atoms::ERLANG, f: gen_atoms::ABS, arity: 1,
+        func: bif::ubif_erlang_abs_1 },
     BifTabItem { m: gen_atoms::ERLANG, f: gen_atoms::ATOM_TO_LIST, arity: 1,
         func: bif::bif_erlang_atom_to_list_1 },
+    BifTabItem { m: gen_atoms::ERLANG, f: gen_atoms::BAND, arity: 2,
+        func: bif::ubif_erlang_band_2 },
+    BifTabItem { m: gen_atoms::ERLANG, f: gen_atoms::BNOT, arity: 1,
+        func: bif::ubif_erlang_bnot_1 },
+    BifTabItem { m: gen_atoms::ERLANG, f: gen_atoms::BOR, arity: 2,
+        func: bif::ubif_erlang_bor_2 },
+    BifTabItem { m: gen_atoms::ERLANG, f: gen_atoms::BSL, arity: 2,
+        func: bif::ubif_erlang_bsl_2 },
+    BifTabItem { m: gen_atoms::ERLANG, f: gen_atoms::BSR, arity: 2,
+        func: bif::ubif_erlang_bsr_2 },
+    BifTabItem { m: gen_atoms::ERLANG, f: gen_atoms::BXOR, arity: 2,
+        func: bif::ubif_erlang_bxor_2 },
+    BifTabItem { m: gen_atoms::ERLANG, f: gen_atoms::DEMONITOR, arity: 1,
+        func: bif::bif_erlang_demonitor_1 },
+    BifTabItem { m: gen_atoms::ERLANG, f: gen_atoms::DEMONITOR, arity: 2,
+        func: bif::bif_erlang_demonitor_2 },
+    BifTabItem { m: gen_atoms::ERLANG, f: gen_atoms::INT_DIV, arity: 2,
+        func: bif::ubif_erlang_int_div_2 },
+    BifTabItem { m: gen_atoms::ERLANG, f: gen_atoms::ERASE, arity: 0,
+        func: bif::bif_erlang_erase_0 },
+    BifTabItem { m: gen_atoms::ERLANG, f: gen_atoms::ERASE, arity: 1,
+        func: bif::bif_erlang_erase_1 },
     BifTabItem { m: gen_atoms::ERLANG, f: gen_atoms::ERROR, arity: 1,
         func: bif::bif_erlang_error_1 },
     BifTabItem { m: gen_atoms::ERLANG, f: gen_atoms::ERROR, arity: 2,
         func: bif::bif_erlang_error_2 },
+    BifTabItem { m: gen_atoms::ERLANG, f: gen_atoms::EXIT, arity: 1,
+        func: bif::bif_erlang_exit_1 },
+    BifTabItem { m: gen_atoms::ERLANG, f: gen_atoms::EXIT, arity: 2,
+        func: bif::bif_erlang_exit_2 },
+    BifTabItem { m: gen_atoms::MAPS, f: gen_atoms::FIND, arity: 2,
+        func: bif::bif_maps_find_2 },
+    BifTabItem { m: gen_atoms::ERLANG, f: gen_atoms::FLOAT, arity: 1,
+        func: bif::ubif_erlang_float_1 },
+    BifTabItem { m: gen_atoms::ERLANG, f: gen_atoms::FLOAT_TO_LIST, arity: 1,
+        func: bif::bif_erlang_float_to_list_1 },
+    BifTabItem { m: gen_atoms::ERLANG, f: gen_atoms::FLOAT_TO_LIST, arity: 2,
+        func: bif::bif_erlang_float_to_list_2 },
+    BifTabItem { m: gen_atoms::MAPS, f: gen_atoms::FROM_LIST, arity: 1,
+        func: bif::bif_maps_from_list_1 },
+    BifTabItem { m: gen_atoms::ERLANG, f: gen_atoms::GET, arity: 0,
+        func: bif::bif_erlang_get_0 },
+    BifTabItem { m: gen_atoms::ERLANG, f: gen_atoms::GET, arity: 1,
+        func: bif::bif_erlang_get_1 },
+    BifTabItem { m: gen_atoms::MAPS, f: gen_atoms::GET, arity: 2,
+        func: bif::bif_maps_get_2 },
+    BifTabItem { m: gen_atoms::ERLANG, f: gen_atoms::GET_KEYS, arity: 0,
+        func: bif::bif_erlang_get_keys_0 },
+    BifTabItem { m: gen_atoms::ERLANG, f: gen_atoms::GET_KEYS, arity: 1,
+        func: bif::bif_erlang_get_keys_1 },
     BifTabItem { m: gen_atoms::ERLANG, f: gen_atoms::HD, arity: 1,
         func: bif::ubif_erlang_hd_1 },
+    BifTabItem { m: gen_atoms::ERLANG, f: gen_atoms::HIBERNATE, arity: 3,
+        func: bif::bif_erlang_hibernate_3 },
     BifTabItem { m: gen_atoms::ERLANG, f: gen_atoms::INTEGER_TO_LIST, arity: 1,
         func: bif::bif_erlang_integer_to_list_1 },
     BifTabItem { m: gen_atoms::ERLANG, f: gen_atoms::IS_BOOLEAN, arity: 1,
         func: bif::ubif_erlang_is_boolean_1 },
     BifTabItem { m: gen_atoms::ERLANG, f: gen_atoms::IS_PROCESS_ALIVE, arity: 1,
         func: bif::bif_erlang_is_process_alive_1 },
+    BifTabItem { m: gen_atoms::MAPS, f: gen_atoms::KEYS, arity: 1,
+        func: bif::bif_maps_keys_1 },
     BifTabItem { m: gen_atoms::ERLANG, f: gen_atoms::LENGTH, arity: 1,
         func: bif::gcbif_erlang_length_1 },
+    BifTabItem { m: gen_atoms::ERLANG, f: gen_atoms::LINK, arity: 1,
+        func: bif::bif_erlang_link_1 },
     BifTabItem { m: gen_atoms::ERLANG, f: gen_atoms::MAKE_FUN, arity: 3,
         func: bif::bif_erlang_make_fun_3 },
+    BifTabItem { m: gen_atoms::ERLANG, f: gen_atoms::MAKE_REF, arity: 0,
+        func: bif::bif_erlang_make_ref_0 },
     BifTabItem { m: gen_atoms::LISTS, f: gen_atoms::MEMBER, arity: 2,
         func: bif::bif_lists_member_2 },
+    BifTabItem { m: gen_atoms::ERLANG, f: gen_atoms::MONITOR, arity: 2,
+        func: bif::bif_erlang_monitor_2 },
     BifTabItem { m: gen_atoms::ERLANG, f: gen_atoms::NIF_ERROR, arity: 1,
         func: bif::bif_erlang_nif_error_1 },
     BifTabItem { m: gen_atoms::ERLANG, f: gen_atoms::NIF_ERROR, arity: 2,
         func: bif::bif_erlang_nif_error_2 },
+    BifTabItem { m: gen_atoms::ERLANG, f: gen_atoms::NODE, arity: 0,
+        func: bif::bif_erlang_node_0 },
+    BifTabItem { m: gen_atoms::BINARY, f: gen_atoms::PART, arity: 3,
+        func: bif::bif_binary_part_3 },
+    BifTabItem { m: gen_atoms::ERLANG, f: gen_atoms::PROCESS_FLAG, arity: 2,
+        func: bif::bif_erlang_process_flag_2 },
+    BifTabItem { m: gen_atoms::ERLANG, f: gen_atoms::PROCESS_FLAG, arity: 3,
+        func: bif::bif_erlang_process_flag_3 },
+    BifTabItem { m: gen_atoms::ERLANG, f: gen_atoms::PROCESS_INFO, arity: 1,
+        func: bif::bif_erlang_process_info_1 },
+    BifTabItem { m: gen_atoms::ERLANG, f: gen_atoms::PROCESS_INFO, arity: 2,
+        func: bif::bif_erlang_process_info_2 },
+    BifTabItem { m: gen_atoms::ERLANG, f: gen_atoms::PROCESSES, arity: 0,
+        func: bif::bif_erlang_processes_0 },
+    BifTabItem { m: gen_atoms::ERLANG, f: gen_atoms::PUT, arity: 2,
+        func: bif::bif_erlang_put_2 },
+    BifTabItem { m: gen_atoms::MAPS, f: gen_atoms::PUT, arity: 3,
+        func: bif::bif_maps_put_3 },
+    BifTabItem { m: gen_atoms::ERLANG, f: gen_atoms::REF_TO_LIST, arity: 1,
+        func: bif::bif_erlang_ref_to_list_1 },
+    BifTabItem { m: gen_atoms::ERLANG, f: gen_atoms::REGISTER, arity: 2,
+        func: bif::bif_erlang_register_2 },
+    BifTabItem { m: gen_atoms::ERLANG, f: gen_atoms::REGISTERED, arity: 0,
+        func: bif::bif_erlang_registered_0 },
+    BifTabItem { m: gen_atoms::ERLANG, f: gen_atoms::REM, arity: 2,
+        func: bif::ubif_erlang_rem_2 },
+    BifTabItem { m: gen_atoms::MAPS, f: gen_atoms::REMOVE, arity: 2,
+        func: bif::bif_maps_remove_2 },
+    BifTabItem { m: gen_atoms::ERLANG, f: gen_atoms::ROUND, arity: 1,
+        func: bif::ubif_erlang_round_1 },
     BifTabItem { m: gen_atoms::ERLANG, f: gen_atoms::SELF, arity: 0,
         func: bif::ubif_erlang_self_0 },
+    BifTabItem { m: gen_atoms::ERLANG, f: gen_atoms::SPAWN, arity: 1,
+        func: bif::bif_erlang_spawn_1 },
+    BifTabItem { m: gen_atoms::ERLANG, f: gen_atoms::SPAWN, arity: 2,
+        func: bif::bif_erlang_spawn_2 },
     BifTabItem { m: gen_atoms::ERLANG, f: gen_atoms::SPAWN, arity: 3,
         func: bif::bif_erlang_spawn_3 },
+    BifTabItem { m: gen_atoms::ERLANG, f: gen_atoms::SPAWN, arity: 4,
+        func: bif::bif_erlang_spawn_4 },
+    BifTabItem { m: gen_atoms::ERLANG, f: gen_atoms::SPAWN_LINK, arity: 1,
+        func: bif::bif_erlang_spawn_link_1 },
+    BifTabItem { m: gen_atoms::ERLANG, f: gen_atoms::SPAWN_LINK, arity: 2,
+        func: bif::bif_erlang_spawn_link_2 },
+    BifTabItem { m: gen_atoms::ERLANG, f: gen_atoms::SPAWN_LINK, arity: 3,
+        func: bif::bif_erlang_spawn_link_3 },
+    BifTabItem { m: gen_atoms::ERLANG, f: gen_atoms::SPAWN_LINK, arity: 4,
+        func: bif::bif_erlang_spawn_link_4 },
+    BifTabItem { m: gen_atoms::ERLANG, f: gen_atoms::SPAWN_MONITOR, arity: 1,
+        func: bif::bif_erlang_spawn_monitor_1 },
+    BifTabItem { m: gen_atoms::ERLANG, f: gen_atoms::SPAWN_MONITOR, arity: 3,
+        func: bif::bif_erlang_spawn_monitor_3 },
+    BifTabItem { m: gen_atoms::ERLANG, f: gen_atoms::SPAWN_OPT, arity: 2,
+        func: bif::bif_erlang_spawn_opt_2 },
+    BifTabItem { m: gen_atoms::ERLANG, f: gen_atoms::SPAWN_OPT, arity: 3,
+        func: bif::bif_erlang_spawn_opt_3 },
+    BifTabItem { m: gen_atoms::ERLANG, f: gen_atoms::SPAWN_OPT, arity: 4,
+        func: bif::bif_erlang_spawn_opt_4 },
+    BifTabItem { m: gen_atoms::ERLANG, f: gen_atoms::SPAWN_OPT, arity: 5,
+        func: bif::bif_erlang_spawn_opt_5 },
+    BifTabItem { m: gen_atoms::ERLANG, f: gen_atoms::SPLIT_BINARY, arity: 2,
+        func: bif::bif_erlang_split_binary_2 },
+    BifTabItem { m: gen_atoms::ERLANG, f: gen_atoms::TERM_TO_BINARY, arity: 1,
+        func: bif::bif_erlang_term_to_binary_1 },
     BifTabItem { m: gen_atoms::ERLANG, f: gen_atoms::TL, arity: 1,
         func: bif::ubif_erlang_tl_1 },
+    BifTabItem { m: gen_atoms::MAPS, f: gen_atoms::TO_LIST, arity: 1,
+        func: bif::bif_maps_to_list_1 },
+    BifTabItem { m: gen_atoms::ERLANG, f: gen_atoms::TRUNC, arity: 1,
+        func: bif::ubif_erlang_trunc_1 },
+    BifTabItem { m: gen_atoms::ERLANG, f: gen_atoms::UNLINK, arity: 1,
+        func: bif::bif_erlang_unlink_1 },
+    BifTabItem { m: gen_atoms::ERLANG, f: gen_atoms::UNREGISTER, arity: 1,
+        func: bif::bif_erlang_unregister_1 },
+    BifTabItem { m: gen_atoms::MAPS, f: gen_atoms::VALUES, arity: 1,
+        func: bif::bif_maps_values_1 },
+    BifTabItem { m: gen_atoms::ERLANG, f: gen_atoms::WHEREIS, arity: 1,
+        func: bif::bif_erlang_whereis_1 },
 ];
 
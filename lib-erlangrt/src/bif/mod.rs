@@ -9,15 +9,19 @@ pub mod gen_bif; // generated
 // Bif definitions grouped by topic
 //
 pub mod bif_arith;
+pub mod bif_binary;
 pub mod bif_compare;
 pub mod bif_lists;
+pub mod bif_maps;
 pub mod bif_process;
+pub mod bif_process_info;
+pub mod bif_spawn;
 pub mod bif_sys;
 pub mod bif_type_conv;
 
 pub use crate::bif::{
-  bif_arith::*, bif_compare::*, bif_lists::*, bif_process::*, bif_sys::*,
-  bif_type_conv::*,
+  bif_arith::*, bif_binary::*, bif_compare::*, bif_lists::*, bif_maps::*,
+  bif_process::*, bif_process_info::*, bif_spawn::*, bif_sys::*, bif_type_conv::*,
 };
 
 /// A BIF function which runs under some process, takes some args (encoded in
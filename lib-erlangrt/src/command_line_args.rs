@@ -1,3 +1,5 @@
+use std::thread;
+
 #[derive(Debug)]
 pub enum NodeName {
   Short(String),
@@ -16,6 +18,9 @@ pub struct ErlStartArgs {
   /// Which modules:functions to start (option -s m f arg1,...)
   pub start: Vec<Vec<String>>,
   pub search_path: Vec<String>,
+  /// How many scheduler threads to run (option +S N), defaults to the number
+  /// of CPU cores.
+  pub num_schedulers: usize,
 }
 
 impl ErlStartArgs {
@@ -24,7 +29,8 @@ impl ErlStartArgs {
       other_args: Vec::new(),
       node: NodeName::Short("nonode@nohost".to_string()),
       start: Vec::new(),
-      search_path: vec![]
+      search_path: vec![],
+      num_schedulers: thread::available_parallelism().map_or(1, |n| n.get()),
     }
   }
 
@@ -34,14 +40,21 @@ impl ErlStartArgs {
       .push(data.iter().map(|s| s.to_string()).collect())
   }
 
-  /// Copy args from any string iterator source
+  /// Copy args from any string iterator source. Options which take a
+  /// parameter consume the next string too.
   pub fn populate_with<ITER>(&mut self, mut iter: ITER)
   where
     ITER: Iterator<Item = String>,
   {
     loop {
       if let Some(s) = iter.next() {
-        self.add_arg1(s.as_ref())
+        match s.as_ref() {
+          "-sname" | "-name" | "+S" => match iter.next() {
+            Some(param) => self.add_arg2(s.as_ref(), param.as_ref()),
+            None => self.add_arg1(s.as_ref()),
+          },
+          other => self.add_arg1(other),
+        }
       } else {
         break;
       }
@@ -70,10 +83,22 @@ impl ErlStartArgs {
       "-name" => {
         self.node = NodeName::Full(args[1].to_string());
       }
+      "+S" if args.len() > 1 => self.set_num_schedulers(args[1]),
+      other if other.starts_with("+S") => self.set_num_schedulers(&other[2..]),
       other => self.other_args.push(String::from(other)),
     }
   }
 
+  /// Parse `Schedulers[:SchedulersOnline]` value of the +S option, only the
+  /// first number is used. Invalid values are ignored.
+  fn set_num_schedulers(&mut self, val: &str) {
+    let total = val.split(':').next().unwrap_or("");
+    match total.parse::<usize>() {
+      Ok(n) if n > 0 => self.num_schedulers = n,
+      _ => println!("Ignoring invalid +S value: {}", val),
+    }
+  }
+
   /// Set node name without changing node mode
   pub fn set_node_name(&mut self, n: &str) {
     match self.node {
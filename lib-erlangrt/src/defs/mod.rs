@@ -42,6 +42,26 @@ impl Reductions {
 
   /// Fetch is base "tax" for fetching an opcode and dispatching to its handler
   pub const FETCH_OPCODE_COST: isize = 1;
+
+  /// Sending a message, the copying of the message is charged separately
+  pub const SEND_COST: isize = 1;
+
+  /// List BIFs (`length`, `++`, `lists:member`) charge a reduction per this
+  /// many list elements, and trap when the time slice is used up
+  pub const LIST_ELEMENTS_PER_REDUCTION: usize = 16;
+
+  /// Copying terms between heaps charges a reduction per this many words
+  pub const COPY_WORDS_PER_REDUCTION: usize = 64;
+
+  /// Garbage collection charges a reduction per this many live words
+  pub const GC_WORDS_PER_REDUCTION: usize = 100;
+
+  /// Cost of processing `n` units of work, each reduction pays for
+  /// `per_reduction` of them. Rounded up, so any work costs something.
+  #[inline]
+  pub const fn for_units(n: usize, per_reduction: usize) -> isize {
+    n.div_ceil(per_reduction) as isize
+  }
 }
 
 // / For n bytes calculate how many words are required to store this
@@ -0,0 +1,614 @@
+//! Arbitrary precision integer arithmetic on digit arrays, similar to `big.c`
+//! in Erlang/OTP. Bignums on the heap are unpacked into `BigInteger`, the
+//! result is computed and written back with `BigInteger::to_term` which
+//! demotes it to a small integer if it fits.
+use crate::{
+  defs::{SWord, Word, WORD_BITS},
+  emulator::heap::Heap,
+  fail::RtResult,
+  term::{boxed::Bignum, lterm::*},
+};
+use core::cmp::Ordering;
+
+const DIGIT_BITS: usize = WORD_BITS;
+
+/// Double width integer which holds a product of two digits.
+type DoubleDigit = u128;
+
+/// An integer as a sign and a magnitude made of `Word` digits, the lowest
+/// digit first. Magnitude never has leading zero digits, so zero has no
+/// digits and is never negative.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BigInteger {
+  negative: bool,
+  digits: Vec<Word>,
+}
+
+impl BigInteger {
+  fn new(negative: bool, mut digits: Vec<Word>) -> Self {
+    while digits.last() == Some(&0) {
+      digits.pop();
+    }
+    let negative = negative && !digits.is_empty();
+    Self { negative, digits }
+  }
+
+  pub fn zero() -> Self {
+    Self::new(false, Vec::new())
+  }
+
+  pub fn from_sword(val: SWord) -> Self {
+    Self::new(val < 0, vec![val.unsigned_abs()])
+  }
+
+  pub fn from_i128(val: i128) -> Self {
+    let magnitude = val.unsigned_abs();
+    let mut digits = Vec::with_capacity(2);
+    let mut rest = magnitude;
+    while rest != 0 {
+      digits.push(rest as Word);
+      rest >>= DIGIT_BITS;
+    }
+    Self::new(val < 0, digits)
+  }
+
+  /// Build from the integer part of a finite float.
+  pub fn from_f64_trunc(val: f64) -> Self {
+    debug_assert!(val.is_finite());
+    let t = val.trunc();
+    // Below 2^127 the conversion to i128 is exact
+    if t.abs() < 1.7e38 {
+      return Self::from_i128(t as i128);
+    }
+    // A large float is a 53-bit mantissa shifted left by the exponent
+    let bits = t.to_bits();
+    let exponent = ((bits >> 52) & 0x7ff) as usize - 1075;
+    let mantissa = (bits & ((1 << 52) - 1)) | (1 << 52);
+    Self::new(t < 0.0, vec![mantissa as Word]).shl(exponent)
+  }
+
+  /// Build from a magnitude stored as little-endian bytes (like in the
+  /// external term format).
+  pub fn from_bytes_le(negative: bool, bytes: &[u8]) -> Self {
+    let word_bytes = DIGIT_BITS / 8;
+    let digits = bytes
+      .chunks(word_bytes)
+      .map(|chunk| {
+        chunk
+          .iter()
+          .rev()
+          .fold(0 as Word, |acc, b| (acc << 8) | Word::from(*b))
+      })
+      .collect();
+    Self::new(negative, digits)
+  }
+
+  /// The magnitude as little-endian bytes without trailing zeros, the
+  /// opposite of `from_bytes_le`.
+  pub fn to_bytes_le(&self) -> Vec<u8> {
+    let mut bytes: Vec<u8> =
+      self.digits.iter().flat_map(|d| d.to_le_bytes().to_vec()).collect();
+    while bytes.last() == Some(&0) {
+      bytes.pop();
+    }
+    bytes
+  }
+
+  /// Unpack a small integer or a bignum, return `None` for other terms.
+  pub fn from_term(t: LTerm) -> Option<Self> {
+    if t.is_small() {
+      return Some(Self::from_sword(t.get_small_signed()));
+    }
+    if !t.is_big_int() {
+      return None;
+    }
+    unsafe { Some(Self::from_bignum(t.get_box_ptr::<Bignum>())) }
+  }
+
+  pub unsafe fn from_bignum(p: *const Bignum) -> Self {
+    Self::new(Bignum::is_negative(p), Bignum::get_digits(p).to_vec())
+  }
+
+  /// Store the value on the heap, as a small integer if it fits, otherwise as
+  /// a bignum.
+  pub fn to_term(&self, hp: &mut Heap) -> RtResult<LTerm> {
+    if let Some(val) = self.to_sword() {
+      if LTerm::small_fits(val) {
+        return Ok(LTerm::make_small_signed(val));
+      }
+    }
+    let p = unsafe { Bignum::create_into(hp, self.negative, &self.digits)? };
+    Ok(LTerm::make_boxed(p))
+  }
+
+  pub fn to_sword(&self) -> Option<SWord> {
+    match self.digits.len() {
+      0 => Some(0),
+      1 => {
+        let magnitude = self.digits[0];
+        if self.negative {
+          if magnitude <= SWord::MIN.unsigned_abs() {
+            Some((magnitude as SWord).wrapping_neg())
+          } else {
+            None
+          }
+        } else if magnitude <= SWord::MAX as Word {
+          Some(magnitude as SWord)
+        } else {
+          None
+        }
+      }
+      _ => None,
+    }
+  }
+
+  /// Convert to the nearest float, very big values become infinite.
+  pub fn to_f64(&self) -> f64 {
+    let base = (DIGIT_BITS as f64).exp2();
+    let magnitude = self
+      .digits
+      .iter()
+      .rev()
+      .fold(0.0, |acc, d| acc * base + *d as f64);
+    if self.negative {
+      -magnitude
+    } else {
+      magnitude
+    }
+  }
+
+  #[inline]
+  pub fn is_zero(&self) -> bool {
+    self.digits.is_empty()
+  }
+
+  #[inline]
+  pub fn is_negative(&self) -> bool {
+    self.negative
+  }
+
+  pub fn neg(&self) -> Self {
+    Self::new(!self.negative, self.digits.clone())
+  }
+
+  pub fn abs(&self) -> Self {
+    Self::new(false, self.digits.clone())
+  }
+
+  pub fn add(&self, other: &Self) -> Self {
+    if self.negative == other.negative {
+      return Self::new(self.negative, add_mag(&self.digits, &other.digits));
+    }
+    // Signs differ, subtract the smaller magnitude from the bigger one
+    match cmp_mag(&self.digits, &other.digits) {
+      Ordering::Equal => Self::zero(),
+      Ordering::Greater => Self::new(self.negative, sub_mag(&self.digits, &other.digits)),
+      Ordering::Less => Self::new(other.negative, sub_mag(&other.digits, &self.digits)),
+    }
+  }
+
+  pub fn sub(&self, other: &Self) -> Self {
+    self.add(&other.neg())
+  }
+
+  pub fn mul(&self, other: &Self) -> Self {
+    Self::new(
+      self.negative != other.negative,
+      mul_mag(&self.digits, &other.digits),
+    )
+  }
+
+  /// Division truncated towards zero and the remainder which has the sign of
+  /// the dividend, like `div` and `rem` in Erlang. Returns `None` if `other`
+  /// is zero.
+  pub fn div_rem(&self, other: &Self) -> Option<(Self, Self)> {
+    if other.is_zero() {
+      return None;
+    }
+    let (q, r) = divrem_mag(&self.digits, &other.digits);
+    Some((
+      Self::new(self.negative != other.negative, q),
+      Self::new(self.negative, r),
+    ))
+  }
+
+  pub fn band(&self, other: &Self) -> Self {
+    self.bitwise(other, |a, b| a & b)
+  }
+
+  pub fn bor(&self, other: &Self) -> Self {
+    self.bitwise(other, |a, b| a | b)
+  }
+
+  pub fn bxor(&self, other: &Self) -> Self {
+    self.bitwise(other, |a, b| a ^ b)
+  }
+
+  /// Bitwise negation of the infinite two's complement form is `-x - 1`.
+  pub fn bnot(&self) -> Self {
+    self.neg().sub(&Self::from_sword(1))
+  }
+
+  pub fn shl(&self, bits: usize) -> Self {
+    Self::new(self.negative, shl_mag(&self.digits, bits))
+  }
+
+  /// Arithmetic shift right, rounds towards negative infinity like `bsr`.
+  pub fn shr(&self, bits: usize) -> Self {
+    if !self.negative {
+      return Self::new(false, shr_mag(&self.digits, bits));
+    }
+    // For negative x: x >> n == -((|x| - 1) >> n) - 1
+    let one = [1];
+    let shifted = shr_mag(&sub_mag(&self.digits, &one), bits);
+    Self::new(true, add_mag(&shifted, &one))
+  }
+
+  /// Format the value with digits `0-9A-Z`, `radix` is from 2 to 36.
+  pub fn to_string_radix(&self, radix: u32) -> String {
+    debug_assert!((2..=36).contains(&radix));
+    if self.is_zero() {
+      return String::from("0");
+    }
+    // Divide by the largest power of radix which fits a digit, each remainder
+    // then gives a fixed count of characters
+    let mut chunk = radix as Word;
+    let mut chunk_chars = 1;
+    while let Some(next) = chunk.checked_mul(radix as Word) {
+      chunk = next;
+      chunk_chars += 1;
+    }
+
+    let mut chunks = Vec::new();
+    let mut rest = self.digits.clone();
+    while !rest.is_empty() {
+      let (q, r) = divrem_mag_digit(&rest, chunk);
+      chunks.push(r);
+      rest = q;
+      while rest.last() == Some(&0) {
+        rest.pop();
+      }
+    }
+
+    let mut result = String::new();
+    if self.negative {
+      result.push('-');
+    }
+    for (i, c) in chunks.iter().rev().enumerate() {
+      let text = format_word_radix(*c, radix);
+      if i > 0 {
+        for _ in text.len()..chunk_chars {
+          result.push('0');
+        }
+      }
+      result.push_str(&text);
+    }
+    result
+  }
+
+  /// Convert both values to two's complement of the same length, long enough
+  /// to also hold the sign, then apply `op` to every digit.
+  fn bitwise(&self, other: &Self, op: impl Fn(Word, Word) -> Word) -> Self {
+    let len = self.digits.len().max(other.digits.len()) + 1;
+    let a = self.to_twos_complement(len);
+    let b = other.to_twos_complement(len);
+    let result = a.iter().zip(b.iter()).map(|(x, y)| op(*x, *y)).collect();
+    Self::from_twos_complement(result)
+  }
+
+  fn to_twos_complement(&self, len: usize) -> Vec<Word> {
+    let mut result = self.digits.clone();
+    result.resize(len, 0);
+    if self.negative {
+      negate_twos_complement(&mut result);
+    }
+    result
+  }
+
+  fn from_twos_complement(mut digits: Vec<Word>) -> Self {
+    let negative = digits.last().is_some_and(|top| top >> (DIGIT_BITS - 1) != 0);
+    if negative {
+      negate_twos_complement(&mut digits);
+    }
+    Self::new(negative, digits)
+  }
+}
+
+impl Ord for BigInteger {
+  fn cmp(&self, other: &Self) -> Ordering {
+    match (self.negative, other.negative) {
+      (false, true) => Ordering::Greater,
+      (true, false) => Ordering::Less,
+      (false, false) => cmp_mag(&self.digits, &other.digits),
+      (true, true) => cmp_mag(&other.digits, &self.digits),
+    }
+  }
+}
+
+impl PartialOrd for BigInteger {
+  fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+    Some(self.cmp(other))
+  }
+}
+
+fn format_word_radix(mut val: Word, radix: u32) -> String {
+  let mut chars = Vec::new();
+  loop {
+    let d = (val % radix as Word) as u32;
+    chars.push(core::char::from_digit(d, radix).unwrap().to_ascii_uppercase());
+    val /= radix as Word;
+    if val == 0 {
+      break;
+    }
+  }
+  chars.iter().rev().collect()
+}
+
+fn negate_twos_complement(digits: &mut [Word]) {
+  let mut carry = true;
+  for d in digits.iter_mut() {
+    let (sum, overflow) = (!*d).overflowing_add(carry as Word);
+    *d = sum;
+    carry = overflow;
+  }
+}
+
+/// Compare magnitudes without leading zero digits.
+fn cmp_mag(a: &[Word], b: &[Word]) -> Ordering {
+  a.len()
+    .cmp(&b.len())
+    .then_with(|| a.iter().rev().cmp(b.iter().rev()))
+}
+
+fn add_mag(a: &[Word], b: &[Word]) -> Vec<Word> {
+  let (long, short) = if a.len() >= b.len() { (a, b) } else { (b, a) };
+  let mut result = Vec::with_capacity(long.len() + 1);
+  let mut carry = false;
+  for (i, x) in long.iter().enumerate() {
+    let y = short.get(i).copied().unwrap_or(0);
+    let (s1, o1) = x.overflowing_add(y);
+    let (s2, o2) = s1.overflowing_add(carry as Word);
+    result.push(s2);
+    carry = o1 || o2;
+  }
+  if carry {
+    result.push(1);
+  }
+  result
+}
+
+/// Subtract magnitudes, `a` must not be less than `b`.
+fn sub_mag(a: &[Word], b: &[Word]) -> Vec<Word> {
+  debug_assert!(cmp_mag(a, b) != Ordering::Less);
+  let mut result = Vec::with_capacity(a.len());
+  let mut borrow = false;
+  for (i, x) in a.iter().enumerate() {
+    let y = b.get(i).copied().unwrap_or(0);
+    let (d1, o1) = x.overflowing_sub(y);
+    let (d2, o2) = d1.overflowing_sub(borrow as Word);
+    result.push(d2);
+    borrow = o1 || o2;
+  }
+  debug_assert!(!borrow);
+  result
+}
+
+fn mul_mag(a: &[Word], b: &[Word]) -> Vec<Word> {
+  if a.is_empty() || b.is_empty() {
+    return Vec::new();
+  }
+  let mut result = vec![0 as Word; a.len() + b.len()];
+  for (i, x) in a.iter().enumerate() {
+    let mut carry: DoubleDigit = 0;
+    for (j, y) in b.iter().enumerate() {
+      let t = (*x as DoubleDigit) * (*y as DoubleDigit)
+        + result[i + j] as DoubleDigit
+        + carry;
+      result[i + j] = t as Word;
+      carry = t >> DIGIT_BITS;
+    }
+    result[i + b.len()] = carry as Word;
+  }
+  result
+}
+
+/// Divide a magnitude by one digit, returns the quotient (possibly with
+/// leading zeroes) and the remainder.
+fn divrem_mag_digit(a: &[Word], d: Word) -> (Vec<Word>, Word) {
+  let mut q = vec![0 as Word; a.len()];
+  let mut rem: DoubleDigit = 0;
+  for (qi, ai) in q.iter_mut().zip(a.iter()).rev() {
+    let cur = (rem << DIGIT_BITS) | *ai as DoubleDigit;
+    *qi = (cur / d as DoubleDigit) as Word;
+    rem = cur % d as DoubleDigit;
+  }
+  (q, rem as Word)
+}
+
+fn shl_mag(a: &[Word], bits: usize) -> Vec<Word> {
+  if a.is_empty() {
+    return Vec::new();
+  }
+  let (word_shift, bit_shift) = (bits / DIGIT_BITS, bits % DIGIT_BITS);
+  let mut result = vec![0 as Word; word_shift];
+  result.reserve(a.len() + 1);
+  if bit_shift == 0 {
+    result.extend_from_slice(a);
+    return result;
+  }
+  let mut carry = 0;
+  for x in a {
+    result.push((x << bit_shift) | carry);
+    carry = x >> (DIGIT_BITS - bit_shift);
+  }
+  result.push(carry);
+  result
+}
+
+fn shr_mag(a: &[Word], bits: usize) -> Vec<Word> {
+  let (word_shift, bit_shift) = (bits / DIGIT_BITS, bits % DIGIT_BITS);
+  if word_shift >= a.len() {
+    return Vec::new();
+  }
+  let src = &a[word_shift..];
+  if bit_shift == 0 {
+    return src.to_vec();
+  }
+  (0..src.len())
+    .map(|i| {
+      let high = src.get(i + 1).map_or(0, |h| h << (DIGIT_BITS - bit_shift));
+      (src[i] >> bit_shift) | high
+    })
+    .collect()
+}
+
+/// Long division of magnitudes (Knuth, TAOCP vol.2 4.3.1 algorithm D).
+/// Returns the quotient and the remainder.
+fn divrem_mag(a: &[Word], b: &[Word]) -> (Vec<Word>, Vec<Word>) {
+  if cmp_mag(a, b) == Ordering::Less {
+    return (Vec::new(), a.to_vec());
+  }
+  if b.len() == 1 {
+    let (q, r) = divrem_mag_digit(a, b[0]);
+    return (q, vec![r]);
+  }
+
+  // Normalize so that the top bit of the divisor is set, the dividend gets
+  // one extra digit
+  let shift = b[b.len() - 1].leading_zeros() as usize;
+  let v = shl_mag(b, shift);
+  let v = &v[..b.len()];
+  let mut u = shl_mag(a, shift);
+  u.resize(a.len() + 1, 0);
+
+  let n = v.len();
+  let m = a.len() - n;
+  let base: DoubleDigit = 1 << DIGIT_BITS;
+  let v_top = v[n - 1] as DoubleDigit;
+  let v_next = v[n - 2] as DoubleDigit;
+  let mut q = vec![0 as Word; m + 1];
+
+  for j in (0..=m).rev() {
+    // Estimate the quotient digit from the top two digits, it is at most 2
+    // too big
+    let num = ((u[j + n] as DoubleDigit) << DIGIT_BITS) | u[j + n - 1] as DoubleDigit;
+    let mut qhat = num / v_top;
+    let mut rhat = num % v_top;
+    while qhat >= base
+      || qhat * v_next > ((rhat << DIGIT_BITS) | u[j + n - 2] as DoubleDigit)
+    {
+      qhat -= 1;
+      rhat += v_top;
+      if rhat >= base {
+        break;
+      }
+    }
+
+    // Multiply and subtract qhat * v from the current window of u
+    let mut borrow: i128 = 0;
+    let mut carry: DoubleDigit = 0;
+    for (i, vi) in v.iter().enumerate() {
+      let p = qhat * *vi as DoubleDigit + carry;
+      carry = p >> DIGIT_BITS;
+      let t = u[i + j] as i128 - borrow - (p as Word) as i128;
+      u[i + j] = t as Word;
+      borrow = if t < 0 { 1 } else { 0 };
+    }
+    let t = u[j + n] as i128 - borrow - carry as i128;
+    u[j + n] = t as Word;
+
+    // The estimate was one too big, add the divisor back
+    if t < 0 {
+      qhat -= 1;
+      let mut carry: DoubleDigit = 0;
+      for (i, vi) in v.iter().enumerate() {
+        let s = u[i + j] as DoubleDigit + *vi as DoubleDigit + carry;
+        u[i + j] = s as Word;
+        carry = s >> DIGIT_BITS;
+      }
+      u[j + n] = u[j + n].wrapping_add(carry as Word);
+    }
+    q[j] = qhat as Word;
+  }
+
+  u.truncate(n);
+  (q, shr_mag(&u, shift))
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use num::{bigint::Sign, BigInt, Integer, Num};
+
+  /// Check results against the `num` crate, which is used at load time.
+  fn to_num(b: &BigInteger) -> BigInt {
+    let bytes: Vec<u8> = b.digits.iter().flat_map(|d| d.to_le_bytes().to_vec()).collect();
+    let sign = if b.negative { Sign::Minus } else { Sign::Plus };
+    BigInt::from_bytes_le(sign, &bytes)
+  }
+
+  fn from_num(n: &BigInt) -> BigInteger {
+    let (sign, bytes) = n.to_bytes_le();
+    BigInteger::from_bytes_le(sign == Sign::Minus, &bytes)
+  }
+
+  fn samples() -> Vec<BigInt> {
+    let texts = [
+      "0",
+      "1",
+      "-1",
+      "12345",
+      "-9223372036854775808",
+      "18446744073709551615",
+      "18446744073709551616",
+      "-340282366920938463463374607431768211457",
+      "123456789012345678901234567890123456789012345678901234567890",
+      "-98765432109876543210987654321098765432109876543210",
+    ];
+    texts.iter().map(|t| BigInt::from_str_radix(t, 10).unwrap()).collect()
+  }
+
+  #[test]
+  fn arithmetic_matches_num() {
+    for a in samples() {
+      let x = from_num(&a);
+      assert_eq!(x.to_string_radix(10), a.to_str_radix(10));
+      assert_eq!(x.to_string_radix(16), a.to_str_radix(16).to_uppercase());
+      for b in samples() {
+        let y = from_num(&b);
+        assert_eq!(to_num(&x.add(&y)), &a + &b);
+        assert_eq!(to_num(&x.sub(&y)), &a - &b);
+        assert_eq!(to_num(&x.mul(&y)), &a * &b);
+        assert_eq!(x.cmp(&y), a.cmp(&b));
+        if let Some((q, r)) = x.div_rem(&y) {
+          let (nq, nr) = a.div_rem(&b);
+          assert_eq!((to_num(&q), to_num(&r)), (nq, nr), "{} div {}", a, b);
+        }
+      }
+    }
+  }
+
+  #[test]
+  fn bitwise_and_shifts() {
+    let x = BigInteger::from_i128(-(1i128 << 100) - 5);
+    let y = BigInteger::from_sword(0xff);
+    assert_eq!(x.band(&y).to_sword(), Some(0xfb));
+    assert_eq!(x.bor(&y).to_sword(), None);
+    assert_eq!(x.bor(&y), BigInteger::from_i128((-(1i128 << 100) - 5) | 0xff));
+    assert_eq!(x.bxor(&y), BigInteger::from_i128((-(1i128 << 100) - 5) ^ 0xff));
+    assert_eq!(x.bnot(), BigInteger::from_i128((1i128 << 100) + 4));
+    assert_eq!(x.shr(98), BigInteger::from_sword(-5));
+    assert_eq!(BigInteger::from_sword(-1).shr(1000), BigInteger::from_sword(-1));
+    assert_eq!(y.shl(100).shr(100), y);
+    assert_eq!(BigInteger::from_sword(1).shl(64).digits, vec![0, 1]);
+  }
+
+  #[test]
+  fn float_conversion() {
+    assert_eq!(BigInteger::from_f64_trunc(-2.75), BigInteger::from_sword(-2));
+    let big = BigInteger::from_f64_trunc(1e40);
+    assert_eq!(big.to_string_radix(10), "10000000000000000303786028427003666890752");
+    assert_eq!(big.to_f64(), 1e40);
+    assert_eq!(BigInteger::from_sword(-5).to_f64(), -5.0);
+  }
+}
@@ -0,0 +1,109 @@
+//! Floating point arithmetic for terms. When a float is mixed with an integer,
+//! the integer is converted to float like `erts_mixed_plus` does it in
+//! `erl_arith.c`. Erlang has no NaN or infinity values, so a result which is
+//! not finite fails with `badarith`.
+use crate::{
+  emulator::{arith::big::BigInteger, heap::Heap},
+  fail::{self, RtResult},
+  term::{boxed, lterm::*},
+};
+
+/// Convert a number (a small, a bignum or a float) to `f64`.
+pub fn to_f64(t: LTerm) -> Option<f64> {
+  if t.is_small() {
+    return Some(t.get_small_signed() as f64);
+  }
+  if t.is_float() {
+    return Some(unsafe { t.get_f64_unsafe() });
+  }
+  BigInteger::from_term(t).map(|big| big.to_f64())
+}
+
+/// Fail with `badarith` if the value is NaN or infinite.
+#[inline]
+pub fn check_finite(val: f64) -> RtResult<f64> {
+  if !val.is_finite() {
+    return fail::create::badarith();
+  }
+  Ok(val)
+}
+
+/// Box a float on the heap, the value must be finite.
+pub fn make_float(hp: &mut Heap, val: f64) -> RtResult<LTerm> {
+  let val = check_finite(val)?;
+  let p = unsafe { boxed::Float::create_into(hp, val)? };
+  Ok(LTerm::make_boxed(p))
+}
+
+fn unpack(a: LTerm) -> RtResult<f64> {
+  match to_f64(a) {
+    Some(val) => check_finite(val),
+    None => fail::create::badarith(),
+  }
+}
+
+fn unpack2(a: LTerm, b: LTerm) -> RtResult<(f64, f64)> {
+  Ok((unpack(a)?, unpack(b)?))
+}
+
+pub fn add(hp: &mut Heap, a: LTerm, b: LTerm) -> RtResult<LTerm> {
+  let (x, y) = unpack2(a, b)?;
+  make_float(hp, x + y)
+}
+
+pub fn subtract(hp: &mut Heap, a: LTerm, b: LTerm) -> RtResult<LTerm> {
+  let (x, y) = unpack2(a, b)?;
+  make_float(hp, x - y)
+}
+
+pub fn multiply(hp: &mut Heap, a: LTerm, b: LTerm) -> RtResult<LTerm> {
+  let (x, y) = unpack2(a, b)?;
+  make_float(hp, x * y)
+}
+
+/// Division `/`, the result is always a float, also for integer arguments.
+pub fn divide(hp: &mut Heap, a: LTerm, b: LTerm) -> RtResult<LTerm> {
+  let (x, y) = unpack2(a, b)?;
+  if y == 0.0 {
+    return fail::create::badarith();
+  }
+  make_float(hp, x / y)
+}
+
+pub fn negate(hp: &mut Heap, a: LTerm) -> RtResult<LTerm> {
+  make_float(hp, -unpack(a)?)
+}
+
+pub fn abs(hp: &mut Heap, a: LTerm) -> RtResult<LTerm> {
+  make_float(hp, unpack(a)?.abs())
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::term::compare::cmp_terms;
+  use core::cmp::Ordering;
+
+  #[test]
+  fn mixed_arithmetic_and_compare() {
+    let mut hp = Heap::new(1024);
+    let one = LTerm::small_1();
+    let half = make_float(&mut hp, 0.5).unwrap();
+
+    let sum = add(&mut hp, one, half).unwrap();
+    assert_eq!(to_f64(sum), Some(1.5));
+    assert!(divide(&mut hp, one, LTerm::small_0()).is_err());
+    assert!(make_float(&mut hp, f64::NAN).is_err());
+    let huge = make_float(&mut hp, 1e300).unwrap();
+    assert!(multiply(&mut hp, huge, huge).is_err());
+
+    let one_f = make_float(&mut hp, 1.0).unwrap();
+    assert_eq!(cmp_terms(one, one_f, false).unwrap(), Ordering::Equal);
+    assert_eq!(cmp_terms(one, one_f, true).unwrap(), Ordering::Less);
+    assert_eq!(cmp_terms(sum, one, false).unwrap(), Ordering::Greater);
+    let big = BigInteger::from_f64_trunc(1e20).add(&BigInteger::from_sword(1));
+    let big = big.to_term(&mut hp).unwrap();
+    let big_f = make_float(&mut hp, 1e20).unwrap();
+    assert_eq!(cmp_terms(big, big_f, false).unwrap(), Ordering::Greater);
+  }
+}
@@ -0,0 +1,226 @@
+//! Integer operations for terms which are small integers or bignums, this
+//! follows `erts_mixed_plus` and its neighbours in `erl_arith.c`. When both
+//! operands are small a fast path is taken, otherwise they are unpacked into
+//! `BigInteger` digits. Arguments which are not integers fail with `badarith`.
+use crate::{
+  defs::{SWord, WORD_BITS},
+  emulator::{arith::big::BigInteger, heap::Heap},
+  fail::{self, RtResult},
+  term::lterm::*,
+};
+
+/// Shifting a non-zero value left by more bits than this fails with
+/// `system_limit` instead of trying to allocate a huge bignum.
+pub const MAX_SHIFT_BITS: usize = 1 << 24;
+
+/// Create a small integer or a bignum if the value does not fit.
+pub fn make_integer(hp: &mut Heap, val: SWord) -> RtResult<LTerm> {
+  if LTerm::small_fits(val) {
+    return Ok(LTerm::make_small_signed(val));
+  }
+  BigInteger::from_sword(val).to_term(hp)
+}
+
+fn unpack(a: LTerm) -> RtResult<BigInteger> {
+  match BigInteger::from_term(a) {
+    Some(x) => Ok(x),
+    None => fail::create::badarith(),
+  }
+}
+
+pub fn unpack2(a: LTerm, b: LTerm) -> RtResult<(BigInteger, BigInteger)> {
+  Ok((unpack(a)?, unpack(b)?))
+}
+
+pub fn add(hp: &mut Heap, a: LTerm, b: LTerm) -> RtResult<LTerm> {
+  if a.is_small() && b.is_small() {
+    // Small integers use less bits than a machine word, this can't overflow
+    return make_integer(hp, a.get_small_signed() + b.get_small_signed());
+  }
+  let (x, y) = unpack2(a, b)?;
+  x.add(&y).to_term(hp)
+}
+
+pub fn subtract(hp: &mut Heap, a: LTerm, b: LTerm) -> RtResult<LTerm> {
+  if a.is_small() && b.is_small() {
+    return make_integer(hp, a.get_small_signed() - b.get_small_signed());
+  }
+  let (x, y) = unpack2(a, b)?;
+  x.sub(&y).to_term(hp)
+}
+
+/// Integer division `div`, truncates towards zero.
+pub fn int_div(hp: &mut Heap, a: LTerm, b: LTerm) -> RtResult<LTerm> {
+  if b == LTerm::small_0() {
+    return fail::create::badarith();
+  }
+  if a.is_small() && b.is_small() {
+    return make_integer(hp, a.get_small_signed() / b.get_small_signed());
+  }
+  let (x, y) = unpack2(a, b)?;
+  match x.div_rem(&y) {
+    Some((q, _)) => q.to_term(hp),
+    None => fail::create::badarith(),
+  }
+}
+
+/// Remainder `rem`, has the sign of the dividend.
+pub fn rem(hp: &mut Heap, a: LTerm, b: LTerm) -> RtResult<LTerm> {
+  if b == LTerm::small_0() {
+    return fail::create::badarith();
+  }
+  if a.is_small() && b.is_small() {
+    return Ok(LTerm::make_small_signed(
+      a.get_small_signed() % b.get_small_signed(),
+    ));
+  }
+  let (x, y) = unpack2(a, b)?;
+  match x.div_rem(&y) {
+    Some((_, r)) => r.to_term(hp),
+    None => fail::create::badarith(),
+  }
+}
+
+pub fn abs(hp: &mut Heap, a: LTerm) -> RtResult<LTerm> {
+  if a.is_small() {
+    return make_integer(hp, a.get_small_signed().abs());
+  }
+  let x = unpack(a)?;
+  if !x.is_negative() {
+    return Ok(a);
+  }
+  x.abs().to_term(hp)
+}
+
+pub fn negate(hp: &mut Heap, a: LTerm) -> RtResult<LTerm> {
+  if a.is_small() {
+    return make_integer(hp, -a.get_small_signed());
+  }
+  unpack(a)?.neg().to_term(hp)
+}
+
+pub fn band(hp: &mut Heap, a: LTerm, b: LTerm) -> RtResult<LTerm> {
+  if a.is_small() && b.is_small() {
+    return Ok(LTerm::make_small_signed(
+      a.get_small_signed() & b.get_small_signed(),
+    ));
+  }
+  let (x, y) = unpack2(a, b)?;
+  x.band(&y).to_term(hp)
+}
+
+pub fn bor(hp: &mut Heap, a: LTerm, b: LTerm) -> RtResult<LTerm> {
+  if a.is_small() && b.is_small() {
+    return Ok(LTerm::make_small_signed(
+      a.get_small_signed() | b.get_small_signed(),
+    ));
+  }
+  let (x, y) = unpack2(a, b)?;
+  x.bor(&y).to_term(hp)
+}
+
+pub fn bxor(hp: &mut Heap, a: LTerm, b: LTerm) -> RtResult<LTerm> {
+  if a.is_small() && b.is_small() {
+    return Ok(LTerm::make_small_signed(
+      a.get_small_signed() ^ b.get_small_signed(),
+    ));
+  }
+  let (x, y) = unpack2(a, b)?;
+  x.bxor(&y).to_term(hp)
+}
+
+pub fn bnot(hp: &mut Heap, a: LTerm) -> RtResult<LTerm> {
+  if a.is_small() {
+    return Ok(LTerm::make_small_signed(!a.get_small_signed()));
+  }
+  unpack(a)?.bnot().to_term(hp)
+}
+
+/// Arithmetic shift left `bsl`, a negative count shifts right.
+pub fn bsl(hp: &mut Heap, a: LTerm, b: LTerm) -> RtResult<LTerm> {
+  shift(hp, a, b, false)
+}
+
+/// Arithmetic shift right `bsr`, a negative count shifts left.
+pub fn bsr(hp: &mut Heap, a: LTerm, b: LTerm) -> RtResult<LTerm> {
+  shift(hp, a, b, true)
+}
+
+fn shift(hp: &mut Heap, a: LTerm, b: LTerm, right: bool) -> RtResult<LTerm> {
+  let (x, count) = unpack2(a, b)?;
+  // Positive direction is left
+  let to_left = count.is_negative() == right;
+  let bits = match count.to_sword() {
+    Some(n) => n.unsigned_abs(),
+    None => usize::MAX,
+  };
+
+  if to_left {
+    if x.is_zero() {
+      return Ok(LTerm::small_0());
+    }
+    if bits > MAX_SHIFT_BITS {
+      return fail::create::system_limit();
+    }
+    if a.is_small() && bits < WORD_BITS {
+      let result = (a.get_small_signed() as i128) << bits;
+      return BigInteger::from_i128(result).to_term(hp);
+    }
+    return x.shl(bits).to_term(hp);
+  }
+
+  if a.is_small() {
+    let val = a.get_small_signed();
+    let bits = bits.min(WORD_BITS - 1);
+    return Ok(LTerm::make_small_signed(val >> bits));
+  }
+  x.shr(bits).to_term(hp)
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::emulator::arith::multiplication::multiply;
+
+  fn big(hp: &mut Heap, text: &str) -> LTerm {
+    let n = num::BigInt::parse_bytes(text.as_bytes(), 10).unwrap();
+    let (sign, bytes) = n.to_bytes_le();
+    BigInteger::from_bytes_le(sign == num::bigint::Sign::Minus, &bytes)
+      .to_term(hp)
+      .unwrap()
+  }
+
+  fn text(t: LTerm) -> String {
+    BigInteger::from_term(t).unwrap().to_string_radix(10)
+  }
+
+  #[test]
+  fn promotes_and_demotes() {
+    let mut hp = Heap::new(1024);
+    let largest = LTerm::make_small_signed(LARGEST_SMALL);
+    let one = LTerm::small_1();
+
+    let sum = add(&mut hp, largest, one).unwrap();
+    assert!(sum.is_big_int());
+    let back = subtract(&mut hp, sum, one).unwrap();
+    assert_eq!(back, largest);
+
+    let sq = multiply(&mut hp, sum, sum).unwrap();
+    let q = int_div(&mut hp, sq, sum).unwrap();
+    assert_eq!(text(q), text(sum));
+    assert_eq!(rem(&mut hp, sq, sum).unwrap(), LTerm::small_0());
+
+    let x = big(&mut hp, "-340282366920938463463374607431768211457");
+    let abs_x = abs(&mut hp, x).unwrap();
+    assert_eq!(text(abs_x), "340282366920938463463374607431768211457");
+    let small = LTerm::make_small_signed;
+    assert_eq!(bsr(&mut hp, x, small(120)).unwrap(), small(-257));
+    let shifted = bsl(&mut hp, small(-3), small(100)).unwrap();
+    assert_eq!(text(shifted), "-3802951800684688204490109616128");
+    assert_eq!(band(&mut hp, x, small(0xff)).unwrap(), small(0xff));
+    let not_x = bnot(&mut hp, x).unwrap();
+    assert_eq!(text(bnot(&mut hp, not_x).unwrap()), text(x));
+    assert!(int_div(&mut hp, x, LTerm::small_0()).is_err());
+    assert!(add(&mut hp, x, LTerm::nil()).is_err());
+  }
+}
@@ -1 +1,4 @@
+pub mod big;
+pub mod float;
+pub mod integer;
 pub mod multiplication;
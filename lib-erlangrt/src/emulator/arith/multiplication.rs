@@ -1,49 +1,34 @@
 use crate::{
   defs::SWord,
-  emulator::heap::Heap,
+  emulator::{
+    arith::{big::BigInteger, integer},
+    heap::Heap,
+  },
   fail::RtResult,
-  term::{boxed::bignum::Bignum, lterm::*},
+  term::lterm::*,
 };
-use num::{bigint::BigInt, cast::ToPrimitive};
-
-fn module() -> &'static str {
-  "arith.multiplication: "
-}
 
+/// Multiplication for small integers and bignums. Algorithm comes from
+/// Erlang/OTP file `erl_arith.c`, function `erts_mixed_times`.
 pub fn multiply(hp: &mut Heap, x: LTerm, y: LTerm) -> RtResult<LTerm> {
-  if x.is_small() {
-    if y.is_small() {
-      // Both a and b are small, check how many bits will be there in result
-      if x == LTerm::small_0() || y == LTerm::small_0() {
-        return Ok(LTerm::small_0());
-      } else if x == LTerm::small_1() {
-        return Ok(y);
-      } else if y == LTerm::small_1() {
-        return Ok(x);
-      }
-      let result = multiply_two_small(hp, x.get_small_signed(), y.get_small_signed())?;
-      return Ok(result);
-    } else {
-      // TODO: See Erlang OTP erl_arith.c function erts_mixed_times
-      panic!("{}b={} other than small notimpl", module(), y)
+  if x.is_small() && y.is_small() {
+    // Both a and b are small, check how many bits will be there in result
+    if x == LTerm::small_0() || y == LTerm::small_0() {
+      return Ok(LTerm::small_0());
+    } else if x == LTerm::small_1() {
+      return Ok(y);
+    } else if y == LTerm::small_1() {
+      return Ok(x);
     }
+    return multiply_two_small(hp, x.get_small_signed(), y.get_small_signed());
   }
-  panic!("{}a={} other than small notimpl", module(), x)
+  let (big_x, big_y) = integer::unpack2(x, y)?;
+  big_x.mul(&big_y).to_term(hp)
 }
 
 /// Implement multiplication for two signed integers, possibly creating a bigint.
 pub fn multiply_two_small(hp: &mut Heap, x: SWord, y: SWord) -> RtResult<LTerm> {
-  let big_x = BigInt::from(x);
-  let big_y = BigInt::from(y);
-  let result = big_x.checked_mul(&big_y).unwrap();
-
-  // If the result fits into smallint, return it as smallint
-  if let Some(i) = result.to_isize() {
-    if LTerm::small_fits(i) {
-      return Ok(LTerm::make_small_signed(i));
-    }
-  }
-
-  let boxptr = unsafe { Bignum::create_into(hp, result) }?;
-  Ok(LTerm::make_boxed(boxptr))
+  // The product of two words always fits a double word
+  let result = x as i128 * y as i128;
+  BigInteger::from_i128(result).to_term(hp)
 }
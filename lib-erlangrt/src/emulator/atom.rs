@@ -12,16 +12,14 @@ use core::ptr;
 use std::{
   collections::BTreeMap,
   sync::{Mutex, MutexGuard},
-  u16,
 };
 
-/// Defines atom properties (length, compare helper integer)
+/// Defines atom properties (name, compare helper integer)
 pub struct Atom {
-  /// Length of utf8-encoded atom name.
-  pub len: u16,
   // /// Length of latin1-encoded atom otherwise -1
   // latin1_chars: i16,
-  /// First 4 bytes used for comparisons
+  /// First 4 bytes used for comparisons, zero padded. Ordering by `ord0`
+  /// agrees with ordering by `name` when they differ.
   pub ord0: u32,
   // TODO: Allocate these on atom heap or as a sequence of static blocks
   pub name: String,
@@ -49,9 +47,7 @@ impl Atom {
       }
     }
 
-    assert!(s.len() <= u16::MAX as usize);
     Atom {
-      len: s.len() as u16,
       ord0,
       name: s.to_string(),
     }
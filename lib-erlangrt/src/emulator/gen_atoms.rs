@@ -10,106 +10,282 @@ pub const MULTIPLY: LTerm = LTerm::make_atom(0);
 pub const SYM_PLUS: LTerm = LTerm::make_atom(1);
 pub const PLUSPLUS: LTerm = LTerm::make_atom(2);
 pub const SYM_MINUS: LTerm = LTerm::make_atom(3);
-pub const NOTEQUAL: LTerm = LTerm::make_atom(4);
-pub const LESSTHAN: LTerm = LTerm::make_atom(5);
-pub const NOTEQUAL_EXACT: LTerm = LTerm::make_atom(6);
-pub const EQUAL_EXACT: LTerm = LTerm::make_atom(7);
-pub const LESSEQUAL: LTerm = LTerm::make_atom(8);
-pub const SYM_EQ_EQ: LTerm = LTerm::make_atom(9);
-pub const GREATERTHAN: LTerm = LTerm::make_atom(10);
-pub const GREATEREQUAL: LTerm = LTerm::make_atom(11);
-pub const APPLY: LTerm = LTerm::make_atom(12);
-pub const ATOM_TO_LIST: LTerm = LTerm::make_atom(13);
-pub const BADARG: LTerm = LTerm::make_atom(14);
-pub const BADARITH: LTerm = LTerm::make_atom(15);
-pub const BADARITY: LTerm = LTerm::make_atom(16);
-pub const BADFUN: LTerm = LTerm::make_atom(17);
-pub const BADMATCH: LTerm = LTerm::make_atom(18);
-pub const CASE_CLAUSE: LTerm = LTerm::make_atom(19);
-pub const ERLANG: LTerm = LTerm::make_atom(20);
-pub const ERROR: LTerm = LTerm::make_atom(21);
-pub const EXIT: LTerm = LTerm::make_atom(22);
-pub const FALSE: LTerm = LTerm::make_atom(23);
-pub const FUNCTION_CLAUSE: LTerm = LTerm::make_atom(24);
-pub const HD: LTerm = LTerm::make_atom(25);
-pub const HIGH: LTerm = LTerm::make_atom(26);
-pub const IF_CLAUSE: LTerm = LTerm::make_atom(27);
-pub const INIT: LTerm = LTerm::make_atom(28);
-pub const INTEGER_TO_LIST: LTerm = LTerm::make_atom(29);
-pub const IS_BOOLEAN: LTerm = LTerm::make_atom(30);
-pub const IS_PROCESS_ALIVE: LTerm = LTerm::make_atom(31);
-pub const KILL: LTerm = LTerm::make_atom(32);
-pub const KILLED: LTerm = LTerm::make_atom(33);
-pub const LENGTH: LTerm = LTerm::make_atom(34);
-pub const LISTS: LTerm = LTerm::make_atom(35);
-pub const LOW: LTerm = LTerm::make_atom(36);
-pub const MAKE_FUN: LTerm = LTerm::make_atom(37);
-pub const MEMBER: LTerm = LTerm::make_atom(38);
-pub const NIF_ERROR: LTerm = LTerm::make_atom(39);
-pub const NOCATCH: LTerm = LTerm::make_atom(40);
-pub const NORMAL: LTerm = LTerm::make_atom(41);
-pub const OK: LTerm = LTerm::make_atom(42);
-pub const SELF: LTerm = LTerm::make_atom(43);
-pub const SPAWN: LTerm = LTerm::make_atom(44);
-pub const SYSTEM_LIMIT: LTerm = LTerm::make_atom(45);
-pub const THROW: LTerm = LTerm::make_atom(46);
-pub const TL: LTerm = LTerm::make_atom(47);
-pub const TRAP_EXIT: LTerm = LTerm::make_atom(48);
-pub const TRUE: LTerm = LTerm::make_atom(49);
-pub const UNDEF: LTerm = LTerm::make_atom(50);
-pub const UNDEFINED: LTerm = LTerm::make_atom(51);
+pub const DIVIDE: LTerm = LTerm::make_atom(4);
+pub const NOTEQUAL: LTerm = LTerm::make_atom(5);
+pub const LESSTHAN: LTerm = LTerm::make_atom(6);
+pub const NOTEQUAL_EXACT: LTerm = LTerm::make_atom(7);
+pub const EQUAL_EXACT: LTerm = LTerm::make_atom(8);
+pub const LESSEQUAL: LTerm = LTerm::make_atom(9);
+pub const SYM_EQ_EQ: LTerm = LTerm::make_atom(10);
+pub const GREATERTHAN: LTerm = LTerm::make_atom(11);
+pub const GREATEREQUAL: LTerm = LTerm::make_atom(12);
+pub const DOWN_UPPERCASE: LTerm = LTerm::make_atom(13);
+pub const EXIT_UPPERCASE: LTerm = LTerm::make_atom(14);
+pub const ABS: LTerm = LTerm::make_atom(15);
+pub const APPLY: LTerm = LTerm::make_atom(16);
+pub const ATOM_TO_LIST: LTerm = LTerm::make_atom(17);
+pub const BADARG: LTerm = LTerm::make_atom(18);
+pub const BADARITH: LTerm = LTerm::make_atom(19);
+pub const BADARITY: LTerm = LTerm::make_atom(20);
+pub const BADFUN: LTerm = LTerm::make_atom(21);
+pub const BADKEY: LTerm = LTerm::make_atom(22);
+pub const BADMAP: LTerm = LTerm::make_atom(23);
+pub const BADMATCH: LTerm = LTerm::make_atom(24);
+pub const BAND: LTerm = LTerm::make_atom(25);
+pub const BINARY: LTerm = LTerm::make_atom(26);
+pub const BNOT: LTerm = LTerm::make_atom(27);
+pub const BOR: LTerm = LTerm::make_atom(28);
+pub const BSL: LTerm = LTerm::make_atom(29);
+pub const BSR: LTerm = LTerm::make_atom(30);
+pub const BXOR: LTerm = LTerm::make_atom(31);
+pub const CASE_CLAUSE: LTerm = LTerm::make_atom(32);
+pub const COMPACT: LTerm = LTerm::make_atom(33);
+pub const CURRENT_FUNCTION: LTerm = LTerm::make_atom(34);
+pub const DECIMALS: LTerm = LTerm::make_atom(35);
+pub const DEMONITOR: LTerm = LTerm::make_atom(36);
+pub const DICTIONARY: LTerm = LTerm::make_atom(37);
+pub const INT_DIV: LTerm = LTerm::make_atom(38);
+pub const EMULATOR_ERROR: LTerm = LTerm::make_atom(39);
+pub const ERASE: LTerm = LTerm::make_atom(40);
+pub const ERLANG: LTerm = LTerm::make_atom(41);
+pub const ERROR: LTerm = LTerm::make_atom(42);
+pub const EXIT: LTerm = LTerm::make_atom(43);
+pub const EXITING: LTerm = LTerm::make_atom(44);
+pub const FALSE: LTerm = LTerm::make_atom(45);
+pub const FIND: LTerm = LTerm::make_atom(46);
+pub const FLOAT: LTerm = LTerm::make_atom(47);
+pub const FLOAT_TO_LIST: LTerm = LTerm::make_atom(48);
+pub const FLUSH: LTerm = LTerm::make_atom(49);
+pub const FROM_LIST: LTerm = LTerm::make_atom(50);
+pub const FULLSWEEP_AFTER: LTerm = LTerm::make_atom(51);
+pub const FUNCTION_CLAUSE: LTerm = LTerm::make_atom(52);
+pub const GET: LTerm = LTerm::make_atom(53);
+pub const GET_KEYS: LTerm = LTerm::make_atom(54);
+pub const HD: LTerm = LTerm::make_atom(55);
+pub const HEAP_SIZE: LTerm = LTerm::make_atom(56);
+pub const HIBERNATE: LTerm = LTerm::make_atom(57);
+pub const HIGH: LTerm = LTerm::make_atom(58);
+pub const IF_CLAUSE: LTerm = LTerm::make_atom(59);
+pub const INFINITY: LTerm = LTerm::make_atom(60);
+pub const INFO: LTerm = LTerm::make_atom(61);
+pub const INIT: LTerm = LTerm::make_atom(62);
+pub const INTEGER_TO_LIST: LTerm = LTerm::make_atom(63);
+pub const IS_BOOLEAN: LTerm = LTerm::make_atom(64);
+pub const IS_PROCESS_ALIVE: LTerm = LTerm::make_atom(65);
+pub const KEYS: LTerm = LTerm::make_atom(66);
+pub const KILL: LTerm = LTerm::make_atom(67);
+pub const KILLED: LTerm = LTerm::make_atom(68);
+pub const LAST_CALLS: LTerm = LTerm::make_atom(69);
+pub const LENGTH: LTerm = LTerm::make_atom(70);
+pub const LINK: LTerm = LTerm::make_atom(71);
+pub const LINKS: LTerm = LTerm::make_atom(72);
+pub const LISTS: LTerm = LTerm::make_atom(73);
+pub const LOW: LTerm = LTerm::make_atom(74);
+pub const MAKE_FUN: LTerm = LTerm::make_atom(75);
+pub const MAKE_REF: LTerm = LTerm::make_atom(76);
+pub const MAPS: LTerm = LTerm::make_atom(77);
+pub const MAX: LTerm = LTerm::make_atom(78);
+pub const MAX_HEAP_SIZE: LTerm = LTerm::make_atom(79);
+pub const MEMBER: LTerm = LTerm::make_atom(80);
+pub const MESSAGE_QUEUE_DATA: LTerm = LTerm::make_atom(81);
+pub const MESSAGE_QUEUE_LEN: LTerm = LTerm::make_atom(82);
+pub const MESSAGES: LTerm = LTerm::make_atom(83);
+pub const MIN_HEAP_SIZE: LTerm = LTerm::make_atom(84);
+pub const MONITOR: LTerm = LTerm::make_atom(85);
+pub const MONITORS: LTerm = LTerm::make_atom(86);
+pub const NIF_ERROR: LTerm = LTerm::make_atom(87);
+pub const NOCATCH: LTerm = LTerm::make_atom(88);
+pub const NODE: LTerm = LTerm::make_atom(89);
+pub const NONODE_AT_NOHOST: LTerm = LTerm::make_atom(90);
+pub const NOPROC: LTerm = LTerm::make_atom(91);
+pub const NORMAL: LTerm = LTerm::make_atom(92);
+pub const OFF_HEAP: LTerm = LTerm::make_atom(93);
+pub const OK: LTerm = LTerm::make_atom(94);
+pub const ON_HEAP: LTerm = LTerm::make_atom(95);
+pub const PARENT: LTerm = LTerm::make_atom(96);
+pub const PART: LTerm = LTerm::make_atom(97);
+pub const PRIORITY: LTerm = LTerm::make_atom(98);
+pub const PROCESS: LTerm = LTerm::make_atom(99);
+pub const PROCESS_FLAG: LTerm = LTerm::make_atom(100);
+pub const PROCESS_INFO: LTerm = LTerm::make_atom(101);
+pub const PROCESSES: LTerm = LTerm::make_atom(102);
+pub const PUT: LTerm = LTerm::make_atom(103);
+pub const REDUCTIONS: LTerm = LTerm::make_atom(104);
+pub const REF_TO_LIST: LTerm = LTerm::make_atom(105);
+pub const REGISTER: LTerm = LTerm::make_atom(106);
+pub const REGISTERED: LTerm = LTerm::make_atom(107);
+pub const REGISTERED_NAME: LTerm = LTerm::make_atom(108);
+pub const REM: LTerm = LTerm::make_atom(109);
+pub const REMOVE: LTerm = LTerm::make_atom(110);
+pub const ROUND: LTerm = LTerm::make_atom(111);
+pub const RUNNABLE: LTerm = LTerm::make_atom(112);
+pub const RUNNING: LTerm = LTerm::make_atom(113);
+pub const SAVE_CALLS: LTerm = LTerm::make_atom(114);
+pub const SCIENTIFIC: LTerm = LTerm::make_atom(115);
+pub const SELF: LTerm = LTerm::make_atom(116);
+pub const SPAWN: LTerm = LTerm::make_atom(117);
+pub const SPAWN_LINK: LTerm = LTerm::make_atom(118);
+pub const SPAWN_MONITOR: LTerm = LTerm::make_atom(119);
+pub const SPAWN_OPT: LTerm = LTerm::make_atom(120);
+pub const SPLIT_BINARY: LTerm = LTerm::make_atom(121);
+pub const STACK_SIZE: LTerm = LTerm::make_atom(122);
+pub const STATUS: LTerm = LTerm::make_atom(123);
+pub const SYSTEM_LIMIT: LTerm = LTerm::make_atom(124);
+pub const TERM_TO_BINARY: LTerm = LTerm::make_atom(125);
+pub const THROW: LTerm = LTerm::make_atom(126);
+pub const TIMEOUT_VALUE: LTerm = LTerm::make_atom(127);
+pub const TL: LTerm = LTerm::make_atom(128);
+pub const TO_LIST: LTerm = LTerm::make_atom(129);
+pub const TRAP_EXIT: LTerm = LTerm::make_atom(130);
+pub const TRUE: LTerm = LTerm::make_atom(131);
+pub const TRUNC: LTerm = LTerm::make_atom(132);
+pub const UNDEF: LTerm = LTerm::make_atom(133);
+pub const UNDEFINED: LTerm = LTerm::make_atom(134);
+pub const UNLINK: LTerm = LTerm::make_atom(135);
+pub const UNREGISTER: LTerm = LTerm::make_atom(136);
+pub const VALUES: LTerm = LTerm::make_atom(137);
+pub const WAITING: LTerm = LTerm::make_atom(138);
+pub const WHEREIS: LTerm = LTerm::make_atom(139);
 
 pub static ATOM_INIT_NAMES: &'static [&'static str] = &[
   "*", // id=0
   "+", // id=1
   "++", // id=2
   "-", // id=3
-  "/=", // id=4
-  "<", // id=5
-  "=/=", // id=6
-  "=:=", // id=7
-  "=<", // id=8
-  "==", // id=9
-  ">", // id=10
-  ">=", // id=11
-  "apply", // id=12
-  "atom_to_list", // id=13
-  "badarg", // id=14
-  "badarith", // id=15
-  "badarity", // id=16
-  "badfun", // id=17
-  "badmatch", // id=18
-  "case_clause", // id=19
-  "erlang", // id=20
-  "error", // id=21
-  "exit", // id=22
-  "false", // id=23
-  "function_clause", // id=24
-  "hd", // id=25
-  "high", // id=26
-  "if_clause", // id=27
-  "init", // id=28
-  "integer_to_list", // id=29
-  "is_boolean", // id=30
-  "is_process_alive", // id=31
-  "kill", // id=32
-  "killed", // id=33
-  "length", // id=34
-  "lists", // id=35
-  "low", // id=36
-  "make_fun", // id=37
-  "member", // id=38
-  "nif_error", // id=39
-  "nocatch", // id=40
-  "normal", // id=41
-  "ok", // id=42
-  "self", // id=43
-  "spawn", // id=44
-  "system_limit", // id=45
-  "throw", // id=46
-  "tl", // id=47
-  "trap_exit", // id=48
-  "true", // id=49
-  "undef", // id=50
-  "undefined", // id=51
+  "/", // id=4
+  "/=", // id=5
+  "<", // id=6
+  "=/=", // id=7
+  "=:=", // id=8
+  "=<", // id=9
+  "==", // id=10
+  ">", // id=11
+  ">=", // id=12
+  "DOWN", // id=13
+  "EXIT", // id=14
+  "abs", // id=15
+  "apply", // id=16
+  "atom_to_list", // id=17
+  "badarg", // id=18
+  "badarith", // id=19
+  "badarity", // id=20
+  "badfun", // id=21
+  "badkey", // id=22
+  "badmap", // id=23
+  "badmatch", // id=24
+  "band", // id=25
+  "binary", // id=26
+  "bnot", // id=27
+  "bor", // id=28
+  "bsl", // id=29
+  "bsr", // id=30
+  "bxor", // id=31
+  "case_clause", // id=32
+  "compact", // id=33
+  "current_function", // id=34
+  "decimals", // id=35
+  "demonitor", // id=36
+  "dictionary", // id=37
+  "div", // id=38
+  "emulator_error", // id=39
+  "erase", // id=40
+  "erlang", // id=41
+  "error", // id=42
+  "exit", // id=43
+  "exiting", // id=44
+  "false", // id=45
+  "find", // id=46
+  "float", // id=47
+  "float_to_list", // id=48
+  "flush", // id=49
+  "from_list", // id=50
+  "fullsweep_after", // id=51
+  "function_clause", // id=52
+  "get", // id=53
+  "get_keys", // id=54
+  "hd", // id=55
+  "heap_size", // id=56
+  "hibernate", // id=57
+  "high", // id=58
+  "if_clause", // id=59
+  "infinity", // id=60
+  "info", // id=61
+  "init", // id=62
+  "integer_to_list", // id=63
+  "is_boolean", // id=64
+  "is_process_alive", // id=65
+  "keys", // id=66
+  "kill", // id=67
+  "killed", // id=68
+  "last_calls", // id=69
+  "length", // id=70
+  "link", // id=71
+  "links", // id=72
+  "lists", // id=73
+  "low", // id=74
+  "make_fun", // id=75
+  "make_ref", // id=76
+  "maps", // id=77
+  "max", // id=78
+  "max_heap_size", // id=79
+  "member", // id=80
+  "message_queue_data", // id=81
+  "message_queue_len", // id=82
+  "messages", // id=83
+  "min_heap_size", // id=84
+  "monitor", // id=85
+  "monitors", // id=86
+  "nif_error", // id=87
+  "nocatch", // id=88
+  "node", // id=89
+  "nonode@nohost", // id=90
+  "noproc", // id=91
+  "normal", // id=92
+  "off_heap", // id=93
+  "ok", // id=94
+  "on_heap", // id=95
+  "parent", // id=96
+  "part", // id=97
+  "priority", // id=98
+  "process", // id=99
+  "process_flag", // id=100
+  "process_info", // id=101
+  "processes", // id=102
+  "put", // id=103
+  "reductions", // id=104
+  "ref_to_list", // id=105
+  "register", // id=106
+  "registered", // id=107
+  "registered_name", // id=108
+  "rem", // id=109
+  "remove", // id=110
+  "round", // id=111
+  "runnable", // id=112
+  "running", // id=113
+  "save_calls", // id=114
+  "scientific", // id=115
+  "self", // id=116
+  "spawn", // id=117
+  "spawn_link", // id=118
+  "spawn_monitor", // id=119
+  "spawn_opt", // id=120
+  "split_binary", // id=121
+  "stack_size", // id=122
+  "status", // id=123
+  "system_limit", // id=124
+  "term_to_binary", // id=125
+  "throw", // id=126
+  "timeout_value", // id=127
+  "tl", // id=128
+  "to_list", // id=129
+  "trap_exit", // id=130
+  "true", // id=131
+  "trunc", // id=132
+  "undef", // id=133
+  "undefined", // id=134
+  "unlink", // id=135
+  "unregister", // id=136
+  "values", // id=137
+  "waiting", // id=138
+  "whereis", // id=139
 ];
@@ -1,78 +1,210 @@
 //! The classic BEAM design approach is to copy terms to the new owning heap
 //! when an object changes its owner process.
+//! Like `size_object` and `copy_struct` in ERTS, the size of a term is
+//! calculated first, then the memory is allocated in one piece and the term is
+//! copied into it. Subterms which are referred to multiple times are copied
+//! only once, so the sharing is preserved and the copy is never bigger than
+//! the original.
 // TODO: Smarter approach with refcounted movable objects or use shared heap or something else
 use crate::{
+  defs::{Word, WordSize},
   emulator::heap::Heap,
   fail::RtResult,
-  term::{
-    boxed,
-    lterm::*,
-    term_builder::{ListBuilder, TupleBuilder},
-  },
+  term::{boxed, lterm::*},
 };
+use core::ptr;
+use std::collections::{HashMap, HashSet};
 
 /// Copies term to another heap.
 pub fn copy_to(term: LTerm, hp: &mut Heap) -> RtResult<LTerm> {
+  if get_object_ptr(term).is_none() {
+    return Ok(term);
+  }
+  unsafe { copy_sized(term, size_of_term(term), hp) }
+}
+
+/// Copies term into a new heap fragment of the exact size. The fragment can
+/// later be attached to some process heap (see `Heap::attach_fragment`).
+/// Immediate values do not need a fragment and are returned as is.
+pub fn copy_to_fragment(term: LTerm) -> RtResult<(LTerm, Option<Heap>)> {
+  if get_object_ptr(term).is_none() {
+    return Ok((term, None));
+  }
+  unsafe {
+    let size = size_of_term(term);
+    let mut fragment = Heap::new(size);
+    let result = copy_sized(term, size, &mut fragment)?;
+    Ok((result, Some(fragment)))
+  }
+}
+
+/// Copy a term which has already been measured with `size_of_term`.
+unsafe fn copy_sized(term: LTerm, size: usize, hp: &mut Heap) -> RtResult<LTerm> {
+  let block = hp.alloc::<Word>(WordSize::new(size), false)?;
+  let mut copier = Copier {
+    block,
+    size,
+    top: 0,
+    copied: HashMap::new(),
+  };
+  let result = copier.copy_object(term);
+  copier.scan(hp);
+  debug_assert_eq!(copier.top, size);
+  Ok(result)
+}
+
+/// For a term which refers to some memory (a cons or a boxed), return the
+/// memory address. For immediate values return `None`.
+fn get_object_ptr(term: LTerm) -> Option<*mut Word> {
   match term.get_term_tag() {
-    TERMTAG_BOXED => unsafe { copy_boxed_to(term, hp) },
-    TERMTAG_HEADER => {
-      panic!("Attempt to copy header value");
+    TERMTAG_BOXED => {
+      if term.is_non_value() || term.is_cp() {
+        panic!("Attempt to copy a non-value or a CP");
+      }
+      Some(term.get_box_ptr_mut::<Word>())
     }
-    TERMTAG_CONS => unsafe { copy_cons_to(term, hp) },
-    TERMTAG_SMALL | TERMTAG_ATOM | TERMTAG_LOCALPID | TERMTAG_LOCALPORT => Ok(term),
+    TERMTAG_CONS => Some(term.get_cons_ptr_mut() as *mut Word),
+    TERMTAG_HEADER => panic!("Attempt to copy header value"),
+    TERMTAG_SMALL | TERMTAG_ATOM | TERMTAG_LOCALPID | TERMTAG_LOCALPORT => None,
     TERMTAG_SPECIAL => match term.get_special_tag() {
-      SPECIALTAG_CONST => Ok(term),
+      SPECIALTAG_CONST => None,
       _ => panic!("Attempt to copy a special value: {}", term),
     },
     t => panic!("Not sure how to copy term with {:?}", t),
   }
 }
 
-/// For each list element copy it to a new element in the destination heap.
-/// Also copy the tail element.
-/// Returns: `RtResult<copied_term>`
-unsafe fn copy_cons_to(lst: LTerm, hp: &mut Heap) -> RtResult<LTerm> {
-  let mut lb = ListBuilder::new(hp)?;
+/// Calculate how many words a copy of the term will take. Every object is
+/// only counted once, even if it is referred to multiple times.
+unsafe fn size_of_term(term: LTerm) -> usize {
+  let mut visited = HashSet::<*mut Word>::new();
+  let mut stack = vec![term];
+  let mut size = 0usize;
 
-  let tail_el_result = cons::for_each(lst, |el| {
-    // Recurse into copy, for each list element
-    lb.append(copy_to(el, hp)?)?;
-    Ok(())
-  })?;
-
-  if let Some(tail_el) = tail_el_result {
-    return Ok(lb.make_term_with_tail(tail_el));
+  while let Some(t) = stack.pop() {
+    let p = match get_object_ptr(t) {
+      Some(p) => p,
+      None => continue,
+    };
+    if !visited.insert(p) {
+      continue;
+    }
+    if t.is_cons() {
+      size += 2;
+      let cons_p = t.get_cons_ptr();
+      stack.push((*cons_p).tl());
+      stack.push((*cons_p).hd());
+    } else {
+      size += boxed::for_each_term_field(p, |field| {
+        stack.push(LTerm::from_raw(ptr::read(field)))
+      });
+    }
   }
+  size
+}
 
-  Ok(lb.make_term())
+/// Copies objects into a preallocated memory block. Copied objects are then
+/// scanned in Cheney fashion and the terms inside them are copied too.
+struct Copier {
+  block: *mut Word,
+  size: usize,
+  top: usize,
+  /// Maps source object addresses to their copies, to preserve sharing.
+  copied: HashMap<*mut Word, LTerm>,
 }
 
-unsafe fn copy_boxed_to(term: LTerm, hp: &mut Heap) -> RtResult<LTerm> {
-  let box_p = term.get_box_ptr::<boxed::BoxHeader>();
-  match (*box_p).get_tag() {
-    boxed::BOXTYPETAG_TUPLE => {
-      let tuple_p = box_p as *const boxed::Tuple;
-      let arity = (*tuple_p).get_arity();
-      let tb = TupleBuilder::with_arity(arity, hp)?;
-      for i in 0..arity {
-        let element = boxed::Tuple::get_element_base0(tuple_p, i);
-        let copied = copy_to(element, hp)?;
-        tb.set_element_base0(i, copied);
+impl Copier {
+  #[inline]
+  unsafe fn alloc(&mut self, n_words: usize) -> *mut Word {
+    debug_assert!(self.top + n_words <= self.size);
+    let p = self.block.add(self.top);
+    self.top += n_words;
+    p
+  }
+
+  /// Copy one object (without the terms it refers to) and return the term
+  /// pointing to the copy. Immediate values are returned unchanged.
+  unsafe fn copy_object(&mut self, term: LTerm) -> LTerm {
+    let src = match get_object_ptr(term) {
+      Some(p) => p,
+      None => return term,
+    };
+    if let Some(result) = self.copied.get(&src) {
+      return *result;
+    }
+
+    let result = if term.is_cons() {
+      let dst = self.alloc(2);
+      ptr::copy_nonoverlapping(src, dst, 2);
+      LTerm::make_cons(dst)
+    } else {
+      let n_words = 1 + boxed::headerword_to_arity(ptr::read(src));
+      let dst = self.alloc(n_words);
+      ptr::copy_nonoverlapping(src, dst, n_words);
+      LTerm::make_boxed(dst)
+    };
+    self.copied.insert(src, result);
+    result
+  }
+
+  /// Step over the copied objects and copy everything they refer to, until
+  /// the whole block is filled.
+  unsafe fn scan(&mut self, hp: &mut Heap) {
+    let mut scan = 0;
+    while scan < self.top {
+      let p = self.block.add(scan);
+      let val = LTerm::from_raw(ptr::read(p));
+      if val.get_term_tag() == TERMTAG_HEADER {
+        if let boxed::BOXTYPETAG_BINARY = boxed::headerword_to_boxtype(val.raw()) {
+          boxed::Binary::on_copy(p as *mut boxed::Binary, hp);
+        }
+        scan += boxed::for_each_term_field(p, |field| {
+          let copy = self.copy_object(LTerm::from_raw(ptr::read(field)));
+          ptr::write(field, copy.raw());
+        });
+      } else {
+        ptr::write(p, self.copy_object(val).raw());
+        scan += 1;
       }
-      return Ok(tb.make_term());
     }
-    boxed::BOXTYPETAG_BIGINTEGER => {}
-    boxed::BOXTYPETAG_EXTERNALPID => {}
-    boxed::BOXTYPETAG_EXTERNALREF => {}
-    boxed::BOXTYPETAG_EXTERNALPORT => {}
-    boxed::BOXTYPETAG_CLOSURE => {}
-    boxed::BOXTYPETAG_FLOAT => {}
-    boxed::BOXTYPETAG_IMPORT => {}
-    boxed::BOXTYPETAG_EXPORT => {}
-    boxed::BOXTYPETAG_MAP => {}
-    boxed::BOXTYPETAG_BINARY => {}
-    _other => {}
   }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::{
+    emulator::heap::allocate_cons,
+    term::{compare::cmp_terms, lterm::tuple::make_tuple2},
+  };
+  use core::cmp::Ordering;
 
-  panic!("Don't know how to copy {}", term);
+  #[test]
+  fn test_copy_preserves_sharing() {
+    let mut hp1 = Heap::new(256);
+    let mut hp2 = Heap::new(256);
+    unsafe {
+      let cell = allocate_cons(&mut hp1).unwrap();
+      (*cell).set_hd(LTerm::make_small_unsigned(1));
+      (*cell).set_tl(LTerm::nil());
+      let lst = LTerm::make_cons(cell);
+      let f = boxed::Float::create_into(&mut hp1, 1.5).unwrap();
+      let inner = make_tuple2(lst, LTerm::make_boxed(f), &mut hp1).unwrap();
+      let outer = make_tuple2(inner, inner, &mut hp1).unwrap();
+
+      let copy = copy_to(outer, &mut hp2).unwrap();
+      // outer 3 + inner 3 + cons 2 + float, the inner tuple is copied once
+      let float_size = boxed::Float::storage_size().words();
+      assert_eq!(hp2.heap_top, 3 + 3 + 2 + float_size);
+      let t = copy.get_tuple_ptr();
+      let inner2 = boxed::Tuple::get_element_base0(t, 0);
+      assert_eq!(inner2, boxed::Tuple::get_element_base0(t, 1));
+      assert_ne!(inner2, inner);
+      let t2 = inner2.get_tuple_ptr();
+      let lst2 = boxed::Tuple::get_element_base0(t2, 0);
+      assert_eq!(cmp_terms(lst, lst2, true).unwrap(), Ordering::Equal);
+      let f2 = boxed::Tuple::get_element_base0(t2, 1).get_box_ptr::<boxed::Float>();
+      assert_eq!((*f2).value, 1.5);
+    }
+  }
 }
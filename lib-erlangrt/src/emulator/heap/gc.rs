@@ -0,0 +1,541 @@
+//! Generational copying garbage collector for process heaps.
+//!
+//! Live data is found by tracing from the root set (live X registers, the
+//! stack and whatever else the owning process passes in) and is evacuated to
+//! a new memory block using Cheney's algorithm. The stack is not moved, only
+//! the terms in it are updated. Objects which have already
+//! survived one collection (these are below the high water mark) are promoted
+//! to the old generation. The old generation is only collected during a
+//! fullsweep, which happens every `fullsweep_after` minor collections.
+//! Heap fragments attached to the heap are evacuated together with the young
+//! generation, this way they are merged into the heap and then released.
+use crate::{
+  defs::Word,
+  emulator::heap::{next_heap_size, Heap},
+  fail::{Error, RtResult},
+  term::{boxed, lterm::*},
+};
+use core::cmp::max;
+
+/// Default count of minor collections before a fullsweep will be performed.
+pub const DEFAULT_FULLSWEEP_AFTER: usize = 65535;
+
+/// A memory range which is being evacuated. Pointers outside of from-space
+/// (for example to module literals) are left unchanged.
+struct Region {
+  begin: *const Word,
+  end: *const Word,
+}
+
+impl Region {
+  #[inline]
+  fn contains(&self, p: *const Word) -> bool {
+    p >= self.begin && p < self.end
+  }
+}
+
+/// A to-space memory block which receives the evacuated objects.
+struct ToSpace {
+  base: *mut Word,
+  top: usize,
+  scan: usize,
+}
+
+impl ToSpace {
+  fn new(base: *mut Word, top: usize) -> Self {
+    Self {
+      base,
+      top,
+      scan: top,
+    }
+  }
+
+  #[inline]
+  unsafe fn alloc(&mut self, n_words: usize) -> *mut Word {
+    let p = self.base.add(self.top);
+    self.top += n_words;
+    p
+  }
+}
+
+/// State of a single collection run.
+struct Collector {
+  young_from: Region,
+  /// Old generation is only evacuated during a fullsweep.
+  old_from: Option<Region>,
+  /// Attached heap fragments, always evacuated to `young_to`.
+  fragments: Vec<Region>,
+  /// Young objects below this address will be promoted to `old_to` during a
+  /// minor collection.
+  promote_below: *const Word,
+  young_to: ToSpace,
+  old_to: Option<ToSpace>,
+}
+
+impl Collector {
+  #[inline]
+  fn in_from_space(&self, p: *const Word) -> bool {
+    if self.young_from.contains(p) {
+      return true;
+    }
+    if let Some(r) = &self.old_from {
+      if r.contains(p) {
+        return true;
+      }
+    }
+    self.fragments.iter().any(|r| r.contains(p))
+  }
+
+  /// Select the to-space for an object located at `p` in from-space.
+  #[inline]
+  fn select_to_space(&mut self, p: *const Word) -> &mut ToSpace {
+    match self.old_to {
+      Some(ref mut old_to)
+        if self.young_from.contains(p) && p < self.promote_below =>
+      {
+        old_to
+      }
+      _ => &mut self.young_to,
+    }
+  }
+
+  /// Given a term, if it points into from-space, copy the object it refers to
+  /// and leave a forwarding pointer in the old location. Returns the updated
+  /// term.
+  unsafe fn evacuate(&mut self, term: LTerm) -> LTerm {
+    match term.get_term_tag() {
+      TERMTAG_BOXED => {
+        if term.is_non_value() || term.is_cp() {
+          return term;
+        }
+        let p = term.get_box_ptr_mut::<Word>();
+        if !self.in_from_space(p) {
+          return term;
+        }
+        let header = LTerm::from_raw(core::ptr::read(p));
+        if header.get_term_tag() != TERMTAG_HEADER {
+          // Already moved, the header was replaced with a forwarding pointer
+          return header;
+        }
+        let n_words = 1 + boxed::headerword_to_arity(header.raw());
+        let dst = self.select_to_space(p).alloc(n_words);
+        core::ptr::copy_nonoverlapping(p, dst, n_words);
+        let new_term = LTerm::make_boxed(dst);
+        core::ptr::write(p, new_term.raw());
+        new_term
+      }
+      TERMTAG_CONS => {
+        let p = term.get_cons_ptr_mut() as *mut Word;
+        if !self.in_from_space(p) {
+          return term;
+        }
+        if LTerm::from_raw(core::ptr::read(p)).is_non_value() {
+          // Already moved, the tail word contains the forwarding cons
+          return LTerm::from_raw(core::ptr::read(p.add(1)));
+        }
+        let dst = self.select_to_space(p).alloc(2);
+        core::ptr::copy_nonoverlapping(p, dst, 2);
+        let new_term = LTerm::make_cons(dst);
+        core::ptr::write(p, LTerm::non_value().raw());
+        core::ptr::write(p.add(1), new_term.raw());
+        new_term
+      }
+      _ => term,
+    }
+  }
+
+  #[inline]
+  unsafe fn evacuate_in_place(&mut self, p: *mut Word) {
+    let val = self.evacuate(LTerm::from_raw(core::ptr::read(p)));
+    core::ptr::write(p, val.raw());
+  }
+
+  /// Update terms inside the boxed object at `p`. Returns the object size
+  /// including the header word.
+  unsafe fn scan_boxed(&mut self, p: *mut Word) -> usize {
+    boxed::for_each_term_field(p, |field| self.evacuate_in_place(field))
+  }
+
+  /// Run Cheney scan over one to-space, updating the copied objects which
+  /// were not yet scanned. Returns true if some work has been done.
+  unsafe fn scan_step(&mut self, use_old: bool) -> bool {
+    let (base, mut scan) = match (use_old, &self.old_to) {
+      (false, _) => (self.young_to.base, self.young_to.scan),
+      (true, Some(old_to)) => (old_to.base, old_to.scan),
+      (true, None) => return false,
+    };
+    let mut did_work = false;
+    loop {
+      let top = if use_old {
+        self.old_to.as_ref().unwrap().top
+      } else {
+        self.young_to.top
+      };
+      if scan >= top {
+        break;
+      }
+      did_work = true;
+      let p = base.add(scan);
+      if LTerm::from_raw(core::ptr::read(p)).get_term_tag() == TERMTAG_HEADER {
+        scan += self.scan_boxed(p);
+      } else {
+        self.evacuate_in_place(p);
+        scan += 1;
+      }
+    }
+    if use_old {
+      self.old_to.as_mut().unwrap().scan = scan;
+    } else {
+      self.young_to.scan = scan;
+    }
+    did_work
+  }
+
+  /// Scan both to-spaces until no more objects are being copied.
+  unsafe fn scan(&mut self) {
+    loop {
+      let young_work = self.scan_step(false);
+      let old_work = self.scan_step(true);
+      if !young_work && !old_work {
+        break;
+      }
+    }
+  }
+}
+
+impl Heap {
+  /// Run garbage collection on the heap, using `roots` as the root set in
+  /// addition to the stack. After the collection the heap is guaranteed to
+  /// have at least `need` words available, the heap size is chosen from the
+  /// size classes (see `next_heap_size`) to fit the live data and `need`.
+  pub fn garbage_collect(
+    &mut self,
+    need: usize,
+    roots: &mut [&mut [LTerm]],
+  ) -> RtResult<()> {
+    let fullsweep = self.minor_gcs >= self.fullsweep_after
+      || (!self.old_data.is_empty()
+        && self.old_data.len() - self.old_top < self.high_water);
+    unsafe {
+      if fullsweep {
+        self.gc_fullsweep(need, roots);
+      } else {
+        self.gc_minor(need, roots);
+      }
+    }
+    if !self.heap_has_available(need) {
+      return Err(Error::HeapIsFull);
+    }
+    Ok(())
+  }
+
+  /// Collect the young generation, promote objects which have survived the
+  /// previous collection.
+  unsafe fn gc_minor(&mut self, need: usize, roots: &mut [&mut [LTerm]]) {
+    if self.old_data.is_empty() {
+      let old_capacity = next_heap_size(max(self.capacity, self.high_water));
+      self.old_data = vec![0; old_capacity];
+      self.old_top = 0;
+    }
+    let young_live =
+      self.heap_top - self.high_water + self.get_fragments_used_words();
+    let new_capacity = next_heap_size(max(self.min_size, young_live + need));
+    let mut new_data: Vec<Word> = vec![0; new_capacity];
+
+    let heap_begin = self.get_heap_start_ptr();
+    let mut gc = Collector {
+      young_from: Region {
+        begin: heap_begin,
+        end: self.get_heap_top_ptr(),
+      },
+      old_from: None,
+      fragments: self.get_fragment_regions(),
+      promote_below: heap_begin.add(self.high_water),
+      young_to: ToSpace::new(new_data.as_mut_ptr(), 0),
+      old_to: Some(ToSpace::new(self.old_data.as_mut_ptr(), self.old_top)),
+    };
+    self.gc_run(&mut gc, roots);
+
+    self.old_top = gc.old_to.as_ref().unwrap().top;
+    self.gc_finish(new_data, gc.young_to.top);
+    self.minor_gcs += 1;
+  }
+
+  /// Drop the stack and move the live data into a heap of exactly its size,
+  /// for a hibernating process. The heap grows back on the next collection.
+  pub fn hibernate(&mut self, roots: &mut [&mut [LTerm]]) {
+    self.stack = Vec::new();
+    self.stack_top = 0;
+    unsafe {
+      // The first pass finds out the live size, the second one copies the
+      // live data into a heap of that size
+      self.gc_fullsweep(0, roots);
+      self.gc_fullsweep_into(max(self.heap_top, 1), roots);
+    }
+  }
+
+  /// Collect both the young and the old generation into a new young heap.
+  unsafe fn gc_fullsweep(&mut self, need: usize, roots: &mut [&mut [LTerm]]) {
+    let live = self.heap_top + self.old_top + self.get_fragments_used_words();
+    let new_capacity = next_heap_size(max(self.min_size, live + need));
+    self.gc_fullsweep_into(new_capacity, roots);
+  }
+
+  /// Do a full sweep into a new young heap of `new_capacity` words, which must
+  /// fit the live data.
+  unsafe fn gc_fullsweep_into(
+    &mut self,
+    new_capacity: usize,
+    roots: &mut [&mut [LTerm]],
+  ) {
+    let mut new_data: Vec<Word> = vec![0; new_capacity];
+
+    let old_begin = self.old_data.as_ptr();
+    let mut gc = Collector {
+      young_from: Region {
+        begin: self.get_heap_start_ptr(),
+        end: self.get_heap_top_ptr(),
+      },
+      old_from: Some(Region {
+        begin: old_begin,
+        end: old_begin.add(self.old_top),
+      }),
+      fragments: self.get_fragment_regions(),
+      promote_below: core::ptr::null(),
+      young_to: ToSpace::new(new_data.as_mut_ptr(), 0),
+      old_to: None,
+    };
+    self.gc_run(&mut gc, roots);
+
+    self.old_data = Vec::new();
+    self.old_top = 0;
+    self.gc_finish(new_data, gc.young_to.top);
+    self.minor_gcs = 0;
+  }
+
+  /// Evacuate all roots including the stack, then scan the copied objects.
+  unsafe fn gc_run(&mut self, gc: &mut Collector, roots: &mut [&mut [LTerm]]) {
+    for cell in &mut self.stack[self.stack_top..] {
+      *cell = gc.evacuate(LTerm::from_raw(*cell)).raw();
+    }
+
+    for root_slice in roots.iter_mut() {
+      for root in root_slice.iter_mut() {
+        *root = gc.evacuate(*root);
+      }
+    }
+
+    gc.scan();
+
+    // Off-heap references in the fragments now belong to this heap, those
+    // which were not copied will be released by the sweep
+    for fragment in self.fragments.iter_mut() {
+      self.off_heap.append(&mut fragment.off_heap);
+    }
+    self.sweep_off_heap(gc);
+    self.fragments.clear();
+  }
+
+  fn get_fragment_regions(&self) -> Vec<Region> {
+    self
+      .fragments
+      .iter()
+      .map(|f| unsafe {
+        Region {
+          begin: f.get_heap_start_ptr(),
+          end: f.get_heap_top_ptr(),
+        }
+      })
+      .collect()
+  }
+
+  /// Update the off-heap list with new locations of the references which
+  /// survived, and release the references which were not copied.
+  unsafe fn sweep_off_heap(&mut self, gc: &Collector) {
+    let mut survivors = Vec::with_capacity(self.off_heap.len());
+    for bin in self.off_heap.drain(..) {
+      let p = bin as *const Word;
+      if !gc.in_from_space(p) {
+        // Not collected this time (in the old generation)
+        survivors.push(bin);
+        continue;
+      }
+      let header = LTerm::from_raw(core::ptr::read(p));
+      if header.get_term_tag() == TERMTAG_HEADER {
+        // Header was not replaced with a forwarding pointer, the object died
+        boxed::Binary::release(bin);
+      } else {
+        survivors.push(header.get_box_ptr_mut::<boxed::Binary>());
+      }
+    }
+    self.off_heap = survivors;
+  }
+
+  /// Replace the heap memory with the new block where live data was copied.
+  fn gc_finish(&mut self, new_data: Vec<Word>, new_heap_top: usize) {
+    self.capacity = new_data.len();
+    self.data = new_data;
+    self.heap_top = new_heap_top;
+    self.high_water = new_heap_top;
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::{
+    defs::ByteSize,
+    emulator::heap::{
+      allocate_cons,
+      copy_term::{copy_to, copy_to_fragment},
+    },
+    term::lterm::tuple::make_tuple2,
+  };
+
+  /// Build a list `[1, 2, ..., n]` on the heap.
+  fn make_list(hp: &mut Heap, n: usize) -> LTerm {
+    let mut result = LTerm::nil();
+    for i in (1..=n).rev() {
+      let cell = allocate_cons(hp).unwrap();
+      unsafe {
+        (*cell).set_hd(LTerm::make_small_unsigned(i));
+        (*cell).set_tl(result);
+      }
+      result = LTerm::make_cons(cell);
+    }
+    result
+  }
+
+  #[test]
+  fn test_gc_keeps_live_data() {
+    let mut hp = Heap::new(256);
+    let _garbage = make_list(&mut hp, 20);
+    let lst = make_list(&mut hp, 10);
+    let tuple = make_tuple2(lst, lst, &mut hp).unwrap();
+    let mut regs = [tuple];
+    let used_before = hp.heap_top;
+
+    hp.garbage_collect(8, &mut [&mut regs]).unwrap();
+
+    // The two tuple elements still share the same list after the copy
+    assert!(hp.heap_top < used_before);
+    assert_eq!(hp.heap_top, 3 + 2 * 10);
+    let t = regs[0].get_tuple_ptr();
+    unsafe {
+      let elem1 = boxed::Tuple::get_element_base0(t, 0);
+      let elem2 = boxed::Tuple::get_element_base0(t, 1);
+      assert_eq!(elem1, elem2);
+      assert_eq!(cons::list_length(elem1).unwrap(), 10);
+    }
+  }
+
+  #[test]
+  fn test_gc_promotes_and_fullsweeps() {
+    let mut hp = Heap::new(256);
+    hp.fullsweep_after = 2;
+    let lst = make_list(&mut hp, 5);
+    let mut regs = [lst];
+
+    // First GC leaves the list in the young generation, second promotes it
+    hp.garbage_collect(0, &mut [&mut regs]).unwrap();
+    assert_eq!(hp.old_top, 0);
+    hp.garbage_collect(0, &mut [&mut regs]).unwrap();
+    assert_eq!(hp.old_top, 10);
+    assert_eq!(hp.heap_top, 0);
+
+    // Fullsweep brings everything back to the young generation
+    hp.garbage_collect(0, &mut [&mut regs]).unwrap();
+    assert_eq!(hp.minor_gcs, 0);
+    assert_eq!(hp.old_top, 0);
+    assert_eq!(hp.heap_top, 10);
+    assert_eq!(cons::list_length(regs[0]).unwrap(), 5);
+  }
+
+  #[test]
+  fn test_gc_grows_heap() {
+    let mut hp = Heap::new(next_heap_size(1));
+    let lst = make_list(&mut hp, 6);
+    assert!(allocate_cons(&mut hp).is_err());
+    let mut regs = [lst];
+
+    hp.garbage_collect(100, &mut [&mut regs]).unwrap();
+    assert!(hp.heap_has_available(100));
+    assert_eq!(hp.capacity, next_heap_size(12 + 100));
+    assert_eq!(cons::list_length(regs[0]).unwrap(), 6);
+  }
+
+  #[test]
+  fn test_gc_hibernate() {
+    let mut hp = Heap::new(256);
+    let _garbage = make_list(&mut hp, 20);
+    let lst = make_list(&mut hp, 10);
+    hp.stack_alloc(4, true);
+    let mut regs = [lst];
+
+    hp.hibernate(&mut [&mut regs]);
+    assert_eq!(hp.stack_depth(), 0);
+    assert_eq!(hp.capacity, 20);
+    assert_eq!(hp.get_total_size(), 20);
+    assert_eq!(cons::list_length(regs[0]).unwrap(), 10);
+
+    // The heap grows back when needed
+    hp.garbage_collect(100, &mut [&mut regs]).unwrap();
+    assert!(hp.heap_has_available(100));
+    assert_eq!(cons::list_length(regs[0]).unwrap(), 10);
+  }
+
+  #[test]
+  fn test_gc_releases_dead_refbins() {
+    let mut hp1 = Heap::new(256);
+    let mut hp2 = Heap::new(256);
+    let data = [1u8; 100];
+    unsafe {
+      let bin1 = boxed::Binary::create_into(&mut hp1, ByteSize::new(data.len())).unwrap();
+      boxed::Binary::store(bin1, &data).unwrap();
+      let bin2_term = copy_to(LTerm::make_boxed(bin1), &mut hp2).unwrap();
+      let bin2 = bin2_term.get_box_ptr::<boxed::Binary>();
+      assert_eq!(boxed::Binary::get_refc(bin2), Some(2));
+
+      // Nothing refers to the binary on the first heap anymore
+      hp1.garbage_collect(0, &mut []).unwrap();
+      assert_eq!(boxed::Binary::get_refc(bin2), Some(1));
+
+      let mut regs = [LTerm::make_boxed(bin2)];
+      hp2.garbage_collect(0, &mut [&mut regs]).unwrap();
+      let bin2_moved = regs[0].get_box_ptr::<boxed::Binary>();
+      assert_eq!(boxed::Binary::get_refc(bin2_moved), Some(1));
+      assert_eq!(boxed::Binary::get_data(bin2_moved), &data[..]);
+    }
+  }
+
+  #[test]
+  fn test_gc_merges_fragments() {
+    let mut hp1 = Heap::new(256);
+    let mut hp2 = Heap::new(256);
+    let data = [1u8; 100];
+    unsafe {
+      let bin = boxed::Binary::create_into(&mut hp1, ByteSize::new(data.len())).unwrap();
+      boxed::Binary::store(bin, &data).unwrap();
+      let lst = make_list(&mut hp1, 5);
+      let msg = make_tuple2(lst, LTerm::make_boxed(bin), &mut hp1).unwrap();
+
+      let (copy, fragment) = copy_to_fragment(msg).unwrap();
+      let fragment = fragment.unwrap();
+      let fragment_size = fragment.heap_top;
+      hp2.attach_fragment(fragment);
+      assert_eq!(hp2.heap_top, 0);
+
+      let mut regs = [copy];
+      hp2.garbage_collect(0, &mut [&mut regs]).unwrap();
+      assert!(hp2.fragments.is_empty());
+      assert_eq!(hp2.off_heap.len(), 1);
+      assert_eq!(hp2.heap_top, fragment_size);
+
+      let t = regs[0].get_tuple_ptr();
+      assert!(hp2.get_heap_start_ptr() <= t as *const Word);
+      assert_eq!(cons::list_length(boxed::Tuple::get_element_base0(t, 0)).unwrap(), 5);
+      let bin2 = boxed::Tuple::get_element_base0(t, 1).get_box_ptr::<boxed::Binary>();
+      assert_eq!(boxed::Binary::get_refc(bin2), Some(2));
+    }
+  }
+}
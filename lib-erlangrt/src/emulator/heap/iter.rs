@@ -17,21 +17,21 @@ impl HeapIterator {
   }
 
   pub unsafe fn next(&mut self) -> Option<*const LTerm> {
+    let end = self.end;
+    if self.p >= end {
+      return None;
+    }
+    let result = self.p;
+
     // Peek inside *p to see if we're at a header, and if so - step over it
     // using header arity. Otherwise step by 1 cell
     let val = core::ptr::read(self.p);
     let size = match val.get_term_tag() {
-      TERMTAG_HEADER => boxed::headerword_to_arity(val.raw()),
+      TERMTAG_HEADER => 1 + boxed::headerword_to_arity(val.raw()),
       _ => 1usize,
     };
+    self.p = self.p.add(size);
 
-    self.p.add(size);
-
-    let end = self.end;
-    if self.p >= end {
-      return None;
-    }
-
-    Some(self.p)
+    Some(result)
   }
 }
@@ -1,5 +1,6 @@
 pub mod copy_term;
 pub mod dump;
+pub mod gc;
 pub mod iter;
 
 use crate::{
@@ -13,7 +14,8 @@ use core::fmt;
 /// Default heap size for constants (literals) when loading a module.
 pub const DEFAULT_LIT_HEAP: usize = 8192;
 
-/// Default heap size when spawning a process. (default: 300)
+/// Default heap size when spawning a process, rounded up to a size class by
+/// `next_heap_size`.
 pub const DEFAULT_PROC_HEAP: usize = 16384;
 
 pub struct NextCatchResult {
@@ -24,17 +26,70 @@ pub struct NextCatchResult {
   pub stack_drop: usize,
 }
 
+/// Heap growth goes through Fibonacci-like size classes until this limit is
+/// reached, after that it grows by 20% (same as ERTS `next_heap_size`).
+const HEAP_SIZE_FIB_LIMIT: usize = 1_300_000;
+
+/// For a requested size in words, return the size class which fits it.
+pub fn next_heap_size(need: usize) -> usize {
+  let mut a = 12usize;
+  let mut b = 38usize;
+  if need <= a {
+    return a;
+  }
+  while need > b {
+    let next = if b < HEAP_SIZE_FIB_LIMIT {
+      a + b + 1
+    } else {
+      b + b / 5
+    };
+    a = b;
+    b = next;
+  }
+  b
+}
+
 /// A heap structure which grows upwards with allocations. Cannot expand
-/// implicitly and will return error when capacity is exceeded. Organize a
-/// garbage collect call to get more memory TODO: gc on heap
+/// implicitly and will return `Error::HeapIsFull` when capacity is exceeded.
+/// Organize a garbage collect call to get more memory (see `gc.rs`), the
+/// collector will also select the next heap size class when required.
+/// The stack lives in a separate memory segment which grows on demand.
 pub struct Heap {
   data: Vec<Word>,
-  /// Heap top, begins at 0 and grows up towards the `stack_top`.
+  /// Heap top, begins at 0 and grows up towards the `capacity`.
   heap_top: usize,
-  /// Stack top, begins at the end of capacity and grows down.
-  stack_top: usize,
-  /// Marks end of the stack and also end of the heap.
+  /// Marks end of the heap.
   capacity: usize,
+  /// GC will not shrink the heap below this size.
+  min_size: usize,
+
+  /// Stack segment, values are stored at its end and the stack grows down.
+  /// There are no pointers into the stack, so it is relocated freely when it
+  /// has to grow.
+  stack: Vec<Word>,
+  /// Stack top, begins at the end of `stack` and grows down.
+  stack_top: usize,
+
+  /// Old generation, objects which have survived two collections are moved
+  /// here and are only collected during a fullsweep.
+  old_data: Vec<Word>,
+  old_top: usize,
+  /// Heap top after the previous collection. Objects below this mark have
+  /// survived one GC and will be promoted to the old generation on the next.
+  high_water: usize,
+  /// Minor collections done since the last fullsweep.
+  minor_gcs: usize,
+  /// Perform a fullsweep after this many minor collections (0 = always).
+  pub fullsweep_after: usize,
+
+  /// References to refcounted objects outside of the heap (currently these
+  /// are binaries on the binary heap). When the GC finds that a reference has
+  /// died, the refcount is decreased.
+  off_heap: Vec<*mut boxed::Binary>,
+
+  /// Heap fragments (messages copied by other processes) which are now
+  /// referred to from this heap. They are merged into the heap on the next GC.
+  fragments: Vec<Heap>,
 }
 
 impl Heap {}
@@ -56,8 +111,17 @@ impl Heap {
     let mut h = Self {
       data: Vec::with_capacity(capacity),
       heap_top: 0,
-      stack_top: capacity,
       capacity,
+      min_size: capacity,
+      stack: Vec::new(),
+      stack_top: 0,
+      old_data: Vec::new(),
+      old_top: 0,
+      high_water: 0,
+      minor_gcs: 0,
+      fullsweep_after: gc::DEFAULT_FULLSWEEP_AFTER,
+      off_heap: Vec::new(),
+      fragments: Vec::new(),
     };
     unsafe { h.data.set_len(capacity) };
     h
@@ -65,19 +129,26 @@ impl Heap {
 
   /// How many words do we have before it will require GC/growth.
   #[inline]
-  fn get_heap_max_capacity(&self) -> usize {
-    self.data.capacity()
+  pub fn get_heap_max_capacity(&self) -> usize {
+    self.capacity
   }
 
-  /// Heap usage stat.
-  #[inline]
-  fn get_heap_used_words(&self) -> usize {
-    self.heap_top
+  /// Size of the heap and the old generation together, without the stack.
+  /// This is the `heap_size` reported by `process_info`.
+  pub fn get_heap_size(&self) -> usize {
+    self.capacity + self.old_data.len()
   }
 
+  /// Size of the heap, the old generation and the stack together, this is
+  /// compared against the `max_heap_size` process flag.
+  pub fn get_total_size(&self) -> usize {
+    self.get_heap_size() + self.stack.len()
+  }
+
+  /// Heap usage stat.
   #[inline]
-  fn get_heap_available(&self) -> usize {
-    self.stack_top - self.heap_top
+  pub fn get_heap_used_words(&self) -> usize {
+    self.heap_top
   }
 
   #[inline]
@@ -90,40 +161,30 @@ impl Heap {
     self.data.as_mut_ptr()
   }
 
-  /// Get pointer to end of the allocated heap (below the stack top).
+  /// Get pointer to end of the allocated heap.
   #[inline]
   unsafe fn get_heap_top_ptr(&self) -> *const Word {
     self.get_heap_start_ptr().add(self.heap_top)
   }
 
-  /// Stack start is same as end of everything, pointer to the first word after
-  /// the allocated memory, used as limit when iterating the stack.
+  /// Stack start is the end of the stack segment, pointer to the first word
+  /// after the stack memory, used as limit when iterating the stack.
   #[inline]
   unsafe fn get_stack_start_ptr(&self) -> *const Word {
-    self.get_end_ptr()
-  }
-
-  #[inline]
-  unsafe fn get_end_ptr(&self) -> *const Word {
-    self.get_heap_start_ptr().add(self.capacity)
+    self.stack.as_ptr().add(self.stack.len())
   }
 
   #[inline]
   unsafe fn get_stack_top_ptr(&self) -> *const Word {
-    self.get_heap_start_ptr().add(self.stack_top)
+    self.stack.as_ptr().add(self.stack_top)
   }
 
   pub fn alloc<T>(&mut self, n: WordSize, init_nil: bool) -> RtResult<*mut T> {
     let pos = self.heap_top;
     let n_words = n.words();
     // Explicitly forbid expanding without a GC, fail if capacity is exceeded
-    if pos + n_words >= self.stack_top {
-      // return Err(Error::HeapIsFull);
-      panic!(
-        "Heap is full requested={} have={}",
-        n,
-        self.get_heap_available()
-      );
+    if pos + n_words > self.capacity {
+      return Err(Error::HeapIsFull);
     }
 
     // Assume we can grow the data without reallocating
@@ -144,6 +205,23 @@ impl Heap {
     Ok(new_chunk as *mut T)
   }
 
+  /// Remember a reference to refcounted off-heap object which was allocated
+  /// on this heap, it will be released when it is no longer reachable.
+  pub fn register_off_heap(&mut self, bin: *mut boxed::Binary) {
+    self.off_heap.push(bin);
+  }
+
+  /// Take ownership of a heap fragment, terms on this heap may now refer to
+  /// the data in it. The fragment will be merged into the heap by the GC.
+  pub fn attach_fragment(&mut self, fragment: Heap) {
+    self.fragments.push(fragment);
+  }
+
+  /// How many words are used by the attached heap fragments.
+  fn get_fragments_used_words(&self) -> usize {
+    self.fragments.iter().map(|f| f.heap_top).sum()
+  }
+
   //  /// Allocate words on heap enough to store bignum digits and copy the given
   //  /// bignum to memory, return the pointer.
   //  pub fn allocate_big(&mut self, big: &num::BigInt) -> Hopefully<BignumPtr> {
@@ -155,53 +233,58 @@ impl Heap {
 
   #[inline]
   pub fn heap_has_available(&self, need: usize) -> bool {
-    self.heap_top + need <= self.stack_top
+    self.heap_top + need <= self.capacity
   }
 
   #[inline]
   pub fn stack_have(&self, need: usize) -> bool {
-    self.heap_top + need <= self.stack_top
+    need <= self.stack_top
   }
 
-  //  pub fn stack_alloc(&mut self, need: Word) -> Hopefully<()> {
-  //    // Check if heap top is too close to stack top, then fail
-  //    if !self.stack_have(need) {
-  //      return Err(Error::HeapIsFull)
-  //    }
-  //    self.stack_alloc_unchecked(need);
-  //    Ok(())
-  //  }
+  /// Grow the stack segment to the next size class, which fits current stack
+  /// contents plus `need` words. Stack values are moved to the end of the new
+  /// segment.
+  fn stack_grow(&mut self, need: usize) {
+    let depth = self.stack_depth();
+    let new_size = next_heap_size(depth + need);
+    let mut new_stack = vec![0; new_size];
+    new_stack[new_size - depth..].copy_from_slice(&self.stack[self.stack_top..]);
+    self.stack = new_stack;
+    self.stack_top = new_size - depth;
+  }
+
+  /// Allocate stack cells, growing the stack segment if it is too small.
+  pub fn stack_alloc(&mut self, need: Word, fill_nil: bool) {
+    if !self.stack_have(need) {
+      self.stack_grow(need);
+    }
+    self.stack_alloc_unchecked(need, fill_nil)
+  }
 
   /// Allocate stack cells without checking. Call `stack_have(n)` beforehand.
   pub fn stack_alloc_unchecked(&mut self, need: Word, fill_nil: bool) {
     self.stack_top -= need;
 
     // Clear the new cells
-    let raw_nil = LTerm::nil().raw();
-    unsafe {
-      let p = self.get_heap_begin_ptr_mut().add(self.stack_top);
-
-      if fill_nil {
-        for y in 0..need {
-          core::ptr::write(p.add(y), raw_nil)
-        }
+    if fill_nil {
+      let raw_nil = LTerm::nil().raw();
+      for cell in &mut self.stack[self.stack_top..self.stack_top + need] {
+        *cell = raw_nil;
       }
     }
   }
 
-  // TODO: Add unsafe push without range checks (batch check+multiple push)
-
-  //  pub fn stack_push(&mut self, val: Word) -> Hopefully<()> {
-  //    if !self.stack_have(1) {
-  //      return Err(Error::HeapIsFull)
-  //    }
-  //    self.stack_push_unchecked(val);
-  //    Ok(())
-  //  }
+  /// Push a LTerm to stack, growing the stack segment if it is too small.
+  pub fn stack_push_lterm(&mut self, val: LTerm) {
+    if !self.stack_have(1) {
+      self.stack_grow(1);
+    }
+    self.stack_push_lterm_unchecked(val)
+  }
 
   #[allow(dead_code)]
   pub fn stack_info(&self) {
-    println!("Stack (s_top {}, s_end {})", self.stack_top, self.capacity)
+    println!("Stack (s_top {}, s_end {})", self.stack_top, self.stack.len())
   }
 
   //  /// Push a value to stack without checking. Call `stack_have(1)` beforehand.
@@ -221,13 +304,13 @@ impl Heap {
       println!("{} {}", "push (unchecked)".green(), val);
     }
     self.stack_top -= 1;
-    self.data[self.stack_top] = val.raw();
+    self.stack[self.stack_top] = val.raw();
   }
 
   /// Check whether `y+1`-th element can be found in stack
   #[inline]
   pub fn stack_have_y(&self, y: Word) -> bool {
-    self.capacity - self.stack_top >= y + 1
+    self.stack.len() - self.stack_top >= y + 1
   }
 
   /// Set stack value (`index`th from stack top) to `val`.
@@ -239,7 +322,7 @@ impl Heap {
     if cfg!(feature = "trace_stack_changes") {
       println!("{}{} = {}", "set y".green(), index, val);
     }
-    self.data[index + self.stack_top + 1] = val.raw();
+    self.stack[index + self.stack_top + 1] = val.raw();
     Ok(())
   }
 
@@ -253,7 +336,7 @@ impl Heap {
       return Err(Error::StackIndexRange(index));
     }
     let pos = index + self.stack_top + 1;
-    let result = LTerm::from_raw(self.data[pos]);
+    let result = LTerm::from_raw(self.stack[pos]);
     debug_assert!(result.is_value(), "Should never get a NON_VALUE from y[]");
     Ok(result)
   }
@@ -262,23 +345,23 @@ impl Heap {
   #[inline]
   pub fn get_y_unchecked(&self, index: Word) -> LTerm {
     let pos = index + self.stack_top + 1;
-    LTerm::from_raw(self.data[pos])
+    LTerm::from_raw(self.stack[pos])
   }
 
   pub fn stack_depth(&self) -> Word {
-    self.capacity - self.stack_top
+    self.stack.len() - self.stack_top
   }
 
   /// Take `cp` from stack top and deallocate `n+1` words of stack.
   pub fn stack_deallocate(&mut self, n: Word) -> LTerm {
     assert!(
-      self.stack_top + n < self.capacity,
+      self.stack_top + n < self.stack.len(),
       "Failed to dealloc {}+1 words (s_top {}, s_end {})",
       n,
       self.stack_top,
-      self.capacity
+      self.stack.len()
     );
-    let cp = LTerm::from_raw(self.data[self.stack_top]);
+    let cp = LTerm::from_raw(self.stack[self.stack_top]);
     assert!(cp.is_cp());
     self.stack_top += n + 1;
     cp
@@ -336,17 +419,60 @@ impl Heap {
   /// Arg: new_stack_top - offset from the heap end
   pub fn drop_stack_words(&mut self, n_drop: usize) {
     println!("drop_stack_words {}", n_drop);
-    assert!(self.stack_top + n_drop < self.capacity);
+    assert!(self.stack_top + n_drop < self.stack.len());
     self.stack_top += n_drop;
   }
 }
 
+impl Drop for Heap {
+  fn drop(&mut self) {
+    // Release references to binaries which were still alive on this heap
+    for bin in self.off_heap.drain(..) {
+      unsafe { boxed::Binary::release(bin) }
+    }
+  }
+}
+
 /// Allocate 2 cells `[Head | Tail]` of raw cons cell, and return the pointer.
 #[inline]
 pub fn allocate_cons(hp: &mut Heap) -> RtResult<*mut boxed::Cons> {
   hp.alloc::<boxed::Cons>(WordSize::new(2), false)
 }
 
+/// Build a term which size is not known in advance in a new heap fragment,
+/// the fragment grows until the result fits.
+pub fn build_in_fragment<F>(build: F) -> RtResult<(LTerm, Heap)>
+where
+  F: Fn(&mut Heap) -> RtResult<LTerm>,
+{
+  let mut size = 64;
+  loop {
+    let mut hp = Heap::new(size);
+    match build(&mut hp) {
+      Ok(term) => return Ok((term, hp)),
+      Err(Error::HeapIsFull) => size *= 2,
+      Err(e) => return Err(e),
+    }
+  }
+}
+
+/// Build a term on the heap, or if the heap is full, in a fragment which is
+/// then attached to the heap. Use this where a garbage collection is not
+/// possible, the partially built result left on the heap becomes garbage.
+pub fn build_on_heap<F>(hp: &mut Heap, build: F) -> RtResult<LTerm>
+where
+  F: Fn(&mut Heap) -> RtResult<LTerm>,
+{
+  match build(hp) {
+    Err(Error::HeapIsFull) => {
+      let (term, fragment) = build_in_fragment(build)?;
+      hp.attach_fragment(fragment);
+      Ok(term)
+    }
+    result => result,
+  }
+}
+
 /// Create a constant iterator for walking the heap.
 /// This is used by heap walkers such as "dump.rs"
 pub unsafe fn heap_iter(hp: &Heap) -> iter::HeapIterator {
@@ -354,3 +480,27 @@ pub unsafe fn heap_iter(hp: &Heap) -> iter::HeapIterator {
   let begin = hp.get_heap_start_ptr() as *const LTerm;
   iter::HeapIterator::new(begin, begin.offset(last))
 }
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn test_next_heap_size() {
+    assert_eq!(next_heap_size(0), 12);
+    assert_eq!(next_heap_size(13), 38);
+    assert_eq!(next_heap_size(39), 51);
+    assert_eq!(next_heap_size(52), 90);
+    assert!(next_heap_size(2_000_000) >= 2_000_000);
+  }
+
+  #[test]
+  fn test_stack_grows() {
+    let mut hp = Heap::new(1);
+    hp.stack_alloc(3, true);
+    hp.set_y(0, LTerm::small_1()).unwrap();
+    hp.stack_alloc(100, true);
+    assert_eq!(hp.stack_depth(), 103);
+    assert_eq!(hp.get_y(100).unwrap(), LTerm::small_1());
+  }
+}
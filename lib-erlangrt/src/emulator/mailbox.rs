@@ -1,76 +1,375 @@
-use crate::term::lterm::*;
+//! Process message queue.
+//!
+//! Like in ERTS, messages form a singly linked list. The save pointer marks
+//! the message which will be examined by the next `loop_rec`, messages before
+//! it have already been looked at by the current `receive` and did not match.
+//! `recv_mark` and `recv_set` allow a `receive` which matches on a freshly
+//! created reference to skip the messages which arrived before the reference
+//! was created.
+use crate::{
+  emulator::heap::{copy_term, Heap},
+  fail::RtResult,
+  term::lterm::*,
+};
+use core::ptr;
+
+/// Defines where the messages in the queue are stored (ERTS option
+/// `message_queue_data`).
+#[derive(Debug, Eq, PartialEq, Copy, Clone)]
+pub enum MessageQueueData {
+  /// Heap fragments of the messages in the queue are merged into the process
+  /// heap on the next GC.
+  OnHeap,
+  /// Messages stay in their heap fragments until they are received, this way
+  /// a long message queue does not slow down the GC of the process.
+  OffHeap,
+}
+
+/// A message copied by the sender into its own heap fragment, so that the
+/// receiving process heap is not touched and does not need to be borrowed.
+pub struct Message {
+  term: LTerm,
+  fragment: Option<Heap>,
+}
+
+// The message and its heap fragment are owned by one process at a time.
+unsafe impl Send for Message {}
+
+impl Message {
+  /// Copy a term into a new message.
+  pub fn new(term: LTerm) -> RtResult<Self> {
+    let (term, fragment) = copy_term::copy_to_fragment(term)?;
+    Ok(Self { term, fragment })
+  }
+
+  /// The message term, valid as long as the message exists.
+  #[inline]
+  pub fn get_term(&self) -> LTerm {
+    self.term
+  }
+
+  /// How many words the message has taken, 0 for immediate values.
+  pub fn size(&self) -> usize {
+    self.fragment.as_ref().map_or(0, |f| f.get_heap_used_words())
+  }
+
+  /// Give the heap fragment of the message to the heap `hp` and return the
+  /// term, which stays valid as long as the heap.
+  pub fn attach_to(self, hp: &mut Heap) -> LTerm {
+    if let Some(fragment) = self.fragment {
+      hp.attach_fragment(fragment);
+    }
+    self.term
+  }
+}
+
+struct MessageNode {
+  msg: Message,
+  next: *mut MessageNode,
+}
+
+/// A position in the message queue is stored as a pointer to the node before
+/// it, null stands for the queue head. This stays valid when the mailbox is
+/// moved, unlike a pointer to the `next` field as used in ERTS.
+type Link = *mut MessageNode;
 
 pub struct ProcessMailbox {
-  inbox: Vec<LTerm>,
-  // TODO: Some structure on proc heap?
-  read_index: usize,
+  first: *mut MessageNode,
+  /// The last node, new messages are linked after it. Null if empty.
+  last: Link,
+  /// Position of the message to be examined by the next `loop_rec`.
+  save: Link,
+  /// Position saved by `recv_mark`, the end of the queue at that moment.
+  saved_last: Link,
+  /// Label given to `recv_mark`, `recv_set` only uses `saved_last` if called
+  /// with the same label. Non-value if there is no mark.
+  mark: LTerm,
+  /// How many messages are in the queue.
+  len: usize,
 }
 
 impl ProcessMailbox {
   pub fn new() -> Self {
     Self {
-      inbox: Vec::with_capacity(32),
-      read_index: 0,
+      first: ptr::null_mut(),
+      last: ptr::null_mut(),
+      save: ptr::null_mut(),
+      saved_last: ptr::null_mut(),
+      mark: LTerm::non_value(),
+      len: 0,
+    }
+  }
+
+  /// Returns the node which follows the position `link`.
+  #[inline]
+  unsafe fn node_at(&self, link: Link) -> *mut MessageNode {
+    if link.is_null() {
+      self.first
+    } else {
+      (*link).next
     }
   }
 
-  /// Copy a message and put into process mailbox.
-  /// Assumes: the message is already copied to receiving process heap.
-  pub fn put(&mut self, message: LTerm) {
-    self.inbox.push(message);
+  /// Put a message into process mailbox.
+  pub fn put(&mut self, message: Message) {
+    let node = Box::into_raw(Box::new(MessageNode {
+      msg: message,
+      next: ptr::null_mut(),
+    }));
+    if self.last.is_null() {
+      self.first = node;
+    } else {
+      unsafe { (*self.last).next = node }
+    }
+    self.last = node;
+    self.len += 1;
   }
 
-  /// Read message at the current receive pointer.
-  pub fn get_current(&mut self) -> Option<LTerm> {
-    if self.inbox.is_empty() {
-      return None;
+  /// How many messages are in the queue.
+  #[inline]
+  pub fn message_count(&self) -> usize {
+    self.len
+  }
+
+  /// Message terms in the queue order, valid as long as the messages stay in
+  /// the queue.
+  pub fn get_messages(&self) -> Vec<LTerm> {
+    let mut result = Vec::with_capacity(self.len);
+    let mut node = self.first;
+    while !node.is_null() {
+      unsafe {
+        result.push((*node).msg.term);
+        node = (*node).next;
+      }
     }
+    result
+  }
 
-    let mri = self.read_index;
-    debug_assert!(mri < self.inbox.len());
+  /// Visit every message term, this is used by the garbage collector which
+  /// will update the terms if they move.
+  fn for_each_message_mut<F: FnMut(&mut Message)>(&mut self, mut f: F) {
+    let mut node = self.first;
+    while !node.is_null() {
+      unsafe {
+        f(&mut (*node).msg);
+        node = (*node).next;
+      }
+    }
+  }
+
+  /// Copy message terms, this is used by the garbage collector which will
+  /// update them if they move. Write them back with `set_message_terms`.
+  pub fn get_message_terms(&mut self) -> Vec<LTerm> {
+    let mut result = Vec::with_capacity(self.len);
+    self.for_each_message_mut(|m| result.push(m.term));
+    result
+  }
+
+  /// Update message terms after they were moved by the garbage collector.
+  pub fn set_message_terms(&mut self, terms: &[LTerm]) {
+    debug_assert_eq!(terms.len(), self.len);
+    let mut i = 0;
+    self.for_each_message_mut(|m| {
+      m.term = terms[i];
+      i += 1;
+    });
+  }
+
+  /// Attach heap fragments of all messages to the heap `hp`, they will be
+  /// merged into it on the GC.
+  pub fn move_fragments_to(&mut self, hp: &mut Heap) {
+    self.for_each_message_mut(|m| {
+      if let Some(fragment) = m.fragment.take() {
+        hp.attach_fragment(fragment);
+      }
+    });
+  }
 
-    let val = self.inbox[mri];
+  /// Read message at the save pointer.
+  pub fn get_current(&mut self) -> Option<LTerm> {
+    let node = unsafe { self.node_at(self.save) };
+    if node.is_null() {
+      return None;
+    }
+    let val = unsafe { (*node).msg.term };
     debug_assert!(val.is_value());
     Some(val)
   }
 
-  // TODO: This is ugly, do proper mailbox algorithm impl here
+  /// Advance the save pointer to the next message. If the end of the queue is
+  /// reached, the pointer stays there and will point to the next message which
+  /// will arrive.
   pub fn step_over(&mut self) {
-    // Guard
-    if self.inbox.is_empty() {
-      self.read_index = 0;
-      return;
+    let node = unsafe { self.node_at(self.save) };
+    debug_assert!(!node.is_null(), "step_over: save pointer at the end");
+    if !node.is_null() {
+      self.save = node;
     }
+  }
 
-    let mut mri = self.read_index;
-    // remember starting pos to know if we traversed all messages and all of
-    // them were nonvalues
-    let starting_pos = mri;
-    let max_mri = self.inbox.len();
-    // Increase mail receive index over nonvalues (received values) until
-    // we hit the end of the mailbox
-    while self.inbox[mri].is_non_value() {
-      mri += 1;
-      if mri == max_mri {
-        mri = 0;
-      }
-      if mri == starting_pos {
-        // Done a full loop around mailbox and all values were non-values
-        self.inbox.clear();
-        self.read_index = 0;
-        return;
+  /// Move the save pointer back to the first message, this is done when the
+  /// `receive` is over (a message was received, the receive timed out or an
+  /// exception happened).
+  pub fn reset_save(&mut self) {
+    self.save = ptr::null_mut();
+  }
+
+  /// Remember the end of the queue, for the `recv_mark` opcode.
+  pub fn set_mark(&mut self, label: LTerm) {
+    self.mark = label;
+    self.saved_last = self.last;
+  }
+
+  /// Move the save pointer to the position remembered by `set_mark` for the
+  /// same label, for the `recv_set` opcode. Messages which arrived before the
+  /// mark will not be examined.
+  pub fn set_save_to_mark(&mut self, label: LTerm) {
+    if self.mark == label {
+      self.save = self.saved_last;
+    }
+  }
+
+  /// Remove the message at the save pointer and return it, reset the save
+  /// pointer. The message heap fragment is attached to the heap `hp`.
+  pub fn remove_current(&mut self, hp: &mut Heap) -> LTerm {
+    let node = unsafe { self.node_at(self.save) };
+    assert!(!node.is_null(), "remove_current: no current message");
+
+    let node = unsafe { Box::from_raw(node) };
+    if self.save.is_null() {
+      self.first = node.next;
+    } else {
+      unsafe { (*self.save).next = node.next }
+    }
+    if node.next.is_null() {
+      self.last = self.save;
+    }
+    self.len -= 1;
+
+    // The saved mark might refer to the removed node
+    self.mark = LTerm::non_value();
+    self.saved_last = ptr::null_mut();
+    self.reset_save();
+
+    node.msg.attach_to(hp)
+  }
+
+  /// Remove all messages for which `pred` returns true, for example the
+  /// `'DOWN'` messages flushed by `demonitor/2`. The save pointer and the mark
+  /// are reset. Returns how many messages were removed.
+  pub fn remove_matching<F: Fn(LTerm) -> bool>(&mut self, pred: F) -> usize {
+    let mut removed = 0;
+    let mut prev: Link = ptr::null_mut();
+    let mut node = self.first;
+    while !node.is_null() {
+      let next = unsafe { (*node).next };
+      if pred(unsafe { (*node).msg.term }) {
+        if prev.is_null() {
+          self.first = next;
+        } else {
+          unsafe { (*prev).next = next }
+        }
+        drop(unsafe { Box::from_raw(node) });
+        removed += 1;
+      } else {
+        prev = node;
       }
+      node = next;
     }
-    self.read_index = mri;
+    self.last = prev;
+    self.len -= removed;
+
+    self.mark = LTerm::non_value();
+    self.saved_last = ptr::null_mut();
+    self.reset_save();
+    removed
+  }
+}
+
+impl Drop for ProcessMailbox {
+  fn drop(&mut self) {
+    let mut node = self.first;
+    while !node.is_null() {
+      let boxed_node = unsafe { Box::from_raw(node) };
+      node = boxed_node.next;
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn msg(n: usize) -> Message {
+    Message::new(LTerm::make_small_unsigned(n)).unwrap()
   }
 
-  // Remove value from current mailbox position and return it, move pointer
-  // forward.
-  pub fn remove_current(&mut self) -> LTerm {
-    let mri = self.read_index;
-    let val = self.inbox[mri];
-    self.inbox[mri] = LTerm::non_value();
-    self.step_over();
-    val
+  #[test]
+  fn test_selective_receive() {
+    let mut hp = Heap::new(16);
+    let mut mb = ProcessMailbox::new();
+    mb.put(msg(1));
+    mb.put(msg(2));
+    mb.put(msg(3));
+
+    // Skip 1, receive 2, save pointer goes back to 1
+    assert_eq!(mb.get_current(), Some(LTerm::make_small_unsigned(1)));
+    mb.step_over();
+    assert_eq!(mb.remove_current(&mut hp), LTerm::make_small_unsigned(2));
+    assert_eq!(mb.get_current(), Some(LTerm::make_small_unsigned(1)));
+
+    // Walk to the end, a new message is seen at the save pointer
+    mb.step_over();
+    mb.step_over();
+    assert_eq!(mb.get_current(), None);
+    mb.put(msg(4));
+    assert_eq!(mb.get_current(), Some(LTerm::make_small_unsigned(4)));
+    assert_eq!(mb.remove_current(&mut hp), LTerm::make_small_unsigned(4));
+    assert_eq!(mb.len, 2);
+    assert_eq!(mb.remove_current(&mut hp), LTerm::make_small_unsigned(1));
+    assert_eq!(mb.remove_current(&mut hp), LTerm::make_small_unsigned(3));
+    assert_eq!(mb.get_current(), None);
+    assert_eq!(mb.len, 0);
+  }
+
+  #[test]
+  fn test_recv_mark() {
+    let mut hp = Heap::new(16);
+    let mut mb = ProcessMailbox::new();
+    let label = LTerm::make_cp(&hp as *const Heap);
+    mb.put(msg(1));
+    mb.set_mark(label);
+    mb.put(msg(2));
+
+    // Wrong label is ignored
+    mb.set_save_to_mark(LTerm::nil());
+    assert_eq!(mb.get_current(), Some(LTerm::make_small_unsigned(1)));
+
+    mb.set_save_to_mark(label);
+    assert_eq!(mb.get_current(), Some(LTerm::make_small_unsigned(2)));
+    assert_eq!(mb.remove_current(&mut hp), LTerm::make_small_unsigned(2));
+    assert_eq!(mb.get_current(), Some(LTerm::make_small_unsigned(1)));
+  }
+
+  #[test]
+  fn test_remove_matching() {
+    let mut hp = Heap::new(16);
+    let mut mb = ProcessMailbox::new();
+    for n in 1..=5 {
+      mb.put(msg(n));
+    }
+    let is_odd = |t: LTerm| t.get_small_unsigned() % 2 == 1;
+    assert_eq!(mb.remove_matching(is_odd), 3);
+    assert_eq!(mb.len, 2);
+    assert_eq!(
+      mb.get_messages(),
+      vec![LTerm::make_small_unsigned(2), LTerm::make_small_unsigned(4)]
+    );
+    // The queue stays linked, new messages go after the last one
+    mb.put(msg(6));
+    assert_eq!(mb.remove_current(&mut hp), LTerm::make_small_unsigned(2));
+    assert_eq!(mb.remove_current(&mut hp), LTerm::make_small_unsigned(4));
+    assert_eq!(mb.remove_current(&mut hp), LTerm::make_small_unsigned(6));
+    assert_eq!(mb.get_current(), None);
   }
 }
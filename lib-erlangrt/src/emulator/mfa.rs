@@ -8,8 +8,8 @@ use core::fmt;
 pub enum Args {
   // list of args
   AsList(LTerm),
-  /* pointer to args with size
-   * AsSlice(*const LTerm, usize), */
+  // pointer to args with size
+  AsSlice(*const LTerm, usize),
 }
 
 /// Reference to an M:F(Args) function, ready to be called with arguments.
@@ -39,6 +39,7 @@ impl MFASomething {
           return len;
         }
       }
+      Args::AsSlice(_, len) => return len,
     }
     panic!("Can't find length for {:?}", self.args)
   }
@@ -52,6 +53,10 @@ impl MFASomething {
         // ignore return value of for_each but do not ignore a possible error
         cons::for_each(lst, func)?;
       }
+      Args::AsSlice(p, len) => {
+        let args = unsafe { core::slice::from_raw_parts(p, len) };
+        args.iter().cloned().try_for_each(func)?;
+      }
     }
     Ok(())
   }
@@ -12,6 +12,11 @@ pub mod mailbox;
 pub mod mfa;
 pub mod module;
 pub mod process;
+pub mod process_dict;
+pub mod registry;
+pub mod run_queue;
 pub mod runtime_ctx;
 pub mod scheduler;
+pub mod signal;
+pub mod timer_wheel;
 pub mod vm;
@@ -47,6 +47,10 @@ pub struct Module {
   pub lit_heap: Heap, // set by module loader
 }
 
+// Module code and literals are immutable once loaded, and they are accessed
+// by all schedulers.
+unsafe impl Send for Module {}
+
 impl Module {
   /// Create an empty module wrapped in atomic refcounted refcell.
   pub fn new(name: &VersionedModuleName) -> Module {
@@ -2,18 +2,32 @@
 //! heap, stack, registers, and message queue.
 
 use crate::{
-  defs::exc_type::ExceptionType,
+  bif::BifFn,
+  defs::{exc_type::ExceptionType, Reductions, Word},
   emulator::{
+    code::CodePtr,
     code_srv::CodeServer,
-    heap::{copy_term, Heap, DEFAULT_PROC_HEAP},
-    mailbox::ProcessMailbox,
+    gen_atoms,
+    heap::{copy_term, next_heap_size, Heap, DEFAULT_PROC_HEAP},
+    mailbox::{Message, MessageQueueData, ProcessMailbox},
     mfa::{MFASomething, MFArity},
-    runtime_ctx,
-    scheduler::{self, Scheduler},
+    process_dict::ProcessDict,
+    run_queue::{QueueItem, QueueLink, Runnable},
+    runtime_ctx::{self, call_bif::BifTrap},
+    scheduler,
+    signal::{make_down_message, make_exit_message, Signal},
+    timer_wheel::TimerRef,
   },
-  fail::RtResult,
+  fail::{Error, RtResult},
   term::lterm::*,
 };
+use std::{
+  collections::{HashMap, HashSet, VecDeque},
+  sync::{
+    atomic::{AtomicPtr, AtomicUsize, Ordering},
+    Arc, Condvar, Mutex,
+  },
+};
 
 //#[allow(dead_code)]
 //#[derive(Debug, Eq, PartialEq, Copy, Clone)]
@@ -36,8 +50,175 @@ use crate::{
 //  }
 //}
 
+/// Part of a process which is shared with other threads: the signal queue
+/// where other processes put their messages and other signals, and the index
+/// of the scheduler which currently owns the process. Stored in the VM process
+/// table.
+pub struct ProcessHandle {
+  pub pid: LTerm,
+  /// Signals sent by other processes, the process handles them when it is
+  /// scheduled and when it runs out of messages to examine. `None` after the
+  /// process has exited, then no more signals are accepted.
+  incoming: Mutex<Option<Vec<Signal>>>,
+  scheduler: AtomicUsize,
+  /// The process itself, null after it has terminated. Other threads may only
+  /// look into it while holding the lock (see `lock`).
+  process: AtomicPtr<Process>,
+  /// Set while the owning scheduler runs or changes the process, or while
+  /// another process reads it (`process_info`).
+  locked: Mutex<bool>,
+  unlocked_cvar: Condvar,
+}
+
+impl ProcessHandle {
+  pub fn new(pid: LTerm) -> Self {
+    Self {
+      pid,
+      incoming: Mutex::new(Some(Vec::new())),
+      scheduler: AtomicUsize::new(0),
+      process: AtomicPtr::new(core::ptr::null_mut()),
+      locked: Mutex::new(false),
+      unlocked_cvar: Condvar::new(),
+    }
+  }
+
+  /// Block until no one else is using the process and take it. This is not a
+  /// guard object because the scheduler takes and releases the lock of a
+  /// process separately. See `VM::with_other_process` for the lock order.
+  pub fn lock(&self) {
+    let mut locked = self.locked.lock().unwrap();
+    while *locked {
+      locked = self.unlocked_cvar.wait(locked).unwrap();
+    }
+    *locked = true;
+  }
+
+  /// Take the process if no one else is using it. Returns whether it was
+  /// taken.
+  pub fn try_lock(&self) -> bool {
+    let mut locked = self.locked.lock().unwrap();
+    if *locked {
+      return false;
+    }
+    *locked = true;
+    true
+  }
+
+  pub fn unlock(&self) {
+    let mut locked = self.locked.lock().unwrap();
+    debug_assert!(*locked, "unlocking a process which is not locked");
+    *locked = false;
+    self.unlocked_cvar.notify_one();
+  }
+
+  /// Whether the process is being run (or read by someone) right now.
+  pub fn is_locked(&self) -> bool {
+    *self.locked.lock().unwrap()
+  }
+
+  /// Set by the scheduler which owns the process, the boxed process does not
+  /// move until it is dropped. Set to null when the process terminates.
+  pub fn set_process(&self, p: *mut Process) {
+    self.process.store(p, Ordering::SeqCst)
+  }
+
+  /// The process, or null if it has terminated. Only valid while the handle
+  /// is locked.
+  pub fn get_process(&self) -> *mut Process {
+    self.process.load(Ordering::SeqCst)
+  }
+
+  /// Index of the scheduler which owns the process.
+  #[inline]
+  pub fn get_scheduler(&self) -> usize {
+    self.scheduler.load(Ordering::SeqCst)
+  }
+
+  #[inline]
+  pub fn set_scheduler(&self, index: usize) {
+    self.scheduler.store(index, Ordering::SeqCst)
+  }
+
+  /// Put a signal to the incoming queue. The owning scheduler must be
+  /// notified afterwards, as the process might be waiting. Returns `false`
+  /// if the process has already exited.
+  pub fn push_signal(&self, signal: Signal) -> bool {
+    match self.incoming.lock().unwrap().as_mut() {
+      Some(incoming) => {
+        incoming.push(signal);
+        true
+      }
+      None => false,
+    }
+  }
+
+  pub fn has_incoming_signals(&self) -> bool {
+    match self.incoming.lock().unwrap().as_ref() {
+      Some(incoming) => !incoming.is_empty(),
+      None => false,
+    }
+  }
+
+  fn take_incoming_signals(&self) -> Vec<Signal> {
+    match self.incoming.lock().unwrap().as_mut() {
+      Some(incoming) => core::mem::take(incoming),
+      None => Vec::new(),
+    }
+  }
+
+  /// Terms of the messages which were sent but not yet handled by the
+  /// process. They stay valid while the process is locked, as only the
+  /// process itself takes the signals away.
+  pub fn get_incoming_messages(&self) -> Vec<LTerm> {
+    match self.incoming.lock().unwrap().as_ref() {
+      Some(incoming) => incoming
+        .iter()
+        .filter_map(|signal| match signal {
+          Signal::Message(m) => Some(m.get_term()),
+          _ => None,
+        })
+        .collect(),
+      None => Vec::new(),
+    }
+  }
+
+  /// Stop accepting signals because the process is exiting, return the
+  /// signals which were not handled.
+  pub fn close(&self) -> Vec<Signal> {
+    self.incoming.lock().unwrap().take().unwrap_or_default()
+  }
+}
+
+/// Options given to `spawn_opt`, the other spawn BIFs use some of them.
+pub struct SpawnOptions {
+  pub prio: scheduler::Prio,
+  /// Heap of the new process is at least this big, in words
+  pub min_heap_size: usize,
+  /// Overrides the default for the heap of the new process
+  pub fullsweep_after: Option<usize>,
+  /// Link the new process to the parent
+  pub link: bool,
+  /// Id of the reference for the parent's monitor on the new process
+  pub monitor: Option<Word>,
+}
+
+impl Default for SpawnOptions {
+  fn default() -> Self {
+    Self {
+      prio: scheduler::Prio::Normal,
+      min_heap_size: DEFAULT_PROC_HEAP,
+      fullsweep_after: None,
+      link: false,
+      monitor: None,
+    }
+  }
+}
+
 pub struct Process {
   pub pid: LTerm,
+  pub handle: Arc<ProcessHandle>,
+  /// The process which has spawned this one, or `[]` for the first process
+  pub parent: LTerm,
 
   // Scheduling and fail state
   /// Scheduling priority (selects the runqueue when this process is scheduled)
@@ -45,7 +226,13 @@ pub struct Process {
 
   /// Current scheduler queue where this process is registered
   pub current_queue: scheduler::Queue,
-  pub owned_by_scheduler: *mut Scheduler,
+  /// Neighbours in the run queue, while the process is queued
+  queue_link: QueueLink<Process>,
+  /// Timer started by `wait_timeout` for the current `receive`.
+  pub receive_timer: Option<TimerRef>,
+  /// Set when the receive timer has fired, `wait_timeout` will then continue
+  /// to the `timeout` instruction (same as ERTS flag `F_TIMO`).
+  pub timed_out: bool,
 
   // Execution Context, etc.
   /// Runtime context with registers, instruction pointer etc
@@ -54,6 +241,14 @@ pub struct Process {
   // Memory
   pub heap: Heap,
   pub mailbox: ProcessMailbox,
+  /// Whether message heap fragments are merged into the heap on GC, even
+  /// before the messages are received.
+  pub message_queue_data: MessageQueueData,
+  /// Process dictionary, its keys and values are on the heap
+  pub dictionary: ProcessDict,
+  /// The process is killed if its memory grows above this many words after
+  /// a garbage collection, 0 means no limit.
+  pub max_heap_size: usize,
 
   // Error handling
   /// Record result of last scheduled timeslice for this process
@@ -64,48 +259,129 @@ pub struct Process {
   pub error: Option<(ExceptionType, LTerm)>,
   /// How many catch frames are there on stack
   pub num_catches: isize,
+  /// Reductions used since the process was spawned
+  pub reductions: usize,
+  /// A BIF which has used up the time slice continues from here, before the
+  /// next opcode
+  pub bif_trap: Option<BifTrap>,
+  /// How many of the last global function calls are remembered in
+  /// `last_calls` (`process_flag(save_calls, N)`), 0 = none.
+  pub save_calls: usize,
+  pub last_calls: VecDeque<MFArity>,
+
+  // Links
+  /// Processes linked to this one, they receive exit signals when this
+  /// process terminates and vice versa.
+  pub links: HashSet<LTerm>,
+  /// Exit signals are converted to `{'EXIT', From, Reason}` messages instead
+  /// of terminating the process (`process_flag(trap_exit, true)`).
+  pub trap_exit: bool,
+
+  // Monitors, by the id of the monitor reference
+  /// Processes monitored by this one
+  pub monitors: HashMap<Word, LTerm>,
+  /// Processes which monitor this one, they receive `'DOWN'` messages when
+  /// this process terminates
+  pub monitored_by: HashMap<Word, LTerm>,
+}
+
+// The process is owned and run by one scheduler thread at a time, it can
+// migrate to another scheduler while it is not running.
+unsafe impl Send for Process {}
+
+impl QueueItem for Process {
+  fn queue_link(&mut self) -> &mut QueueLink<Self> {
+    &mut self.queue_link
+  }
+}
+
+impl Runnable for Process {
+  fn get_pid(&self) -> LTerm {
+    self.pid
+  }
+
+  fn get_prio(&self) -> scheduler::Prio {
+    self.prio
+  }
+
+  /// The receive timer belongs to the scheduler which has started it.
+  fn can_migrate(&self) -> bool {
+    self.receive_timer.is_none()
+  }
 }
 
 impl Process {
   // Call this only from VM, the new process must be immediately registered
-  // in proc registry for this VM
+  // in proc registry for this VM.
   pub fn new(
     pid: LTerm,
-    _parent_pid: LTerm,
+    parent: LTerm,
     mfarity: &MFArity,
-    prio: scheduler::Prio,
+    opts: &SpawnOptions,
     code_server: &mut CodeServer,
   ) -> RtResult<Process> {
+    // Process must start with some code location
+    let ip = code_server.lookup_beam_code_and_load(mfarity)?;
+    Ok(Self::with_code(pid, parent, ip, opts))
+  }
+
+  /// Create a process which starts at `ip`. Links and monitors requested in
+  /// `opts` are set up here, so that they exist before the parent can do
+  /// anything else.
+  pub fn with_code(
+    pid: LTerm,
+    parent: LTerm,
+    ip: CodePtr,
+    opts: &SpawnOptions,
+  ) -> Process {
     assert!(pid.is_local_pid());
-    assert!(_parent_pid.is_local_pid() || _parent_pid == LTerm::nil());
+    assert!(parent.is_local_pid() || parent == LTerm::nil());
 
-    // Process must start with some code location
-    match code_server.lookup_beam_code_and_load(mfarity) {
-      Ok(ip) => {
-        let p = Process {
-          pid,
-
-          // Scheduling
-          prio,
-          current_queue: scheduler::Queue::None,
-          timeslice_result: scheduler::SliceResult::None,
-          owned_by_scheduler: core::ptr::null_mut(),
-
-          // Memory
-          heap: Heap::new(DEFAULT_PROC_HEAP),
-          mailbox: ProcessMailbox::new(),
-
-          // Execution
-          context: runtime_ctx::Context::new(ip),
-
-          error: None,
-          num_catches: 0,
-        };
-        Ok(p)
-        // Ok(sync::Arc::new(sync::RwLock::new(p)))
-      }
-      Err(e) => Err(e),
+    let mut p = Process {
+      pid,
+      handle: Arc::new(ProcessHandle::new(pid)),
+      parent,
+
+      // Scheduling
+      prio: opts.prio,
+      current_queue: scheduler::Queue::None,
+      queue_link: QueueLink::default(),
+      timeslice_result: scheduler::SliceResult::None,
+      receive_timer: None,
+      timed_out: false,
+
+      // Memory
+      heap: Heap::new(next_heap_size(opts.min_heap_size)),
+      mailbox: ProcessMailbox::new(),
+      message_queue_data: MessageQueueData::OnHeap,
+      dictionary: ProcessDict::new(),
+      max_heap_size: 0,
+
+      // Execution
+      context: runtime_ctx::Context::new(ip),
+
+      error: None,
+      num_catches: 0,
+      reductions: 0,
+      bif_trap: None,
+      save_calls: 0,
+      last_calls: VecDeque::new(),
+
+      links: HashSet::new(),
+      trap_exit: false,
+      monitors: HashMap::new(),
+      monitored_by: HashMap::new(),
+    };
+    if let Some(n) = opts.fullsweep_after {
+      p.heap.fullsweep_after = n;
+    }
+    if opts.link {
+      p.links.insert(parent);
     }
+    if let Some(ref_id) = opts.monitor {
+      p.monitored_by.insert(ref_id, parent);
+    }
+    p
   }
 
   /// Copy args from mfargs-MFA-something into new process heap and set the
@@ -113,6 +389,10 @@ impl Process {
   pub fn set_spawn_args(&mut self, mfargs: &MFASomething) -> RtResult<()> {
     let mut xindex = 0;
     mfargs.for_each_arg(|arg| -> RtResult<()> {
+      let (arg, fragment) = copy_term::copy_to_fragment(arg)?;
+      if let Some(f) = fragment {
+        self.heap.attach_fragment(f);
+      }
       self.context.set_x(xindex, arg);
       xindex += 1;
       Ok(())
@@ -125,6 +405,40 @@ impl Process {
     self.error.is_some()
   }
 
+  /// Add the reductions used by the last time slice to the total count.
+  pub fn count_reductions(&mut self) {
+    let used = Reductions::DEFAULT - self.context.reductions;
+    self.reductions += used.max(0) as usize;
+  }
+
+  /// Save the state of a long running BIF, it continues with `resume` when the
+  /// process runs again. Return the result of this to the caller of the BIF.
+  pub fn trap(&mut self, resume: BifFn, args: &[LTerm]) -> RtResult<LTerm> {
+    self.bif_trap = Some(BifTrap::new(resume, args));
+    Err(Error::BifTrap)
+  }
+
+  /// Remember a global function call, if `save_calls` is enabled.
+  #[inline]
+  pub fn save_call(&mut self, mfa: MFArity) {
+    if self.save_calls == 0 {
+      return;
+    }
+    if self.last_calls.len() >= self.save_calls {
+      self.last_calls.pop_front();
+    }
+    self.last_calls.push_back(mfa);
+  }
+
+  /// Change how many calls are remembered, the oldest ones which do not fit
+  /// are forgotten.
+  pub fn set_save_calls(&mut self, n: usize) {
+    self.save_calls = n;
+    while self.last_calls.len() > n {
+      self.last_calls.pop_front();
+    }
+  }
+
   #[inline]
   pub fn clear_exception(&mut self) {
     self.error = None;
@@ -155,19 +469,175 @@ impl Process {
   //    self.error = ProcessError::None;
   //  }
 
-  /// Copy a message and put into process mailbox.
-  pub fn deliver_message(&mut self, message: LTerm) -> RtResult<()> {
-    let m1 = copy_term::copy_to(message, &mut self.heap)?;
-    self.mailbox.put(m1);
+  /// Handle signals which other processes have sent meanwhile: messages go to
+  /// the mailbox, links and monitors are updated, exit signals either become
+  /// messages or terminate the process.
+  /// Returns: the exit reason if the process must terminate, messages which
+  /// came after the fatal exit signal are dropped.
+  pub fn handle_signals(&mut self) -> RtResult<Option<LTerm>> {
+    let mut exit_reason = None;
+    for signal in self.handle.take_incoming_signals() {
+      match signal {
+        Signal::Message(message) => {
+          if exit_reason.is_none() {
+            self.mailbox.put(message)
+          }
+        }
+        Signal::Link(from) => {
+          self.links.insert(from);
+        }
+        Signal::Unlink(from) => {
+          self.links.remove(&from);
+        }
+        Signal::Exit {
+          from,
+          reason,
+          linked,
+        } => {
+          if exit_reason.is_none() {
+            exit_reason = self.handle_exit_signal(from, reason, linked)?;
+          }
+        }
+        Signal::Monitor { from, ref_id } => {
+          self.monitored_by.insert(ref_id, from);
+        }
+        Signal::Demonitor { from, ref_id } => {
+          if self.monitored_by.get(&ref_id) == Some(&from) {
+            self.monitored_by.remove(&ref_id);
+          }
+        }
+        Signal::Down {
+          from,
+          ref_id,
+          reason,
+        } => {
+          // Ignore if the monitor was removed after the signal was sent
+          if self.monitors.remove(&ref_id).is_some() && exit_reason.is_none() {
+            let msg = make_down_message(from, ref_id, reason.get_term())?;
+            self.mailbox.put(msg);
+          }
+        }
+      }
+    }
+    Ok(exit_reason)
+  }
 
-    // Notify our current scheduler that a new message has come to possibly wake
-    // up from infinite or timed wait.
-    unsafe {
-      (*self.owned_by_scheduler).notify_new_incoming_message(self);
+  /// Decide what an exit signal does to this process, returns the exit reason
+  /// if the process must terminate.
+  fn handle_exit_signal(
+    &mut self,
+    from: LTerm,
+    reason: Message,
+    linked: bool,
+  ) -> RtResult<Option<LTerm>> {
+    // The link might have been removed by `unlink` after the signal was sent
+    if linked && !self.links.remove(&from) {
+      return Ok(None);
+    }
+    let reason_term = reason.get_term();
+    if reason_term == gen_atoms::KILL && !linked {
+      // Sent by exit(Pid, kill), cannot be trapped
+      return Ok(Some(gen_atoms::KILLED));
+    }
+    if self.trap_exit {
+      self.mailbox.put(make_exit_message(from, reason_term)?);
+      return Ok(None);
+    }
+    if reason_term == gen_atoms::NORMAL && from != self.pid {
+      return Ok(None);
     }
+    Ok(Some(reason.attach_to(&mut self.heap)))
+  }
+
+  /// Run the garbage collector on process heap, use `live` X registers, the
+  /// stack, the mailbox, the process dictionary and the state of a trapped BIF
+  /// as the root set. Ensures that at least `need` words are available on heap
+  /// afterwards. Heap fragments of the received messages (and with
+  /// `message_queue_data=on_heap` of all messages) are merged. The process is
+  /// charged reductions for the live data.
+  pub fn garbage_collect(
+    &mut self,
+    ctx: &mut runtime_ctx::Context,
+    need: usize,
+    live: usize,
+  ) -> RtResult<()> {
+    let regs = ctx.registers_slice_mut(0, live);
+    self.collect_with_roots(regs, |hp, roots| hp.garbage_collect(need, roots))?;
+    let live = self.heap.get_heap_used_words();
+    ctx.consume_reductions(Reductions::for_units(
+      live,
+      Reductions::GC_WORDS_PER_REDUCTION,
+    ));
+    self.check_max_heap_size(ctx);
     Ok(())
   }
 
+  /// Gather the root set of the process besides the registers `regs`, and
+  /// let `collect` run a garbage collection on the heap with it.
+  fn collect_with_roots<F>(&mut self, regs: &mut [LTerm], collect: F) -> RtResult<()>
+  where
+    F: FnOnce(&mut Heap, &mut [&mut [LTerm]]) -> RtResult<()>,
+  {
+    let error_reason: &mut [LTerm] = match self.error {
+      Some((_, ref mut reason)) => core::slice::from_mut(reason),
+      None => &mut [],
+    };
+    if self.message_queue_data == MessageQueueData::OnHeap {
+      self.mailbox.move_fragments_to(&mut self.heap);
+    }
+    // Messages in the main heap must be updated, even with off_heap setting,
+    // as the setting might have changed since they were merged
+    let mut messages = self.mailbox.get_message_terms();
+    let dictionary = self.dictionary.get_roots_mut();
+    let trap_state: &mut [LTerm] = match self.bif_trap {
+      Some(ref mut trap) => &mut trap.args,
+      None => &mut [],
+    };
+    let mut roots: [&mut [LTerm]; 5] =
+      [regs, &mut messages, error_reason, dictionary, trap_state];
+    let result = collect(&mut self.heap, &mut roots);
+    self.mailbox.set_message_terms(&messages);
+    result
+  }
+
+  /// Prepare the process to sleep until a message arrives
+  /// (`erlang:hibernate/3`). The stack is discarded and the heap is shrunk to
+  /// the live data. When woken up, the process continues at `code` with
+  /// `args` in the registers, returning from there ends the process.
+  pub fn hibernate(&mut self, code: CodePtr, args: &[LTerm]) {
+    let ctx = unsafe { &mut (*self.get_context_p()) };
+    for (i, arg) in args.iter().enumerate() {
+      ctx.set_x(i, *arg);
+    }
+    ctx.live = args.len();
+    ctx.ip = code;
+    ctx.clear_cp();
+    self.num_catches = 0;
+    self.mailbox.reset_save();
+    let regs = ctx.registers_slice_mut(0, args.len());
+    // Moving the data into a smaller heap cannot fail
+    self
+      .collect_with_roots(regs, |hp, roots| {
+        hp.hibernate(roots);
+        Ok(())
+      })
+      .unwrap();
+  }
+
+  /// If the process has grown above `max_heap_size`, it sends itself an exit
+  /// signal `kill` (as ERTS does) and ends its time slice, so that it is
+  /// terminated before it runs again.
+  fn check_max_heap_size(&mut self, ctx: &mut runtime_ctx::Context) {
+    let size = self.heap.get_total_size();
+    if self.max_heap_size == 0 || size <= self.max_heap_size {
+      return;
+    }
+    // The reason is an atom, copying it cannot fail
+    let signal = Signal::exit(self.pid, gen_atoms::KILL, false).unwrap();
+    self.handle.push_signal(signal);
+    ctx.reductions = 0;
+  }
+
   /// Ugly hack to mut-borrow the context without making borrow checker sad.
   /// We guarantee that this borrow will not outlive the process, or we will pay
   /// the price debugging the SIGSEGV.
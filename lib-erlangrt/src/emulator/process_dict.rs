@@ -0,0 +1,186 @@
+//! Process dictionary, a per-process key/value store (`put/2`, `get/1` etc).
+//! Keys and values are terms on the process heap, the dictionary is a part
+//! of the GC root set.
+use crate::{
+  fail::RtResult,
+  term::{compare::cmp_terms, hash::hash_term, lterm::*},
+};
+use core::cmp::Ordering;
+use std::collections::HashMap;
+
+pub struct ProcessDict {
+  /// Keys and values interleaved: `[k0, v0, k1, v1, ...]`, so that they can
+  /// be given to the GC as one slice of roots.
+  data: Vec<LTerm>,
+  /// Key hash for each pair in `data`. Hashes depend on the key contents
+  /// only, so they stay valid when the GC moves the keys.
+  hashes: Vec<u32>,
+  /// Pair indices in `data` grouped by their key hash.
+  buckets: HashMap<u32, Vec<usize>>,
+}
+
+#[inline]
+fn eq_exact(a: LTerm, b: LTerm) -> RtResult<bool> {
+  if a == b {
+    return Ok(true);
+  }
+  Ok(cmp_terms(a, b, true)? == Ordering::Equal)
+}
+
+impl ProcessDict {
+  pub fn new() -> Self {
+    Self {
+      data: Vec::new(),
+      hashes: Vec::new(),
+      buckets: HashMap::new(),
+    }
+  }
+
+  /// Find the pair index of the key, also return the key hash.
+  fn find(&self, key: LTerm) -> RtResult<(u32, Option<usize>)> {
+    let hash = hash_term(key);
+    if let Some(bucket) = self.buckets.get(&hash) {
+      for pair_i in bucket {
+        if eq_exact(self.data[pair_i * 2], key)? {
+          return Ok((hash, Some(*pair_i)));
+        }
+      }
+    }
+    Ok((hash, None))
+  }
+
+  pub fn get(&self, key: LTerm) -> RtResult<Option<LTerm>> {
+    Ok(self.find(key)?.1.map(|pair_i| self.data[pair_i * 2 + 1]))
+  }
+
+  /// Store a value, returns the previous value for the key.
+  pub fn put(&mut self, key: LTerm, value: LTerm) -> RtResult<Option<LTerm>> {
+    match self.find(key)? {
+      (_, Some(pair_i)) => {
+        let old = self.data[pair_i * 2 + 1];
+        self.data[pair_i * 2 + 1] = value;
+        Ok(Some(old))
+      }
+      (hash, None) => {
+        self.buckets.entry(hash).or_default().push(self.hashes.len());
+        self.hashes.push(hash);
+        self.data.push(key);
+        self.data.push(value);
+        Ok(None)
+      }
+    }
+  }
+
+  /// Remove a key, returns its value.
+  pub fn erase(&mut self, key: LTerm) -> RtResult<Option<LTerm>> {
+    let (hash, pair_i) = match self.find(key)? {
+      (hash, Some(pair_i)) => (hash, pair_i),
+      (_, None) => return Ok(None),
+    };
+    let old = self.data[pair_i * 2 + 1];
+    self.unlink(hash, pair_i);
+    // Order does not matter, move the last pair into the hole
+    let last_i = self.hashes.len() - 1;
+    if pair_i != last_i {
+      let last_hash = self.hashes[last_i];
+      self.unlink(last_hash, last_i);
+      self.buckets.entry(last_hash).or_default().push(pair_i);
+    }
+    self.data.swap_remove(pair_i * 2 + 1);
+    self.data.swap_remove(pair_i * 2);
+    self.hashes.swap_remove(pair_i);
+    Ok(Some(old))
+  }
+
+  /// Remove the pair index from its hash bucket.
+  fn unlink(&mut self, hash: u32, pair_i: usize) {
+    let bucket = self.buckets.get_mut(&hash).unwrap();
+    bucket.retain(|i| *i != pair_i);
+    if bucket.is_empty() {
+      self.buckets.remove(&hash);
+    }
+  }
+
+  /// Remove everything and return the removed key/value pairs.
+  pub fn erase_all(&mut self) -> Vec<(LTerm, LTerm)> {
+    let pairs = self.get_pairs();
+    self.data.clear();
+    self.hashes.clear();
+    self.buckets.clear();
+    pairs
+  }
+
+  pub fn get_pairs(&self) -> Vec<(LTerm, LTerm)> {
+    self.data.chunks(2).map(|kv| (kv[0], kv[1])).collect()
+  }
+
+  /// Count of keys in the dictionary.
+  pub fn size(&self) -> usize {
+    self.hashes.len()
+  }
+
+  pub fn get_keys(&self) -> Vec<LTerm> {
+    self.data.iter().step_by(2).cloned().collect()
+  }
+
+  /// Keys which have the value `value`.
+  pub fn get_keys_with_value(&self, value: LTerm) -> RtResult<Vec<LTerm>> {
+    let mut result = Vec::new();
+    for kv in self.data.chunks(2) {
+      if eq_exact(kv[1], value)? {
+        result.push(kv[0]);
+      }
+    }
+    Ok(result)
+  }
+
+  /// Keys and values for the GC to update.
+  pub fn get_roots_mut(&mut self) -> &mut [LTerm] {
+    &mut self.data
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::emulator::gen_atoms;
+
+  #[test]
+  fn test_process_dict() {
+    let small = |n| LTerm::make_small_unsigned(n);
+    let mut pd = ProcessDict::new();
+    assert_eq!(pd.put(gen_atoms::OK, small(1)).unwrap(), None);
+    assert_eq!(pd.put(small(2), small(1)).unwrap(), None);
+    assert_eq!(pd.put(gen_atoms::ERROR, small(3)).unwrap(), None);
+    assert_eq!(pd.put(gen_atoms::OK, small(4)).unwrap(), Some(small(1)));
+    assert_eq!(pd.get_keys().len(), 3);
+    assert_eq!(pd.get(gen_atoms::OK).unwrap(), Some(small(4)));
+    assert_eq!(pd.get(gen_atoms::UNDEFINED).unwrap(), None);
+    assert_eq!(pd.get_keys_with_value(small(1)).unwrap(), vec![small(2)]);
+
+    assert_eq!(pd.erase(gen_atoms::OK).unwrap(), Some(small(4)));
+    assert_eq!(pd.erase(gen_atoms::OK).unwrap(), None);
+    assert_eq!(pd.get(gen_atoms::ERROR).unwrap(), Some(small(3)));
+    assert_eq!(pd.get_roots_mut().len(), 4);
+    assert_eq!(pd.erase_all().len(), 2);
+    assert!(pd.get_pairs().is_empty());
+  }
+
+  #[test]
+  fn test_process_dict_many_keys() {
+    let small = |n| LTerm::make_small_unsigned(n);
+    let mut pd = ProcessDict::new();
+    for i in 0..1000 {
+      assert_eq!(pd.put(small(i), small(i * 2)).unwrap(), None);
+    }
+    // Erase from the front, so that the last pair moves into each hole
+    for i in 0..500 {
+      assert_eq!(pd.erase(small(i)).unwrap(), Some(small(i * 2)));
+    }
+    assert_eq!(pd.size(), 500);
+    for i in 0..1000 {
+      let expected = if i < 500 { None } else { Some(small(i * 2)) };
+      assert_eq!(pd.get(small(i)).unwrap(), expected);
+    }
+  }
+}
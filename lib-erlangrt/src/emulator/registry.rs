@@ -0,0 +1,88 @@
+//! Local process registry: maps atom names to pids. A process can have at
+//! most one name, the name is removed when the process exits.
+use crate::term::lterm::*;
+use std::collections::HashMap;
+
+pub struct Registry {
+  names: HashMap<LTerm, LTerm>,
+  /// Reverse mapping, to find the name of a process
+  by_pid: HashMap<LTerm, LTerm>,
+}
+
+impl Registry {
+  pub fn new() -> Self {
+    Self {
+      names: HashMap::new(),
+      by_pid: HashMap::new(),
+    }
+  }
+
+  /// Register `name` for `pid`. Returns `false` if the name is taken or the
+  /// process already has a name.
+  pub fn register(&mut self, name: LTerm, pid: LTerm) -> bool {
+    debug_assert!(name.is_atom());
+    if self.names.contains_key(&name) || self.by_pid.contains_key(&pid) {
+      return false;
+    }
+    self.names.insert(name, pid);
+    self.by_pid.insert(pid, name);
+    true
+  }
+
+  /// Remove a name. Returns `false` if it was not registered.
+  pub fn unregister(&mut self, name: LTerm) -> bool {
+    match self.names.remove(&name) {
+      Some(pid) => {
+        self.by_pid.remove(&pid);
+        true
+      }
+      None => false,
+    }
+  }
+
+  /// Remove the name of a process, if it has one.
+  pub fn unregister_pid(&mut self, pid: LTerm) {
+    if let Some(name) = self.by_pid.remove(&pid) {
+      self.names.remove(&name);
+    }
+  }
+
+  pub fn whereis(&self, name: LTerm) -> Option<LTerm> {
+    self.names.get(&name).cloned()
+  }
+
+  /// Registered name of a process, if it has one.
+  pub fn get_name(&self, pid: LTerm) -> Option<LTerm> {
+    self.by_pid.get(&pid).cloned()
+  }
+
+  /// All registered names.
+  pub fn get_names(&self) -> Vec<LTerm> {
+    self.names.keys().cloned().collect()
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::emulator::gen_atoms;
+
+  #[test]
+  fn test_registry() {
+    let mut reg = Registry::new();
+    let (pid1, pid2) = (LTerm::make_local_pid(1), LTerm::make_local_pid(2));
+    assert!(reg.register(gen_atoms::INIT, pid1));
+    // Name is taken, or the process already has a name
+    assert!(!reg.register(gen_atoms::INIT, pid2));
+    assert!(!reg.register(gen_atoms::ERLANG, pid1));
+    assert!(reg.register(gen_atoms::ERLANG, pid2));
+    assert_eq!(reg.whereis(gen_atoms::INIT), Some(pid1));
+    assert_eq!(reg.get_name(pid2), Some(gen_atoms::ERLANG));
+
+    reg.unregister_pid(pid1);
+    assert_eq!(reg.whereis(gen_atoms::INIT), None);
+    assert!(reg.unregister(gen_atoms::ERLANG));
+    assert!(!reg.unregister(gen_atoms::ERLANG));
+    assert!(reg.get_names().is_empty());
+  }
+}
@@ -0,0 +1,370 @@
+//! Run queues of a scheduler, one per priority.
+//!
+//! Like in ERTS, the queues are intrusive double linked lists: every process
+//! carries its own `prev`/`next` link, so queuing and removing a process from
+//! the middle of a queue (when it is stolen) do not allocate or move memory.
+//!
+//! Priorities `max` and `high` always run before anything else. `normal` and
+//! `low` share the rest of the time: while both have processes, a `low`
+//! process runs once after every `NORMAL_ADVANTAGE` `normal` ones.
+use crate::{defs::Word, emulator::scheduler::Prio, term::lterm::*};
+use core::ptr;
+
+/// How many Normal processes can be scheduled before Low gets to run.
+const NORMAL_ADVANTAGE: Word = 8;
+
+/// Links to the neighbours of an item in an `IntrusiveQueue`, null if the item
+/// is first or last (or is not queued).
+pub struct QueueLink<T> {
+  prev: *mut T,
+  next: *mut T,
+}
+
+impl<T> Default for QueueLink<T> {
+  fn default() -> Self {
+    Self {
+      prev: ptr::null_mut(),
+      next: ptr::null_mut(),
+    }
+  }
+}
+
+/// Something which can be stored in an `IntrusiveQueue`.
+pub trait QueueItem: Sized {
+  fn queue_link(&mut self) -> &mut QueueLink<Self>;
+}
+
+/// Something which can be scheduled from a `RunQueue`.
+pub trait Runnable: QueueItem {
+  fn get_pid(&self) -> LTerm;
+  fn get_prio(&self) -> Prio;
+  /// Whether another scheduler may take the item
+  fn can_migrate(&self) -> bool;
+}
+
+/// A double linked list through the items. Queued items are owned by the
+/// queue, they are given back as boxes when removed.
+pub struct IntrusiveQueue<T: QueueItem> {
+  head: *mut T,
+  tail: *mut T,
+  len: usize,
+}
+
+impl<T: QueueItem> IntrusiveQueue<T> {
+  pub fn new() -> Self {
+    Self {
+      head: ptr::null_mut(),
+      tail: ptr::null_mut(),
+      len: 0,
+    }
+  }
+
+  #[inline]
+  pub fn len(&self) -> usize {
+    self.len
+  }
+
+  #[inline]
+  pub fn is_empty(&self) -> bool {
+    self.len == 0
+  }
+
+  pub fn push_back(&mut self, item: Box<T>) {
+    let p = Box::into_raw(item);
+    unsafe {
+      *(*p).queue_link() = QueueLink {
+        prev: self.tail,
+        next: ptr::null_mut(),
+      };
+      if self.tail.is_null() {
+        self.head = p;
+      } else {
+        (*self.tail).queue_link().next = p;
+      }
+    }
+    self.tail = p;
+    self.len += 1;
+  }
+
+  pub fn pop_front(&mut self) -> Option<Box<T>> {
+    if self.head.is_null() {
+      return None;
+    }
+    Some(unsafe { self.unlink(self.head) })
+  }
+
+  /// Remove an item from anywhere in the queue. The item must be in this
+  /// queue.
+  unsafe fn unlink(&mut self, p: *mut T) -> Box<T> {
+    let QueueLink { prev, next } = core::mem::take((*p).queue_link());
+    if prev.is_null() {
+      self.head = next;
+    } else {
+      (*prev).queue_link().next = next;
+    }
+    if next.is_null() {
+      self.tail = prev;
+    } else {
+      (*next).queue_link().prev = prev;
+    }
+    self.len -= 1;
+    Box::from_raw(p)
+  }
+
+  /// Find the last item for which `pred` is true and remove it.
+  pub fn remove_last_matching<F: Fn(&T) -> bool>(&mut self, pred: F) -> Option<Box<T>> {
+    let mut p = self.tail;
+    while !p.is_null() {
+      unsafe {
+        if pred(&*p) {
+          return Some(self.unlink(p));
+        }
+        p = (*p).queue_link().prev;
+      }
+    }
+    None
+  }
+
+  pub fn find_mut<F: Fn(&T) -> bool>(&mut self, pred: F) -> Option<&mut T> {
+    let mut p = self.head;
+    while !p.is_null() {
+      unsafe {
+        if pred(&*p) {
+          return Some(&mut *p);
+        }
+        p = (*p).queue_link().next;
+      }
+    }
+    None
+  }
+}
+
+impl<T: QueueItem> Drop for IntrusiveQueue<T> {
+  fn drop(&mut self) {
+    while self.pop_front().is_some() {}
+  }
+}
+
+/// Runnable processes of one scheduler, by priority. Protected by a mutex in
+/// `SchedulerShared` because other schedulers may steal from it.
+pub struct RunQueue<T: Runnable> {
+  /// Indexed by `Prio`
+  queues: [IntrusiveQueue<T>; 4],
+  /// How many Normal processes were picked since the last Low one, while
+  /// both had processes to run
+  advantage_count: Word,
+}
+
+// The queued processes belong to the queue, which is only accessed under a
+// mutex.
+unsafe impl<T: Runnable> Send for RunQueue<T> {}
+
+impl<T: Runnable> RunQueue<T> {
+  pub fn new() -> Self {
+    Self {
+      queues: [
+        IntrusiveQueue::new(),
+        IntrusiveQueue::new(),
+        IntrusiveQueue::new(),
+        IntrusiveQueue::new(),
+      ],
+      advantage_count: 0,
+    }
+  }
+
+  #[inline]
+  fn queue(&mut self, prio: Prio) -> &mut IntrusiveQueue<T> {
+    &mut self.queues[prio as usize]
+  }
+
+  pub fn push(&mut self, item: Box<T>) {
+    let prio = item.get_prio();
+    self.queue(prio).push_back(item)
+  }
+
+  pub fn len(&self) -> usize {
+    self.queues.iter().map(|q| q.len()).sum()
+  }
+
+  /// Select the queue to take the next process from.
+  fn select_prio(&mut self) -> Option<Prio> {
+    if !self.queue(Prio::Max).is_empty() {
+      return Some(Prio::Max);
+    }
+    if !self.queue(Prio::High).is_empty() {
+      return Some(Prio::High);
+    }
+    let have_normal = !self.queue(Prio::Normal).is_empty();
+    let have_low = !self.queue(Prio::Low).is_empty();
+    match (have_normal, have_low) {
+      (true, true) => {
+        if self.advantage_count < NORMAL_ADVANTAGE {
+          self.advantage_count += 1;
+          Some(Prio::Normal)
+        } else {
+          self.advantage_count = 0;
+          Some(Prio::Low)
+        }
+      }
+      (true, false) => Some(Prio::Normal),
+      (false, true) => Some(Prio::Low),
+      (false, false) => None,
+    }
+  }
+
+  /// Take the next process to run, see the module doc for the order.
+  pub fn pick(&mut self) -> Option<Box<T>> {
+    let prio = self.select_prio()?;
+    self.queue(prio).pop_front()
+  }
+
+  /// Take a process for another scheduler, starting from the end of the
+  /// queues. Processes which cannot migrate (for example those with a
+  /// running receive timer which belongs to this scheduler) are skipped.
+  pub fn steal(&mut self) -> Option<Box<T>> {
+    for prio in [Prio::Max, Prio::High, Prio::Normal, Prio::Low] {
+      let stolen = self.queue(prio).remove_last_matching(|p| p.can_migrate());
+      if stolen.is_some() {
+        return stolen;
+      }
+    }
+    None
+  }
+
+  pub fn find_mut(&mut self, pid: LTerm) -> Option<&mut T> {
+    self
+      .queues
+      .iter_mut()
+      .rev()
+      .find_map(|q| q.find_mut(|p| p.get_pid() == pid))
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  struct TestProc {
+    pid: LTerm,
+    prio: Prio,
+    pinned: bool,
+    link: QueueLink<TestProc>,
+  }
+
+  impl QueueItem for TestProc {
+    fn queue_link(&mut self) -> &mut QueueLink<Self> {
+      &mut self.link
+    }
+  }
+
+  impl Runnable for TestProc {
+    fn get_pid(&self) -> LTerm {
+      self.pid
+    }
+
+    fn get_prio(&self) -> Prio {
+      self.prio
+    }
+
+    fn can_migrate(&self) -> bool {
+      !self.pinned
+    }
+  }
+
+  fn test_proc(n: usize, prio: Prio) -> Box<TestProc> {
+    Box::new(TestProc {
+      pid: LTerm::make_local_pid(n),
+      prio,
+      pinned: false,
+      link: QueueLink::default(),
+    })
+  }
+
+  /// Pick a process and put it back, as the scheduler does when the process
+  /// has used its time slice. Returns the priority of the picked process.
+  fn run_one(rq: &mut RunQueue<TestProc>) -> Prio {
+    let p = rq.pick().unwrap();
+    let prio = p.prio;
+    rq.push(p);
+    prio
+  }
+
+  #[test]
+  fn test_intrusive_queue() {
+    let mut q = IntrusiveQueue::new();
+    for n in 0..5 {
+      q.push_back(test_proc(n, Prio::Normal));
+    }
+    let pid = |n| LTerm::make_local_pid(n);
+    // Remove from the middle, the end and the front
+    let odd = q.remove_last_matching(|p| p.pid == pid(1) || p.pid == pid(3));
+    assert_eq!(odd.unwrap().pid, pid(3));
+    assert_eq!(q.remove_last_matching(|_| true).unwrap().pid, pid(4));
+    assert_eq!(q.pop_front().unwrap().pid, pid(0));
+    assert!(q.find_mut(|p| p.pid == pid(2)).is_some());
+    assert_eq!(q.len(), 2);
+    q.push_back(test_proc(5, Prio::Normal));
+    let order: Vec<LTerm> = (0..3).map(|_| q.pop_front().unwrap().pid).collect();
+    assert_eq!(order, vec![pid(1), pid(2), pid(5)]);
+    assert!(q.is_empty() && q.pop_front().is_none());
+  }
+
+  #[test]
+  fn test_normal_low_ratio() {
+    let mut rq = RunQueue::new();
+    rq.push(test_proc(1, Prio::Normal));
+    rq.push(test_proc(2, Prio::Normal));
+    rq.push(test_proc(3, Prio::Low));
+    let rounds = 10 * (NORMAL_ADVANTAGE + 1);
+    let low = (0..rounds).filter(|_| run_one(&mut rq) == Prio::Low).count();
+    assert_eq!(low, 10);
+
+    // Low runs every time if there is nothing else, and this does not give
+    // normal processes any advantage later
+    let mut rq = RunQueue::new();
+    rq.push(test_proc(1, Prio::Low));
+    for _ in 0..20 {
+      assert_eq!(run_one(&mut rq), Prio::Low);
+    }
+    rq.push(test_proc(2, Prio::Normal));
+    let first: Vec<Prio> = (0..NORMAL_ADVANTAGE + 1).map(|_| run_one(&mut rq)).collect();
+    assert_eq!(first.iter().filter(|p| **p == Prio::Low).count(), 1);
+    assert_eq!(*first.last().unwrap(), Prio::Low);
+  }
+
+  #[test]
+  fn test_high_preempts_normal() {
+    let mut rq = RunQueue::new();
+    rq.push(test_proc(1, Prio::Normal));
+    rq.push(test_proc(2, Prio::Low));
+    rq.push(test_proc(3, Prio::High));
+    for _ in 0..100 {
+      assert_eq!(run_one(&mut rq), Prio::High);
+    }
+    // Max goes before high
+    rq.push(test_proc(4, Prio::Max));
+    assert_eq!(run_one(&mut rq), Prio::Max);
+    let max = rq.pick().unwrap();
+    assert_eq!(max.pid, LTerm::make_local_pid(4));
+    // Normal runs when high is gone
+    let high = rq.pick().unwrap();
+    assert_eq!(high.prio, Prio::High);
+    assert_eq!(run_one(&mut rq), Prio::Normal);
+    assert_eq!(rq.len(), 2);
+  }
+
+  #[test]
+  fn test_steal() {
+    let mut rq = RunQueue::new();
+    let mut pinned = test_proc(1, Prio::High);
+    pinned.pinned = true;
+    rq.push(pinned);
+    rq.push(test_proc(2, Prio::Normal));
+    rq.push(test_proc(3, Prio::Normal));
+    // The last normal process is taken, the pinned one stays
+    assert_eq!(rq.steal().unwrap().pid, LTerm::make_local_pid(3));
+    assert_eq!(rq.steal().unwrap().pid, LTerm::make_local_pid(2));
+    assert!(rq.steal().is_none());
+    assert!(rq.find_mut(LTerm::make_local_pid(1)).is_some());
+  }
+}
@@ -27,6 +27,30 @@ pub enum CallBifTarget {
   BifFnPointer(BifFn),
 }
 
+/// State of a long running BIF which has used up the reductions of the process
+/// (like `BIF_TRAP` in ERTS). The BIF stores it with `Process::bif_trap` and
+/// returns `Error::BifTrap`, the process yields and when it runs again,
+/// `resume` is called with `args` before the next opcode is fetched. The
+/// result goes where the result of the original call would go.
+pub struct BifTrap {
+  pub resume: BifFn,
+  /// Saved state of the BIF, these are GC roots
+  pub args: Vec<LTerm>,
+  dst: LTerm,
+  fail_label: LTerm,
+}
+
+impl BifTrap {
+  pub fn new(resume: BifFn, args: &[LTerm]) -> Self {
+    Self {
+      resume,
+      args: args.to_vec(),
+      dst: LTerm::nil(),
+      fail_label: LTerm::nil(),
+    }
+  }
+}
+
 /// Generic bif0,1,2 application. Bif0 cannot have a fail label but bif1 and
 /// bif2 can, so on exception a jump will be performed.
 ///
@@ -73,9 +97,32 @@ pub fn find_and_call_bif(
     }
   };
 
-  // Now having called the function let's see if there was some good result or
-  // an error occured
+  if let Ok(val) = &bif_result {
+    println!("call_bif a={} gc={} call result {}", args.len(), gc, val);
+  }
+  handle_bif_result(ctx, curr_p, bif_result, fail_label, dst)
+}
+
+/// Continue a BIF which has trapped during the previous time slice.
+pub fn resume_trapped_bif(
+  vm: &mut VM,
+  ctx: &mut Context,
+  curr_p: &mut Process,
+  trap: BifTrap,
+) -> RtResult<DispatchResult> {
+  let bif_result = (trap.resume)(vm, curr_p, &trap.args);
+  handle_bif_result(ctx, curr_p, bif_result, trap.fail_label, trap.dst)
+}
 
+/// Having called the function let's see if there was some good result or an
+/// error occured.
+fn handle_bif_result(
+  ctx: &mut Context,
+  curr_p: &mut Process,
+  bif_result: RtResult<LTerm>,
+  fail_label: LTerm,
+  dst: LTerm,
+) -> RtResult<DispatchResult> {
   // On error and if fail label is a CP, perform a goto
   // Assume that error is already written to `reason` in process
   match bif_result {
@@ -86,12 +133,16 @@ pub fn find_and_call_bif(
       // Set exception via dispatchresult; pass through the error
       Err(bif_result.unwrap_err())
     }
+    Err(Error::BifTrap) => {
+      // The result will be stored when the BIF is done
+      set_trap_destination(curr_p, dst, fail_label);
+      Err(Error::BifTrap)
+    }
     Err(_) => {
       // pass through the error
       Err(bif_result.unwrap_err())
     }
     Ok(val) => {
-      println!("call_bif a={} gc={} call result {}", args.len(), gc, val);
       // if dst is not NIL, store the result in it
       if dst != LTerm::nil() {
         ctx.store_value(val, dst, &mut curr_p.heap)?;
@@ -101,6 +152,13 @@ pub fn find_and_call_bif(
   }
 }
 
+/// Remember where the result of a trapped BIF goes.
+pub fn set_trap_destination(curr_p: &mut Process, dst: LTerm, fail_label: LTerm) {
+  let trap = curr_p.bif_trap.as_mut().expect("BIF trapped without a state");
+  trap.dst = dst;
+  trap.fail_label = fail_label;
+}
+
 //#[inline]
 // fn callbif_handle_fail(e: &fail::Error) -> Hopefully<DispatchResult> {
 //  panic!("{}bif call failed with {:?}", module(), e)
@@ -41,6 +41,7 @@ pub fn apply(
     return fail::create::badarity();
   }
 
+  curr_p.save_call(mfa);
   if bif::is_bif(&mfa) {
     return call_bif::find_and_call_bif(
       vm,
@@ -53,8 +54,8 @@ pub fn apply(
       false,
     );
   } else {
-    let code_server = vm.get_code_server_p();
-    match unsafe { (*code_server).lookup_beam_code_and_load(&mfa) } {
+    let lookup_result = vm.code_server().lookup_beam_code_and_load(&mfa);
+    match lookup_result {
       Ok(ip) => {
         if save_cp {
           ctx.cp = ctx.ip
@@ -9,7 +9,7 @@ use crate::{
     process::Process,
     vm::VM,
   },
-  fail::RtResult,
+  fail::{Error, RtResult},
   term::lterm::{
     LTerm, SpecialTag, SPECIALTAG_REGFP, SPECIALTAG_REGX, SPECIALTAG_REGY,
     TERMTAG_SPECIAL,
@@ -79,6 +79,23 @@ impl Context {
     self.regs[index] = val;
   }
 
+  /// Read a float register, `reg` is a special term created by
+  /// `LTerm::make_regfp`.
+  #[inline]
+  pub fn get_fp(&self, reg: LTerm) -> f64 {
+    debug_assert!(reg.is_regfp());
+    self.fpregs[reg.get_special_value()]
+  }
+
+  #[inline]
+  pub fn set_fp(&mut self, reg: LTerm, val: f64) {
+    debug_assert!(reg.is_regfp());
+    if cfg!(feature = "trace_register_changes") {
+      println!("{}{} = {}", "set fp".blue(), reg.get_special_value(), val);
+    }
+    self.fpregs[reg.get_special_value()] = val;
+  }
+
   #[inline]
   pub fn swap_in(&mut self) {
     // This amount is RESET every time process is about to be scheduled in, i.e.
@@ -86,6 +103,13 @@ impl Context {
     self.reductions = Reductions::DEFAULT;
   }
 
+  /// Charge the process for work done besides the opcode dispatch (BIFs,
+  /// garbage collection, copying). The time slice ends when it goes below 0.
+  #[inline]
+  pub fn consume_reductions(&mut self, n: isize) {
+    self.reductions -= n;
+  }
+
   #[inline]
   pub fn fetch_opcode(&mut self) -> opcode::RawOpcode {
     self.reductions -= Reductions::FETCH_OPCODE_COST;
@@ -168,7 +192,8 @@ impl Context {
           let y_result = hp.get_y(y_index);
           return y_result.unwrap();
         }
-        SPECIALTAG_REGFP => panic!("todo fpreg load"),
+        // Float registers hold raw f64, opcodes use `get_fp` to read them
+        SPECIALTAG_REGFP => panic!("{}Can't load float register {}", module(), src),
         _ => return src,
       }
     }
@@ -224,7 +249,7 @@ impl Context {
           let y = dst.get_special_value();
           return hp.set_y(y, val);
         }
-        SPECIALTAG_REGFP => panic!("todo fpreg store"),
+        SPECIALTAG_REGFP => panic!("{}Can't store {} to a float register", module(), val),
         SpecialTag(st) => panic!("store: specialtag {} not supported", st),
       }
     }
@@ -275,8 +300,14 @@ impl Context {
         self.ip = code_p.clone();
       }
       MFALookupResult::FoundBif(bif_fn) => {
-        let x0 = call_bif::call_bif_fn(vm, self, curr_p, *bif_fn, args)?;
-        self.set_x(0, x0);
+        match call_bif::call_bif_fn(vm, self, curr_p, *bif_fn, args) {
+          Ok(x0) => self.set_x(0, x0),
+          Err(Error::BifTrap) => {
+            call_bif::set_trap_destination(curr_p, LTerm::make_regx(0), LTerm::nil());
+            return Err(Error::BifTrap);
+          }
+          Err(e) => return Err(e),
+        }
       }
     }
     Ok(())
@@ -1,25 +1,68 @@
 //! Code related to task scheduling and priorities.
+//!
+//! Every scheduler thread owns its processes. Runnable processes sit in the
+//! scheduler run queue which is shared, so that an idle scheduler can steal
+//! work from the others. Waiting processes stay in the scheduler which put
+//! them to sleep, other threads wake them up by pid via `SchedulerShared`.
+//! The scheduler holds the process lock (`ProcessHandle::lock`) while the
+//! process runs or while its state is changed, so that other processes can
+//! safely look into it.
 use crate::{
   defs::{exc_type::ExceptionType, Word},
-  emulator::{gen_atoms, process::Process},
+  emulator::{
+    gen_atoms, process::Process, run_queue::RunQueue, signal::Signal,
+    timer_wheel::TimerWheel, vm::VMShared,
+  },
   term::lterm::*,
 };
 use colored::Colorize;
-use std::collections::{HashMap, VecDeque};
+use std::{
+  collections::HashMap,
+  sync::{
+    atomic::{AtomicUsize, Ordering},
+    Arc, Condvar, Mutex,
+  },
+  time::Duration,
+};
 
 fn module() -> &'static str {
   "scheduler: "
 }
 
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
 #[allow(dead_code)]
 pub enum Prio {
-  /// Runs when no more jobs to take or at 8x disadvantage to normal
+  /// Runs when no more jobs to take or at 8x disadvantage to normal (see
+  /// `run_queue`)
   Low = 0,
   /// Most of user processes run at this priority
   Normal = 1,
   /// Takes priority always over everything else
   High = 2,
+  /// Reserved for the runtime system processes, runs before `High`
+  Max = 3,
+}
+
+impl Prio {
+  pub fn to_atom(self) -> LTerm {
+    match self {
+      Prio::Low => gen_atoms::LOW,
+      Prio::Normal => gen_atoms::NORMAL,
+      Prio::High => gen_atoms::HIGH,
+      Prio::Max => gen_atoms::MAX,
+    }
+  }
+
+  /// Parse the priority level given to `process_flag` or `spawn_opt`.
+  pub fn from_atom(a: LTerm) -> Option<Prio> {
+    match a {
+      gen_atoms::LOW => Some(Prio::Low),
+      gen_atoms::NORMAL => Some(Prio::Normal),
+      gen_atoms::HIGH => Some(Prio::High),
+      gen_atoms::MAX => Some(Prio::Max),
+      _ => None,
+    }
+  }
 }
 
 /// Enum identifies current registration of the process
@@ -28,6 +71,7 @@ pub enum Prio {
 pub enum Queue {
   None,
   // PendingTimers,
+  Max,
   High,
   Normal,
   Low,
@@ -48,35 +92,150 @@ pub enum SliceResult {
   /// Error, exit or throw occured during the last timeslice, error is stored
   /// in the process, field `error`
   Exception,
+  /// Process has received an exit signal during the last timeslice, the exit
+  /// reason is stored in the process, field `error`
+  Exited,
 }
 
-/// How many Normal processes can be scheduled before Low gets to run.
-const NORMAL_ADVANTAGE: Word = 8;
+struct WakeupState {
+  /// A notification has arrived and was not yet consumed by `wait`
+  pending: bool,
+  /// The scheduler sleeps without a timeout and is counted in
+  /// `VMShared::idle_schedulers`
+  idle: bool,
+  /// Pids of waiting processes which have received messages
+  wake_pids: Vec<LTerm>,
+}
 
-/// Maintains run queues for different priorities and allows queuing processes,
-/// suspending processes, work balancing (TODO), etc.
-pub struct Scheduler {
-  // This is the naive implementation of run queues.
-  // A better approach would be to build an intrusive double linked list through
-  // every process in the queue (as done by the original ERTS).
-  queue_low: VecDeque<LTerm>,
-  queue_normal: VecDeque<LTerm>,
-  queue_high: VecDeque<LTerm>,
-  timed_wait: HashMap<LTerm, ()>,
-  infinite_wait: HashMap<LTerm, ()>,
-
-  /// A counter used to skip some schedulings for low processes
-  advantage_count: Word,
+/// Allows an idle scheduler to sleep until a new message arrives or until the
+/// nearest timer deadline.
+struct Wakeup {
+  state: Mutex<WakeupState>,
+  cvar: Condvar,
+}
+
+impl Wakeup {
+  fn new() -> Self {
+    Self {
+      state: Mutex::new(WakeupState {
+        pending: false,
+        idle: false,
+        wake_pids: Vec::new(),
+      }),
+      cvar: Condvar::new(),
+    }
+  }
+
+  /// Wake up the scheduler. If it was idle, it is removed from the idle count
+  /// right away, so that the count does not reach the number of schedulers
+  /// while there is work to do.
+  fn notify(&self, idle_count: &AtomicUsize) {
+    let mut state = self.state.lock().unwrap();
+    Self::notify_locked(&mut state, idle_count);
+    self.cvar.notify_one();
+  }
+
+  fn notify_locked(state: &mut WakeupState, idle_count: &AtomicUsize) {
+    state.pending = true;
+    if state.idle {
+      state.idle = false;
+      idle_count.fetch_sub(1, Ordering::SeqCst);
+    }
+  }
+
+  /// Block until notified or until the timeout has passed. A notification
+  /// which came before the call is not lost.
+  fn wait(&self, timeout: Option<Duration>) {
+    let mut state = self.state.lock().unwrap();
+    while !state.pending {
+      match timeout {
+        None => state = self.cvar.wait(state).unwrap(),
+        Some(t) => {
+          let (guard, result) = self.cvar.wait_timeout(state, t).unwrap();
+          state = guard;
+          if result.timed_out() {
+            break;
+          }
+        }
+      }
+    }
+    state.pending = false;
+  }
+
+  /// Sleep without a timeout, being counted as idle. Returns `false` without
+  /// sleeping if this was the last scheduler to become idle: then no one is
+  /// left to wake up anybody.
+  fn wait_idle(&self, idle_count: &AtomicUsize, total: usize) -> bool {
+    let mut state = self.state.lock().unwrap();
+    if !state.pending {
+      state.idle = true;
+      if idle_count.fetch_add(1, Ordering::SeqCst) + 1 == total {
+        return false;
+      }
+      while !state.pending {
+        state = self.cvar.wait(state).unwrap();
+      }
+    }
+    state.pending = false;
+    true
+  }
+}
 
+/// The part of a scheduler which is visible to other scheduler threads.
+pub struct SchedulerShared {
+  run_queue: Mutex<RunQueue<Process>>,
+  wakeup: Wakeup,
+}
+
+impl SchedulerShared {
+  pub fn new() -> Self {
+    Self {
+      run_queue: Mutex::new(RunQueue::new()),
+      wakeup: Wakeup::new(),
+    }
+  }
+
+  /// Ask the scheduler to move the process `pid` to the run queue, if it is
+  /// waiting there.
+  pub fn wake_process(&self, pid: LTerm, idle_count: &AtomicUsize) {
+    let mut state = self.wakeup.state.lock().unwrap();
+    state.wake_pids.push(pid);
+    Wakeup::notify_locked(&mut state, idle_count);
+    self.wakeup.cvar.notify_one();
+  }
+
+  pub fn notify(&self, idle_count: &AtomicUsize) {
+    self.wakeup.notify(idle_count)
+  }
+
+  /// Wake up the scheduler only if it is idle. Returns whether it was.
+  pub fn notify_if_idle(&self, idle_count: &AtomicUsize) -> bool {
+    let mut state = self.wakeup.state.lock().unwrap();
+    if !state.idle {
+      return false;
+    }
+    Wakeup::notify_locked(&mut state, idle_count);
+    self.wakeup.cvar.notify_one();
+    true
+  }
+
+  fn take_wake_pids(&self) -> Vec<LTerm> {
+    core::mem::take(&mut self.wakeup.state.lock().unwrap().wake_pids)
+  }
+}
+
+/// Runs processes in one thread. Processes are owned by the scheduler: the
+/// current one, the runnable ones in the run queue and the waiting ones.
+pub struct Scheduler {
+  /// Index in `VMShared::schedulers`
+  index: usize,
+  vm: Arc<VMShared>,
+  /// Processes waiting for a message or a timer
+  waiting: HashMap<LTerm, Box<Process>>,
+  /// Receive timers for the processes which belong to this scheduler
+  timers: TimerWheel,
   /// Currently selected process
-  current: Option<LTerm>,
-
-  //  /// Wait set for infinitely suspended processes (in endless receive)
-  //  wait_inf: HashSet<LTerm>,
-  //  /// Wait set for timed suspended processes (waiting for a timer)
-  //  wait_timed: HashSet<LTerm>,
-  /// Dict of pids to process boxes. Owned by the scheduler
-  processes: HashMap<LTerm, Process>,
+  current: Option<Box<Process>>,
 }
 
 /// Hint from the logic finalizing timeslice result from a running process.
@@ -89,75 +248,77 @@ enum ScheduleHint {
 }
 
 impl Scheduler {
-  pub fn new() -> Self {
+  pub fn new(index: usize, vm: Arc<VMShared>) -> Self {
     Self {
-      queue_low: VecDeque::new(),
-      queue_normal: VecDeque::new(),
-      queue_high: VecDeque::new(),
-      timed_wait: HashMap::new(),
-      infinite_wait: HashMap::new(),
-
-      advantage_count: 0,
+      index,
+      vm,
+      waiting: HashMap::new(),
+      timers: TimerWheel::new(),
       current: None,
-
-      processes: HashMap::new(),
     }
   }
 
-  pub fn get_process_count(&self) -> usize {
-    self.processes.len()
-  }
-
-  /// Register a process `proc_` in the process table and also queue it for
-  /// execution. This is invoked by vm when a new process is spawned.
-  pub fn register_new_process(&mut self, pid: LTerm, mut proc: Process) {
-    proc.owned_by_scheduler = self as *mut Scheduler;
-    self.processes.insert(pid, proc);
-    self.enqueue(pid);
+  #[inline]
+  fn shared(&self) -> &SchedulerShared {
+    &self.vm.schedulers[self.index]
   }
 
-  /// Queue a process by its pid.
-  pub fn enqueue(&mut self, pid: LTerm) {
-    self.enqueue_opt(pid, false);
+  /// Take ownership of a new process and queue it for execution. This is
+  /// invoked by vm when a new process is spawned.
+  pub fn register_new_process(&mut self, proc: Process) {
+    let mut proc = Box::new(proc);
+    proc.handle.set_scheduler(self.index);
+    let p: *mut Process = proc.as_mut();
+    proc.handle.set_process(p);
+    self.enqueue(proc);
   }
 
-  /// Queue a process by its pid.
-  /// Will `panic!` if the process doesn't exist or is already queued.
-  /// Arg: `skip_queue_check` allows skipping the current queue assertion for
-  ///   when you can guarantee that the process is not queued anywhere.
-  pub fn enqueue_opt(&mut self, pid: LTerm, skip_queue_check: bool) {
-    assert!(pid.is_local_pid());
-
-    let prio = {
-      // Lookup the pid
-      let p = self.lookup_pid(pid).unwrap();
-      if !skip_queue_check {
-        assert_eq!(
-          p.current_queue,
-          Queue::None,
-          "Process must not be in any queue when queuing, now in {:?}",
-          p.current_queue
-        );
-      }
-      p.prio
+  /// Queue a process to run. If there is more work than this scheduler can
+  /// do right now, wake up an idle scheduler to steal some.
+  /// Will `panic!` if the process is marked as waiting.
+  pub fn enqueue(&mut self, proc: Box<Process>) {
+    assert_eq!(
+      proc.current_queue,
+      Queue::None,
+      "Process must not be in any queue when queuing, now in {:?}",
+      proc.current_queue
+    );
+    let len = {
+      let mut rq = self.shared().run_queue.lock().unwrap();
+      rq.push(proc);
+      rq.len()
     };
-
-    match prio {
-      Prio::Normal => self.queue_normal.push_back(pid),
-      Prio::Low => self.queue_low.push_back(pid),
-      Prio::High => self.queue_high.push_back(pid),
+    if len > 1 && self.vm.idle_schedulers.load(Ordering::SeqCst) > 0 {
+      self.vm.wake_idle_scheduler(self.index);
     }
   }
 
-  /// Queue a process by its pid into either timed_wait or infinite_wait queue.
+  /// Put a process into the waiting set, unless a message has arrived since
+  /// it has last looked into its mailbox.
   #[inline]
-  pub fn enqueue_wait(&mut self, infinite: bool, pid: LTerm) {
-    assert!(pid.is_local_pid());
-
-    if infinite {
-      self.infinite_wait.insert(pid, ());
+  pub fn enqueue_wait(&mut self, mut proc: Box<Process>) {
+    if proc.handle.has_incoming_signals() {
+      return self.enqueue(proc);
+    }
+    proc.current_queue = if proc.receive_timer.is_none() {
+      Queue::InfiniteWait
     } else {
-      self.timed_wait.insert(pid, ());
+      Queue::TimedWait
+    };
+    self.waiting.insert(proc.pid, proc);
+  }
+
+  /// Start the receive timer for the process, when it fires the process will
+  /// be woken up with `timed_out` flag set.
+  pub fn start_receive_timer(&mut self, proc: &mut Process, timeout_ms: u64) {
+    debug_assert!(proc.receive_timer.is_none());
+    proc.receive_timer = Some(self.timers.add(timeout_ms, proc.pid));
+  }
+
+  /// Cancel the receive timer for the process, if it was running.
+  pub fn cancel_receive_timer(&mut self, proc: &mut Process) {
+    if let Some(tref) = proc.receive_timer.take() {
+      self.timers.cancel(tref);
     }
   }
 
@@ -179,67 +340,105 @@ impl Scheduler {
     }
   }
 
-  /// Get another process from the run queue for this scheduler.
-  /// Returns: `Option(pid)`
-  pub fn next_process(&mut self) -> Option<LTerm> {
-    if let Some(prev_pid) = self.current {
-      let hint = self.next_process_finalize_previous(prev_pid);
+  /// Get another process from the run queue for this scheduler or steal one
+  /// from another scheduler. If there is nothing to run, sleep until a process
+  /// is woken up by a message or a timer.
+  /// Returns: `None` if the VM is stopping, either because there are no more
+  /// processes or all of them are waiting forever (a deadlock).
+  pub fn next_process(&mut self) -> Option<*mut Process> {
+    if self.current.is_some() {
+      let hint = self.next_process_finalize_previous();
       if hint == ScheduleHint::ContinueSameProcess {
         // do not change self.current and just do the same process again
-        return self.current;
+        return self.current_ptr();
       }
     }
 
-    // Do necessities before taking another process
-    self.next_process_duties();
-
     // Now try and find another process to run
     loop {
-      // See if any are waiting in realtime (high) priority queue
-      if let Some(next_pid) = self.next_process_pick_from_the_queues() {
-        self.current = Some(next_pid);
-        break;
+      if self.vm.is_stopping() {
+        return None;
       }
-    }
 
-    Self::log_next_process(self.current);
-    self.current
-  }
-
-  /// Look through the queues and find some queue with highest priority where
-  /// a process is waiting to be selected.
-  /// Advantage counter allows running lower queues even if a higher is running.
-  fn next_process_pick_from_the_queues(&mut self) -> Option<LTerm> {
-    if !self.queue_high.is_empty() {
-      return self.queue_high.pop_front();
-    } else if self.advantage_count < NORMAL_ADVANTAGE {
-      if !self.queue_normal.is_empty() {
-        return self.queue_normal.pop_front();
-      } else if !self.queue_low.is_empty() {
-        return self.queue_low.pop_front();
+      // Do necessities before taking another process
+      self.next_process_duties();
+
+      let picked = self.shared().run_queue.lock().unwrap().pick();
+      if let Some(mut proc) = picked.or_else(|| self.steal_process()) {
+        proc.handle.lock();
+        // Signals which have arrived while the process was not running
+        match proc.handle_signals().unwrap() {
+          None => {
+            self.current = Some(proc);
+            break;
+          }
+          Some(reason) => {
+            self.terminate_process(proc, (ExceptionType::Exit, reason));
+            continue;
+          }
+        }
       }
-      self.advantage_count += 1;
-    } else {
-      if !self.queue_low.is_empty() {
-        return self.queue_low.pop_front();
-      } else if !self.queue_normal.is_empty() {
-        return self.queue_normal.pop_front();
+
+      // TODO: ports and other nodes can wake up processes too
+      match self.timers.next_timeout() {
+        Some(timeout) => self.shared().wakeup.wait(Some(timeout)),
+        None => {
+          let total = self.vm.schedulers.len();
+          if !self.shared().wakeup.wait_idle(&self.vm.idle_schedulers, total) {
+            // Everyone is idle, nothing will ever happen again
+            if self.vm.get_process_count() > 0 {
+              self.report_deadlock();
+            }
+            self.vm.stop();
+            return None;
+          }
+        }
       }
-      self.advantage_count = 0;
-    };
-    return None;
+    }
+
+    Self::log_next_process(self.current.as_ref().map(|p| p.pid));
+    self.current_ptr()
+  }
+
+  #[inline]
+  fn current_ptr(&mut self) -> Option<*mut Process> {
+    self.current.as_mut().map(|p| p.as_mut() as *mut Process)
+  }
+
+  /// Try to take a runnable process from other schedulers, the process then
+  /// belongs to this scheduler.
+  fn steal_process(&mut self) -> Option<Box<Process>> {
+    let n = self.vm.schedulers.len();
+    for i in 1..n {
+      let victim = &self.vm.schedulers[(self.index + i) % n];
+      let stolen = victim.run_queue.lock().unwrap().steal();
+      if let Some(proc) = stolen {
+        proc.handle.set_scheduler(self.index);
+        return Some(proc);
+      }
+    }
+    None
+  }
+
+  /// All processes are waiting for messages which no one will send.
+  fn report_deadlock(&self) {
+    let waiting: Vec<String> =
+      self.vm.get_pids().iter().map(|pid| format!("{}", pid)).collect();
+    println!(
+      "{}{} {} process(es) waiting forever: {}",
+      module(),
+      "Deadlock:".red(),
+      waiting.len(),
+      waiting.join(", ")
+    );
   }
 
   /// When time has come to select next running process, first we take a look
   /// at the previous process, what happened to it.
   #[inline]
-  fn next_process_finalize_previous(&mut self, curr_pid: LTerm) -> ScheduleHint {
-    // Extract the last running process from the process registry
-    let curr_p = self.unsafe_lookup_pid_mut(curr_pid);
-    assert!(!curr_p.is_null());
-
-    // Unspeakable horrors are happening as we speak: (bypassing borrow checker)
-    let curr = unsafe { &mut (*curr_p) };
+  fn next_process_finalize_previous(&mut self) -> ScheduleHint {
+    let mut curr = self.current.take().unwrap();
+    curr.count_reductions();
 
     debug_assert_eq!(
       curr.current_queue,
@@ -250,45 +449,50 @@ impl Scheduler {
 
     match curr.timeslice_result {
       SliceResult::Yield | SliceResult::None => {
-        self.enqueue(curr_pid);
-        self.current = None
+        let handle = curr.handle.clone();
+        self.enqueue(curr);
+        handle.unlock();
       }
 
       SliceResult::Finished => {
         // Scheduler will terminate the process with EXIT:NORMAL
         let err = (ExceptionType::Exit, gen_atoms::NORMAL);
-        self.terminate_process(curr_pid, err)
+        self.terminate_process(curr, err)
       }
 
       SliceResult::Exception => {
-        return self.handle_process_exception(curr_p, curr_pid);
+        if self.handle_process_exception(&mut curr) {
+          self.current = Some(curr);
+          return ScheduleHint::ContinueSameProcess;
+        }
+        let p_error = curr.error.unwrap();
+        self.terminate_process(curr, p_error);
+      }
+
+      SliceResult::Exited => {
+        let p_error = curr.error.unwrap();
+        self.terminate_process(curr, p_error);
       }
 
       SliceResult::Wait => {
-        self.enqueue_wait(true, curr_pid);
+        let handle = curr.handle.clone();
+        self.enqueue_wait(curr);
+        handle.unlock();
       }
     }
     ScheduleHint::TakeAnotherProcess
   }
 
   /// If exception happened, check whether a process is catching anything at
-  /// this moment, otherwise proceed to terminate.
-  fn handle_process_exception(
-    &mut self,
-    proc_p: *mut Process,
-    proc_pid: LTerm,
-  ) -> ScheduleHint {
-    // Bypassing the borrow checker again
-    let proc = unsafe { &mut (*proc_p) };
-
+  /// this moment. Returns `true` if the process continues in a catch handler,
+  /// otherwise it should be terminated.
+  fn handle_process_exception(&mut self, proc: &mut Process) -> bool {
     assert!(proc.is_failed());
     let p_error = proc.error.unwrap();
 
     if proc.num_catches <= 0 {
       // time to terminate, no catches
-      self.terminate_process(proc_pid, p_error);
-      self.current = None;
-      return ScheduleHint::TakeAnotherProcess;
+      return false;
     }
 
     println!("Catching {}:{}", p_error.0, p_error.1);
@@ -305,111 +509,167 @@ impl Scheduler {
         proc.context.jump_ptr(next_catch.loc);
         proc.context.clear_cp();
         proc.heap.drop_stack_words(next_catch.stack_drop);
-
-        // TODO: Clear save mark on recv in process.mailbox
-        return ScheduleHint::ContinueSameProcess;
+        proc.mailbox.reset_save();
+        self.cancel_receive_timer(proc);
+        proc.timed_out = false;
+        true
       }
 
       None => {
         println!("Catch not found, terminating...");
-        self.terminate_process(proc_pid, p_error);
-        self.current = None;
+        false
       }
     }
-    return ScheduleHint::TakeAnotherProcess;
   }
 
   /// Things to do before scheduling another process for execution.
   #[inline]
-  fn next_process_duties(&self) {
-    // TODO: monotonic clock
-    // TODO: wait lists
+  fn next_process_duties(&mut self) {
+    self.process_wake_requests();
+    self.process_expired_timers();
     // TODO: network checks
   }
 
-  /// Borrow a read-only process, if it exists. Return `None` if we are sorry.
-  #[inline]
-  pub fn lookup_pid(&self, pid: LTerm) -> Option<&Process> {
-    assert!(pid.is_local_pid());
-    self.processes.get(&pid)
-  }
-
-  /// Borrow a mutable process, if it exists. Return `None` if we are sorry.
-  #[inline]
-  pub fn lookup_pid_mut(&mut self, pid: LTerm) -> Option<&mut Process> {
-    assert!(pid.is_local_pid());
-    self.processes.get_mut(&pid)
+  /// Move waiting processes which have received messages to the run queue.
+  /// Processes which are not waiting will find the messages on their own.
+  fn process_wake_requests(&mut self) {
+    for pid in self.shared().take_wake_pids() {
+      if let Some(proc) = self.waiting.get(&pid) {
+        if proc.handle.has_incoming_signals() {
+          self.wake_up(pid);
+        }
+      }
+    }
   }
 
-  /// Find a process and instead of borrowing return a pointer to it.
-  #[inline]
-  #[allow(dead_code)]
-  pub fn unsafe_lookup_pid(&self, pid: LTerm) -> *const Process {
-    assert!(pid.is_local_pid());
-    match self.processes.get(&pid) {
-      Some(p) => p as *const Process,
-      None => core::ptr::null(),
+  /// Advance the timer wheel to the current time and wake up processes whose
+  /// receive timers have expired.
+  fn process_expired_timers(&mut self) {
+    if self.timers.is_empty() {
+      return;
+    }
+    for pid in self.timers.advance() {
+      if let Some(p) = self.waiting.get_mut(&pid) {
+        p.handle.lock();
+        p.receive_timer = None;
+        p.timed_out = true;
+        p.handle.unlock();
+        self.wake_up(pid);
+        continue;
+      }
+      // A process which is queued will see the flag in `wait_timeout` on its
+      // own. Processes with a receive timer are never stolen.
+      let mut rq = self.shared().run_queue.lock().unwrap();
+      if let Some(p) = rq.find_mut(pid) {
+        p.handle.lock();
+        p.receive_timer = None;
+        p.timed_out = true;
+        p.handle.unlock();
+      }
     }
   }
 
-  /// Find a process and instead of borrowing return a mutable pointer to it.
-  #[inline]
-  pub fn unsafe_lookup_pid_mut(&mut self, pid: LTerm) -> *mut Process {
-    assert!(pid.is_local_pid());
-    match self.processes.get_mut(&pid) {
-      Some(p) => p as *mut Process,
-      None => core::ptr::null_mut(),
-    }
+  /// Move a waiting process to its run queue.
+  fn wake_up(&mut self, pid: LTerm) {
+    let mut proc = self.waiting.remove(&pid).unwrap();
+    proc.handle.lock();
+    proc.current_queue = Queue::None;
+    proc.handle.unlock();
+    self.enqueue(proc);
   }
 
   /// Assuming that the error was not caught, begin process termination routine.
-  pub fn terminate_process(&mut self, pid: LTerm, e: (ExceptionType, LTerm)) {
-    // assert that process is not in any queue
-    {
-      let p = self.lookup_pid_mut(pid).unwrap();
-      assert_eq!(p.current_queue, Queue::None);
-    }
+  /// The process must be locked, it is unlocked here and no one can find it
+  /// anymore.
+  pub fn terminate_process(&mut self, mut proc: Box<Process>, e: (ExceptionType, LTerm)) {
+    assert_eq!(proc.current_queue, Queue::None);
+    self.cancel_receive_timer(&mut proc);
 
     // root process exits with halt()
     // assert!(p.get_registered_name() != atom::INIT);
 
     // TODO: ets tables
-    // TODO: notify monitors
-    // TODO: cancel known timers who target this process
-    // TODO: notify links
-    // TODO: unregister name if registered
     // TODO: if pending timers - become zombie and sit in pending timers queue
     println!(
       "{}Terminating pid {} error={}:{}",
       module(),
-      pid,
+      proc.pid,
       e.0,
       e.1 //, p.runtime_ctx.regs[0]
     );
 
-    self.timed_wait.remove(&pid);
-    self.infinite_wait.remove(&pid);
-    assert!(!self.queue_normal.contains(&pid));
-    assert!(!self.queue_low.contains(&pid));
-    assert!(!self.queue_high.contains(&pid));
-    self.processes.remove(&pid);
-  }
-
-  /// Called by `Process` when a new message is received. Checks whether the
-  /// process was placed in one of waiting sets and wakes it up.
-  #[inline]
-  pub fn notify_new_incoming_message(&mut self, proc: &mut Process) {
-    // Remove from whatever wait set
-    match proc.current_queue {
-      Queue::InfiniteWait => {
-        self.infinite_wait.remove(&proc.pid);
-        self.enqueue_opt(proc.pid, true);
+    let pid = proc.pid;
+    self.vm.unregister_process(pid);
+    proc.handle.set_process(core::ptr::null_mut());
+    proc.handle.unlock();
+    // Processes which tried to link to or monitor us while we were exiting
+    // get `noproc`
+    for signal in proc.handle.close() {
+      match signal {
+        Signal::Link(from) => self.send_exit_signal(pid, from, gen_atoms::NOPROC),
+        Signal::Monitor { from, ref_id } => {
+          self.send_down_signal(pid, from, ref_id, gen_atoms::NOPROC)
+        }
+        _ => {}
       }
-      Queue::TimedWait => {
-        self.timed_wait.remove(&proc.pid);
-        self.enqueue_opt(proc.pid, true);
-      }
-      _other => {}
     }
+    for linked in proc.links.drain() {
+      self.send_exit_signal(pid, linked, e.1);
+    }
+    for (ref_id, watcher) in proc.monitored_by.drain() {
+      self.send_down_signal(pid, watcher, ref_id, e.1);
+    }
+    for (ref_id, target) in proc.monitors.drain() {
+      self.vm.send_signal(target, Signal::Demonitor { from: pid, ref_id });
+    }
+  }
+
+  /// Tell a process monitoring the terminated process `from` that the
+  /// monitor has fired.
+  fn send_down_signal(&self, from: LTerm, to: LTerm, ref_id: Word, reason: LTerm) {
+    // Same as in `send_exit_signal` this cannot fail
+    let signal = Signal::down(from, ref_id, reason).unwrap();
+    self.vm.send_signal(to, signal);
+  }
+
+  /// Send an exit signal through a link, to a process which was linked to the
+  /// terminated process `from`.
+  fn send_exit_signal(&self, from: LTerm, to: LTerm, reason: LTerm) {
+    // The reason was copied from the heap of a process which exists, and the
+    // copy is sized exactly, so it cannot fail
+    let signal = Signal::exit(from, reason, true).unwrap();
+    self.vm.send_signal(to, signal);
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use std::time::Instant;
+
+  #[test]
+  fn test_wakeup() {
+    let w = Wakeup::new();
+    // Notification before waiting is remembered
+    w.notify(&AtomicUsize::new(0));
+    w.wait(None);
+
+    let started = Instant::now();
+    w.wait(Some(Duration::from_millis(20)));
+    assert!(started.elapsed() >= Duration::from_millis(20));
+  }
+
+  #[test]
+  fn test_wait_idle() {
+    let idle = AtomicUsize::new(0);
+    let w = Wakeup::new();
+    // The only scheduler becoming idle means that nothing will ever happen
+    assert!(!w.wait_idle(&idle, 1));
+    assert_eq!(idle.load(Ordering::SeqCst), 1);
+    // Waking up an idle scheduler takes it out of the idle count
+    w.notify(&idle);
+    assert_eq!(idle.load(Ordering::SeqCst), 0);
+    assert!(w.wait_idle(&idle, 1));
+    assert_eq!(idle.load(Ordering::SeqCst), 0);
   }
 }
@@ -0,0 +1,144 @@
+//! Signals are sent between processes: messages, link and unlink requests,
+//! exit signals and monitor signals. Like in ERTS, signals from one process to
+//! another arrive in the order they were sent, so a message sent before an
+//! exit signal is always seen before the exit.
+use crate::{
+  defs::Word,
+  emulator::{gen_atoms, heap::Heap, mailbox::Message},
+  fail::RtResult,
+  term::{
+    boxed,
+    lterm::{tuple::make_tuple3, *},
+    term_builder::TupleBuilder,
+  },
+};
+
+pub enum Signal {
+  Message(Message),
+  /// The sender has linked to the receiver
+  Link(LTerm),
+  /// The sender has removed its link to the receiver
+  Unlink(LTerm),
+  /// An exit signal. It is `linked` if the sender has terminated and the
+  /// signal travels through a link, otherwise it was sent by `exit/2`.
+  Exit {
+    from: LTerm,
+    reason: Message,
+    linked: bool,
+  },
+  /// The sender monitors the receiver, monitor is identified by the id of
+  /// the reference returned by `monitor/2`
+  Monitor { from: LTerm, ref_id: Word },
+  /// The sender has removed the monitor
+  Demonitor { from: LTerm, ref_id: Word },
+  /// A monitored process has terminated
+  Down {
+    from: LTerm,
+    ref_id: Word,
+    reason: Message,
+  },
+}
+
+impl Signal {
+  /// Create an exit signal, the reason is copied.
+  pub fn exit(from: LTerm, reason: LTerm, linked: bool) -> RtResult<Self> {
+    Ok(Signal::Exit {
+      from,
+      reason: Message::new(reason)?,
+      linked,
+    })
+  }
+
+  /// Create a monitor down signal, the reason is copied.
+  pub fn down(from: LTerm, ref_id: Word, reason: LTerm) -> RtResult<Self> {
+    Ok(Signal::Down {
+      from,
+      ref_id,
+      reason: Message::new(reason)?,
+    })
+  }
+}
+
+/// Create the message `{'DOWN', Ref, process, Pid, Reason}` which is received
+/// by the monitoring process when the monitored process `from` terminates.
+pub fn make_down_message(from: LTerm, ref_id: Word, reason: LTerm) -> RtResult<Message> {
+  let mut hp = Heap::new(6 + boxed::Reference::storage_size().words());
+  let tb = TupleBuilder::with_arity(5, &mut hp)?;
+  let monitor_ref = boxed::Reference::create_into(&mut hp, ref_id)?;
+  unsafe {
+    tb.set_element_base0(0, gen_atoms::DOWN_UPPERCASE);
+    tb.set_element_base0(1, monitor_ref);
+    tb.set_element_base0(2, gen_atoms::PROCESS);
+    tb.set_element_base0(3, from);
+    tb.set_element_base0(4, reason);
+  }
+  Message::new(tb.make_term())
+}
+
+/// Check whether a message is `{'DOWN', Ref, _, _, _}` for the monitor with
+/// the given reference id.
+pub fn is_down_message(msg: LTerm, ref_id: Word) -> bool {
+  if !msg.is_tuple() {
+    return false;
+  }
+  let t = msg.get_tuple_ptr();
+  unsafe {
+    if (*t).get_arity() != 5
+      || boxed::Tuple::get_element_base0(t, 0) != gen_atoms::DOWN_UPPERCASE
+    {
+      return false;
+    }
+    let monitor_ref = boxed::Tuple::get_element_base0(t, 1);
+    monitor_ref.is_local_ref() && boxed::Reference::get_id(monitor_ref) == ref_id
+  }
+}
+
+/// Create the message `{'EXIT', From, Reason}` which is received instead of an
+/// exit signal by a process trapping exits.
+pub fn make_exit_message(from: LTerm, reason: LTerm) -> RtResult<Message> {
+  let mut hp = Heap::new(4);
+  let msg = make_tuple3(gen_atoms::EXIT_UPPERCASE, from, reason, &mut hp)?;
+  Message::new(msg)
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::term::{boxed, lterm::tuple::make_tuple2};
+
+  #[test]
+  fn test_exit_message() {
+    let mut hp = Heap::new(16);
+    let from = LTerm::make_local_pid(1);
+    let reason = make_tuple2(gen_atoms::BADARG, LTerm::nil(), &mut hp).unwrap();
+    let msg = make_exit_message(from, reason).unwrap();
+    // The message does not refer to the original reason
+    drop(hp);
+
+    let mut hp2 = Heap::new(16);
+    let term = msg.attach_to(&mut hp2);
+    unsafe {
+      let t = term.get_tuple_ptr();
+      assert_eq!(boxed::Tuple::get_element_base0(t, 0), gen_atoms::EXIT_UPPERCASE);
+      assert_eq!(boxed::Tuple::get_element_base0(t, 1), from);
+      let r = boxed::Tuple::get_element_base0(t, 2).get_tuple_ptr();
+      assert_eq!(boxed::Tuple::get_element_base0(r, 0), gen_atoms::BADARG);
+    }
+  }
+
+  #[test]
+  fn test_down_message() {
+    let from = LTerm::make_local_pid(2);
+    let msg = make_down_message(from, 42, gen_atoms::NOPROC).unwrap();
+    let mut hp = Heap::new(16);
+    let term = msg.attach_to(&mut hp);
+    assert!(is_down_message(term, 42));
+    assert!(!is_down_message(term, 43));
+    assert!(!is_down_message(gen_atoms::DOWN_UPPERCASE, 42));
+    unsafe {
+      let t = term.get_tuple_ptr();
+      assert_eq!(boxed::Tuple::get_element_base0(t, 3), from);
+      assert_eq!(boxed::Tuple::get_element_base0(t, 4), gen_atoms::NOPROC);
+    }
+  }
+}
@@ -0,0 +1,183 @@
+//! Hierarchical timer wheel, used by the scheduler to wake up processes
+//! waiting in `receive ... after` (same idea as the ERTS timer wheel and the
+//! classic Linux kernel timers).
+//!
+//! Time is measured in milliseconds (ticks) since the wheel was created.
+//! Level 0 has a slot for every tick of the next 64 ticks, each next level
+//! slot covers 64 slots of the previous level. When the level 0 index wraps
+//! around, timers from the current slot of the level above are moved
+//! ("cascaded") down, closer to their expiration.
+use crate::term::lterm::*;
+use std::{
+  collections::HashMap,
+  time::{Duration, Instant},
+};
+
+const SLOT_BITS: usize = 6;
+const SLOTS: usize = 1 << SLOT_BITS;
+const SLOT_MASK: u64 = (SLOTS - 1) as u64;
+const LEVELS: usize = 4;
+
+/// Identifies a timer, to be able to cancel it.
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
+pub struct TimerRef(u64);
+
+struct Timer {
+  expire: u64,
+  pid: LTerm,
+}
+
+pub struct TimerWheel {
+  start: Instant,
+  /// Next tick to be processed, timers expiring before it are overdue.
+  current: u64,
+  /// `LEVELS` arrays of `SLOTS` slots. Cancelled timers are not removed from
+  /// the slots, they are skipped when the slot is processed.
+  slots: Vec<Vec<TimerRef>>,
+  timers: HashMap<TimerRef, Timer>,
+  next_ref: u64,
+}
+
+impl TimerWheel {
+  pub fn new() -> Self {
+    Self {
+      start: Instant::now(),
+      current: 0,
+      slots: (0..LEVELS * SLOTS).map(|_| Vec::new()).collect(),
+      timers: HashMap::new(),
+      next_ref: 0,
+    }
+  }
+
+  /// Milliseconds passed since the wheel was created (monotonic).
+  fn now(&self) -> u64 {
+    self.start.elapsed().as_millis() as u64
+  }
+
+  pub fn is_empty(&self) -> bool {
+    self.timers.is_empty()
+  }
+
+  /// Time left until the nearest timer expires, `None` if there are no timers.
+  pub fn next_timeout(&self) -> Option<Duration> {
+    let nearest = self.timers.values().map(|t| t.expire).min()?;
+    Some(Duration::from_millis(nearest.saturating_sub(self.now())))
+  }
+
+  /// Start a timer which will expire in `timeout_ms` milliseconds.
+  pub fn add(&mut self, timeout_ms: u64, pid: LTerm) -> TimerRef {
+    let expire = self.now() + timeout_ms;
+    self.add_at(expire, pid)
+  }
+
+  /// Start a timer which will expire at the given tick.
+  fn add_at(&mut self, expire: u64, pid: LTerm) -> TimerRef {
+    let tref = TimerRef(self.next_ref);
+    self.next_ref += 1;
+    self.timers.insert(tref, Timer { expire, pid });
+    self.insert(tref, expire);
+    tref
+  }
+
+  pub fn cancel(&mut self, tref: TimerRef) {
+    self.timers.remove(&tref);
+  }
+
+  /// Select a slot for the expiration time, the further the expiration is in
+  /// the future, the higher is the level.
+  fn insert(&mut self, tref: TimerRef, expire: u64) {
+    let delta = expire.saturating_sub(self.current);
+    let mut level = 0;
+    while level < LEVELS - 1 && delta >= 1u64 << (SLOT_BITS * (level + 1)) {
+      level += 1;
+    }
+    let index = if expire < self.current {
+      // Overdue, will be processed on the next tick
+      self.current & SLOT_MASK
+    } else if delta >= 1u64 << (SLOT_BITS * LEVELS) {
+      // Too far, park in the last slot of the top level to be cascaded, it
+      // will be inserted again then
+      (self.current >> (SLOT_BITS * level)).wrapping_sub(1) & SLOT_MASK
+    } else {
+      (expire >> (SLOT_BITS * level)) & SLOT_MASK
+    };
+    self.slots[level * SLOTS + index as usize].push(tref);
+  }
+
+  /// Move timers from the current slot of `level` to the lower levels. If the
+  /// index on this level also wrapped around, cascade the level above first.
+  fn cascade(&mut self, level: usize) {
+    let index = (self.current >> (SLOT_BITS * level)) & SLOT_MASK;
+    if index == 0 && level + 1 < LEVELS {
+      self.cascade(level + 1);
+    }
+    let slot = core::mem::take(&mut self.slots[level * SLOTS + index as usize]);
+    for tref in slot {
+      if let Some(expire) = self.timers.get(&tref).map(|t| t.expire) {
+        self.insert(tref, expire);
+      }
+    }
+  }
+
+  /// Process ticks up to the current time, return pids of the processes for
+  /// which the timers have expired.
+  pub fn advance(&mut self) -> Vec<LTerm> {
+    let now = self.now();
+    self.advance_to(now)
+  }
+
+  fn advance_to(&mut self, now: u64) -> Vec<LTerm> {
+    let mut expired = Vec::new();
+    if self.timers.is_empty() {
+      // Nothing to do, skip the ticks
+      self.current = self.current.max(now + 1);
+      return expired;
+    }
+    while self.current <= now {
+      let index = self.current & SLOT_MASK;
+      if index == 0 && self.current > 0 {
+        self.cascade(1);
+      }
+      let slot = core::mem::take(&mut self.slots[index as usize]);
+      for tref in slot {
+        if let Some(timer) = self.timers.remove(&tref) {
+          debug_assert!(timer.expire <= self.current);
+          expired.push(timer.pid);
+        }
+      }
+      self.current += 1;
+    }
+    expired
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn test_timer_wheel() {
+    let mut tw = TimerWheel::new();
+    let pid = |n| LTerm::make_local_pid(n);
+    tw.add_at(5, pid(1));
+    tw.add_at(100, pid(2));
+    let far = tw.add_at(5000, pid(3));
+    tw.add_at(300_000, pid(4));
+    tw.add_at(17_000_000, pid(5));
+
+    assert!(tw.advance_to(4).is_empty());
+    assert_eq!(tw.advance_to(99), vec![pid(1)]);
+    assert_eq!(tw.advance_to(100), vec![pid(2)]);
+    tw.cancel(far);
+    assert!(tw.advance_to(299_999).is_empty());
+    assert_eq!(tw.advance_to(300_000), vec![pid(4)]);
+
+    // Overdue timer fires on the next tick
+    tw.add_at(10, pid(6));
+    assert_eq!(tw.advance_to(300_001), vec![pid(6)]);
+    assert_eq!(tw.timers.len(), 1);
+    assert!(tw.advance_to(16_999_999).is_empty());
+    assert_eq!(tw.advance_to(17_000_000), vec![pid(5)]);
+    assert!(tw.is_empty());
+  }
+}
@@ -1,42 +1,182 @@
 //! Implements virtual machine, as a collection of processes and their
 //! registrations, schedulers, ETS tables and atom table etc.
+//!
+//! `VMShared` is the state shared by all scheduler threads. Each thread has its
+//! own `VM` value which refers to the shared state and owns the scheduler
+//! running in this thread.
 
 use crate::{
+  bif::BifFn,
   command_line_args::ErlStartArgs,
-  defs::Word,
   emulator::{
     code_srv::CodeServer,
     mfa::MFASomething,
-    process::Process,
-    scheduler::{Prio, Scheduler},
+    process::{Process, ProcessHandle, SpawnOptions},
+    registry::Registry,
+    scheduler::{Scheduler, SchedulerShared},
+    signal::Signal,
   },
+  defs::Word,
   fail::RtResult,
   term::lterm::*,
 };
+use std::{
+  collections::HashMap,
+  sync::{
+    atomic::{AtomicBool, AtomicUsize, Ordering},
+    Arc, Mutex, MutexGuard, RwLock,
+  },
+};
 
 // fn module() -> &'static str { "vm: " }
 
 /// VM environment, heaps, tables, processes all goes here.
 /// Atoms are a global API in `atom.rs`.
-/// Code server is a global API in `code_srv.rs`.
-pub struct VM {
+pub struct VMShared {
   /// Pid counter increments every time a new process is spawned
-  pid_counter: Word,
+  pid_counter: AtomicUsize,
+  /// Reference counter increments every time a new reference is created
+  ref_counter: AtomicUsize,
 
   /// Contains all loaded modules and manages versions
-  pub code_server: CodeServer,
+  pub code_server: Mutex<CodeServer>,
+
+  /// All processes on this node, by pid
+  processes: RwLock<HashMap<LTerm, Arc<ProcessHandle>>>,
+  /// Registered process names
+  pub registry: RwLock<Registry>,
+
+  /// Run queues and wakeup signals of the schedulers, indexed by scheduler
+  pub schedulers: Vec<SchedulerShared>,
+  /// How many schedulers sleep without a timeout, waiting to be woken up
+  pub idle_schedulers: AtomicUsize,
+  /// Set when all schedulers should stop
+  stopping: AtomicBool,
+}
+
+impl VMShared {
+  pub fn new(args: &mut ErlStartArgs) -> Arc<VMShared> {
+    let num_schedulers = args.num_schedulers.max(1);
+    Arc::new(VMShared {
+      pid_counter: AtomicUsize::new(0),
+      ref_counter: AtomicUsize::new(0),
+      code_server: Mutex::new(CodeServer::new(args)),
+      processes: RwLock::new(HashMap::new()),
+      registry: RwLock::new(Registry::new()),
+      schedulers: (0..num_schedulers).map(|_| SchedulerShared::new()).collect(),
+      idle_schedulers: AtomicUsize::new(0),
+      stopping: AtomicBool::new(false),
+    })
+  }
+
+  /// Get a new unique id for a reference.
+  pub fn next_ref_id(&self) -> Word {
+    self.ref_counter.fetch_add(1, Ordering::SeqCst)
+  }
+
+  pub fn get_process_count(&self) -> usize {
+    self.processes.read().unwrap().len()
+  }
+
+  /// Sorted pids of all processes.
+  pub fn get_pids(&self) -> Vec<LTerm> {
+    let mut pids: Vec<LTerm> = self.processes.read().unwrap().keys().cloned().collect();
+    pids.sort_by_key(|pid| pid.raw());
+    pids
+  }
+
+  #[inline]
+  pub fn is_process_alive(&self, pid: LTerm) -> bool {
+    self.processes.read().unwrap().contains_key(&pid)
+  }
+
+  pub fn lookup_process_handle(&self, pid: LTerm) -> Option<Arc<ProcessHandle>> {
+    self.processes.read().unwrap().get(&pid).cloned()
+  }
+
+  pub fn register_process(&self, handle: Arc<ProcessHandle>) {
+    self.processes.write().unwrap().insert(handle.pid, handle);
+  }
+
+  /// Remove a process from the process table, and its registered name.
+  pub fn unregister_process(&self, pid: LTerm) {
+    self.processes.write().unwrap().remove(&pid);
+    // After the process is gone from the table, `register` will not accept it
+    self.registry.write().unwrap().unregister_pid(pid);
+  }
+
+  /// Register a name for a process. Returns `false` if the process does not
+  /// exist, the name is taken or the process already has a name.
+  pub fn register_name(&self, name: LTerm, pid: LTerm) -> bool {
+    let mut registry = self.registry.write().unwrap();
+    self.is_process_alive(pid) && registry.register(name, pid)
+  }
+
+  /// Find a process by its registered name.
+  pub fn whereis(&self, name: LTerm) -> Option<LTerm> {
+    self.registry.read().unwrap().whereis(name)
+  }
+
+  /// Registered name of a process, if it has one.
+  pub fn get_registered_name(&self, pid: LTerm) -> Option<LTerm> {
+    self.registry.read().unwrap().get_name(pid)
+  }
+
+  /// Put a signal into the incoming queue of a process, and wake it up if it
+  /// is waiting. Returns `false` if the process does not exist or has exited,
+  /// the signal is then dropped.
+  pub fn send_signal(&self, pid: LTerm, signal: Signal) -> bool {
+    let handle = match self.lookup_process_handle(pid) {
+      Some(h) => h,
+      None => return false,
+    };
+    if !handle.push_signal(signal) {
+      return false;
+    }
+    // The process might have migrated to another scheduler meanwhile, then
+    // it will find the signal when it runs next time.
+    self.schedulers[handle.get_scheduler()].wake_process(pid, &self.idle_schedulers);
+    true
+  }
+
+  /// Wake up one scheduler which sleeps without a timeout, other than
+  /// `except`, so that it can steal some work.
+  pub fn wake_idle_scheduler(&self, except: usize) {
+    for (index, s) in self.schedulers.iter().enumerate() {
+      if index != except && s.notify_if_idle(&self.idle_schedulers) {
+        return;
+      }
+    }
+  }
+
+  #[inline]
+  pub fn is_stopping(&self) -> bool {
+    self.stopping.load(Ordering::SeqCst)
+  }
+
+  /// Ask all schedulers to stop.
+  pub fn stop(&self) {
+    self.stopping.store(true, Ordering::SeqCst);
+    for s in self.schedulers.iter() {
+      s.notify(&self.idle_schedulers);
+    }
+  }
+}
 
+/// A scheduler thread view of the VM: the shared state and the scheduler which
+/// runs in this thread.
+pub struct VM {
+  pub shared: Arc<VMShared>,
   pub scheduler: Scheduler,
 }
 
 impl VM {
-  /// Create a VM, multiple VMs can be created but atom table and code server
-  /// will be shared (global).
-  pub fn new(args: &mut ErlStartArgs) -> VM {
+  /// Create a VM for the scheduler thread `index`, multiple VMs can be created
+  /// for the same shared state, one per scheduler.
+  pub fn new(shared: &Arc<VMShared>, index: usize) -> VM {
     VM {
-      code_server: CodeServer::new(args),
-      pid_counter: 0,
-      scheduler: Scheduler::new(),
+      shared: shared.clone(),
+      scheduler: Scheduler::new(index, shared.clone()),
     }
   }
 
@@ -47,34 +187,77 @@ impl VM {
     p as *mut Scheduler
   }
 
-  /// Dirty trick to not have to dynamically borrow code server via
-  /// `RefCell<Box<>>` because code server lives just as long as the VM itself.
-  pub fn get_code_server_p(&self) -> *mut CodeServer {
-    let p = &self.code_server as *const CodeServer;
-    p as *mut CodeServer
+  /// Lock the code server. Do not hold the lock for long, other schedulers
+  /// might need it.
+  pub fn code_server(&self) -> MutexGuard<'_, CodeServer> {
+    self.shared.code_server.lock().unwrap()
+  }
+
+  /// Look into or change another process while it is not running: `f`
+  /// receives the process and whether it was running when asked. The calling
+  /// process stays locked. To avoid deadlocks, a process waits only for
+  /// processes with a higher pid: a process with a lower pid is taken only if
+  /// it is free, otherwise the BIF traps to `retry` with `args` and tries
+  /// again in the next time slice.
+  /// Returns `None` if the process does not exist.
+  pub fn with_other_process<T, F>(
+    &self,
+    cur_proc: &mut Process,
+    pid: LTerm,
+    retry: (BifFn, &[LTerm]),
+    f: F,
+  ) -> RtResult<Option<T>>
+  where
+    F: FnOnce(&mut Process, bool) -> T,
+  {
+    debug_assert_ne!(pid, cur_proc.pid);
+    let handle = match self.shared.lookup_process_handle(pid) {
+      Some(h) => h,
+      None => return Ok(None),
+    };
+    let was_running = handle.is_locked();
+    if pid.get_term_val_without_tag() > cur_proc.pid.get_term_val_without_tag() {
+      handle.lock();
+    } else if !handle.try_lock() {
+      let (retry_fn, args) = retry;
+      return cur_proc.trap(retry_fn, args).map(|_| None);
+    }
+    let p = handle.get_process();
+    let result = if p.is_null() {
+      None
+    } else {
+      Some(f(unsafe { &mut *p }, was_running))
+    };
+    handle.unlock();
+    Ok(result)
   }
 
   /// Spawn a new process, create a new pid, register the process and jump to
   /// the MFA specified. Arguments are copies into the new process heap and
-  /// stored into the registers.
+  /// stored into the registers. The process is queued on this scheduler.
   pub fn create_process(
     &mut self,
     parent: LTerm,
     mfargs: &MFASomething,
-    prio: Prio,
+    opts: &SpawnOptions,
   ) -> RtResult<LTerm> {
-    let pid_c = self.pid_counter;
-    self.pid_counter += 1;
+    let pid_c = self.shared.pid_counter.fetch_add(1, Ordering::SeqCst);
 
     let pid = LTerm::make_local_pid(pid_c);
     let mfarity = mfargs.get_mfarity();
-    let cs = self.get_code_server_p();
-    let mut p0 = Process::new(pid, parent, &mfarity, prio, unsafe { &mut (*cs) })?;
+    let mut p0 = Process::new(
+      pid,
+      parent,
+      &mfarity,
+      opts,
+      &mut self.code_server(),
+    )?;
 
     // Error may happen here due to arg term copy error
     p0.set_spawn_args(&mfargs)?;
 
-    self.scheduler.register_new_process(pid, p0);
+    self.shared.register_process(p0.handle.clone());
+    self.scheduler.register_new_process(p0);
     Ok(pid)
   }
 
@@ -1,10 +1,13 @@
 use crate::{
   defs::exc_type::ExceptionType,
-  emulator::{gen_atoms, heap::Heap},
+  emulator::{
+    gen_atoms,
+    heap::{self, Heap},
+  },
   fail::{Error, RtResult},
   term::{
     builders::make_badfun,
-    lterm::{tuple, LTerm},
+    lterm::{cons, tuple, LTerm},
   },
 };
 
@@ -38,3 +41,66 @@ pub fn badfun_val<T>(val: LTerm, hp: &mut Heap) -> RtResult<T> {
   let badfun_tuple = make_badfun(val, hp)?;
   Err(Error::Exception(ExceptionType::Error, badfun_tuple))
 }
+
+/// Fail with `{badmap, Val}`, the tuple goes to a heap fragment if the heap
+/// is full.
+pub fn badmap_val<T>(val: LTerm, hp: &mut Heap) -> RtResult<T> {
+  let badmap_tuple = heap::build_on_heap(hp, |hp| {
+    tuple::make_tuple2(gen_atoms::BADMAP, val, hp)
+  })?;
+  Err(Error::Exception(ExceptionType::Error, badmap_tuple))
+}
+
+/// Fail with `{badkey, Key}`, the tuple goes to a heap fragment if the heap
+/// is full.
+pub fn badkey_val<T>(key: LTerm, hp: &mut Heap) -> RtResult<T> {
+  let badkey_tuple = heap::build_on_heap(hp, |hp| {
+    tuple::make_tuple2(gen_atoms::BADKEY, key, hp)
+  })?;
+  Err(Error::Exception(ExceptionType::Error, badkey_tuple))
+}
+
+pub fn badarith<T>() -> RtResult<T> {
+  Err(Error::Exception(ExceptionType::Error, gen_atoms::BADARITH))
+}
+
+pub fn system_limit<T>() -> RtResult<T> {
+  Err(Error::Exception(ExceptionType::Error, gen_atoms::SYSTEM_LIMIT))
+}
+
+/// Exit reason for a failure inside the emulator which is not an Erlang
+/// exception: `system_limit` when the heap could not grow, otherwise
+/// `{emulator_error, Text}` where the text describes the error.
+pub fn emulator_error_reason(e: &Error, hp: &mut Heap) -> LTerm {
+  if let Error::HeapIsFull = e {
+    return gen_atoms::SYSTEM_LIMIT;
+  }
+  let text = format!("{:?}", e);
+  let reason = heap::build_on_heap(hp, |hp| {
+    let text_list = unsafe { cons::rust_str_to_list(&text, hp) }?;
+    tuple::make_tuple2(gen_atoms::EMULATOR_ERROR, text_list, hp)
+  });
+  reason.unwrap_or(gen_atoms::EMULATOR_ERROR)
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::term::boxed;
+
+  #[test]
+  fn test_emulator_error_reason() {
+    let mut hp = Heap::new(16);
+    let reason = emulator_error_reason(&Error::HeapIsFull, &mut hp);
+    assert_eq!(reason, gen_atoms::SYSTEM_LIMIT);
+
+    // Does not fit the small heap and goes to a fragment
+    let reason = emulator_error_reason(&Error::BoxedIsNotATuple, &mut hp);
+    assert!(reason.is_tuple());
+    let tuple_p = reason.get_tuple_ptr();
+    let tag = unsafe { boxed::Tuple::get_element_base0(tuple_p, 0) };
+    assert_eq!(tag, gen_atoms::EMULATOR_ERROR);
+    let text = unsafe { boxed::Tuple::get_element_base0(tuple_p, 1) };
+    assert_eq!(cons::list_length(text).unwrap(), "BoxedIsNotATuple".len());
+  }
+}
@@ -39,18 +39,26 @@ pub enum Error {
 
   //--- VM Checks --
   Exception(ExceptionType, LTerm), // type, value
+  /// A long running BIF has used up the reductions and saved its state in
+  /// `Process::bif_trap`, the process yields and the BIF continues later.
+  BifTrap,
+  /// The process has hibernated (`erlang:hibernate/3`) and waits for a
+  /// message.
+  Hibernate,
   TermIsNotABoxed,
+  TermIsNotABinary,
   // used by `helper_get_mut_from_boxed_term` when boxed tag is different from
   // what is expected
   BoxedTagCheckFailed,
   BoxedIsNotABigint,
   BoxedIsNotAClosure,
   BoxedIsNotAnExport,
+  BoxedIsNotAFloat,
   BoxedIsNotAnImport,
   BoxedIsNotATuple,
 
   //--- Binary ---
-  CannotCopyIntoRefbin, // To copy into binary, resolve ref into heapbin
+  CannotCopyIntoSubbin, // Sub-binaries refer to data of another binary
   HeapBinTooSmall(usize, usize), // want bytes, have bytes
   ProcBinTooSmall(usize, usize), // want bytes, have bytes
 }
@@ -3,14 +3,14 @@ use crate::{
   emulator::{
     atom,
     mfa::{Args, MFASomething},
-    scheduler::Prio,
-    vm::VM,
+    process::SpawnOptions,
+    vm::{VMShared, VM},
   },
   term::lterm::*,
 };
 use std::{
   io::{stdout, Write},
-  thread, time,
+  thread,
 };
 
 /// Entry point for the command-line interface. Pre-parse command line args
@@ -20,20 +20,38 @@ pub fn start_emulator(args: &mut ErlStartArgs) {
     println!("Erlang Runtime (compat OTP 20)");
   }
 
-  let mut beam_vm = VM::new(args);
+  let shared = VMShared::new(args);
+  let mut vms: Vec<VM> = (0..shared.schedulers.len())
+    .map(|index| VM::new(&shared, index))
+    .collect();
 
   let mfargs = MFASomething::new(
     atom::from_str("test2"),
     atom::from_str("test"),
     Args::AsList(LTerm::nil()),
   );
-  let _rootp = beam_vm
-    .create_process(LTerm::nil(), &mfargs, Prio::Normal)
+  let _rootp = vms[0]
+    .create_process(LTerm::nil(), &mfargs, &SpawnOptions::default())
     .unwrap();
 
-  println!("Process created. Entering main loop...");
-  while beam_vm.tick().unwrap() {
-    thread::sleep(time::Duration::from_millis(0));
+  println!(
+    "Process created. Entering main loop with {} scheduler(s)...",
+    vms.len()
+  );
+  // Each scheduler runs in its own thread and sleeps on its own when there is
+  // nothing to run. The threads exit when the VM is stopped.
+  let threads: Vec<_> = vms
+    .drain(..)
+    .enumerate()
+    .map(|(index, mut vm)| {
+      thread::Builder::new()
+        .name(format!("scheduler{}", index))
+        .spawn(move || while vm.tick().unwrap() {})
+        .unwrap()
+    })
+    .collect();
+  for t in threads {
+    t.join().unwrap();
   }
   stdout().flush().unwrap();
 }
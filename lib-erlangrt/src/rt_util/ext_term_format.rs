@@ -1,10 +1,10 @@
 use super::bin_reader::BinaryReader;
 use crate::{
-  defs::{self, SWord, Word},
-  fail::{Error, RtResult},
-  term::{lterm::LTerm, term_builder::TermBuilder},
+  defs::{SWord, Word},
+  emulator::{arith::big::BigInteger, atom, gen_atoms, mfa::MFArity},
+  fail::{self, Error, RtResult},
+  term::{boxed, lterm::*, map, term_builder::TermBuilder},
 };
-use num::{self, ToPrimitive};
 
 ///// Errors indicating a problem with External Term Format parser.
 //#[derive(Debug)]
@@ -18,6 +18,8 @@ use num::{self, ToPrimitive};
 enum Tag {
   ETF = 131,
   NewFloat = 70,
+  NewPid = 88,
+  NewerReference = 90,
   BitBinary = 77,
   AtomCacheRef_ = 82,
   SmallInteger = 97,
@@ -75,12 +77,45 @@ pub fn decode_naked(r: &mut BinaryReader, tb: &mut TermBuilder) -> RtResult<LTer
 
     x if x == Tag::String as u8 => decode_string(r, tb),
 
-    x if x == Tag::AtomDeprecated as u8 => decode_atom_latin1(r, tb),
+    x if x == Tag::AtomDeprecated as u8 => {
+      let sz = r.read_u16be();
+      decode_atom_latin1(r, sz as Word, tb)
+    }
+
+    x if x == Tag::SmallAtomDeprecated as u8 => {
+      let sz = r.read_u8();
+      decode_atom_latin1(r, sz as Word, tb)
+    }
+
+    x if x == Tag::AtomUtf8 as u8 => {
+      let sz = r.read_u16be();
+      decode_atom_utf8(r, sz as Word, tb)
+    }
+
+    x if x == Tag::SmallAtomUtf8 as u8 => {
+      let sz = r.read_u8();
+      decode_atom_utf8(r, sz as Word, tb)
+    }
+
+    x if x == Tag::Pid as u8 => decode_pid(r, false, tb),
+
+    x if x == Tag::NewPid as u8 => decode_pid(r, true, tb),
+
+    x if x == Tag::NewReference as u8 => decode_reference(r, false, tb),
+
+    x if x == Tag::NewerReference as u8 => decode_reference(r, true, tb),
 
     x if x == Tag::SmallInteger as u8 => decode_u8(r, tb),
 
     x if x == Tag::Integer as u8 => decode_s32(r, tb),
 
+    x if x == Tag::NewFloat as u8 => {
+      let val = f64::from_bits(r.read_u64be());
+      unsafe { tb.create_float(val) }
+    }
+
+    x if x == Tag::Float as u8 => decode_float_str(r, tb),
+
     x if x == Tag::Nil as u8 => Ok(LTerm::nil()),
 
     x if x == Tag::LargeTuple as u8 => {
@@ -105,6 +140,10 @@ pub fn decode_naked(r: &mut BinaryReader, tb: &mut TermBuilder) -> RtResult<LTer
 
     x if x == Tag::Binary as u8 => decode_binary(r, tb),
 
+    x if x == Tag::Map as u8 => decode_map(r, tb),
+
+    x if x == Tag::Export as u8 => decode_export(r, tb),
+
     _ => {
       let msg = format!(
         "Don't know how to decode ETF value tag 0x{:x} ({})",
@@ -115,24 +154,13 @@ pub fn decode_naked(r: &mut BinaryReader, tb: &mut TermBuilder) -> RtResult<LTer
   }
 }
 
-/// Given `size`, read digits for a bigint.
+/// Given `size`, read digits for a bigint. The result is stored as a small
+/// integer if it fits.
 fn decode_big(r: &mut BinaryReader, size: Word, tb: &mut TermBuilder) -> RtResult<LTerm> {
-  let sign = if r.read_u8() == 0 {
-    num::bigint::Sign::Plus
-  } else {
-    num::bigint::Sign::Minus
-  };
+  let negative = r.read_u8() != 0;
   let digits = r.read_bytes(size)?;
-  let big = num::BigInt::from_bytes_le(sign, &digits);
-
-  // Assert that the number fits into small
-  if big.bits() < defs::WORD_BITS - 4 {
-    let b_signed = big.to_isize().unwrap();
-    return Ok(tb.create_small_s(b_signed));
-  }
-
-  // Determine storage size in words
-  unsafe { Ok(tb.create_bignum(big)?) }
+  let big = BigInteger::from_bytes_le(negative, &digits);
+  unsafe { tb.create_bignum(&big) }
 }
 
 fn decode_binary(r: &mut BinaryReader, tb: &mut TermBuilder) -> RtResult<LTerm> {
@@ -159,6 +187,29 @@ fn decode_tuple(
   Ok(tuple_builder.make_term())
 }
 
+/// Read map arity and then keys interleaved with values.
+fn decode_map(r: &mut BinaryReader, tb: &mut TermBuilder) -> RtResult<LTerm> {
+  let arity = r.read_u32be() as usize;
+  let mut pairs = Vec::with_capacity(arity);
+  for _i in 0..arity {
+    let key = decode_naked(r, tb)?;
+    let value = decode_naked(r, tb)?;
+    pairs.push((key, value));
+  }
+  tb.create_map(&pairs)
+}
+
+/// Read `fun M:F/Arity`: module and function atoms and a small arity.
+fn decode_export(r: &mut BinaryReader, tb: &mut TermBuilder) -> RtResult<LTerm> {
+  let m = decode_naked(r, tb)?;
+  let f = decode_naked(r, tb)?;
+  let arity = decode_naked(r, tb)?;
+  if !m.is_atom() || !f.is_atom() || !arity.is_small() {
+    return fail(format!("{}Bad export {}:{}/{}", module(), m, f, arity));
+  }
+  tb.create_export(&MFArity::new(m, f, arity.get_small_unsigned()))
+}
+
 fn decode_u8(r: &mut BinaryReader, tb: &mut TermBuilder) -> RtResult<LTerm> {
   let val = r.read_u8();
   Ok(tb.create_small_s(val as SWord))
@@ -169,12 +220,85 @@ fn decode_s32(r: &mut BinaryReader, tb: &mut TermBuilder) -> RtResult<LTerm> {
   Ok(tb.create_small_s(val as SWord))
 }
 
-fn decode_atom_latin1(r: &mut BinaryReader, tb: &mut TermBuilder) -> RtResult<LTerm> {
-  let sz = r.read_u16be();
-  let val = r.read_str_latin1(sz as Word).unwrap();
+/// Old float format: 31 bytes of text, padded with zeroes.
+fn decode_float_str(r: &mut BinaryReader, tb: &mut TermBuilder) -> RtResult<LTerm> {
+  let text = r.read_str_latin1(31)?;
+  match text.trim_end_matches('\0').parse::<f64>() {
+    Ok(val) => unsafe { tb.create_float(val) },
+    Err(_) => fail(format!("{}Bad float value {}", module(), text)),
+  }
+}
+
+fn decode_atom_latin1(
+  r: &mut BinaryReader,
+  sz: Word,
+  tb: &mut TermBuilder,
+) -> RtResult<LTerm> {
+  let val = r.read_str_latin1(sz).unwrap();
   Ok(tb.create_atom_str(&val))
 }
 
+fn decode_atom_utf8(
+  r: &mut BinaryReader,
+  sz: Word,
+  tb: &mut TermBuilder,
+) -> RtResult<LTerm> {
+  match r.read_str_utf8(sz) {
+    Ok(val) => Ok(tb.create_atom_str(&val)),
+    Err(e) => fail(format!("{}{}", module(), e)),
+  }
+}
+
+/// Only pids of the local node are supported, this VM does not connect to
+/// other nodes. `new_pid` (tag 88) has a 32-bit creation, the old pid format
+/// has 8 bits.
+fn decode_pid(
+  r: &mut BinaryReader,
+  new_pid: bool,
+  tb: &mut TermBuilder,
+) -> RtResult<LTerm> {
+  let node = decode_naked(r, tb)?;
+  let id = r.read_u32be() as Word;
+  let serial = r.read_u32be() as Word;
+  if new_pid {
+    r.read_u32be();
+  } else {
+    r.read_u8();
+  }
+  if node != gen_atoms::NONODE_AT_NOHOST {
+    return fail(format!("{}Pid from a remote node {}", module(), node));
+  }
+  Ok(LTerm::make_local_pid(id | (serial << 32)))
+}
+
+/// Read a reference: number of id words, node, creation (32-bit for the
+/// newer format, 8-bit otherwise), then the id words. A local reference
+/// id is stored in the first two id words.
+fn decode_reference(
+  r: &mut BinaryReader,
+  newer: bool,
+  tb: &mut TermBuilder,
+) -> RtResult<LTerm> {
+  let n_ids = r.read_u16be() as usize;
+  let node = decode_naked(r, tb)?;
+  if newer {
+    r.read_u32be();
+  } else {
+    r.read_u8();
+  }
+  let mut id: u64 = 0;
+  for i in 0..n_ids {
+    let id_word = u64::from(r.read_u32be());
+    if i < 2 {
+      id |= id_word << (32 * i);
+    }
+  }
+  if node != gen_atoms::NONODE_AT_NOHOST {
+    return fail(format!("{}Reference from a remote node {}", module(), node));
+  }
+  tb.create_local_ref(id as Word)
+}
+
 fn decode_list(r: &mut BinaryReader, tb: &mut TermBuilder) -> RtResult<LTerm> {
   let n_elem = r.read_u32be();
   if n_elem == 0 {
@@ -214,3 +338,276 @@ fn decode_string(r: &mut BinaryReader, tb: &mut TermBuilder) -> RtResult<LTerm>
 
   Ok(list_builder.make_term())
 }
+
+/// Encode a term into the external term format, with the leading ETF tag
+/// byte 131. Nested terms are written in order using a stack of terms to
+/// visit, so deep lists and tuples do not grow the CPU stack. Closures,
+/// ports, external pids and external references are not supported and fail
+/// with `badarg`.
+pub fn encode(term: LTerm) -> RtResult<Vec<u8>> {
+  let mut out = vec![Tag::ETF as u8];
+  let mut stack = vec![term];
+
+  while let Some(t) = stack.pop() {
+    if t == LTerm::nil() {
+      out.push(Tag::Nil as u8);
+    } else if t == LTerm::empty_tuple() {
+      out.extend_from_slice(&[Tag::SmallTuple as u8, 0]);
+    } else if t == LTerm::empty_binary() {
+      out.push(Tag::Binary as u8);
+      put_u32(&mut out, 0);
+    } else if t.is_atom() {
+      encode_atom(&mut out, t)?;
+    } else if t.is_small() {
+      encode_small(&mut out, t.get_small_signed());
+    } else if t.is_big_int() {
+      encode_big(&mut out, &BigInteger::from_term(t).unwrap());
+    } else if t.is_float() {
+      out.push(Tag::NewFloat as u8);
+      out.extend_from_slice(&unsafe { t.get_f64_unsafe() }.to_bits().to_be_bytes());
+    } else if t.is_cons() {
+      encode_list(&mut out, &mut stack, t);
+    } else if t.is_tuple() {
+      let tuple_p = t.get_tuple_ptr();
+      let arity = unsafe { (*tuple_p).get_arity() };
+      if arity < 256 {
+        out.extend_from_slice(&[Tag::SmallTuple as u8, arity as u8]);
+      } else {
+        out.push(Tag::LargeTuple as u8);
+        put_u32(&mut out, arity);
+      }
+      for i in (0..arity).rev() {
+        stack.push(unsafe { boxed::Tuple::get_element_base0(tuple_p, i) });
+      }
+    } else if t.is_map() {
+      out.push(Tag::Map as u8);
+      put_u32(&mut out, t.map_size());
+      let mut pairs = Vec::with_capacity(t.map_size());
+      map::for_each_pair(t, |k, v| pairs.push((k, v)));
+      for (k, v) in pairs.iter().rev() {
+        stack.push(*v);
+        stack.push(*k);
+      }
+    } else if t.is_binary() {
+      encode_binary(&mut out, t)?;
+    } else if t.is_local_pid() {
+      let pindex = t.get_term_val_without_tag();
+      out.push(Tag::NewPid as u8);
+      encode_atom(&mut out, gen_atoms::NONODE_AT_NOHOST)?;
+      put_u32(&mut out, pindex & 0xffff_ffff);
+      put_u32(&mut out, pindex >> 32);
+      put_u32(&mut out, 0);
+    } else if t.is_export() {
+      let mfa = unsafe { (*t.get_box_ptr::<boxed::Export>()).exp.mfa };
+      out.push(Tag::Export as u8);
+      encode_atom(&mut out, mfa.m)?;
+      encode_atom(&mut out, mfa.f)?;
+      encode_small(&mut out, mfa.arity as SWord);
+    } else if t.is_local_ref() {
+      let id = boxed::Reference::get_id(t) as u64;
+      out.push(Tag::NewReference as u8);
+      out.extend_from_slice(&2u16.to_be_bytes());
+      encode_atom(&mut out, gen_atoms::NONODE_AT_NOHOST)?;
+      out.push(0);
+      out.extend_from_slice(&(id as u32).to_be_bytes());
+      out.extend_from_slice(&((id >> 32) as u32).to_be_bytes());
+    } else {
+      return fail::create::badarg();
+    }
+  }
+  Ok(out)
+}
+
+#[inline]
+fn put_u32(out: &mut Vec<u8>, val: Word) {
+  out.extend_from_slice(&(val as u32).to_be_bytes());
+}
+
+fn encode_atom(out: &mut Vec<u8>, a: LTerm) -> RtResult<()> {
+  let name = atom::to_str(a)?;
+  if name.len() < 256 {
+    out.extend_from_slice(&[Tag::SmallAtomUtf8 as u8, name.len() as u8]);
+  } else {
+    out.push(Tag::AtomUtf8 as u8);
+    out.extend_from_slice(&(name.len() as u16).to_be_bytes());
+  }
+  out.extend_from_slice(name.as_bytes());
+  Ok(())
+}
+
+fn encode_small(out: &mut Vec<u8>, val: SWord) {
+  if (0..256).contains(&val) {
+    out.extend_from_slice(&[Tag::SmallInteger as u8, val as u8]);
+  } else if val >= i32::MIN as SWord && val <= i32::MAX as SWord {
+    out.push(Tag::Integer as u8);
+    out.extend_from_slice(&(val as i32).to_be_bytes());
+  } else {
+    encode_big(out, &BigInteger::from_sword(val));
+  }
+}
+
+fn encode_big(out: &mut Vec<u8>, big: &BigInteger) {
+  let digits = big.to_bytes_le();
+  if digits.len() < 256 {
+    out.extend_from_slice(&[Tag::SmallBig as u8, digits.len() as u8]);
+  } else {
+    out.push(Tag::LargeBig as u8);
+    put_u32(out, digits.len());
+  }
+  out.push(big.is_negative() as u8);
+  out.extend_from_slice(&digits);
+}
+
+/// Write the list header and push the elements and the tail to `stack`, so
+/// that the first element is encoded next.
+fn encode_list(out: &mut Vec<u8>, stack: &mut Vec<LTerm>, lst: LTerm) {
+  let mut elements = Vec::new();
+  let mut t = lst;
+  while t.is_cons() {
+    let cons_p = t.get_cons_ptr();
+    unsafe {
+      elements.push((*cons_p).hd());
+      t = (*cons_p).tl();
+    }
+  }
+  out.push(Tag::List as u8);
+  put_u32(out, elements.len());
+  stack.push(t);
+  stack.extend(elements.iter().rev());
+}
+
+/// Byte-sized binaries use `Binary` tag, otherwise `BitBinary` also stores
+/// the count of used bits in the last byte.
+fn encode_binary(out: &mut Vec<u8>, t: LTerm) -> RtResult<()> {
+  let bits = unsafe { boxed::Binary::bit_slice_from_term(t)? };
+  let bit_size = bits.get_bit_size();
+  let data = unsafe { bits.to_bytes() };
+  if bit_size % 8 == 0 {
+    out.push(Tag::Binary as u8);
+    put_u32(out, data.len());
+  } else {
+    out.push(Tag::BitBinary as u8);
+    put_u32(out, data.len());
+    out.push((bit_size % 8) as u8);
+  }
+  out.extend_from_slice(&data);
+  Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::{emulator::heap::Heap, term::compare::cmp_terms};
+  use core::cmp::Ordering;
+
+  #[test]
+  fn encode_decode_roundtrip() {
+    let mut hp = Heap::new(1024);
+    let small = LTerm::make_small_signed;
+    let big = BigInteger::from_sword(1).shl(100).neg();
+    let elements = unsafe {
+      let mut tb = TermBuilder::new(&mut hp);
+      let mut lb = tb.create_list_builder().unwrap();
+      lb.append(small(1)).unwrap();
+      lb.append(small(-70000)).unwrap();
+      let lst = lb.make_term_with_tail(small(3));
+      [
+        atom::from_str("hello"),
+        lst,
+        tb.create_bignum(&big).unwrap(),
+        tb.create_float(-1.5).unwrap(),
+        tb.create_binary(&[1, 2, 3]).unwrap(),
+        tb.create_local_ref(0x1_0000_0002).unwrap(),
+        LTerm::make_local_pid(7),
+        LTerm::empty_tuple(),
+        LTerm::nil(),
+      ]
+    };
+    let tuple = boxed::Tuple::create_into(&mut hp, elements.len()).unwrap();
+    for (i, elem) in elements.iter().enumerate() {
+      unsafe { boxed::Tuple::set_element_base0(tuple, i, *elem) }
+    }
+    let term = LTerm::make_boxed(tuple);
+
+    let data = encode(term).unwrap();
+    let mut r = BinaryReader::from_bytes(data);
+    let decoded = decode(&mut r, &mut TermBuilder::new(&mut hp)).unwrap();
+    assert!(r.eof());
+    assert_eq!(cmp_terms(term, decoded, true).unwrap(), Ordering::Equal);
+    let ref2 = unsafe { boxed::Tuple::get_element_base0(decoded.get_tuple_ptr(), 5) };
+    assert_eq!(boxed::Reference::get_id(ref2), 0x1_0000_0002);
+  }
+
+  fn encode_ok(t: LTerm) -> Vec<u8> {
+    encode(t).unwrap()
+  }
+
+  fn decode_bytes(hp: &mut Heap, data: Vec<u8>) -> RtResult<LTerm> {
+    let mut r = BinaryReader::from_bytes(data);
+    decode(&mut r, &mut TermBuilder::new(hp))
+  }
+
+  #[test]
+  fn encode_exact_bytes() {
+    let mut hp = Heap::new(1024);
+    let small = LTerm::make_small_signed;
+    assert_eq!(encode_ok(small(255)), vec![131, 97, 255]);
+    assert_eq!(encode_ok(small(-1)), vec![131, 98, 255, 255, 255, 255]);
+    assert_eq!(encode_ok(small(1 << 40)), vec![131, 110, 6, 0, 0, 0, 0, 0, 0, 1]);
+    assert_eq!(encode_ok(LTerm::nil()), vec![131, 106]);
+    assert_eq!(encode_ok(LTerm::empty_tuple()), vec![131, 104, 0]);
+    assert_eq!(encode_ok(LTerm::empty_binary()), vec![131, 109, 0, 0, 0, 0]);
+    assert_eq!(encode_ok(atom::from_str("ok")), vec![131, 119, 2, b'o', b'k']);
+
+    // NewReference: 2 id words, node, 8-bit creation, low id word first
+    let r = boxed::Reference::create_into(&mut hp, 0x1_0000_0002).unwrap();
+    let mut expected = vec![131, 114, 0, 2, 119, 13];
+    expected.extend_from_slice(b"nonode@nohost");
+    expected.extend_from_slice(&[0, 0, 0, 0, 2, 0, 0, 0, 1]);
+    assert_eq!(encode_ok(r), expected);
+
+    // A bitstring of 12 bits has 4 bits used in the last byte
+    let bin = unsafe { TermBuilder::new(&mut hp).create_binary(&[0xab, 0xcd]) }.unwrap();
+    let bits = unsafe { boxed::Binary::create_sub(&mut hp, bin, 0, 12) }.unwrap();
+    assert_eq!(encode_ok(bits), vec![131, 77, 0, 0, 0, 2, 4, 0xab, 0xc0]);
+
+    let remote = LTerm::make_remote_pid(&mut hp, atom::from_str("a@b"), 1).unwrap();
+    assert!(encode(remote).is_err());
+  }
+
+  #[test]
+  fn encode_decode_export_and_map() {
+    let mut hp = Heap::new(1024);
+    let mfa = MFArity::new(gen_atoms::ERLANG, atom::from_str("self"), 0);
+    let export = unsafe { boxed::Export::create_into(&mut hp, &mfa).unwrap() };
+    let pairs = [(LTerm::small_1(), export), (atom::from_str("k"), LTerm::nil())];
+    let m = map::from_pairs(&pairs, &mut hp).unwrap();
+    let decoded = decode_bytes(&mut hp, encode_ok(m)).unwrap();
+    assert!(decoded.is_map());
+    assert_eq!(cmp_terms(m, decoded, true).unwrap(), Ordering::Equal);
+  }
+
+  #[test]
+  fn decode_references_and_pids() {
+    let mut hp = Heap::new(1024);
+    let mut node = vec![119, 13];
+    node.extend_from_slice(b"nonode@nohost");
+
+    // NewerReference has a 32-bit creation, and 3 id words like in OTP
+    let mut data = vec![131, 90, 0, 3];
+    data.extend_from_slice(&node);
+    data.extend_from_slice(&[0, 0, 0, 1, 0, 0, 0, 7, 0, 0, 0, 0, 0, 0, 0, 9]);
+    let r = decode_bytes(&mut hp, data).unwrap();
+    assert_eq!(boxed::Reference::get_id(r), 7);
+
+    // A reference from another node is not supported
+    let data = vec![131, 114, 0, 1, 100, 0, 3, b'a', b'@', b'b', 0, 0, 0, 0, 1];
+    assert!(decode_bytes(&mut hp, data).is_err());
+
+    // Old Pid format with a latin1 atom node and an 8-bit creation
+    let mut data = vec![131, 103, 100, 0, 13];
+    data.extend_from_slice(b"nonode@nohost");
+    data.extend_from_slice(&[0, 0, 0, 5, 0, 0, 0, 0, 0]);
+    assert_eq!(decode_bytes(&mut hp, data).unwrap(), LTerm::make_local_pid(5));
+  }
+}
@@ -1,5 +1,5 @@
 use crate::{
-  defs::{ByteSize, WordSize},
+  defs::{Word, WordSize},
   emulator::heap::Heap,
   fail::{Error, RtResult},
   term::{
@@ -7,40 +7,56 @@ use crate::{
     lterm::*,
   },
 };
-use core::{mem::size_of, ptr};
-use num::bigint::BigInt;
+use core::{ptr, slice};
 
-#[allow(dead_code)]
+/// A big integer stored on the process heap: the header, a sign word and then
+/// the magnitude digits, lowest digit first. None of the words after the
+/// header are terms, so the garbage collector copies them as they are.
+/// Arithmetic is done in `emulator::arith::big`.
+#[repr(C)]
 pub struct Bignum {
   header: BoxHeader,
-
-  /// The actual value. NOTE: Here we trust the storage class (BigInt)
-  /// to manage the memory for its digits on the general application heap.
-  /// This impl stores bignum in dynamic heap with the num library
-  // TODO: Not nice! Manage own data for Bignum.
-  pub value: BigInt,
+  /// 1 for negative values, 0 otherwise
+  sign: Word,
 }
 
 impl Bignum {
-  const fn storage_size() -> WordSize {
-    // This impl stores bignum in dynamic heap with the num library
-    ByteSize::new(size_of::<Bignum>()).words_rounded_up()
-  }
-
-  fn new(bignum_size: WordSize, value: BigInt) -> Bignum {
-    Bignum {
-      header: BoxHeader::new(BOXTYPETAG_BIGINTEGER, bignum_size.words()),
-      value,
-    }
+  #[inline]
+  pub const fn storage_size(n_digits: usize) -> WordSize {
+    WordSize::new(BoxHeader::storage_size().words() + 1 + n_digits)
   }
 
-  pub unsafe fn create_into(hp: &mut Heap, value: BigInt) -> RtResult<*mut Bignum> {
-    let n_words = Bignum::storage_size();
+  /// Allocate a bignum and copy the digits into it. The value is expected to
+  /// not fit into a small integer.
+  pub unsafe fn create_into(
+    hp: &mut Heap,
+    negative: bool,
+    digits: &[Word],
+  ) -> RtResult<*mut Bignum> {
+    debug_assert!(!digits.is_empty());
+    let n_words = Self::storage_size(digits.len());
     let this = hp.alloc::<Bignum>(n_words, false)?;
+    ptr::write(
+      this,
+      Bignum {
+        header: BoxHeader::new(BOXTYPETAG_BIGINTEGER, n_words.words() - 1),
+        sign: negative as Word,
+      },
+    );
+    let digits_p = this.add(1) as *mut Word;
+    ptr::copy_nonoverlapping(digits.as_ptr(), digits_p, digits.len());
+    Ok(this)
+  }
 
-    ptr::write(this, Bignum::new(n_words, value));
+  #[inline]
+  pub unsafe fn is_negative(this: *const Bignum) -> bool {
+    (*this).sign != 0
+  }
 
-    Ok(this)
+  /// The magnitude, lowest digit first.
+  pub unsafe fn get_digits<'a>(this: *const Bignum) -> &'a [Word] {
+    let n_digits = (*this).header.get_arity() - 1;
+    slice::from_raw_parts(this.add(1) as *const Word, n_digits)
   }
 
   #[allow(dead_code)]
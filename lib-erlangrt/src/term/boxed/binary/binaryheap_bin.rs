@@ -1,8 +1,66 @@
-use crate::{defs::ByteSize, term::boxed};
+use crate::defs::{ByteSize, Word};
+use core::{
+  mem::size_of,
+  ptr,
+  sync::atomic::{AtomicUsize, Ordering},
+};
+use std::alloc;
 
-/// Defines operations with a binary on the binary heap
-/// Pointer to this can be directly casted from pointer to boxed::Binary
+/// Defines operations with a binary on the binary heap. This memory is shared
+/// between processes and is not owned by any process heap, instead it is
+/// refcounted and referred to from `ReferenceToBinary` (ProcBin) boxes.
+/// Data bytes follow in memory after this struct.
+#[repr(C)]
 pub struct BinaryHeapBinary {
-  pub bin: boxed::binary::Binary,
+  refc: AtomicUsize,
   pub size: ByteSize,
 }
+
+impl BinaryHeapBinary {
+  fn layout(size: ByteSize) -> alloc::Layout {
+    let total = size_of::<BinaryHeapBinary>() + size.bytes();
+    alloc::Layout::from_size_align(total, size_of::<Word>()).unwrap()
+  }
+
+  /// Allocate memory for a binary of `size` bytes with refcount 1.
+  pub unsafe fn create(size: ByteSize) -> *mut BinaryHeapBinary {
+    let layout = Self::layout(size);
+    let this = alloc::alloc(layout) as *mut BinaryHeapBinary;
+    if this.is_null() {
+      alloc::handle_alloc_error(layout);
+    }
+    ptr::write(
+      this,
+      BinaryHeapBinary {
+        refc: AtomicUsize::new(1),
+        size,
+      },
+    );
+    this
+  }
+
+  #[inline]
+  pub unsafe fn get_data_mut(this: *mut BinaryHeapBinary) -> *mut u8 {
+    this.add(1) as *mut u8
+  }
+
+  #[inline]
+  #[allow(dead_code)]
+  pub unsafe fn get_refc(this: *const BinaryHeapBinary) -> usize {
+    (*this).refc.load(Ordering::Acquire)
+  }
+
+  /// Increase refcount, done when a new reference is created on some heap.
+  #[inline]
+  pub unsafe fn retain(this: *mut BinaryHeapBinary) {
+    (*this).refc.fetch_add(1, Ordering::Relaxed);
+  }
+
+  /// Decrease refcount, done when a reference dies during GC or when its
+  /// heap is destroyed. Frees the memory when the last reference is gone.
+  pub unsafe fn release(this: *mut BinaryHeapBinary) {
+    if (*this).refc.fetch_sub(1, Ordering::AcqRel) == 1 {
+      alloc::dealloc(this as *mut u8, Self::layout((*this).size));
+    }
+  }
+}
@@ -0,0 +1,83 @@
+use core::cmp::Ordering;
+
+/// A read-only view over binary or bitstring data, which can begin at any
+/// bit offset (sub-binaries do that) and have any bit length.
+#[derive(Copy, Clone)]
+pub struct BitSlice {
+  data: *const u8,
+  bit_offset: usize,
+  bit_size: usize,
+}
+
+impl BitSlice {
+  pub fn new(data: *const u8, bit_offset: usize, bit_size: usize) -> Self {
+    Self {
+      data,
+      bit_offset,
+      bit_size,
+    }
+  }
+
+  pub fn empty() -> Self {
+    Self::new(core::ptr::null(), 0, 0)
+  }
+
+  #[inline]
+  pub fn get_bit_size(&self) -> usize {
+    self.bit_size
+  }
+
+  /// Read `n` bits (up to 8) starting at bit position `pos`, the result is
+  /// aligned to the right (least significant bits).
+  pub unsafe fn get_bits(&self, pos: usize, n: usize) -> u8 {
+    debug_assert!(n > 0 && n <= 8);
+    debug_assert!(pos + n <= self.bit_size);
+    let abs_pos = self.bit_offset + pos;
+    let byte_i = abs_pos / 8;
+    let shift = abs_pos % 8;
+    // Take 16 bits big-endian from the data and cut out the `n` requested
+    let hi = core::ptr::read(self.data.add(byte_i)) as u16;
+    let lo = if shift + n > 8 {
+      core::ptr::read(self.data.add(byte_i + 1)) as u16
+    } else {
+      0
+    };
+    let window = (hi << 8) | lo;
+    ((window << shift) >> (16 - n)) as u8
+  }
+
+  #[inline]
+  pub unsafe fn get_byte(&self, i: usize) -> u8 {
+    self.get_bits(i * 8, 8)
+  }
+
+  /// Copy the bits into a new byte vector, the last byte if incomplete is
+  /// padded with zero bits on the right.
+  #[allow(dead_code)]
+  pub unsafe fn to_bytes(self) -> Vec<u8> {
+    let mut result = Vec::with_capacity(self.bit_size / 8 + 1);
+    let mut pos = 0;
+    while pos < self.bit_size {
+      let n = core::cmp::min(8, self.bit_size - pos);
+      result.push(self.get_bits(pos, n) << (8 - n));
+      pos += n;
+    }
+    result
+  }
+
+  /// Compare two bitstrings bit by bit, if one is a prefix of another, the
+  /// shorter one is less.
+  pub unsafe fn cmp(&self, other: &BitSlice) -> Ordering {
+    let common = core::cmp::min(self.bit_size, other.bit_size);
+    let mut pos = 0;
+    while pos < common {
+      let n = core::cmp::min(8, common - pos);
+      let ord = self.get_bits(pos, n).cmp(&other.get_bits(pos, n));
+      if ord != Ordering::Equal {
+        return ord;
+      }
+      pos += n;
+    }
+    self.bit_size.cmp(&other.bit_size)
+  }
+}
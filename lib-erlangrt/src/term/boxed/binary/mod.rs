@@ -2,33 +2,40 @@ use crate::{
   defs::{ByteSize, WordSize},
   emulator::heap::Heap,
   fail::{Error, RtResult},
-  term::boxed::{
-    binary::{
-      binaryheap_bin::BinaryHeapBinary, procheap_bin::ProcessHeapBinary,
-      refc_bin::ReferenceToBinary,
+  term::{
+    boxed::{
+      binary::{
+        binaryheap_bin::BinaryHeapBinary, procheap_bin::ProcessHeapBinary,
+        refc_bin::ReferenceToBinary, sub_bin::SubBinary,
+      },
+      BoxHeader, BOXTYPETAG_BINARY,
     },
-    BoxHeader, BOXTYPETAG_BINARY,
+    lterm::LTerm,
   },
 };
-use core::{fmt, ptr};
+use core::{cmp::Ordering, fmt, mem::size_of, ptr, slice};
 
 mod binaryheap_bin;
+pub mod bit_slice;
 mod procheap_bin;
 mod refc_bin;
+mod sub_bin;
+
+pub use self::bit_slice::BitSlice;
 
 #[derive(Copy, Clone)]
-#[allow(dead_code)]
 pub enum BinaryType {
   // contains size, followed in memory by the data bytes
   ProcessHeap,
-  // contains reference to heapbin
+  // contains reference to heapbin, which stores data on the shared binary
+  // heap with refcount
   RefToBinaryHeap,
-  // stores data on a separate heap somewhere else with refcount
-  BinaryHeap,
+  // refers to a part of another binary, can have any bit length and offset
+  Sub,
 }
 
 /// Binary which stores everything in its allocated memory on process heap.
-#[allow(dead_code)]
+#[repr(C)]
 pub struct Binary {
   header: BoxHeader,
   bin_type: BinaryType,
@@ -39,45 +46,236 @@ impl Binary {
     if size.bytes() <= ProcessHeapBinary::ONHEAP_THRESHOLD {
       return BinaryType::ProcessHeap;
     }
-    BinaryType::BinaryHeap
+    BinaryType::RefToBinaryHeap
   }
 
   fn new(b_type: BinaryType, size: ByteSize) -> Binary {
-    let arity = Binary::storage_size(b_type, size).words();
+    let arity = Binary::storage_size(b_type, size).words() - 1;
     Binary {
       header: BoxHeader::new(BOXTYPETAG_BINARY, arity),
-      bin_type: BinaryType::ProcessHeap,
+      bin_type: b_type,
     }
   }
 
   pub fn storage_size(b_type: BinaryType, size: ByteSize) -> WordSize {
-    let header_size: ByteSize;
     match b_type {
-      BinaryType::BinaryHeap => {
-        header_size = ByteSize::new(std::mem::size_of::<BinaryHeapBinary>());
-      }
       BinaryType::ProcessHeap => {
-        header_size = ByteSize::new(std::mem::size_of::<ProcessHeapBinary>());
+        let header_size = ByteSize::new(size_of::<ProcessHeapBinary>());
+        WordSize::new(
+          header_size.words_rounded_up().words() + size.words_rounded_up().words(),
+        )
       }
+      // Data is stored on the binary heap, only the reference is on our heap
       BinaryType::RefToBinaryHeap => {
-        header_size = ByteSize::new(std::mem::size_of::<ReferenceToBinary>());
+        ByteSize::new(size_of::<ReferenceToBinary>()).words_rounded_up()
       }
+      // Data is stored in the original binary
+      BinaryType::Sub => ByteSize::new(size_of::<SubBinary>()).words_rounded_up(),
     }
-    WordSize::new(
-      header_size.words_rounded_up().words() + size.words_rounded_up().words(),
-    )
   }
 
+  /// Allocate a binary of `size` bytes. Small binaries are placed on the
+  /// process heap, larger than `ONHEAP_THRESHOLD` are placed on the binary
+  /// heap and a reference is created on the process heap.
   pub unsafe fn create_into(hp: &mut Heap, size: ByteSize) -> RtResult<*mut Binary> {
     let b_type = Binary::get_binary_type_for(size);
     let storage_sz = Binary::storage_size(b_type, size);
     let this = hp.alloc::<Binary>(storage_sz, false)?;
 
-    ptr::write(this, Binary::new(b_type, size));
+    match b_type {
+      BinaryType::Sub => unreachable!(),
+      BinaryType::ProcessHeap => {
+        let phb = ProcessHeapBinary {
+          bin: Binary::new(b_type, size),
+          size,
+        };
+        ptr::write(this as *mut ProcessHeapBinary, phb);
+      }
+      BinaryType::RefToBinaryHeap => {
+        let refb = ReferenceToBinary {
+          bin: Binary::new(b_type, size),
+          size,
+          pointer: BinaryHeapBinary::create(size),
+        };
+        ptr::write(this as *mut ReferenceToBinary, refb);
+        hp.register_off_heap(this);
+      }
+    }
 
     Ok(this)
   }
 
+  /// Create a sub-binary of `bit_size` bits beginning at `bit_offset` in the
+  /// binary `orig`. If `orig` is a sub-binary itself, the new sub-binary will
+  /// refer to its original binary instead. Caller must check that the range
+  /// fits in `orig`.
+  pub unsafe fn create_sub(
+    hp: &mut Heap,
+    orig: LTerm,
+    bit_offset: usize,
+    bit_size: usize,
+  ) -> RtResult<LTerm> {
+    if bit_size == 0 {
+      return Ok(LTerm::empty_binary());
+    }
+    let mut orig_p = orig.get_box_ptr::<Binary>();
+    let mut orig_term = orig;
+    let mut abs_offset = bit_offset;
+    if let BinaryType::Sub = (*orig_p).bin_type {
+      let sub_p = orig_p as *const SubBinary;
+      abs_offset += (*sub_p).get_bit_offset();
+      orig_term = (*sub_p).orig;
+      orig_p = orig_term.get_box_ptr::<Binary>();
+    }
+    debug_assert!(abs_offset + bit_size <= Binary::get_bit_size(orig_p));
+
+    // Whole binary is requested, no need to create a sub-binary
+    if abs_offset == 0 && bit_size == Binary::get_bit_size(orig_p) {
+      return Ok(orig_term);
+    }
+
+    let b_type = BinaryType::Sub;
+    let storage_sz = Binary::storage_size(b_type, ByteSize::new(0));
+    let this = hp.alloc::<SubBinary>(storage_sz, false)?;
+    let sub = SubBinary {
+      bin: Binary::new(b_type, ByteSize::new(0)),
+      size: ByteSize::new(bit_size / 8),
+      bit_size: bit_size % 8,
+      offset: ByteSize::new(abs_offset / 8),
+      bit_offset: abs_offset % 8,
+      orig: orig_term,
+    };
+    ptr::write(this, sub);
+    Ok(LTerm::make_boxed(this))
+  }
+
+  /// For a sub-binary return pointer to the original binary term, so that
+  /// the GC can update it when the original binary moves.
+  pub unsafe fn get_orig_term_mut(this: *mut Binary) -> Option<*mut LTerm> {
+    match (*this).bin_type {
+      BinaryType::Sub => Some(&mut (*(this as *mut SubBinary)).orig),
+      _ => None,
+    }
+  }
+
+  /// Called after a binary was copied word by word to the heap `hp`. A
+  /// reference to binary heap is a new reference, so the refcount is
+  /// increased and it is registered in the new heap.
+  pub unsafe fn on_copy(this: *mut Binary, hp: &mut Heap) {
+    if let BinaryType::RefToBinaryHeap = (*this).bin_type {
+      let refb_ptr = this as *mut ReferenceToBinary;
+      BinaryHeapBinary::retain((*refb_ptr).pointer);
+      hp.register_off_heap(this);
+    }
+  }
+
+  /// Called when a binary registered in heap's off-heap list dies during GC
+  /// or when the heap is destroyed, drops the reference.
+  pub unsafe fn release(this: *mut Binary) {
+    if let BinaryType::RefToBinaryHeap = (*this).bin_type {
+      let refb_ptr = this as *mut ReferenceToBinary;
+      BinaryHeapBinary::release((*refb_ptr).pointer);
+    }
+  }
+
+  /// For a reference to binary heap, return the refcount of the binary.
+  #[allow(dead_code)]
+  pub unsafe fn get_refc(this: *const Binary) -> Option<usize> {
+    match (*this).bin_type {
+      BinaryType::ProcessHeap | BinaryType::Sub => None,
+      BinaryType::RefToBinaryHeap => {
+        let refb_ptr = this as *const ReferenceToBinary;
+        Some(BinaryHeapBinary::get_refc((*refb_ptr).pointer))
+      }
+    }
+  }
+
+  /// Size in whole bytes, for a bitstring the incomplete last byte is not
+  /// included.
+  pub unsafe fn get_size(this: *const Binary) -> ByteSize {
+    match (*this).bin_type {
+      BinaryType::ProcessHeap => (*(this as *const ProcessHeapBinary)).size,
+      BinaryType::RefToBinaryHeap => (*(this as *const ReferenceToBinary)).size,
+      BinaryType::Sub => (*(this as *const SubBinary)).size,
+    }
+  }
+
+  pub unsafe fn get_bit_size(this: *const Binary) -> usize {
+    match (*this).bin_type {
+      BinaryType::Sub => (*(this as *const SubBinary)).get_bit_size(),
+      _ => Binary::get_size(this).bytes() * 8,
+    }
+  }
+
+  /// Check whether binary has bit length divisible by 8 (otherwise it is a
+  /// bitstring).
+  #[inline]
+  pub unsafe fn is_byte_sized(this: *const Binary) -> bool {
+    Binary::get_bit_size(this) & 7 == 0
+  }
+
+  /// Get pointer to the first data byte, wherever it is stored. For a
+  /// sub-binary this points to the byte where it begins, and there may also
+  /// be bit offset (see `get_bit_slice`).
+  unsafe fn get_data_mut(this: *mut Binary) -> *mut u8 {
+    match (*this).bin_type {
+      BinaryType::ProcessHeap => (this as *mut ProcessHeapBinary).add(1) as *mut u8,
+      BinaryType::RefToBinaryHeap => {
+        let refb_ptr = this as *mut ReferenceToBinary;
+        BinaryHeapBinary::get_data_mut((*refb_ptr).pointer)
+      }
+      BinaryType::Sub => {
+        let sub_p = this as *mut SubBinary;
+        let orig_p = (*sub_p).orig.get_box_ptr_mut::<Binary>();
+        Binary::get_data_mut(orig_p).add((*sub_p).offset.bytes())
+      }
+    }
+  }
+
+  /// Access binary contents as a byte slice. Sub-binaries which do not begin
+  /// at a byte boundary must be read using `get_bit_slice`.
+  #[allow(dead_code)]
+  pub unsafe fn get_data<'a>(this: *const Binary) -> &'a [u8] {
+    if let BinaryType::Sub = (*this).bin_type {
+      debug_assert_eq!((*(this as *const SubBinary)).bit_offset, 0);
+    }
+    let size = Binary::get_size(this).bytes();
+    if size == 0 {
+      return &[];
+    }
+    let data_p = Binary::get_data_mut(this as *mut Binary);
+    slice::from_raw_parts(data_p, size)
+  }
+
+  /// Access binary contents as bits, works for any binary type.
+  pub unsafe fn get_bit_slice(this: *const Binary) -> BitSlice {
+    let data_p = Binary::get_data_mut(this as *mut Binary);
+    let bit_offset = match (*this).bin_type {
+      BinaryType::Sub => (*(this as *const SubBinary)).bit_offset,
+      _ => 0,
+    };
+    BitSlice::new(data_p, bit_offset, Binary::get_bit_size(this))
+  }
+
+  /// Get a bit view for a binary term, also accepting the empty binary
+  /// constant.
+  pub unsafe fn bit_slice_from_term(t: LTerm) -> RtResult<BitSlice> {
+    if t == LTerm::empty_binary() {
+      return Ok(BitSlice::empty());
+    }
+    if !t.is_binary() {
+      return Err(Error::TermIsNotABinary);
+    }
+    Ok(Binary::get_bit_slice(t.get_box_ptr::<Binary>()))
+  }
+
+  /// Compare two binary terms bit by bit.
+  pub unsafe fn cmp_terms(a: LTerm, b: LTerm) -> RtResult<Ordering> {
+    let a_bits = Binary::bit_slice_from_term(a)?;
+    let b_bits = Binary::bit_slice_from_term(b)?;
+    Ok(a_bits.cmp(&b_bits))
+  }
+
   /// Given a byte array, copy it to the binary's memory (depending on
   /// the binary type).
   pub unsafe fn store(this: *mut Binary, data: &[u8]) -> RtResult<()> {
@@ -86,40 +284,23 @@ impl Binary {
       return Ok(());
     }
 
+    let avail_size = Binary::get_size(this).bytes();
     match (*this).bin_type {
-      BinaryType::ProcessHeap => {
-        let phb_ptr = this as *mut ProcessHeapBinary;
-        let avail_size = (*phb_ptr).size.bytes();
-        if avail_size < data_len {
-          return Err(Error::ProcBinTooSmall(data_len, avail_size));
-        }
+      BinaryType::Sub => return Err(Error::CannotCopyIntoSubbin),
+      BinaryType::ProcessHeap if avail_size < data_len => {
+        return Err(Error::ProcBinTooSmall(data_len, avail_size));
       }
-      BinaryType::BinaryHeap => {
-        let bh_ptr = this as *mut BinaryHeapBinary;
-        let avail_size = (*bh_ptr).size.bytes();
-        if (*bh_ptr).size.bytes() < data_len {
-          return Err(Error::HeapBinTooSmall(data_len, avail_size));
-        }
-      }
-      BinaryType::RefToBinaryHeap => {
-        // TODO: Maybe should be possible? Assist with resolution into BinaryHeapBinary
-        return Err(Error::CannotCopyIntoRefbin);
+      BinaryType::RefToBinaryHeap if avail_size < data_len => {
+        return Err(Error::HeapBinTooSmall(data_len, avail_size));
       }
+      _ => {}
     }
 
-    // Take a byte after the Binary struct, that'll be first data byte
-    let bin_bytes = this.add(1) as *mut u8;
-
+    let bin_bytes = Binary::get_data_mut(this);
     ptr::copy_nonoverlapping(&data[0], bin_bytes, data_len);
     Ok(())
   }
 
-  #[inline]
-  unsafe fn get_byte(this: *const Binary, i: usize) -> u8 {
-    let p = this.add(1) as *const u8;
-    core::ptr::read(p.add(i))
-  }
-
   /// Called from LTerm formatting function to print binary contents
   pub unsafe fn format_binary(
     this: *const Binary,
@@ -128,27 +309,73 @@ impl Binary {
     write!(f, "<<")?;
     match (*this).bin_type {
       BinaryType::RefToBinaryHeap => {
-        let refb_ptr = this as *mut ReferenceToBinary;
-        let refb_size = (*refb_ptr).size;
-        write!(f, "#refbin[{}]", refb_size)?;
+        let refb_ptr = this as *const ReferenceToBinary;
+        write!(f, "#refbin[{}]", (*refb_ptr).size)?;
       }
       BinaryType::ProcessHeap => {
-        let phb_ptr = this as *mut ProcessHeapBinary;
-        let phb_size = (*phb_ptr).size;
-        write!(f, "#procbin[{}]", phb_size)?;
-        for i in 0..phb_size.bytes() {
-          if i > 0 {
-            write!(f, ", ")?;
-          }
-          write!(f, "{}", Binary::get_byte(this, i))?;
-        }
+        let phb_ptr = this as *const ProcessHeapBinary;
+        write!(f, "#procbin[{}]", (*phb_ptr).size)?;
+      }
+      BinaryType::Sub => {
+        let sub_p = this as *const SubBinary;
+        write!(f, "#subbin[{}]", (*sub_p).get_bit_size())?;
+      }
+    }
+    let bits = Binary::get_bit_slice(this);
+    let n_bytes = bits.get_bit_size() / 8;
+    for i in 0..n_bytes {
+      if i > 0 {
+        write!(f, ", ")?;
       }
-      BinaryType::BinaryHeap => {
-        let bhb_ptr = this as *mut BinaryHeapBinary;
-        let bhb_size = (*bhb_ptr).size;
-        write!(f, "#heapbin[{}]", bhb_size)?;
+      write!(f, "{}", bits.get_byte(i))?;
+    }
+    // Incomplete last byte is printed as value:bits
+    let tail_bits = bits.get_bit_size() % 8;
+    if tail_bits > 0 {
+      if n_bytes > 0 {
+        write!(f, ", ")?;
       }
+      write!(f, "{}:{}", bits.get_bits(n_bytes * 8, tail_bits), tail_bits)?;
     }
     write!(f, ">>")
   }
 }
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::emulator::heap::copy_term;
+
+  unsafe fn make_bin(hp: &mut Heap, data: &[u8]) -> LTerm {
+    let bin = Binary::create_into(hp, ByteSize::new(data.len())).unwrap();
+    Binary::store(bin, data).unwrap();
+    LTerm::make_boxed(bin)
+  }
+
+  #[test]
+  fn test_sub_binary() {
+    let mut hp = Heap::new(256);
+    unsafe {
+      let bin = make_bin(&mut hp, &[0x12, 0x34, 0x56]);
+      // Sub of a sub refers to the original binary, and can be unaligned
+      let sub1 = Binary::create_sub(&mut hp, bin, 8, 16).unwrap();
+      let sub2 = Binary::create_sub(&mut hp, sub1, 4, 12).unwrap();
+      let sub2_p = sub2.get_box_ptr::<Binary>();
+      assert_eq!(Binary::get_bit_size(sub2_p), 12);
+      assert!(!Binary::is_byte_sized(sub2_p));
+      assert_eq!(format!("{}", sub2), "<<#subbin[12]69, 6:4>>");
+
+      // Compare bit by bit, a prefix is less
+      let expected = make_bin(&mut hp, &[0x34, 0x56]);
+      assert_eq!(Binary::cmp_terms(sub1, expected).unwrap(), Ordering::Equal);
+      assert_eq!(Binary::cmp_terms(sub2, sub1).unwrap(), Ordering::Greater);
+      let prefix = Binary::create_sub(&mut hp, bin, 8, 4).unwrap();
+      assert_eq!(Binary::cmp_terms(prefix, sub1).unwrap(), Ordering::Less);
+
+      // Copy of a bitstring to another heap keeps the value
+      let mut hp2 = Heap::new(256);
+      let copy = copy_term::copy_to(sub2, &mut hp2).unwrap();
+      assert_eq!(Binary::cmp_terms(copy, sub2).unwrap(), Ordering::Equal);
+    }
+  }
+}
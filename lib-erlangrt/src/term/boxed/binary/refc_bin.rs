@@ -1,23 +1,15 @@
 use crate::{
-  defs::{ByteSize, Word},
+  defs::ByteSize,
   term::boxed::{self, binary::binaryheap_bin::BinaryHeapBinary},
 };
 
-/// Defines operations with reference to binary.
+/// Defines operations with reference to binary (also known as ProcBin).
 /// Pointer to this can be directly casted from pointer to boxed::Binary
+/// Every reference holds one refcount on the binary heap binary and is
+/// registered in the owning heap, so that the GC can release it when it dies.
+#[repr(C)]
 pub struct ReferenceToBinary {
   pub bin: boxed::binary::Binary,
   pub size: ByteSize,
-  refc: Word,
   pub pointer: *mut BinaryHeapBinary,
 }
-
-impl ReferenceToBinary {
-  #[allow(dead_code)]
-  pub unsafe fn on_destroy(this: *mut ReferenceToBinary) {
-    if (*this).refc > 0 {
-      (*this).refc -= 1;
-      return;
-    }
-  }
-}
@@ -0,0 +1,36 @@
+use crate::{
+  defs::ByteSize,
+  term::{boxed, lterm::LTerm},
+};
+
+/// Defines a sub-binary, a view into part of another binary's data, which
+/// can also begin and end at any bit (a bitstring).
+/// Pointer to this can be directly casted from pointer to boxed::Binary
+#[repr(C)]
+pub struct SubBinary {
+  pub bin: boxed::binary::Binary,
+  /// Whole bytes in the sub-binary
+  pub size: ByteSize,
+  /// Count of bits in addition to `size` (0..7)
+  pub bit_size: usize,
+  /// Offset of the first byte in the original binary
+  pub offset: ByteSize,
+  /// Count of bits in addition to `offset` (0..7)
+  pub bit_offset: usize,
+  /// The binary which owns the data, a process heap binary or a reference to
+  /// binary heap but never another sub-binary. Stored as a term, so that GC
+  /// will keep it alive.
+  pub orig: LTerm,
+}
+
+impl SubBinary {
+  #[inline]
+  pub fn get_bit_size(&self) -> usize {
+    self.size.bytes() * 8 + self.bit_size
+  }
+
+  #[inline]
+  pub fn get_bit_offset(&self) -> usize {
+    self.offset.bytes() * 8 + self.bit_offset
+  }
+}
@@ -33,6 +33,7 @@ pub const BOXTYPETAG_EXPORT: BoxTypeTag = BoxTypeTag(8);
 pub const BOXTYPETAG_MAP: BoxTypeTag = BoxTypeTag(9);
 
 pub const BOXTYPETAG_BINARY: BoxTypeTag = BoxTypeTag(10);
+pub const BOXTYPETAG_LOCALREF: BoxTypeTag = BoxTypeTag(11);
 // max 15 (1 << HEADER_TAG_BITS)
 
 const BOXTYPE_TAG_BITS: Word = 4;
@@ -40,6 +41,8 @@ const BOXTYPE_TAG_BITS: Word = 4;
 const BOXTYPE_TAG_MASK: Word = (1 << BOXTYPE_TAG_BITS) - 1;
 
 /// Term header in memory, followed by corresponding data.
+/// Arity is the count of memory words which follow the header word, this is
+/// used by heap walkers (such as the garbage collector) to step over the data.
 pub struct BoxHeader {
   /// Format is <arity> <boxtype:BOXTYPE_TAG_BITS> <TAG_HEADER:TERM_TAG_BITS>
   header_word: Word,
@@ -21,6 +21,7 @@ const fn module() -> &'static str {
 
 /// Boxed `Closure` is placed on heap and referred via LTerm::p
 #[allow(dead_code)]
+#[repr(C)]
 pub struct Closure {
   pub header: BoxHeader,
 
@@ -33,7 +34,7 @@ pub struct Closure {
 
 impl Closure {
   #[inline]
-  const fn storage_size(nfrozen: Word) -> WordSize {
+  pub const fn storage_size(nfrozen: Word) -> WordSize {
     ByteSize::new(size_of::<Closure>())
       .words_rounded_up()
       .add(nfrozen)
@@ -10,6 +10,7 @@ use crate::{
 use core::{mem::size_of, ptr};
 
 #[allow(dead_code)]
+#[repr(C)]
 pub struct Export {
   header: BoxHeader,
   pub exp: export::Export,
@@ -24,12 +25,11 @@ impl Export {
   fn new(mfa: &MFArity) -> Export {
     let n_words = Export::storage_size();
     Export {
-      header: BoxHeader::new(BOXTYPETAG_EXPORT, n_words.words()),
+      header: BoxHeader::new(BOXTYPETAG_EXPORT, n_words.words() - 1),
       exp: export::Export::new(*mfa),
     }
   }
 
-  #[allow(dead_code)]
   pub unsafe fn create_into(hp: &mut Heap, mfa: &MFArity) -> RtResult<LTerm> {
     let n_words = Export::storage_size();
     let this = hp.alloc::<Export>(n_words, false)?;
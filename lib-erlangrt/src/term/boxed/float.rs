@@ -7,6 +7,7 @@ use crate::{
 use core::{mem::size_of, ptr};
 
 #[allow(dead_code)]
+#[repr(C)]
 pub struct Float {
   header: BoxHeader,
   pub value: f64,
@@ -14,7 +15,7 @@ pub struct Float {
 
 impl Float {
   #[allow(dead_code)]
-  const fn storage_size() -> WordSize {
+  pub const fn storage_size() -> WordSize {
     ByteSize::new(core::mem::size_of::<Float>()).words_rounded_up()
   }
 
@@ -22,7 +23,7 @@ impl Float {
   fn new(value: f64) -> Float {
     let storage_size = ByteSize::new(size_of::<Float>()).words_rounded_up();
     Float {
-      header: BoxHeader::new(BOXTYPETAG_FLOAT, storage_size.words()),
+      header: BoxHeader::new(BOXTYPETAG_FLOAT, storage_size.words() - 1),
       value,
     }
   }
@@ -11,6 +11,7 @@ use crate::{
 use core::{mem::size_of, ptr};
 
 #[allow(dead_code)]
+#[repr(C)]
 pub struct Import {
   header: BoxHeader,
   pub mfarity: MFArity,
@@ -33,7 +34,7 @@ impl Import {
     ptr::write(
       this,
       Import {
-        header: BoxHeader::new(BOXTYPETAG_IMPORT, n_words.words()),
+        header: BoxHeader::new(BOXTYPETAG_IMPORT, n_words.words() - 1),
         mfarity,
         is_bif,
       },
@@ -0,0 +1,133 @@
+use crate::{
+  defs::{Word, WordSize},
+  emulator::heap::Heap,
+  fail::RtResult,
+  term::{
+    boxed::{BoxHeader, BOXTYPETAG_MAP},
+    lterm::LTerm,
+  },
+};
+use core::{ptr, slice};
+
+/// Maps up to this size are stored flat, bigger maps become a HAMT.
+pub const MAP_SMALL_MAP_LIMIT: usize = 32;
+
+/// How many bits of the key hash are used for each level of a HAMT.
+pub const HAMT_BITS_PER_LEVEL: usize = 4;
+
+/// After this many levels the 32-bit hash is used up and the keys which still
+/// share a slot are stored in a collision node.
+pub const HAMT_MAX_DEPTH: usize = 32 / HAMT_BITS_PER_LEVEL;
+
+const MAP_KIND_BITS: usize = 2;
+const MAP_KIND_MASK: usize = (1 << MAP_KIND_BITS) - 1;
+
+/// All map objects share the box tag, the kind is stored in the info word.
+#[derive(Debug, Eq, PartialEq, Copy, Clone)]
+pub enum MapKind {
+  /// A small map: info, sorted keys tuple, then values in the key order.
+  Flat = 0,
+  /// The root of a HAMT: info, bitmap of used slots, then children.
+  HashHead = 1,
+  /// An inner node of a HAMT: info with the bitmap, then children.
+  HashNode = 2,
+  /// Keys which have the same hash: info, then leaves.
+  Collision = 3,
+}
+
+/// A map object in memory. Every word which follows the header is a term (the
+/// info word is a small integer) so the garbage collector and term copying do
+/// not need to know the layout. The info word contains `MapKind` and a payload
+/// which is the map size for `Flat` and `HashHead`, the bitmap for `HashNode`
+/// and the count of leaves for `Collision`.
+///
+/// Children of HAMT nodes are either other nodes, or leaves which are cons
+/// cells `[Key | Value]`. Nodes are never modified after creation, updates copy
+/// the path from the root to the changed leaf.
+#[repr(C)]
+pub struct Map {
+  header: BoxHeader,
+  info: LTerm,
+}
+
+impl Map {
+  /// Size of a map object in memory with the header and the info word.
+  #[inline]
+  pub const fn storage_size(n_fields: usize) -> WordSize {
+    WordSize::new(n_fields + 1 + BoxHeader::storage_size().words())
+  }
+
+  /// Allocate a map object of `kind` followed by `fields`.
+  pub fn create_into(
+    hp: &mut Heap,
+    kind: MapKind,
+    payload: usize,
+    fields: &[LTerm],
+  ) -> RtResult<LTerm> {
+    let p = hp.alloc::<Map>(Self::storage_size(fields.len()), false)?;
+    let info = (payload << MAP_KIND_BITS) | kind as usize;
+    unsafe {
+      ptr::write(
+        p,
+        Map {
+          header: BoxHeader::new(BOXTYPETAG_MAP, fields.len() + 1),
+          info: LTerm::make_small_unsigned(info),
+        },
+      );
+      let fields_p = p.add(1) as *mut LTerm;
+      ptr::copy_nonoverlapping(fields.as_ptr(), fields_p, fields.len());
+    }
+    Ok(LTerm::make_boxed(p))
+  }
+
+  pub unsafe fn get_kind(this: *const Map) -> MapKind {
+    match (*this).info.get_small_unsigned() & MAP_KIND_MASK {
+      0 => MapKind::Flat,
+      1 => MapKind::HashHead,
+      2 => MapKind::HashNode,
+      _ => MapKind::Collision,
+    }
+  }
+
+  #[inline]
+  pub unsafe fn get_payload(this: *const Map) -> usize {
+    (*this).info.get_small_unsigned() >> MAP_KIND_BITS
+  }
+
+  /// The words which follow the info word.
+  pub unsafe fn get_fields<'a>(this: *const Map) -> &'a [LTerm] {
+    let n_fields = (*this).header.get_arity() - 1;
+    slice::from_raw_parts(this.add(1) as *const LTerm, n_fields)
+  }
+
+  /// Count of key-value pairs in a map (a flatmap or a HAMT head).
+  pub unsafe fn get_size(this: *const Map) -> usize {
+    debug_assert!(matches!(
+      Self::get_kind(this),
+      MapKind::Flat | MapKind::HashHead
+    ));
+    Self::get_payload(this)
+  }
+
+  /// For a flatmap return the sorted keys and the values.
+  pub unsafe fn get_flat_keys_values<'a>(this: *const Map) -> (&'a [LTerm], &'a [LTerm]) {
+    debug_assert_eq!(Self::get_kind(this), MapKind::Flat);
+    let fields = Self::get_fields(this);
+    let keys = fields[0];
+    if keys == LTerm::empty_tuple() {
+      return (&[], &[]);
+    }
+    let keys_p = keys.get_box_ptr::<Word>().add(1) as *const LTerm;
+    (slice::from_raw_parts(keys_p, fields.len() - 1), &fields[1..])
+  }
+
+  /// For a HAMT head or an inner node return the bitmap and the children.
+  pub unsafe fn get_bitmap_children<'a>(this: *const Map) -> (usize, &'a [LTerm]) {
+    let fields = Self::get_fields(this);
+    match Self::get_kind(this) {
+      MapKind::HashHead => (fields[0].get_small_unsigned(), &fields[1..]),
+      MapKind::HashNode => (Self::get_payload(this), fields),
+      kind => panic!("Map kind {:?} has no bitmap", kind),
+    }
+  }
+}
@@ -28,5 +28,47 @@ pub use self::tuple::Tuple;
 pub mod cons;
 pub use self::cons::Cons;
 
+pub mod reference;
+pub use self::reference::Reference;
+
+pub mod map;
+pub use self::map::Map;
+
 pub mod box_header;
 pub use self::box_header::*;
+
+use crate::defs::Word;
+
+/// For a boxed object at `p` call `f` with address of every word which
+/// contains a term, these are the fields to update when the object is moved
+/// or copied. Returns the object size in words including the header.
+pub unsafe fn for_each_term_field<F>(p: *mut Word, mut f: F) -> usize
+where
+  F: FnMut(*mut Word),
+{
+  let header_word = core::ptr::read(p);
+  let arity = headerword_to_arity(header_word);
+  let size = 1 + arity;
+  let fields = match headerword_to_boxtype(header_word) {
+    BOXTYPETAG_TUPLE => 1..size,
+    // All map words are terms, including the info word which is a small
+    BOXTYPETAG_MAP => 1..size,
+    // Only the node field in an external pid is a term, then goes the id
+    BOXTYPETAG_EXTERNALPID => 1..2,
+    // Frozen values follow the closure struct
+    BOXTYPETAG_CLOSURE => Closure::storage_size(0).words()..size,
+    BOXTYPETAG_BINARY => {
+      // A sub-binary refers to its original binary
+      if let Some(orig_p) = Binary::get_orig_term_mut(p as *mut Binary) {
+        f(orig_p as *mut Word);
+      }
+      return size;
+    }
+    // Other boxed types do not contain any terms
+    _ => return size,
+  };
+  for i in fields {
+    f(p.add(i));
+  }
+  size
+}
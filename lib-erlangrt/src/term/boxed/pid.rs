@@ -10,6 +10,7 @@ use crate::{
 use core::{mem::size_of, ptr};
 
 /// Represents Pid box on heap.
+#[repr(C)]
 pub struct ExternalPid {
   pub header: BoxHeader,
   pub node: LTerm,
@@ -0,0 +1,46 @@
+use crate::{
+  defs::{ByteSize, Word, WordSize},
+  emulator::heap::Heap,
+  fail::RtResult,
+  term::{
+    boxed::{BoxHeader, BOXTYPETAG_LOCALREF},
+    lterm::LTerm,
+  },
+};
+use core::{mem::size_of, ptr};
+
+/// A local reference, unique within the VM. The id is taken from a VM-wide
+/// counter (see `VMShared::next_ref_id`).
+#[repr(C)]
+pub struct Reference {
+  header: BoxHeader,
+  pub id: Word,
+}
+
+impl Reference {
+  pub const fn storage_size() -> WordSize {
+    ByteSize::new(size_of::<Reference>()).words_rounded_up()
+  }
+
+  fn new(id: Word) -> Reference {
+    let arity = Reference::storage_size().words() - 1;
+    Reference {
+      header: BoxHeader::new(BOXTYPETAG_LOCALREF, arity),
+      id,
+    }
+  }
+
+  /// Allocate a reference on heap and return the term pointing to it.
+  pub fn create_into(hp: &mut Heap, id: Word) -> RtResult<LTerm> {
+    let p = hp.alloc::<Reference>(Reference::storage_size(), false)?;
+    unsafe { ptr::write(p, Reference::new(id)) }
+    Ok(LTerm::make_boxed(p))
+  }
+
+  /// Read the id of a local reference, the caller must check `is_local_ref`.
+  pub fn get_id(term: LTerm) -> Word {
+    debug_assert!(term.is_local_ref());
+    let p = term.get_box_ptr::<Reference>();
+    unsafe { (*p).id }
+  }
+}
@@ -10,6 +10,7 @@ use crate::{
 
 /// A fixed-size array which stores everything in its allocated memory on
 /// process heap.
+#[repr(C)]
 pub struct Tuple {
   header: BoxHeader,
 }
@@ -38,6 +38,7 @@ pub enum TermClass {
   Pid,
   Tuple,
   Map,
+  Nil,
   List,
   Binary,
   // Means the value should not be used in comparisons but here it is anyway
@@ -69,7 +70,7 @@ fn classify_special(val: LTerm) -> TermClass {
   match val.get_special_tag() {
     SPECIALTAG_CONST => {
       if val == LTerm::nil() {
-        TermClass::List
+        TermClass::Nil
       } else if val == LTerm::empty_binary() {
         TermClass::Binary
       } else if val == LTerm::empty_tuple() {
@@ -90,11 +91,12 @@ unsafe fn classify_boxed(val: LTerm) -> TermClass {
   let box_tag = (*val_box_ptr).get_tag();
   match box_tag {
     boxed::BOXTYPETAG_TUPLE => TermClass::Tuple,
+    boxed::BOXTYPETAG_MAP => TermClass::Map,
     boxed::BOXTYPETAG_BINARY => TermClass::Binary,
     boxed::BOXTYPETAG_EXTERNALPID => TermClass::Pid,
-    boxed::BOXTYPETAG_EXTERNALREF => TermClass::Ref,
-    boxed::BOXTYPETAG_CLOSURE => TermClass::Fun,
-    boxed::BOXTYPETAG_FLOAT => TermClass::Number,
+    boxed::BOXTYPETAG_EXTERNALREF | boxed::BOXTYPETAG_LOCALREF => TermClass::Ref,
+    boxed::BOXTYPETAG_CLOSURE | boxed::BOXTYPETAG_EXPORT => TermClass::Fun,
+    boxed::BOXTYPETAG_FLOAT | boxed::BOXTYPETAG_BIGINTEGER => TermClass::Number,
     _ => panic!(
       "classify: Unexpected boxed_tag={:?} raw={}",
       box_tag,
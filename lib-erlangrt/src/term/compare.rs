@@ -1,64 +1,97 @@
 use crate::{
-  emulator::atom,
+  defs::Word,
+  emulator::{arith::big::BigInteger, atom, gen_atoms, mfa::MFArity},
   fail::RtResult,
-  term::{classify, lterm::*},
+  term::{
+    boxed,
+    classify::{self, TermClass},
+    lterm::*,
+    map,
+  },
 };
 use core::cmp::Ordering;
 
-/// When comparing nested terms they might turn out to be equal. `CompareOp`
-/// is stored in `stack` in `eq_terms()` function and tells where to resume
-/// comparing the previous term.
-#[allow(dead_code)]
+/// When comparing nested terms they might turn out to be equal. A
+/// `ContinueCompare` is stored on the helper stack in `cmp_terms()` and tells
+/// where to resume comparing the previous term.
 enum ContinueCompare {
   // This begins the compare while not knowing types for `a` or `b`.
   AnyType { a: LTerm, b: LTerm },
-  // Resume comparing Cons cells, we just reenter `eq_terms_cons`.
+  // Resume comparing Cons cells, we just reenter `cmp_terms_any_type`.
   Cons { a: LTerm, b: LTerm },
+  // Resume comparing tuple elements starting from `index`.
+  Tuple { a: LTerm, b: LTerm, index: usize },
+  // Resume comparing two sequences of terms (keys then values of two maps in
+  // their key order or frozen values of two closures) from `index`. The first
+  // `exact_count` elements are always compared exactly.
+  Elements {
+    a: Vec<LTerm>,
+    b: Vec<LTerm>,
+    index: usize,
+    exact_count: usize,
+  },
 }
 
-#[allow(dead_code)]
 enum EqResult {
   /// Equality result is concluded to be the `bool` value.
   Concluded(Ordering),
   /// Equality is not concluded, but comparing these two terms will give the
   /// result (equivalent to `goto tailrecur_ne` in Erlang/OTP). This happens
   /// when a first element of a nested structure compares equal but some
-  /// members remain to be checked recursively.
+  /// members remain to be checked recursively. With `exact` the nested terms
+  /// are compared exactly, no matter how the outer terms are compared.
   CompareNested {
     a: LTerm,
     b: LTerm,
+    exact: bool,
     state: ContinueCompare,
   },
+  /// Nothing remains to be checked in the current terms, the result is given
+  /// by comparing these two terms (equivalent to `goto tailrecur` in
+  /// Erlang/OTP).
+  CompareTail { a: LTerm, b: LTerm },
 }
 
-/// Compare two terms for equality, fail if types are different even if
-/// coercion is otherwise possible.
+/// Compare two terms in the standard Erlang term order:
+/// number < atom < reference < fun < port < pid < tuple < map < nil < list <
+/// bit string. With `exact` an integer and a float are never equal (like in
+/// `=:=`), otherwise they are compared by value (like in `==`).
 pub fn cmp_terms(a: LTerm, b: LTerm, exact: bool) -> RtResult<Ordering> {
   // Comparison might want to recurse.
   // To avoid stack growth, do a switch here and continue comparing in a loop.
-  // We grow `stack` vector instead of a CPU stack.
+  // We grow `stack` vector instead of a CPU stack. Each stored op remembers
+  // whether it compares exactly.
   const DEFAULT_CAPACITY: usize = 8;
-  let mut stack = Vec::<ContinueCompare>::with_capacity(DEFAULT_CAPACITY);
+  let mut stack = Vec::<(ContinueCompare, bool)>::with_capacity(DEFAULT_CAPACITY);
   let mut op = ContinueCompare::AnyType { a, b };
+  let mut op_exact = exact;
 
   // The main comparison loop which is able to step deeper into recursive structures
   loop {
     let eq_result = match op {
       ContinueCompare::AnyType { a: a1, b: b1 }
-      | ContinueCompare::Cons { a: a1, b: b1 } => cmp_terms_any_type(a1, b1, exact)?,
+      | ContinueCompare::Cons { a: a1, b: b1 } => {
+        cmp_terms_any_type(a1, b1, op_exact)?
+      }
+      ContinueCompare::Tuple { a: a1, b: b1, index } => unsafe {
+        cmp_tuples(a1, b1, index, op_exact)
+      },
+      ContinueCompare::Elements {
+        a: a1,
+        b: b1,
+        index,
+        exact_count,
+      } => cmp_elements(a1, b1, index, exact_count, op_exact),
     };
 
     match eq_result {
-      EqResult::Concluded(result) if result == Ordering::Equal => {
-        if stack.is_empty() {
-          // println!("comparison {} {} concluded {:?}", a, b, result);
-          return Ok(result);
-        } else {
-          // println!("comparison {} {} got intermediate result {:?}", a, b, result);
-          op = stack.pop().unwrap();
-          continue;
-        } // stack not empty
-      }
+      EqResult::Concluded(Ordering::Equal) => match stack.pop() {
+        None => return Ok(Ordering::Equal),
+        Some((continue_op, continue_exact)) => {
+          op = continue_op;
+          op_exact = continue_exact;
+        }
+      },
 
       EqResult::Concluded(result) => return Ok(result),
 
@@ -68,11 +101,16 @@ pub fn cmp_terms(a: LTerm, b: LTerm, exact: bool) -> RtResult<Ordering> {
       EqResult::CompareNested {
         a: a3,
         b: b3,
+        exact: nested_exact,
         state: continue_op,
       } => {
-        stack.push(continue_op);
+        stack.push((continue_op, op_exact));
+        op = ContinueCompare::AnyType { a: a3, b: b3 };
+        op_exact = nested_exact;
+      }
+
+      EqResult::CompareTail { a: a3, b: b3 } => {
         op = ContinueCompare::AnyType { a: a3, b: b3 };
-        continue;
       }
     }
   }
@@ -97,25 +135,65 @@ fn cmp_terms_any_type(a: LTerm, b: LTerm, exact: bool) -> RtResult<EqResult> {
     return Ok(EqResult::Concluded(a_small.cmp(&b_small)));
   }
 
+  // Integers compare by value, no matter if they are small or bignums
+  if a.is_integer() && b.is_integer() {
+    return Ok(EqResult::Concluded(cmp_integers(a, b)));
+  }
+
   // Maybe some of a and b are floats
   let a_is_float = a.is_float();
   let b_is_float = b.is_float();
 
-  // If not exact then allow comparing float to int/bigint
-  if !exact && (a_is_float || a_is_small) && (b_is_float || b_is_small) {
-    return Ok(EqResult::Concluded(cmp_numbers_not_exact(a, b)));
-  } else if a_is_float && b_is_float {
+  if a_is_float && b_is_float {
     return Ok(EqResult::Concluded(cmp_floats(a, b)));
   }
 
-  // If types don't compare equal, we can stop comparing here?
-  // TODO: Except when we compare numbers
-  //  let order = cmp_type_order(a, b);
-  //  if order != Ordering::Equal {
-  //    return EqResult::Concluded(order);
-  //  }
+  // A float and an integer compare by value. In exact comparison they are
+  // never equal, on equal values the integer goes first.
+  if (a_is_float && b.is_integer()) || (a.is_integer() && b_is_float) {
+    let order = cmp_numbers_not_exact(a, b);
+    if exact && order == Ordering::Equal {
+      return Ok(EqResult::Concluded(if a_is_float {
+        Ordering::Greater
+      } else {
+        Ordering::Less
+      }));
+    }
+    return Ok(EqResult::Concluded(order));
+  }
+
+  // Terms of different types are ordered by their type only
+  let a_class = classify::classify_term(a);
+  let order = a_class.cmp(&classify::classify_term(b));
+  if order != Ordering::Equal {
+    return Ok(EqResult::Concluded(order));
+  }
 
-  cmp_terms_primary(a, b, exact)
+  match a_class {
+    TermClass::Ref => Ok(EqResult::Concluded(cmp_refs(a, b))),
+    TermClass::Fun => Ok(unsafe { cmp_funs(a, b, exact) }),
+    TermClass::Port => Ok(EqResult::Concluded(cmp_ports(a, b))),
+    TermClass::Pid => Ok(EqResult::Concluded(cmp_pids(a, b))),
+    TermClass::Tuple => Ok(unsafe { cmp_tuples(a, b, 0, exact) }),
+    TermClass::Map => cmp_maps(a, b, exact),
+    TermClass::Nil => Ok(EqResult::Concluded(Ordering::Equal)),
+    TermClass::List => Ok(unsafe { cmp_cons(a, b, exact) }),
+    TermClass::Binary => {
+      let result = unsafe { boxed::Binary::cmp_terms(a, b) }?;
+      Ok(EqResult::Concluded(result))
+    }
+    // Numbers and atoms are already compared above, the rest are not values
+    TermClass::Number | TermClass::Atom | TermClass::Special_ => {
+      panic!("cmp_terms: can't compare {} and {}", a, b)
+    }
+  }
+}
+
+/// Compare two integers, each can be a small or a bignum.
+fn cmp_integers(a: LTerm, b: LTerm) -> Ordering {
+  let a_int = BigInteger::from_term(a).unwrap();
+  let b_int = BigInteger::from_term(b).unwrap();
+  a_int.cmp(&b_int)
 }
 
 #[inline]
@@ -140,12 +218,28 @@ fn cmp_f64_naive(a: f64, b: f64) -> Ordering {
   Ordering::Equal
 }
 
-fn cmp_numbers_not_exact(_a: LTerm, _b: LTerm) -> Ordering {
-  panic!("TODO: eq_numbers_not_exact")
+/// Compare an integer and a float by value. The integer part of the float is
+/// compared exactly (a bignum can be bigger than any float precision), then
+/// its fraction decides.
+fn cmp_numbers_not_exact(a: LTerm, b: LTerm) -> Ordering {
+  if a.is_float() {
+    return cmp_numbers_not_exact(b, a).reverse();
+  }
+  let b_float = unsafe { b.get_f64_unsafe() };
+  let b_int_part = b_float.trunc();
+  let a_int = BigInteger::from_term(a).unwrap();
+  match a_int.cmp(&BigInteger::from_f64_trunc(b_int_part)) {
+    Ordering::Equal => cmp_f64_naive(0.0, b_float - b_int_part),
+    order => order,
+  }
 }
 
-/// Compare two atoms for equality. Returns the ordering result.
+/// Atoms are ordered by their text. The first bytes packed in `ord0` are
+/// compared first, the names are compared only if these are equal.
 fn cmp_atoms(a: LTerm, b: LTerm) -> Ordering {
+  if a == b {
+    return Ordering::Equal;
+  }
   assert_ne!(a, LTerm::nil());
   let atomp_a = atom::lookup(a);
   debug_assert!(!atomp_a.is_null(), "cmp_atoms: atom lookup {} failed", a);
@@ -156,231 +250,73 @@ fn cmp_atoms(a: LTerm, b: LTerm) -> Ordering {
 
   // This should really be safe, as pointers to Atom exist statically forever
   unsafe {
-    let a_len = (*atomp_a).len;
-    let b_len = (*atomp_b).len;
-    if a_len == b_len {
-      return (*atomp_a).name.cmp(&(*atomp_b).name);
-    }
-    a_len.cmp(&b_len)
+    (*atomp_a)
+      .ord0
+      .cmp(&(*atomp_b).ord0)
+      .then_with(|| (*atomp_a).name.cmp(&(*atomp_b).name))
   }
 }
 
-/// Compare order of two types without looking into their value.
-fn cmp_type_order(a: LTerm, b: LTerm) -> Ordering {
-  let aclass = classify::classify_term(a);
-  let bclass = classify::classify_term(b);
-  aclass.cmp(&bclass)
+/// Only local references exist in this VM, they are ordered by their id.
+fn cmp_refs(a: LTerm, b: LTerm) -> Ordering {
+  boxed::Reference::get_id(a).cmp(&boxed::Reference::get_id(b))
 }
 
-/// Switch between comparisons for equality by primary tag (immediate or boxes
-/// or fail immediately for different primary tags).
-fn cmp_terms_primary(a: LTerm, b: LTerm, exact: bool) -> RtResult<EqResult> {
-  //  let a_val = a.raw();
-  let a_prim_tag = a.get_term_tag();
-
-  //  let b_val = b.raw();
-  let b_prim_tag = b.get_term_tag();
-  if b_prim_tag != a_prim_tag {
-    // different primary types, compare their classes
-    // This can be optimized a little but is there any value in optimization?
-    return Ok(EqResult::Concluded(cmp_type_order(a, b)));
-  }
-
-  match a_prim_tag {
-    TERMTAG_BOXED => {
-      if a.is_cp() || b.is_cp() {
-        panic!("eq_terms for CP is unsupported")
-      }
-      cmp_terms_box(a, b)
-    }
-
-    TERMTAG_CONS => {
-      if !b.is_cons() {
-        return Ok(EqResult::Concluded(cmp_mixed_types(a, b)?));
-      }
-
-      Ok(unsafe { cmp_cons(a, b) })
-    }
-
-    _ => {
-      // Any non-boxed compare
-      Ok(EqResult::Concluded(cmp_terms_immed(a, b, exact)?))
-    } //_ => panic!("Primary tag {:?} eq_terms unsupported", a_prim_tag)
-  }
+/// Only local ports exist in this VM, they are ordered by their id.
+fn cmp_ports(a: LTerm, b: LTerm) -> Ordering {
+  a.get_term_val_without_tag().cmp(&b.get_term_val_without_tag())
 }
 
-// TODO: If this function is used a lot, optimize by doing case on tag bits
-fn cmp_terms_immed(a: LTerm, b: LTerm, _exact: bool) -> RtResult<Ordering> {
-  //  let av = a.raw();
-  //  let bv = b.raw();
-
-  if (a == LTerm::nil() || a == LTerm::empty_tuple() || a == LTerm::empty_binary())
-    && (a.raw() == b.raw())
-  {
-    return Ok(Ordering::Equal);
-  }
-
-  if a.is_local_port() {
-    if b.is_local_port() {
-      panic!("TODO: cmp local vs local port")
-    } else if b.is_external_port() {
-      panic!("TODO: cmp local vs ext port")
-    } else {
-      return cmp_mixed_types(a, b);
-    }
-  }
-
-  if a.is_local_pid() {
-    if b.is_local_pid() {
-      // Concluded by comparing raw values
-      return Ok(a.raw().cmp(&b.raw()));
-    } else if b.is_external_pid() {
-      panic!("TODO: cmp local vs ext pid")
-    } else {
-      return cmp_mixed_types(a, b);
-    }
-  }
-
-  if a.is_boxed() {
-    return cmp_terms_immed_box(a, b);
+/// Pids are ordered by their node name and then by their id. Local pids
+/// belong to the local node.
+fn cmp_pids(a: LTerm, b: LTerm) -> Ordering {
+  let (a_node, a_id) = get_pid_node_and_id(a);
+  let (b_node, b_id) = get_pid_node_and_id(b);
+  if a_node != b_node {
+    return cmp_atoms(a_node, b_node);
   }
+  a_id.cmp(&b_id)
+}
 
-  // if both are internal immediates, compare their raw values or their tags
-  if a.is_internal_immediate() && b.is_internal_immediate() {
-    let a_tag = a.get_term_tag();
-    let b_tag = b.get_term_tag();
-
-    // If tags are same, we can conclude with comparing their values
-    if a_tag == b_tag {
-      let a_val = a.get_term_val_without_tag();
-      let b_val = b.get_term_val_without_tag();
-      return Ok(a_val.cmp(&b_val));
-    }
-    return Ok(a_tag.cmp(&b_tag));
+fn get_pid_node_and_id(pid: LTerm) -> (LTerm, Word) {
+  if pid.is_local_pid() {
+    return (gen_atoms::NONODE_AT_NOHOST, pid.get_term_val_without_tag());
   }
-
-  panic!("TODO: eq_terms_immed {} {}", a, b)
+  let pid_p = pid.get_box_ptr::<boxed::ExternalPid>();
+  unsafe { ((*pid_p).node, (*pid_p).id) }
 }
 
-#[inline]
-fn cmp_terms_immed_box(a: LTerm, b: LTerm) -> RtResult<Ordering> {
-  if a.is_tuple() {
-    if b.is_tuple() {
-      panic!("TODO: cmp tuple vs tuple")
-    } else {
-      return cmp_mixed_types(a, b);
-    }
-  } else if a.is_map() {
-    if a.is_flat_map() {
-      if !b.is_flat_map() {
-        if b.is_hash_map() {
-          let b_size = b.map_size();
-          return Ok(a.map_size().cmp(&b_size));
-        }
-      } else {
-        // Compare two flat maps
-        panic!("TODO: cmp flatmap vs flatmap (+exact)")
-      }
-    } else if !b.is_hash_map() {
-      if b.is_flat_map() {
-        let b_size = b.map_size();
-        return Ok(a.map_size().cmp(&b_size));
-      }
-    } else {
-      // Compare two hash maps
-      panic!("TODO: cmp flatmap vs flatmap (+exact)")
+/// Closures (local funs) go before exports. Exports are ordered by their
+/// module, function and arity. Closures are ordered by the same for the
+/// function which implements them, then by the count of frozen values and
+/// then by the frozen values.
+unsafe fn cmp_funs(a: LTerm, b: LTerm, exact: bool) -> EqResult {
+  match (a.is_export(), b.is_export()) {
+    (false, true) => return EqResult::Concluded(Ordering::Less),
+    (true, false) => return EqResult::Concluded(Ordering::Greater),
+    (true, true) => {
+      let a_p = a.get_box_ptr::<boxed::Export>();
+      let b_p = b.get_box_ptr::<boxed::Export>();
+      return EqResult::Concluded(cmp_mfas(&(*a_p).exp.mfa, &(*b_p).exp.mfa));
     }
-
-  // Hashmap compare strategy:
-  // Phase 1. While keys are identical
-  //    Do synchronous stepping through leafs of both trees in hash
-  //    order. Maintain value compare result of minimal key.
-  //
-  // Phase 2. If key diff was found in phase 1
-  //    Ignore values from now on.
-  //    Continue iterate trees by always advancing the one
-  //    lagging behind hash-wise. Identical keys are skipped.
-  //    A minimal key can only be candidate as tie-breaker if we
-  //    have passed that hash value in the other tree (which means
-  //    the key did not exist in the other tree).
-  } else if a.is_float() {
-    if !b.is_float() {
-      // TODO: If b is integer and we don't do exact comparison?
-      return cmp_mixed_types(a, b);
-    } else {
-      let a_float = a.get_f64()?;
-      let b_float = b.get_f64()?;
-      return Ok(a_float.partial_cmp(&b_float).unwrap());
-    }
-  } else if a.is_big_int() {
-    if !b.is_big_int() {
-      return cmp_mixed_types(a, b);
-    }
-  } else if a.is_export() {
-    if !b.is_export() {
-      return cmp_mixed_types(a, b);
-    }
-    // Compare two exports: from utils.c line ~2918
-    // cmp atoms a.module and b.module
-    // cmp atoms a.fn and b.fn
-    // cmp arity
-    panic!("TODO compare 2 exports")
-  } else if a.is_boxed() {
-    if !a.is_fun() {
-      return cmp_mixed_types(a, b);
-    }
-    // Compare 2 function objects: from utils.c line ~2937
-    // compare a.module, b.module
-    // compare old_index
-    // compare old_uniq
-    // compare num_Free
-    panic!("TODO compare 2 fun objects")
-  } else if a.is_external_pid() {
-    if b.is_local_pid() {
-      panic!("TODO compare ext vs local pid")
-    } else if b.is_external_pid() {
-      panic!("TODO compare ext vs ext pid")
-    } else {
-      return cmp_mixed_types(a, b);
-    }
-  } else if a.is_external_port() {
-    if b.is_local_port() {
-      panic!("TODO compare ext vs local port")
-    } else if b.is_external_port() {
-      panic!("TODO compare ext vs ext port")
-    } else {
-      return cmp_mixed_types(a, b);
-    }
-  } else if a.is_local_ref() {
-    if b.is_local_ref() {
-      panic!("TODO compare local vs local ref")
-    } else if b.is_external_ref() {
-      panic!("TODO compare local vs ext ref")
-    } else {
-      return cmp_mixed_types(a, b);
-    }
-  } else if a.is_external_ref() {
-    if b.is_local_ref() {
-      panic!("TODO compare ext vs local ref")
-    } else if b.is_external_ref() {
-      panic!("TODO compare ext vs ext ref")
-    } else {
-      return cmp_mixed_types(a, b);
-    }
-  } else {
-    // must be a binary
-    assert!(a.is_binary());
-    if !b.is_binary() {
-      return cmp_mixed_types(a, b);
-    }
-    panic!("TODO cmp binaries")
+    (false, false) => {}
+  }
+  let a_p = a.get_box_ptr::<boxed::Closure>();
+  let b_p = b.get_box_ptr::<boxed::Closure>();
+  let order = cmp_mfas(&(*a_p).mfa, &(*b_p).mfa)
+    .then_with(|| (*a_p).nfrozen.cmp(&(*b_p).nfrozen));
+  if order != Ordering::Equal {
+    return EqResult::Concluded(order);
   }
-  panic!("TODO: eq_terms_immed_box {} {}", a, b)
+  let a_frozen = boxed::Closure::get_frozen(a_p).to_vec();
+  let b_frozen = boxed::Closure::get_frozen(b_p).to_vec();
+  cmp_elements(a_frozen, b_frozen, 0, 0, exact)
 }
 
-/// Deeper comparison of two values with different types
-fn cmp_mixed_types(_a: LTerm, _b: LTerm) -> RtResult<Ordering> {
-  panic!("TODO: cmp_mixed_types(a, b)")
+fn cmp_mfas(a: &MFArity, b: &MFArity) -> Ordering {
+  cmp_atoms(a.m, b.m)
+    .then_with(|| cmp_atoms(a.f, b.f))
+    .then_with(|| a.arity.cmp(&b.arity))
 }
 
 /// Compare two cons (list) cells.
@@ -388,7 +324,7 @@ fn cmp_mixed_types(_a: LTerm, _b: LTerm) -> RtResult<Ordering> {
 /// we will store the position and return `EqResult::CompareNested`.
 /// This will be pushed to a helper stack by the caller (`cmp_terms()`).
 /// The function cannot fail.
-unsafe fn cmp_cons(a: LTerm, b: LTerm) -> EqResult {
+unsafe fn cmp_cons(a: LTerm, b: LTerm, exact: bool) -> EqResult {
   let mut a_ptr = a.get_cons_ptr();
   let mut b_ptr = b.get_cons_ptr();
 
@@ -406,6 +342,7 @@ unsafe fn cmp_cons(a: LTerm, b: LTerm) -> EqResult {
       return EqResult::CompareNested {
         a: a_head,
         b: b_head,
+        exact,
         state: continue_op,
       };
     }
@@ -417,14 +354,10 @@ unsafe fn cmp_cons(a: LTerm, b: LTerm) -> EqResult {
     if LTerm::is_same(atl, btl) {
       return EqResult::Concluded(Ordering::Equal);
     }
-    if !atl.is_list() || !btl.is_list() {
-      // Just do a regular compare of `a.tl` vs `b.tl`
-      let continue_op = ContinueCompare::AnyType { a: atl, b: btl };
-      return EqResult::CompareNested {
-        a: atl,
-        b: btl,
-        state: continue_op,
-      };
+    if !atl.is_cons() || !btl.is_cons() {
+      // Just do a regular compare of `a.tl` vs `b.tl`, one of them can be a
+      // shorter list ending with `[]` or an improper list tail
+      return EqResult::CompareTail { a: atl, b: btl };
     }
 
     // Take the next linked cons cell and continue comparing
@@ -433,7 +366,202 @@ unsafe fn cmp_cons(a: LTerm, b: LTerm) -> EqResult {
   }
 }
 
-fn cmp_terms_box(_a: LTerm, _b: LTerm) -> RtResult<EqResult> {
-  // TODO: see if cmp_terms_immed_box can be useful
-  panic!("TODO: eq_terms_box")
+/// Compare two tuples (boxed or the empty tuple constant) by their arity and
+/// then by elements starting from `index`. Elements which are not the same
+/// word are compared on the helper stack in `cmp_terms()`.
+unsafe fn cmp_tuples(a: LTerm, b: LTerm, index: usize, exact: bool) -> EqResult {
+  let arity = |t: LTerm| {
+    if t == LTerm::empty_tuple() {
+      0
+    } else {
+      (*t.get_tuple_ptr()).get_arity()
+    }
+  };
+  let a_arity = arity(a);
+  if index == 0 {
+    let b_arity = arity(b);
+    if a_arity != b_arity {
+      return EqResult::Concluded(a_arity.cmp(&b_arity));
+    }
+  }
+  for i in index..a_arity {
+    let a_elem = boxed::Tuple::get_element_base0(a.get_tuple_ptr(), i);
+    let b_elem = boxed::Tuple::get_element_base0(b.get_tuple_ptr(), i);
+    if !LTerm::is_same(a_elem, b_elem) {
+      return EqResult::CompareNested {
+        a: a_elem,
+        b: b_elem,
+        exact,
+        state: ContinueCompare::Tuple { a, b, index: i + 1 },
+      };
+    }
+  }
+  EqResult::Concluded(Ordering::Equal)
+}
+
+/// Maps are ordered by their size, then by their keys in the key order, then
+/// by the values in the same order. Keys are always compared exactly.
+fn cmp_maps(a: LTerm, b: LTerm, exact: bool) -> RtResult<EqResult> {
+  let size_order = a.map_size().cmp(&b.map_size());
+  if size_order != Ordering::Equal {
+    return Ok(EqResult::Concluded(size_order));
+  }
+  // Keys followed by values, so that keys conclude the order first
+  let flatten = |pairs: Vec<(LTerm, LTerm)>| -> Vec<LTerm> {
+    let values = pairs.iter().map(|pair| pair.1);
+    pairs.iter().map(|pair| pair.0).chain(values).collect()
+  };
+  let size = a.map_size();
+  let a_elements = flatten(map::get_sorted_pairs(a)?);
+  let b_elements = flatten(map::get_sorted_pairs(b)?);
+  Ok(cmp_elements(a_elements, b_elements, 0, size, exact))
+}
+
+/// Compare two sequences of terms of the same length from `index`. The first
+/// `exact_count` of them are compared exactly.
+fn cmp_elements(
+  a: Vec<LTerm>,
+  b: Vec<LTerm>,
+  index: usize,
+  exact_count: usize,
+  exact: bool,
+) -> EqResult {
+  for i in index..a.len() {
+    let (a_val, b_val) = (a[i], b[i]);
+    if !LTerm::is_same(a_val, b_val) {
+      return EqResult::CompareNested {
+        a: a_val,
+        b: b_val,
+        exact: exact || i < exact_count,
+        state: ContinueCompare::Elements {
+          a,
+          b,
+          index: i + 1,
+          exact_count,
+        },
+      };
+    }
+  }
+  EqResult::Concluded(Ordering::Equal)
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::emulator::{
+    arith::float,
+    heap::{allocate_cons, Heap},
+  };
+
+  fn cons(hp: &mut Heap, hd: LTerm, tl: LTerm) -> LTerm {
+    let p = allocate_cons(hp).unwrap();
+    unsafe {
+      (*p).set_hd(hd);
+      (*p).set_tl(tl);
+    }
+    LTerm::make_cons(p)
+  }
+
+  fn pair(hp: &mut Heap, a: LTerm, b: LTerm) -> LTerm {
+    let p = boxed::Tuple::create_into(hp, 2).unwrap();
+    unsafe {
+      boxed::Tuple::set_element_base0(p, 0, a);
+      boxed::Tuple::set_element_base0(p, 1, b);
+    }
+    LTerm::make_boxed(p)
+  }
+
+  fn export(hp: &mut Heap, f: &str, arity: usize) -> LTerm {
+    let mfa = MFArity::new(gen_atoms::ERLANG, atom::from_str(f), arity);
+    unsafe { boxed::Export::create_into(hp, &mfa).unwrap() }
+  }
+
+  #[test]
+  fn test_type_order() {
+    let mut hp = Heap::new(1024);
+    let one = LTerm::small_1();
+    let terms = [
+      one,
+      atom::from_str("a"),
+      atom::from_str("aa"),
+      atom::from_str("abcd"),
+      atom::from_str("abcde"),
+      atom::from_str("abd"),
+      atom::from_str("b"),
+      boxed::Reference::create_into(&mut hp, 1).unwrap(),
+      boxed::Reference::create_into(&mut hp, 2).unwrap(),
+      export(&mut hp, "foo", 1),
+      export(&mut hp, "foo", 2),
+      LTerm::make_local_pid(1),
+      LTerm::empty_tuple(),
+      pair(&mut hp, one, one),
+      map::create_empty(&mut hp).unwrap(),
+      LTerm::nil(),
+      cons(&mut hp, one, LTerm::nil()),
+      LTerm::empty_binary(),
+    ];
+    for (i, a) in terms.iter().enumerate() {
+      for (j, b) in terms.iter().enumerate() {
+        let order = cmp_terms(*a, *b, true).unwrap();
+        assert_eq!(order, i.cmp(&j), "comparing {} and {}", a, b);
+      }
+    }
+  }
+
+  #[test]
+  fn test_lists_exact_and_not() {
+    let mut hp = Heap::new(1024);
+    let (one, two) = (LTerm::small_1(), LTerm::make_small_signed(2));
+    let one_f = float::make_float(&mut hp, 1.0).unwrap();
+    let short = cons(&mut hp, one, LTerm::nil());
+    let tail = cons(&mut hp, two, LTerm::nil());
+    let long = cons(&mut hp, one, tail);
+    let improper = cons(&mut hp, one, two);
+    let short_f = cons(&mut hp, one_f, LTerm::nil());
+
+    assert_eq!(cmp_terms(short, long, true).unwrap(), Ordering::Less);
+    assert_eq!(cmp_terms(long, short, false).unwrap(), Ordering::Greater);
+    assert_eq!(cmp_terms(improper, long, true).unwrap(), Ordering::Less);
+    assert_eq!(cmp_terms(short, short_f, false).unwrap(), Ordering::Equal);
+    assert_eq!(cmp_terms(short, short_f, true).unwrap(), Ordering::Less);
+  }
+
+  #[test]
+  fn test_map_keys_exact() {
+    let mut hp = Heap::new(1024);
+    let (one, two) = (LTerm::small_1(), LTerm::make_small_signed(2));
+    let one_f = float::make_float(&mut hp, 1.0).unwrap();
+    let key = pair(&mut hp, one, one);
+    let key_f = pair(&mut hp, one, one_f);
+    let m = map::from_pairs(&[(key, one)], &mut hp).unwrap();
+    let m_f = map::from_pairs(&[(key_f, one)], &mut hp).unwrap();
+    let m_vf = map::from_pairs(&[(key, one_f)], &mut hp).unwrap();
+    let m_v2 = map::from_pairs(&[(key, two)], &mut hp).unwrap();
+
+    // Nested keys differ in exact compare even for `==`
+    assert_eq!(cmp_terms(m, m_f, false).unwrap(), Ordering::Less);
+    assert_eq!(cmp_terms(m_f, m, false).unwrap(), Ordering::Greater);
+    // Values follow the requested compare
+    assert_eq!(cmp_terms(m, m_vf, false).unwrap(), Ordering::Equal);
+    assert_eq!(cmp_terms(m, m_vf, true).unwrap(), Ordering::Less);
+    assert_eq!(cmp_terms(m_vf, m_v2, false).unwrap(), Ordering::Less);
+  }
+
+  #[test]
+  fn test_deep_nesting() {
+    const DEPTH: usize = 100_000;
+    let mut hp = Heap::new(DEPTH * 12 + 1024);
+    let mut a = LTerm::small_1();
+    let mut b = LTerm::small_1();
+    for _ in 0..DEPTH {
+      a = cons(&mut hp, a, LTerm::nil());
+      b = cons(&mut hp, b, LTerm::nil());
+      a = pair(&mut hp, a, LTerm::nil());
+      b = pair(&mut hp, b, LTerm::nil());
+    }
+    assert_eq!(cmp_terms(a, b, true).unwrap(), Ordering::Equal);
+    let c = pair(&mut hp, a, LTerm::small_0());
+    let d = pair(&mut hp, b, LTerm::small_1());
+    assert_eq!(cmp_terms(c, d, true).unwrap(), Ordering::Less);
+  }
 }
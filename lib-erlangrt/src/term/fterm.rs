@@ -55,7 +55,9 @@ pub enum FTerm {
   LoadTimeLit(Word),
   /// A list of value/label pairs, a jump table
   LoadTimeExtlist(Vec<FTerm>),
-  LoadTimeAlloclist,
+  /// Heap need of `test_heap` and `allocate_heap`, in words and in floats
+  /// which will be boxed from float registers
+  LoadTimeAlloclist { words: Word, floats: Word },
 }
 
 impl FTerm {
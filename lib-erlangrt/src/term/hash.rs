@@ -0,0 +1,127 @@
+//! Term hashing, used to place keys in large maps. Terms which compare exactly
+//! equal have the same hash.
+use crate::term::{
+  boxed::{self, BoxHeader},
+  lterm::*,
+};
+
+/// Starting values mixed into the hash for compound terms, so that a list and
+/// a tuple with the same elements hash differently.
+const SALT_CONS: u64 = 0x436f_6e73;
+const SALT_TUPLE: u64 = 0x5475_706c;
+const SALT_MAP: u64 = 0x4d61_7073;
+const SALT_BINARY: u64 = 0x4269_6e61;
+const SALT_BIGNUM: u64 = 0x4269_676e;
+
+/// FNV-1a style accumulator over 64-bit words.
+struct TermHasher {
+  state: u64,
+}
+
+impl TermHasher {
+  fn new() -> Self {
+    Self {
+      state: 0xcbf2_9ce4_8422_2325,
+    }
+  }
+
+  #[inline]
+  fn mix(&mut self, word: u64) {
+    self.state = (self.state ^ word).wrapping_mul(0x0000_0100_0000_01b3);
+  }
+
+  fn mix_bytes(&mut self, bytes: &[u8]) {
+    self.mix(bytes.len() as u64);
+    for b in bytes {
+      self.mix(u64::from(*b));
+    }
+  }
+
+  /// Spread the state bits (the fmix64 step of MurmurHash3) and fold them to
+  /// 32 bits, the lowest bits are used first by the HAMT.
+  fn finish(&self) -> u32 {
+    let mut h = self.state;
+    h ^= h >> 33;
+    h = h.wrapping_mul(0xff51_afd7_ed55_8ccd);
+    h ^= h >> 33;
+    h = h.wrapping_mul(0xc4ce_b9fe_1a85_ec53);
+    h ^= h >> 33;
+    h as u32
+  }
+}
+
+/// Calculate the hash of a term. Nested terms are walked using a helper stack
+/// instead of recursion.
+pub fn hash_term(t: LTerm) -> u32 {
+  let mut hasher = TermHasher::new();
+  let mut stack = vec![t];
+
+  while let Some(term) = stack.pop() {
+    if term.is_cons() {
+      let p = term.get_cons_ptr();
+      hasher.mix(SALT_CONS);
+      unsafe {
+        stack.push((*p).tl());
+        stack.push((*p).hd());
+      }
+    } else if term.is_boxed() {
+      unsafe { hash_boxed(term, &mut hasher, &mut stack) }
+    } else {
+      // Immediate values are equal when their raw words are equal
+      hasher.mix(term.raw() as u64);
+    }
+  }
+  hasher.finish()
+}
+
+unsafe fn hash_boxed(term: LTerm, hasher: &mut TermHasher, stack: &mut Vec<LTerm>) {
+  let header_p = term.get_box_ptr::<BoxHeader>();
+  let box_tag = (*header_p).get_tag();
+  match box_tag {
+    boxed::BOXTYPETAG_TUPLE => {
+      let p = term.get_tuple_ptr();
+      let arity = (*p).get_arity();
+      hasher.mix(SALT_TUPLE);
+      hasher.mix(arity as u64);
+      for i in (0..arity).rev() {
+        stack.push(boxed::Tuple::get_element_base0(p, i));
+      }
+    }
+    boxed::BOXTYPETAG_MAP => {
+      // Pairs of a large map are not stored in a canonical order, so their
+      // hashes are combined in an order independent way
+      let mut pairs_hash = 0u64;
+      crate::term::map::for_each_pair(term, |k, v| {
+        let pair = (u64::from(hash_term(k)) << 32) | u64::from(hash_term(v));
+        pairs_hash = pairs_hash.wrapping_add(pair);
+      });
+      hasher.mix(SALT_MAP);
+      hasher.mix(term.map_size() as u64);
+      hasher.mix(pairs_hash);
+    }
+    boxed::BOXTYPETAG_FLOAT => {
+      // 0.0 and -0.0 compare exactly equal, so they must hash the same
+      let val = term.get_f64_unsafe();
+      let val = if val == 0.0 { 0.0 } else { val };
+      hasher.mix(val.to_bits())
+    }
+    boxed::BOXTYPETAG_BINARY => {
+      let slice = boxed::Binary::get_bit_slice(term.get_box_ptr::<boxed::Binary>());
+      hasher.mix(SALT_BINARY);
+      hasher.mix(slice.get_bit_size() as u64);
+      hasher.mix_bytes(&slice.to_bytes());
+    }
+    boxed::BOXTYPETAG_BIGINTEGER => {
+      let p = term.get_box_ptr::<boxed::Bignum>();
+      hasher.mix(SALT_BIGNUM);
+      hasher.mix(boxed::Bignum::is_negative(p) as u64);
+      for digit in boxed::Bignum::get_digits(p) {
+        hasher.mix(*digit as u64);
+      }
+    }
+    boxed::BOXTYPETAG_LOCALREF => hasher.mix(boxed::Reference::get_id(term) as u64),
+    // Other boxed values only contribute their type, this is correct but can
+    // produce collisions
+    _ => hasher.mix(box_tag.get() as u64),
+  }
+}
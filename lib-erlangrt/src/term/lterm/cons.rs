@@ -1,9 +1,9 @@
 //! Utility functions for handling lists
 use crate::{
   defs::exc_type::ExceptionType,
-  emulator::{gen_atoms, heap::Heap},
-  fail::{Error, RtResult},
-  term::{boxed, lterm::lterm_impl::LTerm, term_builder::ListBuilder},
+  emulator::{arith::big::BigInteger, gen_atoms, heap::Heap},
+  fail::{self, Error, RtResult},
+  term::{lterm::lterm_impl::LTerm, term_builder::ListBuilder},
 };
 
 // TODO: Rewrite this with for_each when i can think clear again
@@ -29,37 +29,6 @@ pub fn list_length(val: LTerm) -> RtResult<usize> {
   }
 }
 
-/// Copies list `src` to heap `hp`.
-/// Arg: `src` list, must not be NIL (check for it before calling this).
-/// Returns: pointer to the list head (first element of the result tuple) and
-/// the pointer to the last cell (second element).
-/// OBSERVE that the tail of the returned copy is uninitialized memory.
-pub unsafe fn copy_list_leave_tail(
-  src: LTerm,
-  hp: &mut Heap,
-) -> RtResult<(LTerm, *mut boxed::Cons)> {
-  let mut lb = ListBuilder::new(hp)?;
-
-  // Copy elements one by one
-  if let Ok(Some(tail)) = for_each(src, |elem| {
-    lb.append(elem)?;
-    Ok(())
-  }) {
-    return Ok((lb.make_term_with_tail(tail), lb.tail_p));
-  }
-  Ok((lb.make_term(), lb.tail_p))
-
-  //  loop {
-  //    lb.append((*src_p).hd());
-  //    let src_tl = (*src_p).tl();
-  //    if !src_tl.is_cons() {
-  //      return Ok((lb.make_term(), lb.get_write_p()));
-  //    }
-  //    lb.next()?;
-  //    src_p = src_tl.get_cons_ptr();
-  //  }
-}
-
 /// For each list element run the function. Tail element (usually NIL) is ignored.
 /// Returns: Tail element (NIL for proper list) or `None` for empty list
 pub fn for_each<T>(lst: LTerm, mut func: T) -> RtResult<Option<LTerm>>
@@ -85,35 +54,6 @@ where
   }
 }
 
-/// Finds if any element of lst satisfies `predicate` function.
-/// For each list element run the predicate until it returns true, then the
-/// result becomes true. If predicate did not return true for any element,
-/// the result becomes false.
-pub fn any<T>(lst: LTerm, mut predicate: T) -> bool
-where
-  T: FnMut(LTerm) -> bool,
-{
-  if lst == LTerm::nil() {
-    return false;
-  }
-  let mut p = lst.get_cons_ptr();
-  loop {
-    let hd_el = unsafe { (*p).hd() };
-    if predicate(hd_el) {
-      return true;
-    }
-
-    let tl_el = unsafe { (*p).tl() };
-    if tl_el.is_cons() {
-      // for a list tail element step forward
-      p = tl_el.get_cons_ptr();
-    } else {
-      // for a non list end here
-      return false;
-    }
-  }
-}
-
 /// Given Rust `String`, create list of characters on heap
 // TODO: Optimize by adding new string type which is not a list?
 pub unsafe fn rust_str_to_list(s: &String, hp: &mut Heap) -> RtResult<LTerm> {
@@ -125,40 +65,16 @@ pub unsafe fn rust_str_to_list(s: &String, hp: &mut Heap) -> RtResult<LTerm> {
   Ok(lb.make_term())
 }
 
-/// Given an integer LTerm, convert it to a string with `base`.
+/// Given an integer LTerm (a small or a bignum), convert it to a string in
+/// base 10.
 pub unsafe fn integer_to_list(val: LTerm, hp: &mut Heap) -> RtResult<LTerm> {
-  if val.is_big_int() {
-    panic!("TODO: impl integer_to_list for bigint");
-  }
-
-  let base = 10isize;
-  let mut i_val = val.get_small_signed();
-  let mut lb = ListBuilder::new(hp)?;
-
-  let sign = if i_val < 0 {
-    i_val = -i_val;
-    true
-  } else {
-    false
-  };
-
-  if i_val == 0 {
-    lb.append(LTerm::make_char('0'))?;
+  let text = if val.is_small() {
+    val.get_small_signed().to_string()
   } else {
-    loop {
-      let digit = '0' as usize + (i_val % base) as usize;
-      let digit_term = LTerm::make_small_unsigned(digit);
-      lb.prepend(digit_term)?;
-      if i_val == 0 {
-        break;
-      }
-      i_val /= base;
-    } // loop
-
-    if sign {
-      lb.prepend(LTerm::make_char('-'))?;
+    match BigInteger::from_term(val) {
+      Some(big) => big.to_string_radix(10),
+      None => return fail::create::badarg(),
     }
-  } // if not 0
-
-  return Ok(lb.make_term());
+  };
+  rust_str_to_list(&text, hp)
 }
@@ -2,10 +2,11 @@
 // Printing low_level Terms as "{}"
 use crate::{
   defs::Word,
-  emulator::atom,
+  emulator::{arith::big::BigInteger, atom},
   term::{
-    boxed,
+    boxed::{self, map::MapKind},
     lterm::{cons, lterm_impl::*},
+    map,
   },
 };
 use core::fmt;
@@ -77,19 +78,27 @@ unsafe fn format_box_contents(
     boxed::BOXTYPETAG_BINARY => {
       boxed::Binary::format_binary(val_ptr as *const boxed::Binary, f)
     }
-    boxed::BOXTYPETAG_BIGINTEGER => write!(f, "Big<>"),
+    boxed::BOXTYPETAG_BIGINTEGER => {
+      let big = BigInteger::from_bignum(val_ptr as *const boxed::Bignum);
+      write!(f, "{}", big.to_string_radix(10))
+    }
     boxed::BOXTYPETAG_TUPLE => format_tuple(val_ptr, f),
+    boxed::BOXTYPETAG_MAP => format_map(val_ptr, f),
     boxed::BOXTYPETAG_CLOSURE => {
       let fun_p = val_ptr as *const boxed::Closure;
       write!(f, "Fun<{}>", (*fun_p).mfa)
     }
     boxed::BOXTYPETAG_FLOAT => {
       let fptr = val_ptr as *const boxed::Float;
-      write!(f, "{}", (*fptr).value)
+      write!(f, "{:?}", (*fptr).value)
     }
     boxed::BOXTYPETAG_EXTERNALPID => write!(f, "ExtPid<>"),
     boxed::BOXTYPETAG_EXTERNALPORT => write!(f, "ExtPort<>"),
     boxed::BOXTYPETAG_EXTERNALREF => write!(f, "ExtRef<>"),
+    boxed::BOXTYPETAG_LOCALREF => {
+      let rptr = val_ptr as *const boxed::Reference;
+      write!(f, "#Ref<0.{}>", (*rptr).id)
+    }
     boxed::BOXTYPETAG_IMPORT => {
       let iptr = val_ptr as *const boxed::Import;
       write!(f, "Import<{}>", (*iptr).mfarity)
@@ -153,6 +162,26 @@ unsafe fn format_tuple(p: *const Word, f: &mut fmt::Formatter) -> fmt::Result {
   write!(f, "}}")
 }
 
+unsafe fn format_map(p: *const Word, f: &mut fmt::Formatter) -> fmt::Result {
+  let map_p = p as *const boxed::Map;
+  match boxed::Map::get_kind(map_p) {
+    MapKind::Flat | MapKind::HashHead => {}
+    // Inner nodes of a large map are not terms on their own
+    kind => return write!(f, "MapNode<{:?}>", kind),
+  }
+
+  let mut pairs = Vec::new();
+  map::for_each_pair(LTerm::make_boxed(p), |k, v| pairs.push((k, v)));
+  write!(f, "#{{")?;
+  for (i, (k, v)) in pairs.iter().enumerate() {
+    if i > 0 {
+      write!(f, ", ")?
+    }
+    write!(f, "{} => {}", k, v)?;
+  }
+  write!(f, "}}")
+}
+
 pub unsafe fn format_cons(term: LTerm, f: &mut fmt::Formatter) -> fmt::Result {
   write!(f, "[")?;
   let mut first = true;
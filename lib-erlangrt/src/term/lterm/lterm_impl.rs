@@ -545,8 +545,10 @@ impl LTerm {
     if !self.is_boxed() {
       return Err(Error::TermIsNotABoxed);
     }
-    let _p = self.get_box_ptr::<BoxHeader>();
-    panic!("notimpl: float box")
+    if !self.is_float() {
+      return Err(Error::BoxedIsNotAFloat);
+    }
+    Ok(unsafe { self.get_f64_unsafe() })
   }
 
   /// Returns float value, performs no extra checks. The caller is responsible
@@ -590,18 +592,22 @@ impl LTerm {
   /// that the value is a map (assert!) assuming that the caller has checked
   /// it by now.
   pub fn is_flat_map(self) -> bool {
-    false
+    let p = self.get_box_ptr::<boxed::Map>();
+    unsafe { boxed::Map::get_kind(p) == boxed::map::MapKind::Flat }
   }
 
   /// Check whether a value is a hash map >= 32 elements (HAMT). Does NOT check
   /// that the value is a map (assert!) assuming that the caller has checked
   /// it by now.
   pub fn is_hash_map(self) -> bool {
-    false
+    let p = self.get_box_ptr::<boxed::Map>();
+    unsafe { boxed::Map::get_kind(p) == boxed::map::MapKind::HashHead }
   }
 
+  /// Count of key-value pairs in a map, the caller must check `is_map`.
   pub fn map_size(self) -> usize {
-    0
+    let p = self.get_box_ptr::<boxed::Map>();
+    unsafe { boxed::Map::get_size(p) }
   }
 
   // === === EXPORT === ===
@@ -649,7 +655,7 @@ impl LTerm {
   }
 
   pub fn is_local_ref(self) -> bool {
-    false
+    self.is_boxed_of_type(boxed::BOXTYPETAG_LOCALREF)
   }
 
   pub fn is_external_ref(self) -> bool {
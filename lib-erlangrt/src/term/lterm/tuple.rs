@@ -13,3 +13,19 @@ pub fn make_tuple2(elem1: LTerm, elem2: LTerm, hp: &mut Heap) -> RtResult<LTerm>
   }
   Ok(tb.make_term())
 }
+
+/// Create a 3-tuple, for example `{'EXIT', Pid, Reason}`.
+pub fn make_tuple3(
+  elem1: LTerm,
+  elem2: LTerm,
+  elem3: LTerm,
+  hp: &mut Heap,
+) -> RtResult<LTerm> {
+  let tb = TupleBuilder::with_arity(3, hp)?;
+  unsafe {
+    tb.set_element_base0(0, elem1);
+    tb.set_element_base0(1, elem2);
+    tb.set_element_base0(2, elem3);
+  }
+  Ok(tb.make_term())
+}
@@ -0,0 +1,625 @@
+//! Operations on map terms. Small maps are flat: a sorted tuple of keys and
+//! the values in the same order. When a map grows over
+//! `MAP_SMALL_MAP_LIMIT` it becomes a hash array mapped trie (HAMT) indexed
+//! by 4-bit pieces of the key hash, and it becomes flat again when it shrinks.
+//! Maps are immutable, all updates create new map objects.
+use crate::{
+  emulator::heap::{allocate_cons, Heap},
+  fail::RtResult,
+  term::{
+    boxed::{
+      map::{MapKind, HAMT_BITS_PER_LEVEL, HAMT_MAX_DEPTH, MAP_SMALL_MAP_LIMIT},
+      Map, Tuple,
+    },
+    compare,
+    hash::hash_term,
+    lterm::*,
+  },
+};
+use core::cmp::Ordering;
+
+/// An empty map.
+pub fn create_empty(hp: &mut Heap) -> RtResult<LTerm> {
+  Map::create_into(hp, MapKind::Flat, 0, &[LTerm::empty_tuple()])
+}
+
+/// Find the value for `key`. The caller must check that `map` is a map.
+pub fn get(map: LTerm, key: LTerm) -> RtResult<Option<LTerm>> {
+  let p = map.get_box_ptr::<Map>();
+  unsafe {
+    if Map::get_kind(p) == MapKind::Flat {
+      let (keys, values) = Map::get_flat_keys_values(p);
+      return Ok(flat_find(keys, key)?.ok().map(|i| values[i]));
+    }
+    hamt_get(p, key)
+  }
+}
+
+/// Create a copy of `map` where `key` is associated with `value`.
+pub fn put(map: LTerm, key: LTerm, value: LTerm, hp: &mut Heap) -> RtResult<LTerm> {
+  let p = map.get_box_ptr::<Map>();
+  unsafe {
+    if Map::get_kind(p) == MapKind::Flat {
+      return flat_put(p, key, value, hp);
+    }
+    let (bitmap, children) = Map::get_bitmap_children(p);
+    let (bitmap, children, added) =
+      hamt_put_children(bitmap, children, 0, hash_term(key), key, value, hp)?;
+    let size = Map::get_size(p) + added as usize;
+    create_head(bitmap, &children, size, hp)
+  }
+}
+
+/// Associate several keys at once, same as calling `put` for each pair in
+/// their order.
+pub fn put_pairs(map: LTerm, pairs: &[(LTerm, LTerm)], hp: &mut Heap) -> RtResult<LTerm> {
+  if map.is_flat_map() && pairs.len() > 1 {
+    // Building the flatmap once is cheaper than creating one for every pair
+    let mut all = Vec::with_capacity(map.map_size() + pairs.len());
+    for_each_pair(map, |k, v| all.push((k, v)));
+    all.extend_from_slice(pairs);
+    return from_pairs(&all, hp);
+  }
+  let mut result = map;
+  for (key, value) in pairs {
+    result = put(result, *key, *value, hp)?;
+  }
+  Ok(result)
+}
+
+/// Create a copy of `map` without `key`, or return the same map if there was
+/// no such key.
+pub fn remove(map: LTerm, key: LTerm, hp: &mut Heap) -> RtResult<LTerm> {
+  if get(map, key)?.is_none() {
+    return Ok(map);
+  }
+  let p = map.get_box_ptr::<Map>();
+  unsafe {
+    let size = Map::get_size(p) - 1;
+    if size <= MAP_SMALL_MAP_LIMIT {
+      // Flatmaps stay flat, and a HAMT which became small enough is flattened
+      let mut pairs = Vec::with_capacity(size);
+      for (k, v) in get_sorted_pairs(map)? {
+        if !keys_equal(k, key)? {
+          pairs.push((k, v));
+        }
+      }
+      return create_flat_sorted(&pairs, hp);
+    }
+    let (bitmap, children) = Map::get_bitmap_children(p);
+    match hamt_remove_children(bitmap, children, 0, hash_term(key), key, hp)? {
+      Some((bitmap, children)) => create_head(bitmap, &children, size, hp),
+      None => Ok(map),
+    }
+  }
+}
+
+/// Build a map from key-value pairs, when a key repeats the last value wins.
+pub fn from_pairs(pairs: &[(LTerm, LTerm)], hp: &mut Heap) -> RtResult<LTerm> {
+  // Stable sort keeps the repeating keys in their original order
+  let mut sorted = pairs.to_vec();
+  let mut cmp_error = None;
+  sorted.sort_by(|a, b| {
+    compare::cmp_terms(a.0, b.0, true).unwrap_or_else(|e| {
+      cmp_error.get_or_insert(e);
+      Ordering::Equal
+    })
+  });
+  if let Some(e) = cmp_error {
+    return Err(e);
+  }
+  let mut unique: Vec<(LTerm, LTerm)> = Vec::with_capacity(sorted.len());
+  for pair in sorted {
+    match unique.last_mut() {
+      Some(last) if keys_equal(last.0, pair.0)? => *last = pair,
+      _ => unique.push(pair),
+    }
+  }
+
+  if unique.len() <= MAP_SMALL_MAP_LIMIT {
+    return create_flat_sorted(&unique, hp);
+  }
+  let leaves = unique
+    .iter()
+    .map(|(k, v)| Ok((hash_term(*k), create_leaf(*k, *v, hp)?)))
+    .collect::<RtResult<Vec<(u32, LTerm)>>>()?;
+  let (bitmap, children) = hamt_build_children(&leaves, 0, hp)?;
+  create_head(bitmap, &children, unique.len(), hp)
+}
+
+/// Call `f` for every key and value. Flatmaps are visited in the key order,
+/// HAMT maps in the order of their hashes.
+pub fn for_each_pair<F>(map: LTerm, mut f: F)
+where
+  F: FnMut(LTerm, LTerm),
+{
+  let p = map.get_box_ptr::<Map>();
+  unsafe {
+    if Map::get_kind(p) == MapKind::Flat {
+      let (keys, values) = Map::get_flat_keys_values(p);
+      for (k, v) in keys.iter().zip(values.iter()) {
+        f(*k, *v)
+      }
+      return;
+    }
+    let mut stack = vec![map];
+    while let Some(node) = stack.pop() {
+      if node.is_cons() {
+        let (k, v) = get_leaf(node);
+        f(k, v);
+        continue;
+      }
+      let node_p = node.get_box_ptr::<Map>();
+      let children = match Map::get_kind(node_p) {
+        MapKind::Collision => Map::get_fields(node_p),
+        _ => Map::get_bitmap_children(node_p).1,
+      };
+      stack.extend(children.iter().rev());
+    }
+  }
+}
+
+/// Collect the pairs of a map sorted by the key order, like they are stored
+/// in a flatmap.
+pub fn get_sorted_pairs(map: LTerm) -> RtResult<Vec<(LTerm, LTerm)>> {
+  let mut pairs = Vec::with_capacity(map.map_size());
+  for_each_pair(map, |k, v| pairs.push((k, v)));
+  if map.is_hash_map() {
+    let mut cmp_error = None;
+    pairs.sort_by(|a, b| {
+      compare::cmp_terms(a.0, b.0, true).unwrap_or_else(|e| {
+        cmp_error.get_or_insert(e);
+        Ordering::Equal
+      })
+    });
+    if let Some(e) = cmp_error {
+      return Err(e);
+    }
+  }
+  Ok(pairs)
+}
+
+#[inline]
+fn keys_equal(a: LTerm, b: LTerm) -> RtResult<bool> {
+  Ok(LTerm::is_same(a, b) || compare::cmp_terms(a, b, true)? == Ordering::Equal)
+}
+
+// Flatmap helpers
+//
+
+/// Binary search for the key in the sorted keys, returns `Ok(index)` if found
+/// or `Err(index)` where the key should be inserted.
+fn flat_find(keys: &[LTerm], key: LTerm) -> RtResult<Result<usize, usize>> {
+  let mut lo = 0;
+  let mut hi = keys.len();
+  while lo < hi {
+    let mid = (lo + hi) / 2;
+    match compare::cmp_terms(keys[mid], key, true)? {
+      Ordering::Less => lo = mid + 1,
+      Ordering::Greater => hi = mid,
+      Ordering::Equal => return Ok(Ok(mid)),
+    }
+  }
+  Ok(Err(lo))
+}
+
+unsafe fn flat_put(
+  p: *const Map,
+  key: LTerm,
+  value: LTerm,
+  hp: &mut Heap,
+) -> RtResult<LTerm> {
+  let (keys, values) = Map::get_flat_keys_values(p);
+  match flat_find(keys, key)? {
+    Ok(index) => {
+      // The keys do not change, the new map shares the keys tuple
+      let mut fields = Map::get_fields(p).to_vec();
+      fields[index + 1] = value;
+      Map::create_into(hp, MapKind::Flat, keys.len(), &fields)
+    }
+    Err(index) => {
+      let mut pairs: Vec<(LTerm, LTerm)> =
+        keys.iter().cloned().zip(values.iter().cloned()).collect();
+      pairs.insert(index, (key, value));
+      if pairs.len() <= MAP_SMALL_MAP_LIMIT {
+        create_flat_sorted(&pairs, hp)
+      } else {
+        from_pairs(&pairs, hp)
+      }
+    }
+  }
+}
+
+/// Create a flatmap from pairs which are sorted and have no repeating keys.
+fn create_flat_sorted(pairs: &[(LTerm, LTerm)], hp: &mut Heap) -> RtResult<LTerm> {
+  if pairs.is_empty() {
+    return create_empty(hp);
+  }
+  let keys_p = Tuple::create_into(hp, pairs.len())?;
+  let mut fields = Vec::with_capacity(pairs.len() + 1);
+  fields.push(LTerm::make_boxed(keys_p));
+  for (i, (k, v)) in pairs.iter().enumerate() {
+    unsafe { Tuple::set_element_base0(keys_p, i, *k) }
+    fields.push(*v);
+  }
+  Map::create_into(hp, MapKind::Flat, pairs.len(), &fields)
+}
+
+// HAMT helpers
+//
+
+/// Index of the child slot for `hash` in a node at `depth`.
+#[inline]
+fn hash_slot(hash: u32, depth: usize) -> usize {
+  (hash as usize >> (depth * HAMT_BITS_PER_LEVEL)) & ((1 << HAMT_BITS_PER_LEVEL) - 1)
+}
+
+/// Position of the child for the `slot` bit among the children present in
+/// the `bitmap`.
+#[inline]
+fn child_position(bitmap: usize, slot: usize) -> usize {
+  (bitmap & ((1 << slot) - 1)).count_ones() as usize
+}
+
+fn create_leaf(key: LTerm, value: LTerm, hp: &mut Heap) -> RtResult<LTerm> {
+  let p = allocate_cons(hp)?;
+  unsafe {
+    (*p).set_hd(key);
+    (*p).set_tl(value);
+  }
+  Ok(LTerm::make_cons(p))
+}
+
+#[inline]
+fn get_leaf(leaf: LTerm) -> (LTerm, LTerm) {
+  let p = leaf.get_cons_ptr();
+  unsafe { ((*p).hd(), (*p).tl()) }
+}
+
+fn create_head(
+  bitmap: usize,
+  children: &[LTerm],
+  size: usize,
+  hp: &mut Heap,
+) -> RtResult<LTerm> {
+  let mut fields = Vec::with_capacity(children.len() + 1);
+  fields.push(LTerm::make_small_unsigned(bitmap));
+  fields.extend_from_slice(children);
+  Map::create_into(hp, MapKind::HashHead, size, &fields)
+}
+
+#[inline]
+fn create_node(bitmap: usize, children: &[LTerm], hp: &mut Heap) -> RtResult<LTerm> {
+  Map::create_into(hp, MapKind::HashNode, bitmap, children)
+}
+
+#[inline]
+fn create_collision(leaves: &[LTerm], hp: &mut Heap) -> RtResult<LTerm> {
+  Map::create_into(hp, MapKind::Collision, leaves.len(), leaves)
+}
+
+unsafe fn hamt_get(head: *const Map, key: LTerm) -> RtResult<Option<LTerm>> {
+  let hash = hash_term(key);
+  let (mut bitmap, mut children) = Map::get_bitmap_children(head);
+  let mut depth = 0;
+  loop {
+    let slot = hash_slot(hash, depth);
+    if bitmap & (1 << slot) == 0 {
+      return Ok(None);
+    }
+    let child = children[child_position(bitmap, slot)];
+    if child.is_cons() {
+      let (k, v) = get_leaf(child);
+      return Ok(if keys_equal(k, key)? { Some(v) } else { None });
+    }
+    depth += 1;
+    let node_p = child.get_box_ptr::<Map>();
+    if Map::get_kind(node_p) == MapKind::Collision {
+      for leaf in Map::get_fields(node_p) {
+        let (k, v) = get_leaf(*leaf);
+        if keys_equal(k, key)? {
+          return Ok(Some(v));
+        }
+      }
+      return Ok(None);
+    }
+    let (b, c) = Map::get_bitmap_children(node_p);
+    bitmap = b;
+    children = c;
+  }
+}
+
+/// Insert or replace the key in children of a node at `depth`, returns the new
+/// bitmap and children and whether the map has grown.
+fn hamt_put_children(
+  bitmap: usize,
+  children: &[LTerm],
+  depth: usize,
+  hash: u32,
+  key: LTerm,
+  value: LTerm,
+  hp: &mut Heap,
+) -> RtResult<(usize, Vec<LTerm>, bool)> {
+  let slot = hash_slot(hash, depth);
+  let pos = child_position(bitmap, slot);
+  let mut new_children = children.to_vec();
+  if bitmap & (1 << slot) == 0 {
+    new_children.insert(pos, create_leaf(key, value, hp)?);
+    return Ok((bitmap | (1 << slot), new_children, true));
+  }
+  let (child, added) = hamt_put_subtree(children[pos], depth + 1, hash, key, value, hp)?;
+  new_children[pos] = child;
+  Ok((bitmap, new_children, added))
+}
+
+/// Insert or replace the key in a subtree which is a leaf, a node or a
+/// collision node at `depth`.
+fn hamt_put_subtree(
+  subtree: LTerm,
+  depth: usize,
+  hash: u32,
+  key: LTerm,
+  value: LTerm,
+  hp: &mut Heap,
+) -> RtResult<(LTerm, bool)> {
+  if subtree.is_cons() {
+    let (k, _) = get_leaf(subtree);
+    let new_leaf = create_leaf(key, value, hp)?;
+    if keys_equal(k, key)? {
+      return Ok((new_leaf, false));
+    }
+    let pair = [(hash_term(k), subtree), (hash, new_leaf)];
+    return Ok((hamt_build_subtree(&pair, depth, hp)?, true));
+  }
+
+  let p = subtree.get_box_ptr::<Map>();
+  unsafe {
+    if Map::get_kind(p) == MapKind::Collision {
+      let mut leaves = Map::get_fields(p).to_vec();
+      let new_leaf = create_leaf(key, value, hp)?;
+      for leaf in leaves.iter_mut() {
+        if keys_equal(get_leaf(*leaf).0, key)? {
+          *leaf = new_leaf;
+          return Ok((create_collision(&leaves, hp)?, false));
+        }
+      }
+      leaves.push(new_leaf);
+      return Ok((create_collision(&leaves, hp)?, true));
+    }
+    let (bitmap, children) = Map::get_bitmap_children(p);
+    let (bitmap, children, added) =
+      hamt_put_children(bitmap, children, depth, hash, key, value, hp)?;
+    Ok((create_node(bitmap, &children, hp)?, added))
+  }
+}
+
+/// Group leaves by their slot at `depth` and build the children of a node.
+fn hamt_build_children(
+  leaves: &[(u32, LTerm)],
+  depth: usize,
+  hp: &mut Heap,
+) -> RtResult<(usize, Vec<LTerm>)> {
+  let mut groups: Vec<Vec<(u32, LTerm)>> = vec![Vec::new(); 1 << HAMT_BITS_PER_LEVEL];
+  for leaf in leaves {
+    groups[hash_slot(leaf.0, depth)].push(*leaf);
+  }
+  let mut bitmap = 0;
+  let mut children = Vec::new();
+  for (slot, group) in groups.iter().enumerate() {
+    if !group.is_empty() {
+      bitmap |= 1 << slot;
+      children.push(hamt_build_subtree(group, depth + 1, hp)?);
+    }
+  }
+  Ok((bitmap, children))
+}
+
+/// Build a subtree at `depth` for leaves which share the slots above it.
+fn hamt_build_subtree(
+  leaves: &[(u32, LTerm)],
+  depth: usize,
+  hp: &mut Heap,
+) -> RtResult<LTerm> {
+  if leaves.len() == 1 {
+    return Ok(leaves[0].1);
+  }
+  if depth == HAMT_MAX_DEPTH {
+    let collided: Vec<LTerm> = leaves.iter().map(|leaf| leaf.1).collect();
+    return create_collision(&collided, hp);
+  }
+  let (bitmap, children) = hamt_build_children(leaves, depth, hp)?;
+  create_node(bitmap, &children, hp)
+}
+
+/// Result of removing a key from a HAMT subtree.
+enum Removed {
+  NotFound,
+  /// The subtree is gone, it was the leaf with the key
+  Empty,
+  /// The subtree was replaced
+  Subtree(LTerm),
+}
+
+/// Remove the key from children of a node at `depth`, returns the new bitmap
+/// and children, or `None` if the key was not found.
+fn hamt_remove_children(
+  bitmap: usize,
+  children: &[LTerm],
+  depth: usize,
+  hash: u32,
+  key: LTerm,
+  hp: &mut Heap,
+) -> RtResult<Option<(usize, Vec<LTerm>)>> {
+  let slot = hash_slot(hash, depth);
+  if bitmap & (1 << slot) == 0 {
+    return Ok(None);
+  }
+  let pos = child_position(bitmap, slot);
+  let mut new_children = children.to_vec();
+  match hamt_remove_subtree(children[pos], depth + 1, hash, key, hp)? {
+    Removed::NotFound => return Ok(None),
+    Removed::Empty => {
+      new_children.remove(pos);
+      return Ok(Some((bitmap & !(1 << slot), new_children)));
+    }
+    Removed::Subtree(child) => new_children[pos] = child,
+  }
+  Ok(Some((bitmap, new_children)))
+}
+
+fn hamt_remove_subtree(
+  subtree: LTerm,
+  depth: usize,
+  hash: u32,
+  key: LTerm,
+  hp: &mut Heap,
+) -> RtResult<Removed> {
+  if subtree.is_cons() {
+    if keys_equal(get_leaf(subtree).0, key)? {
+      return Ok(Removed::Empty);
+    }
+    return Ok(Removed::NotFound);
+  }
+
+  let p = subtree.get_box_ptr::<Map>();
+  let remaining = unsafe {
+    if Map::get_kind(p) == MapKind::Collision {
+      let leaves = Map::get_fields(p);
+      let mut remaining = Vec::with_capacity(leaves.len());
+      for leaf in leaves {
+        if !keys_equal(get_leaf(*leaf).0, key)? {
+          remaining.push(*leaf);
+        }
+      }
+      if remaining.len() == leaves.len() {
+        return Ok(Removed::NotFound);
+      }
+      if remaining.len() > 1 {
+        return Ok(Removed::Subtree(create_collision(&remaining, hp)?));
+      }
+      remaining
+    } else {
+      let (bitmap, children) = Map::get_bitmap_children(p);
+      match hamt_remove_children(bitmap, children, depth, hash, key, hp)? {
+        None => return Ok(Removed::NotFound),
+        Some((bitmap, children)) => {
+          if children.len() > 1 || !children[0].is_cons() {
+            return Ok(Removed::Subtree(create_node(bitmap, &children, hp)?));
+          }
+          children
+        }
+      }
+    }
+  };
+  // A single leaf left in a node replaces the node, lookups compare the key
+  // in the leaf so it can be stored at any depth
+  Ok(Removed::Subtree(remaining[0]))
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::emulator::arith::float;
+
+  fn small(n: usize) -> LTerm {
+    LTerm::make_small_unsigned(n)
+  }
+
+  #[test]
+  fn test_map_put_get_flat() {
+    let mut hp = Heap::new(1024);
+    let mut m = create_empty(&mut hp).unwrap();
+    for i in (0..10).rev() {
+      m = put(m, small(i), small(i * 10), &mut hp).unwrap();
+    }
+    assert!(m.is_flat_map());
+    assert_eq!(m.map_size(), 10);
+    assert_eq!(get(m, small(3)).unwrap(), Some(small(30)));
+    assert_eq!(get(m, small(11)).unwrap(), None);
+
+    // Keys are stored in order
+    let mut keys = Vec::new();
+    for_each_pair(m, |k, _| keys.push(k.get_small_unsigned()));
+    assert_eq!(keys, (0..10).collect::<Vec<usize>>());
+
+    let m2 = put(m, small(3), small(33), &mut hp).unwrap();
+    assert_eq!(m2.map_size(), 10);
+    assert_eq!(get(m2, small(3)).unwrap(), Some(small(33)));
+    assert_eq!(get(m, small(3)).unwrap(), Some(small(30)));
+  }
+
+  #[test]
+  fn test_map_grows_into_hamt_and_shrinks() {
+    let mut hp = Heap::new(65536);
+    let mut m = create_empty(&mut hp).unwrap();
+    let count = 200;
+    for i in 0..count {
+      m = put(m, small(i), small(i + 1), &mut hp).unwrap();
+      assert_eq!(m.is_hash_map(), i + 1 > MAP_SMALL_MAP_LIMIT);
+    }
+    assert_eq!(m.map_size(), count);
+    for i in 0..count {
+      assert_eq!(get(m, small(i)).unwrap(), Some(small(i + 1)));
+    }
+    assert_eq!(get_sorted_pairs(m).unwrap().len(), count);
+
+    for i in 0..count {
+      m = remove(m, small(i), &mut hp).unwrap();
+      assert_eq!(m.map_size(), count - i - 1);
+      assert_eq!(get(m, small(i)).unwrap(), None);
+      assert_eq!(m.is_flat_map(), count - i - 1 <= MAP_SMALL_MAP_LIMIT);
+    }
+  }
+
+  #[test]
+  fn test_map_from_pairs() {
+    let mut hp = Heap::new(16384);
+    let mut pairs: Vec<(LTerm, LTerm)> = (0..100).map(|i| (small(i), small(i))).collect();
+    pairs.push((small(5), small(500)));
+    let m = from_pairs(&pairs, &mut hp).unwrap();
+    assert!(m.is_hash_map());
+    assert_eq!(m.map_size(), 100);
+    assert_eq!(get(m, small(5)).unwrap(), Some(small(500)));
+
+    let m2 = from_pairs(&pairs[95..], &mut hp).unwrap();
+    assert!(m2.is_flat_map());
+    assert_eq!(m2.map_size(), 6);
+    assert_eq!(get(m2, small(5)).unwrap(), Some(small(500)));
+  }
+
+  #[test]
+  fn test_map_compare() {
+    let mut hp = Heap::new(16384);
+    let pairs: Vec<(LTerm, LTerm)> = (0..40).map(|i| (small(i), small(i))).collect();
+    let m1 = from_pairs(&pairs, &mut hp).unwrap();
+    let mut m2 = create_empty(&mut hp).unwrap();
+    for (k, v) in pairs.iter().rev() {
+      m2 = put(m2, *k, *v, &mut hp).unwrap();
+    }
+    assert_eq!(compare::cmp_terms(m1, m2, true).unwrap(), Ordering::Equal);
+
+    let m3 = put(m2, small(39), small(40), &mut hp).unwrap();
+    assert_eq!(compare::cmp_terms(m2, m3, true).unwrap(), Ordering::Less);
+    let m4 = remove(m2, small(0), &mut hp).unwrap();
+    assert_eq!(compare::cmp_terms(m2, m4, true).unwrap(), Ordering::Greater);
+  }
+
+  #[test]
+  fn test_map_signed_zero_keys() {
+    let mut hp = Heap::new(16384);
+    let zero = float::make_float(&mut hp, 0.0).unwrap();
+    let neg_zero = float::make_float(&mut hp, -0.0).unwrap();
+    // Same rule for a flatmap and for a HAMT: both zeros are one key
+    for size in &[1, MAP_SMALL_MAP_LIMIT + 8] {
+      let mut pairs: Vec<(LTerm, LTerm)> =
+        (0..*size).map(|i| (small(i), small(i))).collect();
+      pairs.push((zero, small(1000)));
+      let mut m = from_pairs(&pairs, &mut hp).unwrap();
+      assert_eq!(m.is_hash_map(), *size > MAP_SMALL_MAP_LIMIT);
+      m = put(m, neg_zero, small(2000), &mut hp).unwrap();
+      assert_eq!(m.map_size(), size + 1);
+      assert_eq!(get(m, zero).unwrap(), Some(small(2000)));
+      assert_eq!(get(m, neg_zero).unwrap(), Some(small(2000)));
+    }
+  }
+}
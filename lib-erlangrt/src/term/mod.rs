@@ -13,6 +13,8 @@ pub mod builders; // simple term builder helpers
 pub mod classify; // term ordering (for comparisons)
 pub mod compare; // term comparisons (less, equal, greater)
 pub mod fterm; // friendly term as Rust enum
+pub mod hash; // term hashing for maps
 pub mod integral; // integral value (small or bignum) for fterms
 pub mod lterm; // low level packed term
+pub mod map; // map operations (flatmap and HAMT)
 pub mod term_builder; // implements ITermBuilder for RT VM // term in memory (dynamic runtime dispatch)
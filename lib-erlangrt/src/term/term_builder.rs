@@ -1,12 +1,11 @@
 //! Implements term builder for use with library term algorithms (used to
 //! decouple libraries from the actual term implementation).
 use crate::{
-  defs::{ByteSize, WordSize},
-  emulator::{atom, heap::Heap},
+  defs::{ByteSize, Word, WordSize},
+  emulator::{arith::big::BigInteger, atom, heap::Heap, mfa::MFArity},
   fail::RtResult,
-  term::{boxed, lterm::*},
+  term::{boxed, lterm::*, map},
 };
-use num;
 
 // TODO: Remove templating on term type here
 
@@ -90,6 +89,7 @@ impl ListBuilder {
   /// Build list back: Create a new cons, where tail points to current.
   /// New previous cell becomes the current.
   /// Remember to terminate the first cell of the list with NIL.
+  #[allow(dead_code)]
   pub unsafe fn prepend(&mut self, val: LTerm) -> RtResult<()> {
     if self.head_p.is_null() {
       self.head_p = self.make_cell()?;
@@ -132,10 +132,16 @@ impl TermBuilder {
     }
   }
 
-  pub unsafe fn create_bignum(&self, n: num::BigInt) -> RtResult<LTerm> {
+  /// Store an integer, as a small if it fits or as a bignum.
+  pub unsafe fn create_bignum(&self, n: &BigInteger) -> RtResult<LTerm> {
     let ref_heap = self.heap.as_mut().unwrap();
-    let big_p = boxed::Bignum::create_into(ref_heap, n)?;
-    Ok(LTerm::make_boxed(big_p))
+    n.to_term(ref_heap)
+  }
+
+  pub unsafe fn create_float(&self, val: f64) -> RtResult<LTerm> {
+    let ref_heap = self.heap.as_mut().unwrap();
+    let float_p = boxed::Float::create_into(ref_heap, val)?;
+    Ok(LTerm::make_boxed(float_p))
   }
 
   pub unsafe fn create_binary(&mut self, data: &[u8]) -> RtResult<LTerm> {
@@ -146,6 +152,16 @@ impl TermBuilder {
     Ok(LTerm::make_boxed(rbin))
   }
 
+  pub fn create_local_ref(&mut self, id: Word) -> RtResult<LTerm> {
+    let ref_heap = unsafe { self.heap.as_mut() }.unwrap();
+    boxed::Reference::create_into(ref_heap, id)
+  }
+
+  pub fn create_export(&mut self, mfa: &MFArity) -> RtResult<LTerm> {
+    let ref_heap = unsafe { self.heap.as_mut() }.unwrap();
+    unsafe { boxed::Export::create_into(ref_heap, mfa) }
+  }
+
   #[inline]
   pub fn create_atom_str(&self, a: &str) -> LTerm {
     atom::from_str(a)
@@ -162,6 +178,11 @@ impl TermBuilder {
     Ok(TupleBuilder::new(raw_tuple))
   }
 
+  pub fn create_map(&mut self, pairs: &[(LTerm, LTerm)]) -> RtResult<LTerm> {
+    let ref_heap = unsafe { self.heap.as_mut() }.unwrap();
+    map::from_pairs(pairs, ref_heap)
+  }
+
   pub fn create_list_builder(&mut self) -> RtResult<ListBuilder> {
     unsafe { ListBuilder::new(self.heap) }
   }
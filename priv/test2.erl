@@ -19,8 +19,18 @@ test() ->
     test_ring(),
     test_try_catch(),
     test_mochijson(),
+    test_float(1.5, 4),
     test_apply(lists, erlang).
 
+%%-----------------------------------------------
+%% Float arithmetic on arguments goes through the float registers, boxing the
+%% result makes the compiler emit an alloc list with {floats, N}
+test_float(X, Y) ->
+    Z = X * Y + X / Y,
+    true = Z > 6.37 andalso Z < 6.38,
+    6 = trunc(Z),
+    -1.5 = -X.
+
 %%-----------------------------------------------
 test_apply(Lists, Erlang) ->
     true = apply(Lists, any, [fun(X) -> X =:= test1 end, [test2, test1]]),